@@ -0,0 +1,513 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+
+use yangtze_apis::{
+    v1::{NamespaceName, YangtzeError},
+    v1alpha1::{
+        network_interface::{
+            self, InterfaceKind, NetworkInterface, NetworkInterfaceState, NetworkInterfaceStatus,
+        },
+        subnet::{self, Subnet},
+        vpc::{self, Vpc, MAX_VNI},
+    },
+};
+use yangtze_client::YangtzeClient;
+
+mod netlink;
+
+/// The UDP port of VXLAN by IANA.
+pub const VXLAN_PORT: u16 = 4789;
+
+/// A Subnet attached to the host through an uplink.
+#[derive(Clone, Debug)]
+pub struct Attachment {
+    pub subnet: String,
+    pub uplink: String,
+    /// Whether the uplink is enslaved to the bridge as is, e.g. a
+    /// representor, instead of by the VLAN of the Subnet.
+    pub port: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Vrf {
+        table: u32,
+    },
+    Bridge,
+    Vxlan {
+        vni: u32,
+        local: Option<Ipv4Addr>,
+    },
+    Vlan {
+        parent: String,
+        id: u16,
+    },
+    /// An existing link enslaved to a bridge, never created by the agent.
+    Port,
+}
+
+/// A link of the dataplane, created by the agent if not found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Link {
+    pub name: String,
+    pub kind: Kind,
+    pub master: Option<String>,
+    /// The addresses with their prefix length.
+    pub addresses: Vec<(IpAddr, u8)>,
+}
+
+/// The desired links of the host, in the order of creation, i.e. the
+/// masters first.
+#[derive(Debug, Default)]
+pub struct Dataplane {
+    pub links: Vec<Link>,
+}
+
+impl Dataplane {
+    fn add(&mut self, link: Link) {
+        match self.links.iter_mut().find(|l| l.name == link.name) {
+            Some(l) => {
+                for a in link.addresses {
+                    if !l.addresses.contains(&a) {
+                        l.addresses.push(a);
+                    }
+                }
+            }
+            None => self.links.push(link),
+        }
+    }
+
+    fn sort(&mut self) {
+        let rank = |k: &Kind| match k {
+            Kind::Vrf { .. } => 0,
+            Kind::Bridge => 1,
+            Kind::Vxlan { .. } => 2,
+            Kind::Vlan { .. } | Kind::Port => 3,
+        };
+        self.links.sort_by_key(|l| rank(&l.kind));
+    }
+}
+
+/// Builds the Dataplane of the attached Subnets: a VRF, a bridge and a VXLAN
+/// device for each Vpc, with the gateways of its Subnets on the bridge and a
+/// VLAN of the uplink, or the uplink itself for a port, bridged in for each
+/// Subnet. Returns the errors of the attachments which are skipped, by their
+/// index.
+pub fn desired(
+    vpcs: &[Vpc],
+    subnets: &[Subnet],
+    attachments: &[Attachment],
+    vtep: Option<Ipv4Addr>,
+) -> (Dataplane, Vec<(usize, String)>) {
+    let mut dataplane = Dataplane::default();
+    let mut errors = vec![];
+
+    for (i, a) in attachments.iter().enumerate() {
+        let Some(s) = subnets.iter().find(|s| s.meta_data.name == a.subnet) else {
+            errors.push((i, format!("Subnet <{}> not found", a.subnet)));
+            continue;
+        };
+        let Some(name) = &s.spec.vpc else {
+            errors.push((i, format!("Subnet <{}> is not in a Vpc", s)));
+            continue;
+        };
+        let Some(v) = vpcs.iter().find(|v| &v.meta_data.name == name) else {
+            errors.push((i, format!("Vpc <{}> of Subnet <{}> not found", name, s)));
+            continue;
+        };
+        let vni = v.spec.vni;
+        if vni == 0 && v.spec.fabric.is_some() {
+            errors.push((i, format!("the VNI of Vpc <{}> is not allocated yet", v)));
+            continue;
+        }
+        if vni == 0 || vni > MAX_VNI {
+            errors.push((i, format!("invalid VNI <{}> of Vpc <{}>", vni, v)));
+            continue;
+        }
+
+        let mut addresses = vec![];
+        if let Some(gateway) = &s.spec.gateway {
+            let prefix = s
+                .spec
+                .cidr
+                .split_once('/')
+                .and_then(|(_, p)| p.parse::<u8>().ok());
+            match (gateway.parse::<IpAddr>(), prefix) {
+                (Ok(ip), Some(prefix)) => addresses.push((ip, prefix)),
+                _ => {
+                    errors.push((i, format!("invalid gateway of Subnet <{}>", s)));
+                    continue;
+                }
+            }
+        }
+
+        let vrf = format!("yzvrf{}", vni);
+        let bridge = format!("yzbr{}", vni);
+        dataplane.add(Link {
+            name: vrf.clone(),
+            kind: Kind::Vrf {
+                table: v.spec.table(),
+            },
+            master: None,
+            addresses: vec![],
+        });
+        dataplane.add(Link {
+            name: bridge.clone(),
+            kind: Kind::Bridge,
+            master: Some(vrf),
+            addresses,
+        });
+        dataplane.add(Link {
+            name: format!("yzvx{}", vni),
+            kind: Kind::Vxlan { vni, local: vtep },
+            master: Some(bridge.clone()),
+            addresses: vec![],
+        });
+
+        if a.port {
+            dataplane.add(Link {
+                name: a.uplink.clone(),
+                kind: Kind::Port,
+                master: Some(bridge),
+                addresses: vec![],
+            });
+        } else if let Some(id) = s.spec.vlan {
+            let name = format!("{}.{}", a.uplink, id);
+            // The limit of the names of the Linux links, IFNAMSIZ - 1.
+            if name.len() > 15 {
+                errors.push((i, format!("the VLAN name <{}> is too long", name)));
+                continue;
+            }
+            dataplane.add(Link {
+                name,
+                kind: Kind::Vlan {
+                    parent: a.uplink.clone(),
+                    id,
+                },
+                master: Some(bridge),
+                addresses: vec![],
+            });
+        }
+    }
+
+    dataplane.sort();
+    (dataplane, errors)
+}
+
+/// Realizes the Vpcs of the Subnets attached to the host with VRFs, bridges,
+/// VXLAN devices and VLANs through netlink, and removes the links it created
+/// which are no longer desired. The NetworkInterfaces of the Node are
+/// attached too, and their state reported.
+pub struct Reconciler {
+    pub client: YangtzeClient,
+    pub namespace: String,
+    pub node: String,
+    pub attachments: Vec<Attachment>,
+    /// The local address of the VXLAN tunnels.
+    pub vtep: Option<Ipv4Addr>,
+    pub interval: u64,
+}
+
+impl Reconciler {
+    pub async fn run(self) {
+        let handle = match netlink::connect() {
+            Ok(handle) => handle,
+            Err(e) => {
+                tracing::error!("Failed to connect to netlink: {}", e);
+                return;
+            }
+        };
+
+        loop {
+            if let Err(e) = self.reconcile(&handle).await {
+                tracing::error!("Failed to reconcile the dataplane: {}", e);
+            }
+            tokio::time::sleep(Duration::from_secs(self.interval)).await;
+        }
+    }
+
+    async fn reconcile(&self, handle: &rtnetlink::Handle) -> Result<(), YangtzeError> {
+        let vpcs: Vec<Vpc> = self.list(vpc::VERSION_KIND.kind).await?;
+        let subnets: Vec<Subnet> = self.list(subnet::VERSION_KIND.kind).await?;
+        let interfaces: Vec<NetworkInterface> = self
+            .list::<NetworkInterface>(network_interface::VERSION_KIND.kind)
+            .await?
+            .into_iter()
+            .filter(|i| i.spec.node == self.node)
+            // Only the interfaces resolved by the controller.
+            .filter(|i| i.status.as_ref().is_some_and(|s| s.address.is_some()))
+            .collect();
+
+        // The attachments of the interfaces follow the ones of the command line.
+        let mut attachments = self.attachments.clone();
+        let mut owners = vec![None; attachments.len()];
+        for (n, i) in interfaces.iter().enumerate() {
+            if i.spec.kind != InterfaceKind::Vf {
+                attachments.push(Attachment {
+                    subnet: i.spec.subnet.clone(),
+                    uplink: i.spec.interface.clone(),
+                    port: i.spec.kind == InterfaceKind::Representor,
+                });
+                owners.push(Some(n));
+            }
+        }
+
+        let mut reasons: Vec<Option<String>> = vec![None; interfaces.len()];
+        let (dataplane, errors) = desired(&vpcs, &subnets, &attachments, self.vtep);
+        for (a, e) in errors {
+            match owners[a] {
+                Some(n) => reasons[n] = Some(e),
+                None => tracing::warn!("Skip an attachment of the dataplane: {}", e),
+            }
+        }
+
+        let applied = netlink::apply(handle, &dataplane).await;
+        for (n, i) in interfaces.iter().enumerate() {
+            if reasons[n].is_some() {
+                continue;
+            }
+            reasons[n] = match &applied {
+                Err(e) => Some(e.clone()),
+                Ok(()) => self.configure(handle, i).await.err(),
+            };
+        }
+
+        for (i, reason) in interfaces.into_iter().zip(reasons) {
+            self.report(i, reason).await?;
+        }
+
+        applied.map_err(YangtzeError::GeneralError)
+    }
+
+    /// Sets the MTU of the interface, or the MAC and VLAN of the VF.
+    async fn configure(
+        &self,
+        handle: &rtnetlink::Handle,
+        i: &NetworkInterface,
+    ) -> Result<(), String> {
+        let Some(status) = &i.status else {
+            return Ok(());
+        };
+        let mtu = status.mtu.unwrap_or(network_interface::DEFAULT_MTU);
+
+        match i.spec.kind {
+            InterfaceKind::Vf => {
+                let vf = i.spec.vf.ok_or("the index of the VF is required")?;
+                let mac = status
+                    .mac
+                    .as_deref()
+                    .and_then(parse_mac)
+                    .ok_or(format!("invalid MAC <{:?}>", status.mac))?;
+                netlink::set_vf(
+                    handle,
+                    &i.spec.interface,
+                    vf,
+                    mac,
+                    status.vlan.unwrap_or(0),
+                    mtu,
+                )
+                .await
+            }
+            _ => netlink::set_mtu(handle, &i.spec.interface, mtu).await,
+        }
+    }
+
+    /// Reports the state of a NetworkInterface programmed by the agent.
+    async fn report(
+        &self,
+        i: NetworkInterface,
+        reason: Option<String>,
+    ) -> Result<(), YangtzeError> {
+        let Some(current) = i.status.clone() else {
+            return Ok(());
+        };
+        let state = match reason {
+            Some(_) => NetworkInterfaceState::Error,
+            None => NetworkInterfaceState::Ready,
+        };
+        if current.state == state && current.reason == reason {
+            return Ok(());
+        }
+
+        match &reason {
+            Some(r) => tracing::warn!("Failed to program NetworkInterface <{}>: {}", i, r),
+            None => tracing::info!(
+                "NetworkInterface <{}> is ready on <{}>.",
+                i,
+                i.spec.interface
+            ),
+        }
+
+        let mut i = i;
+        i.status = Some(NetworkInterfaceStatus {
+            state,
+            reason,
+            ..current
+        });
+        self.client.update::<NetworkInterface>(i).await?;
+
+        Ok(())
+    }
+
+    async fn list<T: DeserializeOwned>(&self, kind: &str) -> Result<Vec<T>, YangtzeError> {
+        self.client
+            .clone()
+            .version("v1alpha1")
+            .kind(kind)
+            .list(NamespaceName {
+                namespace: Some(self.namespace.clone()),
+                name: None,
+            })
+            .await
+    }
+}
+
+/// Parses a MAC like `02:00:00:00:00:01`.
+fn parse_mac(s: &str) -> Option<[u8; 6]> {
+    let bytes: Vec<u8> = s
+        .split(':')
+        .map(|b| u8::from_str_radix(b, 16).ok())
+        .collect::<Option<_>>()?;
+    bytes.try_into().ok()
+}
+
+#[cfg(test)]
+pub mod fixtures {
+    use super::*;
+
+    pub fn vpc(name: &str, vni: u32) -> Vpc {
+        serde_json::from_value(serde_json::json!({
+            "meta_data": {"kind": "vpc", "namespace": "default", "name": name, "labels": [], "version": 0},
+            "spec": {"vni": vni, "table": 1000 + vni},
+        }))
+        .unwrap()
+    }
+
+    pub fn subnet(name: &str, vpc: &str, cidr: &str, gateway: &str, vlan: Option<u16>) -> Subnet {
+        serde_json::from_value(serde_json::json!({
+            "meta_data": {"kind": "subnet", "namespace": "default", "name": name, "labels": [], "version": 0},
+            "spec": {"cidr": cidr, "gateway": gateway, "vpc": vpc, "vlan": vlan},
+        }))
+        .unwrap()
+    }
+
+    pub fn attachment(subnet: &str, uplink: &str, port: bool) -> Attachment {
+        Attachment {
+            subnet: subnet.to_string(),
+            uplink: uplink.to_string(),
+            port,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::*;
+    use super::*;
+
+    fn names(dataplane: &Dataplane) -> Vec<&str> {
+        dataplane.links.iter().map(|l| l.name.as_str()).collect()
+    }
+
+    #[test]
+    fn build_vpc_with_vlan_and_port() {
+        let vpcs = [vpc("v1", 100)];
+        let subnets = [
+            subnet("s1", "v1", "10.0.1.0/24", "10.0.1.1", Some(10)),
+            subnet("s2", "v1", "10.0.2.0/24", "10.0.2.1", None),
+        ];
+        let attachments = [
+            attachment("s1", "eth0", false),
+            attachment("s2", "pf0hpf", true),
+        ];
+        let vtep = Some(Ipv4Addr::new(10, 255, 0, 1));
+        let (dataplane, errors) = desired(&vpcs, &subnets, &attachments, vtep);
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            names(&dataplane),
+            vec!["yzvrf100", "yzbr100", "yzvx100", "eth0.10", "pf0hpf"]
+        );
+        let link = |name: &str| dataplane.links.iter().find(|l| l.name == name).unwrap();
+        assert_eq!(link("yzvrf100").kind, Kind::Vrf { table: 1100 });
+        assert_eq!(link("yzvrf100").master, None);
+        assert_eq!(link("yzbr100").master.as_deref(), Some("yzvrf100"));
+        // The gateways of both Subnets are on the one bridge of the Vpc.
+        assert_eq!(
+            link("yzbr100").addresses,
+            vec![
+                ("10.0.1.1".parse().unwrap(), 24),
+                ("10.0.2.1".parse().unwrap(), 24)
+            ]
+        );
+        assert_eq!(
+            link("yzvx100").kind,
+            Kind::Vxlan {
+                vni: 100,
+                local: vtep
+            }
+        );
+        assert_eq!(
+            link("eth0.10").kind,
+            Kind::Vlan {
+                parent: "eth0".to_string(),
+                id: 10
+            }
+        );
+        assert_eq!(link("eth0.10").master.as_deref(), Some("yzbr100"));
+        assert_eq!(link("pf0hpf").kind, Kind::Port);
+    }
+
+    #[test]
+    fn skip_invalid_attachments() {
+        let mut pending = vpc("v2", 0);
+        pending.spec.fabric = Some("f1".to_string());
+        let vpcs = [vpc("v1", 100), pending];
+        let subnets = [
+            subnet("s1", "v1", "10.0.1.0/24", "10.0.1.1", Some(10)),
+            subnet("s2", "v2", "10.0.2.0/24", "10.0.2.1", Some(20)),
+            subnet("s3", "v3", "10.0.3.0/24", "10.0.3.1", Some(30)),
+            subnet("s4", "v1", "10.0.4.0/24", "10.0.4.300", Some(40)),
+        ];
+        let attachments = [
+            attachment("s0", "eth0", false),
+            attachment("s2", "eth0", false),
+            attachment("s3", "eth0", false),
+            attachment("s4", "eth0", false),
+            attachment("s1", "enp129s0f0np0", false),
+            attachment("s1", "eth0", false),
+        ];
+        let (dataplane, errors) = desired(&vpcs, &subnets, &attachments, None);
+
+        let indexes: Vec<_> = errors.iter().map(|(i, _)| *i).collect();
+        assert_eq!(indexes, vec![0, 1, 2, 3, 4]);
+        assert!(errors[1].1.contains("not allocated yet"));
+        assert!(errors[4].1.contains("too long"));
+        assert_eq!(
+            names(&dataplane),
+            vec!["yzvrf100", "yzbr100", "yzvx100", "eth0.10"]
+        );
+    }
+
+    #[test]
+    fn parse_macs() {
+        assert_eq!(parse_mac("02:00:00:00:00:0a"), Some([2, 0, 0, 0, 0, 10]));
+        assert_eq!(parse_mac("02:00:00:00:00"), None);
+        assert_eq!(parse_mac("02:00:00:00:00:0g"), None);
+    }
+}
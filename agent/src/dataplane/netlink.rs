@@ -0,0 +1,643 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use futures::TryStreamExt;
+use netlink_packet_core::{NetlinkBuffer, NetlinkMessage, NLM_F_DUMP, NLM_F_REQUEST};
+use netlink_packet_route::{
+    nlas::address::Nla as AddressNla,
+    nlas::link::{Info, InfoData, InfoKind, InfoVlan, InfoVrf, InfoVxlan, Nla},
+    AddressMessage, LinkMessage, LinkMessageBuffer, RtnlMessage, IFF_UP, IFLA_IFALIAS, IFLA_IFNAME,
+    IFLA_INFO_DATA, IFLA_INFO_KIND, IFLA_LINK, IFLA_LINKINFO, IFLA_MASTER, IFLA_MTU,
+    IFLA_VFINFO_LIST, IFLA_VLAN_ID, IFLA_VRF_TABLE, IFLA_VXLAN_ID, IFLA_VXLAN_LOCAL,
+    RTEXT_FILTER_VF, RTM_NEWLINK,
+};
+use netlink_packet_utils::{nla::NlasIterator, DecodeError};
+use netlink_sys::{protocols::NETLINK_ROUTE, Socket, SocketAddr};
+use rtnetlink::Handle;
+
+use crate::dataplane::{Dataplane, Kind, Link, VXLAN_PORT};
+
+/// The alias marking the links created by the agent, which are removed once
+/// no longer desired.
+const ALIAS: &str = "yangtze";
+
+/// The attributes of a VF in IFLA_VFINFO_LIST, from `if_link.h`.
+const IFLA_VF_INFO: u16 = 1;
+const IFLA_VF_MAC: u16 = 1;
+const IFLA_VF_VLAN: u16 = 2;
+
+/// A link of the host as dumped by netlink.
+struct Observed {
+    index: u32,
+    kind: Option<Kind>,
+    /// The lower link of a VLAN.
+    parent: Option<u32>,
+    master: Option<u32>,
+    up: bool,
+    owned: bool,
+    mtu: Option<u32>,
+    /// The MACs and VLANs of the VFs by their index.
+    vfs: HashMap<u32, ([u8; 6], u16)>,
+}
+
+pub fn connect() -> io::Result<Handle> {
+    let (connection, handle, _) = rtnetlink::new_connection()?;
+    tokio::spawn(connection);
+    Ok(handle)
+}
+
+/// Creates the missing links of the Dataplane, fixes the masters, states and
+/// addresses of the existing ones, deletes the owned links not desired and
+/// releases the ports no longer desired from the owned bridges.
+pub async fn apply(handle: &Handle, dataplane: &Dataplane) -> Result<(), String> {
+    let err = |e: rtnetlink::Error| e.to_string();
+    let io_err = |e: io::Error| format!("failed to dump the links: {}", e);
+
+    let mut links = dump().map_err(io_err)?;
+
+    for l in &dataplane.links {
+        match links.get(&l.name) {
+            Some(o) if matches(&links, o, &l.kind) => {}
+            Some(o) if o.owned => {
+                tracing::info!("Recreate the link <{}> as {:?}.", l.name, l.kind);
+                handle.link().del(o.index).execute().await.map_err(err)?;
+                create(handle, &links, l).await?;
+                links = dump().map_err(io_err)?;
+            }
+            Some(_) => {
+                return Err(format!(
+                    "the link <{}> exists and is not managed by the agent",
+                    l.name
+                ));
+            }
+            None if l.kind == Kind::Port => {
+                return Err(format!("the port <{}> not found", l.name));
+            }
+            None => {
+                tracing::info!("Create the link <{}> as {:?}.", l.name, l.kind);
+                create(handle, &links, l).await?;
+                links = dump().map_err(io_err)?;
+            }
+        }
+
+        let Some(o) = links.get(&l.name) else {
+            return Err(format!("the link <{}> was not created", l.name));
+        };
+        let master = match &l.master {
+            Some(m) => match links.get(m) {
+                Some(m) => Some(m.index),
+                None => return Err(format!("the master <{}> of <{}> not found", m, l.name)),
+            },
+            None => None,
+        };
+        if o.master != master || !o.up {
+            let mut req = handle.link().set(o.index).up();
+            req = match master {
+                Some(m) => req.master(m),
+                None => req.nomaster(),
+            };
+            req.execute().await.map_err(err)?;
+        }
+
+        addresses(handle, o.index, &l.addresses)
+            .await
+            .map_err(err)?;
+    }
+
+    let owned: Vec<u32> = links
+        .values()
+        .filter(|o| o.owned)
+        .map(|o| o.index)
+        .collect();
+    for (name, o) in &links {
+        if dataplane.links.iter().any(|l| &l.name == name) {
+            continue;
+        }
+        if o.owned {
+            tracing::info!("Delete the link <{}>.", name);
+            handle.link().del(o.index).execute().await.map_err(err)?;
+        } else if o.master.is_some_and(|m| owned.contains(&m)) {
+            tracing::info!("Release the port <{}>.", name);
+            handle
+                .link()
+                .set(o.index)
+                .nomaster()
+                .execute()
+                .await
+                .map_err(err)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sets the MTU of a link.
+pub async fn set_mtu(handle: &Handle, name: &str, mtu: u32) -> Result<(), String> {
+    let links = dump().map_err(|e| format!("failed to dump the links: {}", e))?;
+    let Some(o) = links.get(name) else {
+        return Err(format!("the link <{}> not found", name));
+    };
+    if o.mtu == Some(mtu) {
+        return Ok(());
+    }
+
+    tracing::info!("Set the MTU of <{}> to {}.", name, mtu);
+    handle
+        .link()
+        .set(o.index)
+        .mtu(mtu)
+        .execute()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Sets the MAC and VLAN of a VF of a physical function, whose MTU is raised
+/// to the one of the VF if lower.
+pub async fn set_vf(
+    handle: &Handle,
+    pf: &str,
+    vf: u32,
+    mac: [u8; 6],
+    vlan: u16,
+    mtu: u32,
+) -> Result<(), String> {
+    let links = dump().map_err(|e| format!("failed to dump the links: {}", e))?;
+    let Some(o) = links.get(pf) else {
+        return Err(format!("the physical function <{}> not found", pf));
+    };
+    if o.mtu.is_some_and(|m| m < mtu) {
+        set_mtu(handle, pf, mtu).await?;
+    }
+    if o.vfs.get(&vf) == Some(&(mac, vlan)) {
+        return Ok(());
+    }
+    if !o.vfs.contains_key(&vf) {
+        return Err(format!("the VF {} of <{}> not found", vf, pf));
+    }
+
+    // struct ifla_vf_mac and struct ifla_vf_vlan of `if_link.h`.
+    let mut vf_mac = vf.to_ne_bytes().to_vec();
+    vf_mac.extend(mac);
+    vf_mac.resize(4 + 32, 0);
+    let mut vf_vlan = vf.to_ne_bytes().to_vec();
+    vf_vlan.extend((vlan as u32).to_ne_bytes());
+    vf_vlan.extend(0u32.to_ne_bytes());
+
+    let mut info = nla(IFLA_VF_MAC, &vf_mac);
+    info.extend(nla(IFLA_VF_VLAN, &vf_vlan));
+
+    tracing::info!(
+        "Set the MAC and VLAN {} of the VF {} of <{}>.",
+        vlan,
+        vf,
+        pf
+    );
+    let mut req = handle.link().set(o.index);
+    req.message_mut()
+        .nlas
+        .push(Nla::VfInfoList(nla(IFLA_VF_INFO, &info)));
+    req.execute().await.map_err(|e| e.to_string())
+}
+
+/// Encodes an attribute, padded to 4 bytes.
+fn nla(kind: u16, value: &[u8]) -> Vec<u8> {
+    let mut buf = ((4 + value.len()) as u16).to_ne_bytes().to_vec();
+    buf.extend(kind.to_ne_bytes());
+    buf.extend(value);
+    buf.resize((buf.len() + 3) & !3, 0);
+    buf
+}
+
+/// Whether a link is of the kind; the parent of a VLAN is matched by index,
+/// and a port by its name only.
+fn matches(links: &HashMap<String, Observed>, o: &Observed, kind: &Kind) -> bool {
+    match (&o.kind, kind) {
+        (_, Kind::Port) => true,
+        (Some(Kind::Vlan { id: a, .. }), Kind::Vlan { parent, id: b }) => {
+            a == b && o.parent.is_some() && o.parent == links.get(parent).map(|l| l.index)
+        }
+        (Some(k), _) => k == kind,
+        (None, _) => false,
+    }
+}
+
+async fn create(
+    handle: &Handle,
+    links: &HashMap<String, Observed>,
+    l: &Link,
+) -> Result<(), String> {
+    let mut req = handle.link().add();
+    let message = req.message_mut();
+    message.nlas.push(Nla::IfName(l.name.clone()));
+
+    let (kind, data) = match &l.kind {
+        Kind::Vrf { table } => (
+            InfoKind::Vrf,
+            Some(InfoData::Vrf(vec![InfoVrf::TableId(*table)])),
+        ),
+        Kind::Bridge => (InfoKind::Bridge, None),
+        Kind::Vxlan { vni, local } => {
+            let mut data = vec![
+                InfoVxlan::Id(*vni),
+                InfoVxlan::Port(VXLAN_PORT),
+                // The remote MACs are learned by EVPN instead.
+                InfoVxlan::Learning(0),
+            ];
+            if let Some(local) = local {
+                data.push(InfoVxlan::Local(local.octets().to_vec()));
+            }
+            (InfoKind::Vxlan, Some(InfoData::Vxlan(data)))
+        }
+        Kind::Vlan { parent, id } => {
+            let Some(p) = links.get(parent) else {
+                return Err(format!("the uplink <{}> of <{}> not found", parent, l.name));
+            };
+            message.nlas.push(Nla::Link(p.index));
+            (
+                InfoKind::Vlan,
+                Some(InfoData::Vlan(vec![InfoVlan::Id(*id)])),
+            )
+        }
+        Kind::Port => return Err(format!("the port <{}> not found", l.name)),
+    };
+    let mut info = vec![Info::Kind(kind)];
+    info.extend(data.map(Info::Data));
+    message.nlas.push(Nla::Info(info));
+
+    req.execute().await.map_err(|e| e.to_string())?;
+
+    // The alias is only taken by the changes of a link, not its creation.
+    let created = dump().map_err(|e| format!("failed to dump the links: {}", e))?;
+    let Some(c) = created.get(&l.name) else {
+        return Err(format!("the link <{}> was not created", l.name));
+    };
+    let mut req = handle.link().set(c.index);
+    req.message_mut().nlas.push(Nla::IfAlias(ALIAS.to_string()));
+    req.execute().await.map_err(|e| e.to_string())
+}
+
+/// Adds the missing addresses of a link and removes the others.
+async fn addresses(
+    handle: &Handle,
+    index: u32,
+    desired: &[(IpAddr, u8)],
+) -> Result<(), rtnetlink::Error> {
+    let observed: Vec<AddressMessage> = handle
+        .address()
+        .get()
+        .set_link_index_filter(index)
+        .execute()
+        .try_collect()
+        .await?;
+
+    let mut found = vec![];
+    for m in observed {
+        let Some(ip) = address(&m) else {
+            continue;
+        };
+        // Keep the link-local addresses of IPv6.
+        if matches!(ip, IpAddr::V6(v6) if v6.segments()[0] & 0xffc0 == 0xfe80) {
+            continue;
+        }
+        if desired.contains(&(ip, m.header.prefix_len)) {
+            found.push((ip, m.header.prefix_len));
+        } else {
+            tracing::info!("Remove the address <{}/{}>.", ip, m.header.prefix_len);
+            handle.address().del(m).execute().await?;
+        }
+    }
+
+    for (ip, prefix) in desired.iter().filter(|a| !found.contains(a)) {
+        tracing::info!("Add the address <{}/{}>.", ip, prefix);
+        handle.address().add(index, *ip, *prefix).execute().await?;
+    }
+
+    Ok(())
+}
+
+fn address(m: &AddressMessage) -> Option<IpAddr> {
+    m.nlas.iter().find_map(|n| match n {
+        AddressNla::Address(b) if b.len() == 4 => {
+            Some(IpAddr::V4(Ipv4Addr::new(b[0], b[1], b[2], b[3])))
+        }
+        AddressNla::Address(b) if b.len() == 16 => {
+            let octets: [u8; 16] = b.as_slice().try_into().ok()?;
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    })
+}
+
+/// Dumps the links through a netlink socket of its own and reads the
+/// attributes one by one, as netlink-packet-route fails on the attributes
+/// unknown to it, e.g. those of the VXLAN links of the recent kernels.
+fn dump() -> io::Result<HashMap<String, Observed>> {
+    let mut socket = Socket::new(NETLINK_ROUTE)?;
+    socket.bind_auto()?;
+    socket.connect(&SocketAddr::new(0, 0))?;
+
+    let mut message = LinkMessage::default();
+    message.nlas.push(Nla::ExtMask(RTEXT_FILTER_VF));
+    let mut req = NetlinkMessage::from(RtnlMessage::GetLink(message));
+    req.header.flags = NLM_F_REQUEST | NLM_F_DUMP;
+    req.finalize();
+    let mut buf = vec![0; req.buffer_len()];
+    req.serialize(&mut buf);
+    socket.send(&buf, 0)?;
+
+    let invalid = |e: DecodeError| io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+
+    let mut links = HashMap::new();
+    loop {
+        let (buf, _) = socket.recv_from_full()?;
+        let mut offset = 0;
+        while offset < buf.len() {
+            let msg = NetlinkBuffer::new_checked(&buf[offset..]).map_err(invalid)?;
+            // The messages are aligned to 4 bytes.
+            offset += (msg.length() as usize + 3) & !3;
+
+            match msg.message_type() as libc::c_int {
+                libc::NLMSG_DONE => return Ok(links),
+                libc::NLMSG_ERROR => {
+                    let code = msg
+                        .payload()
+                        .get(..4)
+                        .map_or(0, |b| i32::from_ne_bytes([b[0], b[1], b[2], b[3]]));
+                    return Err(io::Error::from_raw_os_error(-code));
+                }
+                _ if msg.message_type() == RTM_NEWLINK => {
+                    if let Some((name, o)) = observe(msg.payload()).map_err(invalid)? {
+                        links.insert(name, o);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn observe(payload: &[u8]) -> Result<Option<(String, Observed)>, DecodeError> {
+    let buf = LinkMessageBuffer::new_checked(payload)?;
+    let mut name = None;
+    let mut observed = Observed {
+        index: buf.link_index(),
+        kind: None,
+        parent: None,
+        master: None,
+        up: buf.flags() & IFF_UP != 0,
+        owned: false,
+        mtu: None,
+        vfs: HashMap::new(),
+    };
+
+    for nla in buf.nlas() {
+        let nla = nla?;
+        let value = nla.value();
+        match nla.kind() {
+            IFLA_IFNAME => name = Some(string(value)),
+            IFLA_IFALIAS => observed.owned = string(value) == ALIAS,
+            IFLA_MASTER => observed.master = u32_of(value),
+            IFLA_LINK => observed.parent = u32_of(value),
+            IFLA_LINKINFO => observed.kind = kind(value)?,
+            IFLA_MTU => observed.mtu = u32_of(value),
+            IFLA_VFINFO_LIST => observed.vfs = vfs(value)?,
+            _ => {}
+        }
+    }
+
+    Ok(name.map(|n| (n, observed)))
+}
+
+/// Reads the kind of a link and the settings compared from IFLA_LINKINFO.
+fn kind(info: &[u8]) -> Result<Option<Kind>, DecodeError> {
+    let mut kind = None;
+    let mut data = vec![];
+    for nla in NlasIterator::new(info) {
+        let nla = nla?;
+        match nla.kind() {
+            IFLA_INFO_KIND => kind = Some(string(nla.value())),
+            IFLA_INFO_DATA => data = nla.value().to_vec(),
+            _ => {}
+        }
+    }
+
+    let mut attrs = HashMap::new();
+    for nla in NlasIterator::new(&data) {
+        let nla = nla?;
+        attrs.insert(nla.kind(), nla.value().to_vec());
+    }
+
+    Ok(match kind.as_deref() {
+        Some("vrf") => attrs
+            .get(&IFLA_VRF_TABLE)
+            .and_then(|v| u32_of(v))
+            .map(|table| Kind::Vrf { table }),
+        Some("bridge") => Some(Kind::Bridge),
+        Some("vxlan") => attrs
+            .get(&IFLA_VXLAN_ID)
+            .and_then(|v| u32_of(v))
+            .map(|vni| Kind::Vxlan {
+                vni,
+                local: attrs
+                    .get(&IFLA_VXLAN_LOCAL)
+                    .filter(|b| b.len() == 4)
+                    .map(|b| Ipv4Addr::new(b[0], b[1], b[2], b[3])),
+            }),
+        // The parent is matched by its index in `Observed`.
+        Some("vlan") => attrs
+            .get(&IFLA_VLAN_ID)
+            .filter(|b| b.len() >= 2)
+            .map(|b| Kind::Vlan {
+                parent: String::new(),
+                id: u16::from_ne_bytes([b[0], b[1]]),
+            }),
+        _ => None,
+    })
+}
+
+/// Reads the MACs and VLANs of the VFs from IFLA_VFINFO_LIST.
+fn vfs(list: &[u8]) -> Result<HashMap<u32, ([u8; 6], u16)>, DecodeError> {
+    let mut vfs = HashMap::new();
+    for info in NlasIterator::new(list) {
+        let info = info?;
+        if info.kind() != IFLA_VF_INFO {
+            continue;
+        }
+
+        let (mut index, mut mac, mut vlan) = (None, [0; 6], 0);
+        for nla in NlasIterator::new(info.value()) {
+            let nla = nla?;
+            let value = nla.value();
+            match nla.kind() {
+                IFLA_VF_MAC if value.len() >= 10 => {
+                    index = u32_of(value);
+                    mac.copy_from_slice(&value[4..10]);
+                }
+                IFLA_VF_VLAN => vlan = value.get(4..).and_then(u32_of).unwrap_or(0) as u16,
+                _ => {}
+            }
+        }
+        if let Some(index) = index {
+            vfs.insert(index, (mac, vlan));
+        }
+    }
+
+    Ok(vfs)
+}
+
+fn string(value: &[u8]) -> String {
+    String::from_utf8_lossy(value)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+fn u32_of(value: &[u8]) -> Option<u32> {
+    let b: [u8; 4] = value.get(..4)?.try_into().ok()?;
+    Some(u32::from_ne_bytes(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+
+    use netlink_packet_route::{nlas::route::Nla as RouteNla, RouteMessage};
+    use rtnetlink::IpVersion;
+
+    use super::*;
+    use crate::dataplane::{desired, fixtures::*};
+
+    /// Runs the test in a throwaway network namespace, on a thread of its
+    /// own as only the calling thread is moved to the namespace; needs root
+    /// and the vrf, 8021q and vxlan modules.
+    fn in_netns<F, T>(test: F)
+    where
+        F: FnOnce(Handle) -> T + Send + 'static,
+        T: Future<Output = ()>,
+    {
+        std::thread::spawn(|| {
+            assert_eq!(unsafe { libc::unshare(libc::CLONE_NEWNET) }, 0);
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            runtime.block_on(async {
+                let handle = connect().unwrap();
+                test(handle).await
+            })
+        })
+        .join()
+        .unwrap();
+    }
+
+    async fn routes(handle: &Handle, table: u32) -> Vec<String> {
+        let routes: Vec<RouteMessage> = handle
+            .route()
+            .get(IpVersion::V4)
+            .execute()
+            .try_collect()
+            .await
+            .unwrap();
+        routes
+            .iter()
+            .filter(|r| r.nlas.iter().any(|n| n == &RouteNla::Table(table)))
+            .filter_map(|r| {
+                r.nlas.iter().find_map(|n| match n {
+                    RouteNla::Destination(b) if b.len() == 4 => Some(format!(
+                        "{}/{}",
+                        Ipv4Addr::new(b[0], b[1], b[2], b[3]),
+                        r.header.destination_prefix_length
+                    )),
+                    _ => None,
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    #[ignore = "needs root to create a network namespace"]
+    fn apply_vpc_in_netns() {
+        in_netns(|handle| async move {
+            // The uplink and the representor of a host.
+            for (name, peer) in [("up0", "up0p"), ("rep0", "rep0p")] {
+                handle
+                    .link()
+                    .add()
+                    .veth(name.to_string(), peer.to_string())
+                    .execute()
+                    .await
+                    .unwrap();
+            }
+
+            let vpcs = [vpc("v1", 100)];
+            let subnets = [
+                subnet("s1", "v1", "10.0.1.0/24", "10.0.1.1", Some(10)),
+                subnet("s2", "v1", "10.0.2.0/24", "10.0.2.1", None),
+            ];
+            let attachments = [
+                attachment("s1", "up0", false),
+                attachment("s2", "rep0", true),
+            ];
+            let vtep = Some(Ipv4Addr::new(10, 255, 0, 1));
+            let (dataplane, errors) = desired(&vpcs, &subnets, &attachments, vtep);
+            assert!(errors.is_empty());
+
+            apply(&handle, &dataplane).await.unwrap();
+            let links = dump().unwrap();
+            let link = |name: &str| links.get(name).unwrap();
+            let index = |name: &str| Some(link(name).index);
+            assert_eq!(link("yzvrf100").kind, Some(Kind::Vrf { table: 1100 }));
+            assert_eq!(link("yzbr100").kind, Some(Kind::Bridge));
+            assert_eq!(
+                link("yzvx100").kind,
+                Some(Kind::Vxlan {
+                    vni: 100,
+                    local: vtep
+                })
+            );
+            assert_eq!(link("up0.10").parent, index("up0"));
+            assert_eq!(link("yzbr100").master, index("yzvrf100"));
+            for name in ["yzvx100", "up0.10", "rep0"] {
+                assert_eq!(link(name).master, index("yzbr100"), "{}", name);
+            }
+            for name in ["yzvrf100", "yzbr100", "yzvx100", "up0.10"] {
+                assert!(link(name).owned && link(name).up, "{}", name);
+            }
+            assert!(!link("rep0").owned && !link("up0").owned);
+
+            // The gateways route the Subnets in the table of the Vpc.
+            let table = routes(&handle, 1100).await;
+            assert!(table.contains(&"10.0.1.0/24".to_string()), "{:?}", table);
+            assert!(table.contains(&"10.0.2.0/24".to_string()), "{:?}", table);
+
+            // Nothing is recreated once applied.
+            apply(&handle, &dataplane).await.unwrap();
+            let again = dump().unwrap();
+            for name in ["yzvrf100", "yzbr100", "yzvx100", "up0.10"] {
+                assert_eq!(again.get(name).unwrap().index, link(name).index);
+            }
+
+            // The owned links are removed and the representor released.
+            apply(&handle, &Dataplane::default()).await.unwrap();
+            let links = dump().unwrap();
+            for name in ["yzvrf100", "yzbr100", "yzvx100", "up0.10"] {
+                assert!(!links.contains_key(name), "{}", name);
+            }
+            assert_eq!(links.get("rep0").unwrap().master, None);
+            assert!(links.contains_key("up0"));
+        });
+    }
+}
@@ -0,0 +1,185 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use yangtze_apis::v1alpha1::nvme_port::NvmeTransport;
+
+/// The NQN of the discovery controllers, which are not attachments.
+const DISCOVERY_NQN: &str = "nqn.2014-08.org.nvmexpress.discovery";
+
+/// A controller of the host connected to an NVMe-oF subsystem.
+#[derive(Clone, Debug)]
+pub struct Controller {
+    /// e.g. `nvme1`.
+    pub name: String,
+    pub nqn: String,
+    /// The NQN of the host the controller was connected as.
+    pub hostnqn: String,
+    pub transport: NvmeTransport,
+    pub traddr: String,
+    pub trsvcid: String,
+    pub state: String,
+}
+
+/// The NVMe over Fabrics host of the kernel: connects through
+/// `/dev/nvme-fabrics`, as `nvme connect` does, and reads the controllers
+/// and namespaces from sysfs.
+pub struct Fabrics {
+    /// e.g. `/sys/class`.
+    pub sysfs: PathBuf,
+    /// e.g. `/dev/nvme-fabrics`.
+    pub device: PathBuf,
+}
+
+impl Fabrics {
+    /// The controllers connected over TCP or RDMA, except the discovery ones.
+    pub fn controllers(&self) -> Vec<Controller> {
+        let Ok(entries) = fs::read_dir(self.sysfs.join("nvme")) else {
+            return vec![];
+        };
+
+        let mut controllers: Vec<Controller> = entries
+            .flatten()
+            .filter_map(|e| {
+                let dir = e.path();
+                let transport = match read(&dir, "transport").as_str() {
+                    "tcp" => NvmeTransport::Tcp,
+                    "rdma" => NvmeTransport::Rdma,
+                    _ => return None,
+                };
+                let nqn = read(&dir, "subsysnqn");
+                if nqn == DISCOVERY_NQN {
+                    return None;
+                }
+
+                let address = read(&dir, "address");
+                let field = |key: &str| {
+                    address
+                        .split(',')
+                        .filter_map(|kv| kv.split_once('='))
+                        .find(|(k, _)| *k == key)
+                        .map(|(_, v)| v.to_string())
+                        .unwrap_or_default()
+                };
+
+                Some(Controller {
+                    name: e.file_name().to_string_lossy().to_string(),
+                    nqn,
+                    hostnqn: read(&dir, "hostnqn"),
+                    transport,
+                    traddr: field("traddr"),
+                    trsvcid: field("trsvcid"),
+                    state: read(&dir, "state"),
+                })
+            })
+            .collect();
+        controllers.sort_by(|a, b| a.name.cmp(&b.name));
+
+        controllers
+    }
+
+    pub fn connect(
+        &self,
+        transport: NvmeTransport,
+        traddr: &str,
+        trsvcid: u16,
+        nqn: &str,
+        host_nqn: &str,
+    ) -> io::Result<()> {
+        let options = format!(
+            "nqn={},transport={},traddr={},trsvcid={},hostnqn={}",
+            nqn, transport, traddr, trsvcid, host_nqn
+        );
+
+        let mut f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.device)?;
+        f.write_all(options.as_bytes())?;
+
+        // The kernel replies with the instance of the new controller.
+        let mut reply = String::new();
+        let _ = f.read_to_string(&mut reply);
+        tracing::info!(
+            "Connected to <{}> at {} {}:{}.",
+            nqn,
+            transport,
+            traddr,
+            trsvcid
+        );
+        tracing::debug!("The reply of {}: {}", self.device.display(), reply.trim());
+
+        Ok(())
+    }
+
+    pub fn disconnect(&self, controller: &str) -> io::Result<()> {
+        let path = self
+            .sysfs
+            .join("nvme")
+            .join(controller)
+            .join("delete_controller");
+        fs::write(path, "1")?;
+        tracing::info!("Disconnected controller <{}>.", controller);
+
+        Ok(())
+    }
+
+    /// The block device of a namespace of the subsystem, the multipath one
+    /// of the subsystem if any, e.g. `/dev/nvme1n1`.
+    pub fn device(&self, nqn: &str, nsid: u32) -> Option<String> {
+        let subsystems = fs::read_dir(self.sysfs.join("nvme-subsystem"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|e| e.path())
+            .filter(|dir| read(dir, "subsysnqn") == nqn);
+        let controllers = self
+            .controllers()
+            .into_iter()
+            .filter(|c| c.nqn == nqn)
+            .map(|c| self.sysfs.join("nvme").join(c.name));
+
+        subsystems
+            .chain(controllers)
+            .find_map(|dir| namespace(&dir, nsid))
+            .map(|name| format!("/dev/{}", name))
+    }
+}
+
+/// The block device of the namespace in a subsystem or controller directory,
+/// e.g. `nvme1n1`; the hidden per-path ones, e.g. `nvme1c1n1`, are skipped.
+fn namespace(dir: &Path, nsid: u32) -> Option<String> {
+    fs::read_dir(dir).ok()?.flatten().find_map(|e| {
+        let name = e.file_name().to_string_lossy().to_string();
+        let (prefix, id) = name.strip_prefix("nvme")?.split_once('n')?;
+        if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let id = match read(&e.path(), "nsid").parse::<u32>() {
+            Ok(id) => id,
+            Err(_) => id.parse().ok()?,
+        };
+
+        (id == nsid).then_some(name)
+    })
+}
+
+fn read(dir: &Path, attr: &str) -> String {
+    fs::read_to_string(dir.join(attr))
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,366 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+
+use yangtze_apis::{
+    v1::{NamespaceName, YangtzeError},
+    v1alpha1::{
+        nvme_namespace::{self, NvmeNamespace},
+        nvme_port::{self, NvmePort},
+        nvme_subsystem::{self, NvmeSubsystem},
+        volume_attachment::{
+            self, NvmePath, VolumeAttachment, VolumeAttachmentState, VolumeAttachmentStatus,
+        },
+    },
+};
+use yangtze_client::YangtzeClient;
+
+use crate::inventory;
+
+mod fabrics;
+
+use fabrics::{Controller, Fabrics};
+
+/// Connects the Node to the NvmeNamespaces of its VolumeAttachments through
+/// every NvmePort exposing their subsystem, and disconnects the subsystems
+/// no longer attached; only the controllers of the host NQN to the
+/// NvmeSubsystems, or to the subsystems it attached, are disconnected.
+pub struct Initiator {
+    pub client: YangtzeClient,
+    pub namespace: String,
+    pub node: String,
+    pub host_root: PathBuf,
+    /// The fabrics device, `/dev/nvme-fabrics` or a fake file.
+    pub fabrics: PathBuf,
+    pub interval: u64,
+}
+
+/// The objects of the target of an attachment.
+struct Objects {
+    namespaces: Vec<NvmeNamespace>,
+    subsystems: Vec<NvmeSubsystem>,
+    ports: Vec<NvmePort>,
+}
+
+impl Initiator {
+    pub async fn run(self) {
+        let fabrics = Fabrics {
+            sysfs: self.host_root.join("sys/class"),
+            device: self.fabrics.clone(),
+        };
+
+        // The subsystems attached since the agent started, whose NvmeSubsystem
+        // may be deleted with the attachments.
+        let mut subsystems = HashSet::new();
+        loop {
+            if let Err(e) = self.reconcile(&fabrics, &mut subsystems).await {
+                tracing::error!("Failed to reconcile the VolumeAttachments: {}", e);
+            }
+            tokio::time::sleep(Duration::from_secs(self.interval)).await;
+        }
+    }
+
+    async fn reconcile(
+        &self,
+        fabrics: &Fabrics,
+        subsystems: &mut HashSet<String>,
+    ) -> Result<(), YangtzeError> {
+        let client = self.client(volume_attachment::VERSION_KIND.kind);
+        let attachments: Vec<VolumeAttachment> = self
+            .list(&client)
+            .await?
+            .into_iter()
+            .filter(|a: &VolumeAttachment| a.spec.node == self.node)
+            .collect();
+        if attachments.is_empty() && fabrics.controllers().is_empty() {
+            return Ok(());
+        }
+
+        let objects = Objects {
+            namespaces: self
+                .list(&self.client(nvme_namespace::VERSION_KIND.kind))
+                .await?,
+            subsystems: self
+                .list(&self.client(nvme_subsystem::VERSION_KIND.kind))
+                .await?,
+            ports: self
+                .list(&self.client(nvme_port::VERSION_KIND.kind))
+                .await?,
+        };
+        let host_nqn = self.host_nqn();
+
+        let mut attached = vec![];
+        for a in attachments {
+            let status = match &host_nqn {
+                Some(host_nqn) => self.attach(fabrics, &objects, &a, host_nqn, &mut attached),
+                None => error("no host NQN of the Node".to_string()),
+            };
+            if a.status.as_ref() == Some(&status) {
+                continue;
+            }
+
+            if a.status.as_ref().map(|s| &s.state) != Some(&status.state) {
+                tracing::info!(
+                    "VolumeAttachment <{}> is {} {}",
+                    a,
+                    status.state,
+                    status.reason.clone().unwrap_or_default()
+                );
+            }
+
+            let mut a = a;
+            a.status = Some(status);
+            if let Err(e) = client.update(a).await {
+                tracing::error!("Failed to update the VolumeAttachment: {}", e);
+            }
+        }
+
+        // The controllers of other hosts can not be told apart.
+        let Some(host_nqn) = host_nqn else {
+            return Ok(());
+        };
+        subsystems.extend(attached.iter().cloned());
+        subsystems.extend(objects.subsystems.iter().map(|s| s.spec.nqn.clone()));
+        for c in stale(fabrics.controllers(), &host_nqn, subsystems, &attached) {
+            if let Err(e) = fabrics.disconnect(&c.name) {
+                tracing::error!("Failed to disconnect <{}>: {}", c.name, e);
+            }
+        }
+        let controllers = fabrics.controllers();
+        subsystems.retain(|nqn| {
+            attached.contains(nqn)
+                || controllers
+                    .iter()
+                    .any(|c| &c.nqn == nqn && c.hostnqn == host_nqn)
+        });
+
+        Ok(())
+    }
+
+    /// Connects the namespace of an attachment; the NQN of its subsystem is
+    /// added to `attached`.
+    fn attach(
+        &self,
+        fabrics: &Fabrics,
+        objects: &Objects,
+        a: &VolumeAttachment,
+        host_nqn: &str,
+        attached: &mut Vec<String>,
+    ) -> VolumeAttachmentStatus {
+        let Some(ns) = objects
+            .namespaces
+            .iter()
+            .find(|n| n.meta_data.name == a.spec.namespace)
+        else {
+            return error(format!("NvmeNamespace <{}> not found", a.spec.namespace));
+        };
+        let Some(s) = objects
+            .subsystems
+            .iter()
+            .find(|s| s.meta_data.name == ns.spec.subsystem)
+        else {
+            return error(format!("NvmeSubsystem <{}> not found", ns.spec.subsystem));
+        };
+        let nqn = &s.spec.nqn;
+        attached.push(nqn.clone());
+
+        let mut status = VolumeAttachmentStatus {
+            state: VolumeAttachmentState::Pending,
+            reason: None,
+            host_nqn: Some(host_nqn.to_string()),
+            device: None,
+            paths: vec![],
+            multipath: false,
+        };
+
+        let attached_hosts = s
+            .status
+            .as_ref()
+            .map(|s| s.attached_hosts.as_slice())
+            .unwrap_or_default();
+        let any = s.spec.allowed_hosts.is_empty() && attached_hosts.is_empty();
+        let allowed = s.spec.allowed_hosts.iter().chain(attached_hosts);
+        if !any && !allowed.into_iter().any(|h| h == host_nqn) {
+            status.reason = Some(format!(
+                "waiting for NvmeSubsystem <{}> to allow the host",
+                s
+            ));
+            return status;
+        }
+
+        let ports: Vec<&NvmePort> = objects
+            .ports
+            .iter()
+            .filter(|p| p.spec.subsystems.contains(&s.meta_data.name))
+            .collect();
+        if ports.is_empty() {
+            return error(format!("no NvmePort exposes NvmeSubsystem <{}>", s));
+        }
+
+        let controllers = fabrics.controllers();
+        let mut errors = vec![];
+        for p in &ports {
+            let connected = controllers.iter().any(|c| {
+                &c.nqn == nqn
+                    && c.transport == p.spec.transport
+                    && c.traddr == p.spec.address
+                    && c.trsvcid == p.spec.service.to_string()
+            });
+            if connected {
+                continue;
+            }
+
+            let res = fabrics.connect(
+                p.spec.transport,
+                &p.spec.address,
+                p.spec.service,
+                nqn,
+                host_nqn,
+            );
+            if let Err(e) = res {
+                errors.push(format!(
+                    "failed to connect to {}:{}: {}",
+                    p.spec.address, p.spec.service, e
+                ));
+            }
+        }
+
+        status.paths = fabrics
+            .controllers()
+            .into_iter()
+            .filter(|c| &c.nqn == nqn)
+            .map(path)
+            .collect();
+        status.multipath = status.paths.iter().filter(|p| p.state == "live").count() > 1;
+        status.device = fabrics.device(nqn, ns.spec.nsid);
+        status.reason = (!errors.is_empty()).then(|| errors.join("; "));
+        status.state = match (&status.device, status.paths.is_empty()) {
+            (Some(_), _) => VolumeAttachmentState::Attached,
+            (None, true) if !errors.is_empty() => VolumeAttachmentState::Error,
+            (None, _) => VolumeAttachmentState::Attaching,
+        };
+
+        status
+    }
+
+    /// The NQN of the host, as `nvme gen-hostnqn` derives it from the DMI
+    /// UUID if `/etc/nvme/hostnqn` is missing.
+    fn host_nqn(&self) -> Option<String> {
+        if let Ok(nqn) = fs::read_to_string(self.host_root.join("etc/nvme/hostnqn")) {
+            return Some(nqn.trim().to_string());
+        }
+
+        let uuid = inventory::collect(&self.host_root).system.uuid;
+        (!uuid.is_empty()).then(|| format!("nqn.2014-08.org.nvmexpress:uuid:{}", uuid))
+    }
+
+    fn client(&self, kind: &str) -> YangtzeClient {
+        self.client.clone().version("v1alpha1").kind(kind)
+    }
+
+    async fn list<T: DeserializeOwned>(
+        &self,
+        client: &YangtzeClient,
+    ) -> Result<Vec<T>, YangtzeError> {
+        client
+            .list(NamespaceName {
+                namespace: Some(self.namespace.clone()),
+                name: None,
+            })
+            .await
+    }
+}
+
+/// The controllers of the host to the subsystems no longer attached.
+fn stale(
+    controllers: Vec<Controller>,
+    host_nqn: &str,
+    subsystems: &HashSet<String>,
+    attached: &[String],
+) -> Vec<Controller> {
+    controllers
+        .into_iter()
+        .filter(|c| {
+            c.hostnqn == host_nqn && subsystems.contains(&c.nqn) && !attached.contains(&c.nqn)
+        })
+        .collect()
+}
+
+fn path(c: Controller) -> NvmePath {
+    NvmePath {
+        controller: c.name,
+        transport: c.transport,
+        address: format!("{}:{}", c.traddr, c.trsvcid),
+        state: c.state,
+    }
+}
+
+fn error(reason: String) -> VolumeAttachmentStatus {
+    VolumeAttachmentStatus {
+        state: VolumeAttachmentState::Error,
+        reason: Some(reason),
+        host_nqn: None,
+        device: None,
+        paths: vec![],
+        multipath: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use yangtze_apis::v1alpha1::nvme_port::NvmeTransport;
+
+    use super::*;
+
+    const HOST_NQN: &str = "nqn.2014-08.org.nvmexpress:uuid:1";
+
+    fn controller(name: &str, nqn: &str, hostnqn: &str) -> Controller {
+        Controller {
+            name: name.to_string(),
+            nqn: nqn.to_string(),
+            hostnqn: hostnqn.to_string(),
+            transport: NvmeTransport::Tcp,
+            traddr: "10.0.0.1".to_string(),
+            trsvcid: "4420".to_string(),
+            state: "live".to_string(),
+        }
+    }
+
+    #[test]
+    fn disconnect_only_stale_controllers() {
+        let controllers = vec![
+            controller("nvme1", "nqn.yangtze:vol1", HOST_NQN),
+            controller("nvme2", "nqn.yangtze:vol2", HOST_NQN),
+            // Connected by others on the host.
+            controller("nvme3", "nqn.yangtze:vol2", "nqn.other"),
+            controller("nvme4", "nqn.array:lun1", HOST_NQN),
+        ];
+        let subsystems: HashSet<String> = ["nqn.yangtze:vol1", "nqn.yangtze:vol2"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let attached = vec!["nqn.yangtze:vol1".to_string()];
+
+        let names: Vec<String> = stale(controllers, HOST_NQN, &subsystems, &attached)
+            .into_iter()
+            .map(|c| c.name)
+            .collect();
+        assert_eq!(names, vec!["nvme2"]);
+    }
+}
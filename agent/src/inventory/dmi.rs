@@ -0,0 +1,47 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::path::Path;
+
+use yangtze_apis::v1alpha1::node::SystemInfo;
+
+use super::read_trimmed;
+
+pub fn system(root: &Path) -> SystemInfo {
+    let dmi = root.join("sys/class/dmi/id");
+    let read = |name: &str| read_trimmed(&dmi.join(name)).unwrap_or_default();
+
+    SystemInfo {
+        vendor: read("sys_vendor"),
+        product: read("product_name"),
+        serial: read("product_serial"),
+        uuid: read("product_uuid"),
+        bios_version: read("bios_version"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_from_fixture() {
+        let system = system(&super::super::fixture());
+
+        assert_eq!(system.vendor, "Supermicro");
+        assert_eq!(system.product, "SYS-1029U-TRT");
+        assert_eq!(system.serial, "S123456");
+        assert_eq!(system.uuid, "00000000-0000-0000-0000-3cecef000001");
+        assert_eq!(system.bios_version, "3.4");
+    }
+}
@@ -0,0 +1,125 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use yangtze_apis::v1alpha1::node::{Dpu, Nic, NvmeDevice, SystemInfo};
+
+mod dmi;
+mod net;
+mod nvme;
+mod pci;
+mod proc;
+
+/// The hardware of the host reported in the Node.
+#[derive(Clone, Debug, Default)]
+pub struct Inventory {
+    pub hostname: String,
+    pub system: SystemInfo,
+    pub cpu_model: String,
+    pub cpus: u32,
+    pub memory: u64,
+    pub nics: Vec<Nic>,
+    pub dpus: Vec<Dpu>,
+    pub nvmes: Vec<NvmeDevice>,
+}
+
+/// Collects the inventory from sysfs, DMI and procfs under `root`, which is
+/// `/` on the host, the host mount in a container, or a fixture tree.
+pub fn collect(root: &Path) -> Inventory {
+    let (cpu_model, cpus) = proc::cpus(root);
+
+    Inventory {
+        hostname: read_trimmed(&root.join("proc/sys/kernel/hostname")).unwrap_or_default(),
+        system: dmi::system(root),
+        cpu_model,
+        cpus,
+        memory: proc::memory(root),
+        nics: net::nics(root),
+        dpus: pci::dpus(root),
+        nvmes: nvme::devices(root),
+    }
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+fn read_number<T: std::str::FromStr>(path: &Path) -> Option<T> {
+    read_trimmed(path)?.parse().ok()
+}
+
+/// Parses the `KEY=VALUE` lines of an uevent file.
+fn uevent(path: &Path) -> HashMap<String, String> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|l| l.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+/// The sorted names of the entries of a directory.
+fn entries(path: &Path) -> Vec<String> {
+    let mut names: Vec<_> = fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+
+    names
+}
+
+/// The fixture tree of a host with a BlueField-2 DPU and an NVMe SSD.
+#[cfg(test)]
+fn fixture() -> std::path::PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sysfs")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collect_fixture() {
+        let inventory = collect(&fixture());
+
+        assert_eq!(inventory.hostname, "node1");
+        assert_eq!(inventory.system.vendor, "Supermicro");
+        assert_eq!(inventory.cpus, 2);
+        assert_eq!(inventory.memory, 16384000 * 1024);
+        assert_eq!(
+            inventory.nics.iter().map(|n| &n.name).collect::<Vec<_>>(),
+            ["eth0", "eth1"]
+        );
+        assert_eq!(inventory.dpus.len(), 1);
+        assert_eq!(inventory.nvmes.len(), 1);
+    }
+
+    #[test]
+    fn collect_missing_root() {
+        let inventory = collect(Path::new("/nonexistent"));
+
+        assert!(inventory.hostname.is_empty());
+        assert_eq!(inventory.cpus, 0);
+        assert!(inventory.nics.is_empty());
+        assert!(inventory.dpus.is_empty());
+        assert!(inventory.nvmes.is_empty());
+    }
+}
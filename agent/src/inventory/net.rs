@@ -0,0 +1,71 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::path::Path;
+
+use yangtze_apis::v1alpha1::node::Nic;
+
+use super::{entries, read_number, read_trimmed, uevent};
+
+/// The physical NICs, i.e. the interfaces backed by a PCI device.
+pub fn nics(root: &Path) -> Vec<Nic> {
+    let net = root.join("sys/class/net");
+
+    let mut nics = vec![];
+    for name in entries(&net) {
+        let dev = net.join(&name).join("device");
+        let uevent = uevent(&dev.join("uevent"));
+        let Some(pci_address) = uevent.get("PCI_SLOT_NAME") else {
+            continue;
+        };
+
+        nics.push(Nic {
+            mac: read_trimmed(&net.join(&name).join("address")).unwrap_or_default(),
+            pci_address: pci_address.clone(),
+            driver: uevent.get("DRIVER").cloned().unwrap_or_default(),
+            // The kernel reports -1 or fails to read the speed if the link is down.
+            speed: read_number::<i64>(&net.join(&name).join("speed"))
+                .filter(|s| *s > 0)
+                .map(|s| s as u32),
+            sriov_total_vfs: read_number(&dev.join("sriov_totalvfs")).unwrap_or_default(),
+            name,
+        });
+    }
+
+    nics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nics_from_fixture() {
+        let nics = nics(&super::super::fixture());
+
+        // lo has no PCI device.
+        assert_eq!(nics.len(), 2);
+
+        assert_eq!(nics[0].name, "eth0");
+        assert_eq!(nics[0].mac, "b8:ce:f6:00:00:01");
+        assert_eq!(nics[0].pci_address, "0000:03:00.0");
+        assert_eq!(nics[0].driver, "mlx5_core");
+        assert_eq!(nics[0].speed, Some(100000));
+        assert_eq!(nics[0].sriov_total_vfs, 16);
+
+        assert_eq!(nics[1].name, "eth1");
+        assert_eq!(nics[1].driver, "ixgbe");
+        assert_eq!(nics[1].speed, None);
+        assert_eq!(nics[1].sriov_total_vfs, 0);
+    }
+}
@@ -0,0 +1,70 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::path::Path;
+
+use yangtze_apis::v1alpha1::node::NvmeDevice;
+
+use super::{entries, read_number, read_trimmed, uevent};
+
+/// The size of the sectors in the `size` files of sysfs.
+const SECTOR_SIZE: u64 = 512;
+
+/// The NVMe controllers and the total size of their namespaces.
+pub fn devices(root: &Path) -> Vec<NvmeDevice> {
+    let class = root.join("sys/class/nvme");
+
+    let mut devices = vec![];
+    for name in entries(&class) {
+        let ctrl = class.join(&name);
+        let read = |f: &str| read_trimmed(&ctrl.join(f)).unwrap_or_default();
+
+        let size = entries(&ctrl)
+            .iter()
+            .filter(|ns| ns.starts_with(&format!("{}n", name)))
+            .filter_map(|ns| read_number::<u64>(&ctrl.join(ns).join("size")))
+            .map(|sectors| sectors * SECTOR_SIZE)
+            .sum();
+
+        devices.push(NvmeDevice {
+            model: read("model"),
+            serial: read("serial"),
+            firmware: read("firmware_rev"),
+            pci_address: uevent(&ctrl.join("device/uevent"))
+                .remove("PCI_SLOT_NAME")
+                .unwrap_or_default(),
+            size,
+            name,
+        });
+    }
+
+    devices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn devices_from_fixture() {
+        let devices = devices(&super::super::fixture());
+
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].name, "nvme0");
+        assert_eq!(devices[0].model, "SAMSUNG MZQL23T8HCLS-00A07");
+        assert_eq!(devices[0].serial, "S64HNE0R000001");
+        assert_eq!(devices[0].firmware, "GDC5602Q");
+        assert_eq!(devices[0].pci_address, "0000:81:00.0");
+        assert_eq!(devices[0].size, (2097152 + 1048576) * SECTOR_SIZE);
+    }
+}
@@ -0,0 +1,133 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use yangtze_apis::v1alpha1::{node::Dpu, xpu::RepresentorPort};
+
+use super::{entries, read_trimmed};
+
+const MELLANOX: &str = "0x15b3";
+
+/// The device ids of the network controllers integrated in BlueField DPUs.
+const BLUEFIELD: &[(&str, &str)] = &[
+    ("0xa2d2", "BlueField"),
+    ("0xa2d6", "BlueField-2"),
+    ("0xa2dc", "BlueField-3"),
+];
+
+/// The DPUs found on the PCI bus; the functions of a DPU share the same
+/// domain:bus:device, e.g. 0000:03:00.0 and 0000:03:00.1.
+pub fn dpus(root: &Path) -> Vec<Dpu> {
+    let devices = root.join("sys/bus/pci/devices");
+
+    let mut dpus: BTreeMap<String, Dpu> = BTreeMap::new();
+    for addr in entries(&devices) {
+        let dev = devices.join(&addr);
+        if read_trimmed(&dev.join("vendor")).as_deref() != Some(MELLANOX) {
+            continue;
+        }
+
+        let device = read_trimmed(&dev.join("device")).unwrap_or_default();
+        let Some((_, model)) = BLUEFIELD.iter().find(|(id, _)| *id == device) else {
+            continue;
+        };
+
+        let slot = addr
+            .rsplit_once('.')
+            .map(|(slot, _)| slot.to_string())
+            .unwrap_or(addr.clone());
+        dpus.entry(slot.clone())
+            .or_insert(Dpu {
+                name: slot,
+                model: model.to_string(),
+                pci_addresses: vec![],
+                firmware: String::new(),
+                representors: vec![],
+            })
+            .pci_addresses
+            .push(addr);
+    }
+
+    for dpu in dpus.values_mut() {
+        for addr in &dpu.pci_addresses {
+            let dev = devices.join(addr);
+            if dpu.firmware.is_empty() {
+                dpu.firmware = firmware(&dev).unwrap_or_default();
+            }
+            dpu.representors.extend(representors(&dev));
+        }
+    }
+
+    dpus.into_values().collect()
+}
+
+/// The firmware version reported by the RDMA device of a function.
+fn firmware(dev: &Path) -> Option<String> {
+    let ib = dev.join("infiniband");
+    entries(&ib)
+        .into_iter()
+        .find_map(|name| read_trimmed(&ib.join(name).join("fw_ver")))
+}
+
+/// The representors of a function in switchdev mode, i.e. its netdevs with a
+/// `phys_port_name` such as `pf0hpf` or `pf0vf1`; the uplink `p0` is not one.
+fn representors(dev: &Path) -> Vec<RepresentorPort> {
+    let net = dev.join("net");
+    entries(&net)
+        .into_iter()
+        .filter_map(|name| {
+            let function = read_trimmed(&net.join(&name).join("phys_port_name"))?;
+            if !function.starts_with("pf") {
+                return None;
+            }
+
+            Some(RepresentorPort {
+                mac: read_trimmed(&net.join(&name).join("address")).unwrap_or_default(),
+                function,
+                name,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dpus_from_fixture() {
+        let dpus = dpus(&super::super::fixture());
+
+        assert_eq!(dpus.len(), 1);
+        assert_eq!(dpus[0].name, "0000:03:00");
+        assert_eq!(dpus[0].model, "BlueField-2");
+        assert_eq!(dpus[0].pci_addresses, ["0000:03:00.0", "0000:03:00.1"]);
+        assert_eq!(dpus[0].firmware, "24.35.2000");
+
+        // The uplink p0 is not a representor.
+        let representors: Vec<_> = dpus[0]
+            .representors
+            .iter()
+            .map(|r| (r.name.as_str(), r.function.as_str(), r.mac.as_str()))
+            .collect();
+        assert_eq!(
+            representors,
+            [
+                ("pf0hpf", "pf0hpf", "b8:ce:f6:00:00:10"),
+                ("pf1vf0", "pf1vf0", "b8:ce:f6:00:00:21")
+            ]
+        );
+    }
+}
@@ -0,0 +1,65 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs;
+use std::path::Path;
+
+use super::read_trimmed;
+
+/// The model and the number of the logical CPUs.
+pub fn cpus(root: &Path) -> (String, u32) {
+    let cpuinfo = fs::read_to_string(root.join("proc/cpuinfo")).unwrap_or_default();
+
+    let model = cpuinfo
+        .lines()
+        .filter_map(|l| l.split_once(':'))
+        .find(|(k, _)| k.trim() == "model name")
+        .map(|(_, v)| v.trim().to_string())
+        .unwrap_or_default();
+    let count = cpuinfo
+        .lines()
+        .filter_map(|l| l.split_once(':'))
+        .filter(|(k, _)| k.trim() == "processor")
+        .count() as u32;
+
+    (model, count)
+}
+
+/// The total memory in bytes.
+pub fn memory(root: &Path) -> u64 {
+    read_trimmed(&root.join("proc/meminfo"))
+        .unwrap_or_default()
+        .lines()
+        .find_map(|l| l.strip_prefix("MemTotal:"))
+        .and_then(|l| l.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+        .map(|kb| kb * 1024)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpus_from_fixture() {
+        let (model, count) = cpus(&super::super::fixture());
+
+        assert_eq!(model, "Intel(R) Xeon(R) Gold 6338 CPU @ 2.00GHz");
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn memory_from_fixture() {
+        assert_eq!(memory(&super::super::fixture()), 16384000 * 1024);
+    }
+}
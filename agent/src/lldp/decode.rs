@@ -0,0 +1,198 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use yangtze_apis::v1alpha1::node::LldpNeighbor;
+
+pub const ETH_P_LLDP: u16 = 0x88cc;
+const ETH_P_8021Q: u16 = 0x8100;
+
+const TLV_END: u8 = 0;
+const TLV_CHASSIS_ID: u8 = 1;
+const TLV_PORT_ID: u8 = 2;
+const TLV_TTL: u8 = 3;
+const TLV_PORT_DESCRIPTION: u8 = 4;
+const TLV_SYSTEM_NAME: u8 = 5;
+const TLV_MANAGEMENT_ADDRESS: u8 = 8;
+
+/// The chassis/port id subtype of a MAC address.
+const CHASSIS_SUBTYPE_MAC: u8 = 4;
+const PORT_SUBTYPE_MAC: u8 = 3;
+/// The chassis/port id subtype of a network address.
+const CHASSIS_SUBTYPE_ADDRESS: u8 = 5;
+const PORT_SUBTYPE_ADDRESS: u8 = 4;
+
+/// The address family numbers of the management addresses.
+const FAMILY_IPV4: u8 = 1;
+const FAMILY_IPV6: u8 = 2;
+
+/// Decodes an Ethernet frame carrying a LLDPDU; returns the neighbor and its
+/// TTL in seconds, or `None` if the frame is not a valid LLDPDU.
+pub fn decode(interface: &str, frame: &[u8]) -> Option<(LldpNeighbor, u16)> {
+    let mut ethertype = u16::from_be_bytes([*frame.get(12)?, *frame.get(13)?]);
+    let mut payload = frame.get(14..)?;
+    if ethertype == ETH_P_8021Q {
+        ethertype = u16::from_be_bytes([*payload.get(2)?, *payload.get(3)?]);
+        payload = payload.get(4..)?;
+    }
+    if ethertype != ETH_P_LLDP {
+        return None;
+    }
+
+    let mut neighbor = LldpNeighbor {
+        interface: interface.to_string(),
+        ..LldpNeighbor::default()
+    };
+    let mut ttl = None;
+
+    while payload.len() >= 2 {
+        let header = u16::from_be_bytes([payload[0], payload[1]]);
+        let (typ, len) = ((header >> 9) as u8, (header & 0x1ff) as usize);
+        let value = payload.get(2..2 + len)?;
+        payload = &payload[2 + len..];
+
+        match typ {
+            TLV_END => break,
+            TLV_CHASSIS_ID => {
+                neighbor.chassis_id = id(value, CHASSIS_SUBTYPE_MAC, CHASSIS_SUBTYPE_ADDRESS)?
+            }
+            TLV_PORT_ID => neighbor.port_id = id(value, PORT_SUBTYPE_MAC, PORT_SUBTYPE_ADDRESS)?,
+            TLV_TTL => ttl = Some(u16::from_be_bytes([*value.first()?, *value.get(1)?])),
+            TLV_PORT_DESCRIPTION => neighbor.port_description = text(value),
+            TLV_SYSTEM_NAME => neighbor.system_name = text(value),
+            TLV_MANAGEMENT_ADDRESS if neighbor.management_address.is_none() => {
+                neighbor.management_address = management_address(value);
+            }
+            _ => {}
+        }
+    }
+
+    // The chassis id, port id and TTL are mandatory.
+    if neighbor.chassis_id.is_empty() || neighbor.port_id.is_empty() {
+        return None;
+    }
+
+    Some((neighbor, ttl?))
+}
+
+fn id(value: &[u8], mac: u8, address: u8) -> Option<String> {
+    let (subtype, id) = value.split_first()?;
+    match *subtype {
+        s if s == mac && id.len() == 6 => Some(format_mac(id)),
+        s if s == address => network_address(id),
+        _ => Some(text(id)),
+    }
+}
+
+fn management_address(value: &[u8]) -> Option<String> {
+    let len = *value.first()? as usize;
+    network_address(value.get(1..1 + len)?)
+}
+
+/// A network address prefixed by its address family number.
+fn network_address(value: &[u8]) -> Option<String> {
+    let (family, addr) = value.split_first()?;
+    match *family {
+        FAMILY_IPV4 => {
+            let octets: [u8; 4] = addr.try_into().ok()?;
+            Some(std::net::Ipv4Addr::from(octets).to_string())
+        }
+        FAMILY_IPV6 => {
+            let octets: [u8; 16] = addr.try_into().ok()?;
+            Some(std::net::Ipv6Addr::from(octets).to_string())
+        }
+        _ => None,
+    }
+}
+
+fn text(value: &[u8]) -> String {
+    String::from_utf8_lossy(value)
+        .trim_end_matches('\0')
+        .trim()
+        .to_string()
+}
+
+fn format_mac(mac: &[u8]) -> String {
+    mac.iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tlv(typ: u8, value: &[u8]) -> Vec<u8> {
+        let mut tlv = ((typ as u16) << 9 | value.len() as u16)
+            .to_be_bytes()
+            .to_vec();
+        tlv.extend_from_slice(value);
+        tlv
+    }
+
+    fn frame(tlvs: &[Vec<u8>]) -> Vec<u8> {
+        let mut frame = vec![0x01, 0x80, 0xc2, 0x00, 0x00, 0x0e, 0, 0, 0, 0, 0, 1];
+        frame.extend_from_slice(&ETH_P_LLDP.to_be_bytes());
+        frame.extend(tlvs.iter().flatten());
+        frame
+    }
+
+    fn mandatory() -> Vec<Vec<u8>> {
+        vec![
+            tlv(TLV_CHASSIS_ID, &[CHASSIS_SUBTYPE_MAC, 0, 0, 0, 0, 0, 1]),
+            tlv(TLV_PORT_ID, b"\x05Ethernet0"),
+            tlv(TLV_TTL, &120u16.to_be_bytes()),
+        ]
+    }
+
+    #[test]
+    fn decode_mandatory() {
+        let (neighbor, ttl) = decode("eth0", &frame(&mandatory())).unwrap();
+
+        assert_eq!(neighbor.chassis_id, "00:00:00:00:00:01");
+        assert_eq!(neighbor.port_id, "Ethernet0");
+        assert_eq!(ttl, 120);
+    }
+
+    #[test]
+    fn reject_truncated_tlv() {
+        let complete = frame(&mandatory()).len();
+        let mut tlvs = mandatory();
+        tlvs.push(tlv(TLV_SYSTEM_NAME, b"spine1"));
+        let frame = frame(&tlvs);
+
+        // Cut in a mandatory TLV, or in the value of the system name.
+        for len in (0..complete).chain(complete + 2..frame.len()) {
+            assert!(decode("eth0", &frame[..len]).is_none(), "{}", len);
+        }
+        assert!(decode("eth0", &frame).is_some());
+    }
+
+    #[test]
+    fn reject_missing_mandatory() {
+        for skip in 0..3 {
+            let mut tlvs = mandatory();
+            tlvs.remove(skip);
+
+            assert!(decode("eth0", &frame(&tlvs)).is_none(), "{}", skip);
+        }
+    }
+
+    #[test]
+    fn reject_other_ethertype() {
+        let mut frame = frame(&mandatory());
+        frame[12..14].copy_from_slice(&0x0800u16.to_be_bytes());
+
+        assert!(decode("eth0", &frame).is_none());
+    }
+}
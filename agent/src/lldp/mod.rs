@@ -0,0 +1,100 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use yangtze_apis::v1alpha1::node::LldpNeighbor;
+
+mod decode;
+mod pcap;
+mod socket;
+
+/// The interval a switch usually sends LLDPDUs, used to replay pcap files.
+const REPLAY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Where the LLDPDUs of an interface come from.
+#[derive(Clone, Debug)]
+pub enum Source {
+    Socket(String),
+    Pcap { interface: String, path: PathBuf },
+}
+
+/// The interface, chassis id and port id of a neighbor.
+type NeighborKey = (String, String, String);
+
+/// The LLDP neighbors of the host, which expire after their TTL.
+#[derive(Default)]
+pub struct Neighbors {
+    neighbors: Mutex<HashMap<NeighborKey, (LldpNeighbor, Instant)>>,
+}
+
+impl Neighbors {
+    pub fn insert(&self, neighbor: LldpNeighbor, ttl: u16) {
+        let key = (
+            neighbor.interface.clone(),
+            neighbor.chassis_id.clone(),
+            neighbor.port_id.clone(),
+        );
+        let expires = Instant::now() + Duration::from_secs(ttl as u64);
+
+        let mut neighbors = self.neighbors.lock().unwrap();
+        // A TTL of 0 means the neighbor is shutting down.
+        if ttl == 0 {
+            neighbors.remove(&key);
+        } else {
+            neighbors.insert(key, (neighbor, expires));
+        }
+    }
+
+    /// The neighbors not expired, sorted by interface.
+    pub fn list(&self) -> Vec<LldpNeighbor> {
+        let now = Instant::now();
+
+        let mut neighbors = self.neighbors.lock().unwrap();
+        neighbors.retain(|_, (_, expires)| *expires > now);
+
+        let mut list: Vec<_> = neighbors.values().map(|(n, _)| n.clone()).collect();
+        list.sort_by(|a, b| (&a.interface, &a.chassis_id).cmp(&(&b.interface, &b.chassis_id)));
+
+        list
+    }
+}
+
+/// Starts a thread per source to collect the neighbors.
+pub fn start(sources: Vec<Source>, neighbors: Arc<Neighbors>) {
+    for source in sources {
+        let neighbors = neighbors.clone();
+        thread::spawn(move || match source {
+            Source::Socket(interface) => {
+                tracing::info!("Listening to LLDP on <{}>.", interface);
+                if let Err(e) = socket::listen(&interface, neighbors) {
+                    tracing::error!("Failed to listen to LLDP on <{}>: {}", interface, e);
+                }
+            }
+            Source::Pcap { interface, path } => loop {
+                match pcap::replay(&interface, &path, &neighbors) {
+                    Ok(n) => tracing::debug!("Replayed {} LLDPDUs on <{}>.", n, interface),
+                    Err(e) => {
+                        tracing::error!("Failed to replay <{}>: {}", path.display(), e);
+                        break;
+                    }
+                }
+                thread::sleep(REPLAY_INTERVAL);
+            },
+        });
+    }
+}
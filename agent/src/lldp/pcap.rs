@@ -0,0 +1,140 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{decode, Neighbors};
+
+const MAGIC: u32 = 0xa1b2c3d4;
+const MAGIC_NANOS: u32 = 0xa1b23c4d;
+const GLOBAL_HEADER_LEN: usize = 24;
+const RECORD_HEADER_LEN: usize = 16;
+
+/// Replays the LLDPDUs of a pcap file as if they were received on
+/// `interface`, e.g. to run the agent without switches.
+pub fn replay(interface: &str, path: &Path, neighbors: &Arc<Neighbors>) -> io::Result<usize> {
+    let data = fs::read(path)?;
+    let frames = read(&data)?;
+
+    let mut count = 0;
+    for frame in frames {
+        if let Some((neighbor, ttl)) = decode::decode(interface, frame) {
+            neighbors.insert(neighbor, ttl);
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Splits a pcap file (not pcapng) into its frames.
+fn read(data: &[u8]) -> io::Result<Vec<&[u8]>> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    let header = data
+        .get(..GLOBAL_HEADER_LEN)
+        .ok_or(invalid("truncated pcap header"))?;
+    let magic = [header[0], header[1], header[2], header[3]];
+    let big_endian = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+        (MAGIC | MAGIC_NANOS, _) => false,
+        (_, MAGIC | MAGIC_NANOS) => true,
+        _ => return Err(invalid("not a pcap file")),
+    };
+    let u32_at = |b: &[u8], i: usize| {
+        let v = [b[i], b[i + 1], b[i + 2], b[i + 3]];
+        if big_endian {
+            u32::from_be_bytes(v)
+        } else {
+            u32::from_le_bytes(v)
+        }
+    };
+
+    let mut frames = vec![];
+    let mut rest = &data[GLOBAL_HEADER_LEN..];
+    while !rest.is_empty() {
+        let record = rest
+            .get(..RECORD_HEADER_LEN)
+            .ok_or(invalid("truncated pcap record"))?;
+        let len = u32_at(record, 8) as usize;
+        let frame = rest
+            .get(RECORD_HEADER_LEN..RECORD_HEADER_LEN + len)
+            .ok_or(invalid("truncated pcap frame"))?;
+        frames.push(frame);
+        rest = &rest[RECORD_HEADER_LEN + len..];
+    }
+
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use yangtze_apis::v1alpha1::node::LldpNeighbor;
+
+    use super::*;
+
+    /// Two LLDPDUs of spine1 and spine2, the latter VLAN tagged, and one of
+    /// spine3 with a truncated system name TLV.
+    fn fixture() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/lldp.pcap")
+    }
+
+    #[test]
+    fn read_frames() {
+        let data = fs::read(fixture()).unwrap();
+
+        assert_eq!(read(&data).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn replay_fixture() {
+        let neighbors = Arc::new(Neighbors::default());
+
+        assert_eq!(replay("eth0", &fixture(), &neighbors).unwrap(), 2);
+        assert_eq!(
+            neighbors.list(),
+            [
+                LldpNeighbor {
+                    interface: "eth0".to_string(),
+                    chassis_id: "b8:6a:97:00:00:01".to_string(),
+                    port_id: "Ethernet4".to_string(),
+                    port_description: "leaf1:Ethernet0".to_string(),
+                    system_name: "spine1".to_string(),
+                    management_address: Some("10.0.0.100".to_string()),
+                },
+                LldpNeighbor {
+                    interface: "eth0".to_string(),
+                    chassis_id: "spine2".to_string(),
+                    port_id: "b8:6a:97:00:00:02".to_string(),
+                    port_description: String::new(),
+                    system_name: "spine2".to_string(),
+                    management_address: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated_pcap() {
+        let data = fs::read(fixture()).unwrap();
+
+        assert!(read(&data[..GLOBAL_HEADER_LEN - 1]).is_err());
+        assert!(read(&data[..GLOBAL_HEADER_LEN + RECORD_HEADER_LEN - 1]).is_err());
+        assert!(read(&data[..data.len() - 1]).is_err());
+        assert!(read(&[0; GLOBAL_HEADER_LEN]).is_err());
+    }
+}
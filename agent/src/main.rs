@@ -1,3 +1,236 @@
-fn main() {
-    println!("Hello, world!");
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use clap::Parser;
+
+use yangtze_apis::v1::YangtzeError;
+use yangtze_client::{YangtzeClient, YangtzeConfig};
+
+mod dataplane;
+mod initiator;
+mod inventory;
+mod lldp;
+mod node;
+mod nvmet;
+mod provision;
+mod routing;
+mod rshim;
+mod xpu;
+
+#[derive(Parser)]
+#[command(name = "yangtze-agent")]
+#[command(version = "0.1.0")]
+#[command(about = "Yangtze host agent", long_about = None)]
+struct Cli {
+    #[arg(
+        long,
+        env = "YANGTZE_APISERVER",
+        default_value = "http://127.0.0.1:8080"
+    )]
+    apiserver: String,
+
+    #[arg(long, default_value = "default")]
+    namespace: String,
+
+    /// The name of the Node, the hostname by default
+    #[arg(long, env = "YANGTZE_NODE_NAME")]
+    node_name: Option<String>,
+
+    #[arg(long)]
+    bmc_address: Option<String>,
+
+    /// Seconds between two heartbeats
+    #[arg(long, default_value_t = 10)]
+    heartbeat_interval: u64,
+
+    /// The root of the host filesystem to read sysfs and procfs from,
+    /// e.g. /host when running in a container
+    #[arg(long, default_value = "/")]
+    host_root: PathBuf,
+
+    /// The uplinks to listen to LLDP on, all the physical NICs by default
+    #[arg(long)]
+    lldp_interface: Vec<String>,
+
+    /// Replay the LLDPDUs of a pcap file as received on an interface,
+    /// as <interface>=<file>
+    #[arg(long, value_parser = parse_pcap)]
+    lldp_pcap: Vec<lldp::Source>,
+
+    /// The directory of the rshim devices of the DPUs, e.g. a fake one of
+    /// named pipes for testing
+    #[arg(long, default_value = "/dev")]
+    rshim_root: PathBuf,
+
+    /// The configfs tree of the NVMe-oF target, e.g. a fake directory for
+    /// testing
+    #[arg(long, default_value = "/sys/kernel/config/nvmet")]
+    nvmet_root: PathBuf,
+
+    /// The JSON-RPC socket of the SPDK target
+    #[arg(long, default_value = "/var/tmp/spdk.sock")]
+    spdk_socket: PathBuf,
+
+    /// The NVMe over Fabrics device connecting to the targets, e.g. a fake
+    /// file for testing
+    #[arg(long, default_value = "/dev/nvme-fabrics")]
+    nvme_fabrics: PathBuf,
+
+    /// Attach a Subnet to the host through an uplink, as <subnet>=<uplink>
+    #[arg(long, value_parser = parse_attachment)]
+    subnet: Vec<dataplane::Attachment>,
+
+    /// The local address of the VXLAN tunnels of the Vpcs
+    #[arg(long)]
+    vtep_address: Option<Ipv4Addr>,
+
+    /// The configuration file of FRR, installed from the Node
+    #[arg(long, default_value = "/etc/frr/frr.conf")]
+    frr_config: PathBuf,
+
+    /// The command reloading FRR after installing its configuration
+    #[arg(long, default_value = "systemctl reload frr")]
+    frr_reload_command: String,
+}
+
+fn parse_attachment(s: &str) -> Result<dataplane::Attachment, String> {
+    let (subnet, uplink) = s
+        .split_once('=')
+        .ok_or(format!("invalid <{}>, expect <subnet>=<uplink>", s))?;
+
+    Ok(dataplane::Attachment {
+        subnet: subnet.to_string(),
+        uplink: uplink.to_string(),
+        port: false,
+    })
+}
+
+fn parse_pcap(s: &str) -> Result<lldp::Source, String> {
+    let (interface, path) = s
+        .split_once('=')
+        .ok_or(format!("invalid <{}>, expect <interface>=<file>", s))?;
+
+    Ok(lldp::Source::Pcap {
+        interface: interface.to_string(),
+        path: PathBuf::from(path),
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<(), YangtzeError> {
+    let subscriber = tracing_subscriber::FmtSubscriber::new();
+    // use that subscriber to process traces emitted after this point
+    tracing::subscriber::set_global_default(subscriber)
+        .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+
+    let cli = Cli::parse();
+
+    let client = YangtzeClient::new(&YangtzeConfig {
+        address: cli.apiserver.clone(),
+    })?;
+
+    let mut sources = cli.lldp_pcap.clone();
+    let interfaces = match (cli.lldp_interface.is_empty(), sources.is_empty()) {
+        (false, _) => cli.lldp_interface.clone(),
+        (true, true) => inventory::collect(&cli.host_root)
+            .nics
+            .into_iter()
+            .map(|n| n.name)
+            .collect(),
+        (true, false) => vec![],
+    };
+    sources.extend(interfaces.into_iter().map(lldp::Source::Socket));
+
+    let neighbors = Arc::new(lldp::Neighbors::default());
+    lldp::start(sources, neighbors.clone());
+
+    let name = cli
+        .node_name
+        .clone()
+        .unwrap_or(inventory::collect(&cli.host_root).hostname);
+
+    let rshim = rshim::Manager {
+        client: client.clone(),
+        namespace: cli.namespace.clone(),
+        node: name.clone(),
+        root: cli.rshim_root.clone(),
+        interval: cli.heartbeat_interval,
+    };
+    tokio::spawn(rshim.run());
+
+    let nvmet = nvmet::Reconciler {
+        client: client.clone(),
+        namespace: cli.namespace.clone(),
+        node: name.clone(),
+        root: cli.nvmet_root.clone(),
+        spdk_socket: cli.spdk_socket.clone(),
+        interval: cli.heartbeat_interval,
+    };
+    tokio::spawn(nvmet.run());
+
+    let dataplane = dataplane::Reconciler {
+        client: client.clone(),
+        namespace: cli.namespace.clone(),
+        node: name.clone(),
+        attachments: cli.subnet.clone(),
+        vtep: cli.vtep_address,
+        interval: cli.heartbeat_interval,
+    };
+    tokio::spawn(dataplane.run());
+
+    let routing = routing::Installer {
+        client: client.clone(),
+        namespace: cli.namespace.clone(),
+        node: name.clone(),
+        config: cli.frr_config,
+        reload_command: cli.frr_reload_command,
+        interval: cli.heartbeat_interval,
+    };
+    tokio::spawn(routing.run());
+
+    let initiator = initiator::Initiator {
+        client: client.clone(),
+        namespace: cli.namespace.clone(),
+        node: name.clone(),
+        host_root: cli.host_root.clone(),
+        fabrics: cli.nvme_fabrics,
+        interval: cli.heartbeat_interval,
+    };
+    tokio::spawn(initiator.run());
+
+    let agent = Arc::new(node::Agent {
+        client: client.clone(),
+        namespace: cli.namespace.clone(),
+        name: name.clone(),
+        bmc_address: cli.bmc_address,
+        heartbeat_interval: cli.heartbeat_interval,
+        host_root: cli.host_root.clone(),
+        neighbors,
+    });
+
+    let executor = provision::Executor {
+        client,
+        namespace: cli.namespace,
+        node: name,
+        host_root: cli.host_root,
+        interval: cli.heartbeat_interval,
+        agent: agent.clone(),
+    };
+    tokio::spawn(executor.run());
+
+    agent.run().await
 }
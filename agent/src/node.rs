@@ -0,0 +1,181 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use yangtze_apis::{
+    v1::{now, Metadata, NamespaceName, YangtzeError},
+    v1alpha1::node::{self, Node, NodeSpec, NodeState, NodeStatus},
+};
+use yangtze_client::YangtzeClient;
+
+use crate::inventory::{self, Inventory};
+use crate::lldp::Neighbors;
+use crate::xpu;
+
+pub struct Agent {
+    pub client: YangtzeClient,
+    pub namespace: String,
+    pub name: String,
+    pub bmc_address: Option<String>,
+    pub heartbeat_interval: u64,
+    pub host_root: PathBuf,
+    pub neighbors: Arc<Neighbors>,
+}
+
+impl Agent {
+    /// Registers the host as a Node and keeps sending heartbeats.
+    pub async fn run(&self) -> Result<(), YangtzeError> {
+        let client = self
+            .client
+            .clone()
+            .version(node::VERSION_KIND.version)
+            .kind(node::VERSION_KIND.kind);
+
+        let mut node = self.register(&client).await?;
+        tracing::info!("The host was registered as Node <{}>.", node.meta_data);
+
+        loop {
+            tokio::time::sleep(Duration::from_secs(self.heartbeat_interval)).await;
+
+            match self.heartbeat(&client, &node).await {
+                Ok(n) => node = n,
+                Err(e) => tracing::error!("Failed to send heartbeat: {}", e),
+            }
+        }
+    }
+
+    /// Reports the inventory of the host right away, e.g. to inspect it.
+    pub async fn inspect(&self) -> Result<Node, YangtzeError> {
+        let client = self
+            .client
+            .clone()
+            .version(node::VERSION_KIND.version)
+            .kind(node::VERSION_KIND.kind);
+
+        let nn = NamespaceName {
+            namespace: Some(self.namespace.clone()),
+            name: Some(self.name.clone()),
+        };
+        let node = client
+            .list::<Node>(nn)
+            .await?
+            .pop()
+            .ok_or(YangtzeError::GeneralError(format!(
+                "Node <{}> is not registered",
+                self.name
+            )))?;
+
+        self.heartbeat(&client, &node).await
+    }
+
+    async fn register(&self, client: &YangtzeClient) -> Result<Node, YangtzeError> {
+        let inv = inventory::collect(&self.host_root);
+        let name = self.name.clone();
+
+        let nn = NamespaceName {
+            namespace: Some(self.namespace.clone()),
+            name: Some(name.clone()),
+        };
+
+        match client.list::<Node>(nn).await?.pop() {
+            None => {
+                let node = Node {
+                    meta_data: Metadata {
+                        uuid: None,
+                        kind: node::VERSION_KIND.kind.to_string(),
+                        namespace: self.namespace.clone(),
+                        name,
+                        labels: vec![],
+                        version: 0,
+                    },
+                    spec: NodeSpec {
+                        hostname: inv.hostname.clone(),
+                        serial: inv.system.serial.clone(),
+                        bmc_address: self.bmc_address.clone(),
+                        bmc: None,
+                        power: None,
+                        reservations: vec![],
+                        boot_profile: None,
+                        asn: None,
+                        loopback: None,
+                        frr_config: None,
+                    },
+                    status: Some(self.status(&inv, None)),
+                };
+                client.create(node).await
+            }
+            Some(mut node) => {
+                node.spec.hostname = inv.hostname.clone();
+                node.spec.serial = inv.system.serial.clone();
+                if self.bmc_address.is_some() {
+                    node.spec.bmc_address = self.bmc_address.clone();
+                }
+                node.status = Some(self.status(&inv, node.status.take()));
+                client.update(node).await
+            }
+        }
+    }
+
+    async fn heartbeat(&self, client: &YangtzeClient, node: &Node) -> Result<Node, YangtzeError> {
+        let id = node
+            .meta_data
+            .uuid
+            .ok_or(YangtzeError::InvalidConfig(format!(
+                "The id of <{}> is none.",
+                node
+            )))?;
+
+        let inv = inventory::collect(&self.host_root);
+        if let Err(e) = xpu::report(
+            &self.client,
+            &self.namespace,
+            &node.meta_data.name,
+            &inv.dpus,
+        )
+        .await
+        {
+            tracing::error!("Failed to report the DPUs of <{}>: {}", node, e);
+        }
+
+        // Get the latest Node, as the controller may have changed its status.
+        let mut node = client.get::<Node>(id.to_string()).await?;
+        node.status = Some(self.status(&inv, node.status.take()));
+
+        client.update(node).await
+    }
+
+    /// The status from the inventory; the boot attempts recorded by the
+    /// boot servers and the power observed through the BMC are kept from
+    /// the `last` status.
+    fn status(&self, inv: &Inventory, last: Option<NodeStatus>) -> NodeStatus {
+        let (boots, power) = last.map(|s| (s.boots, s.power)).unwrap_or_default();
+
+        NodeStatus {
+            state: NodeState::Ready,
+            last_heartbeat: now(),
+            system: inv.system.clone(),
+            cpu_model: inv.cpu_model.clone(),
+            cpus: inv.cpus,
+            memory: inv.memory,
+            nics: inv.nics.clone(),
+            dpus: inv.dpus.clone(),
+            nvmes: inv.nvmes.clone(),
+            neighbors: self.neighbors.list(),
+            boots,
+            power,
+        }
+    }
+}
@@ -0,0 +1,531 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::net::IpAddr;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+use super::{Backend, Namespace, Port, Subsystem, Target};
+
+/// The Linux nvmet target configured through its configfs tree, where
+/// `mkdir` creates an object with its attributes and `rmdir` removes it.
+///
+/// The tree is owned by the agent: the objects not in the Target are
+/// removed. A plain directory mimics configfs, e.g. for testing.
+pub struct Configfs {
+    root: PathBuf,
+}
+
+impl Configfs {
+    pub fn new(root: &Path) -> Self {
+        Configfs {
+            root: root.to_path_buf(),
+        }
+    }
+}
+
+impl Backend for Configfs {
+    fn available(&self) -> bool {
+        self.root.join("subsystems").is_dir()
+    }
+
+    fn subsystem(&self, s: &Subsystem) -> io::Result<()> {
+        if !self.available() {
+            return Err(io::Error::new(
+                ErrorKind::NotFound,
+                format!("nvmet is not available at <{}>", self.root.display()),
+            ));
+        }
+
+        let dir = self.root.join("subsystems").join(&s.nqn);
+        mkdir(&dir)?;
+        mkdir(&dir.join("allowed_hosts"))?;
+        mkdir(&dir.join("namespaces"))?;
+
+        // Any host is allowed only without allowed hosts.
+        let links = dir.join("allowed_hosts");
+        unlink_except(&links, &s.allowed_hosts)?;
+        write(
+            &dir,
+            "attr_allow_any_host",
+            bool_attr(s.allowed_hosts.is_empty()),
+        )?;
+        for host in &s.allowed_hosts {
+            let target = self.root.join("hosts").join(host);
+            mkdir(&target)?;
+            link(&target, &links.join(host))?;
+        }
+
+        if let Some(serial) = &s.serial {
+            write(&dir, "attr_serial", serial)?;
+        }
+
+        Ok(())
+    }
+
+    fn namespace(&self, nqn: &str, ns: &Namespace) -> io::Result<()> {
+        if !Path::new(&ns.device).exists() {
+            return Err(io::Error::new(
+                ErrorKind::NotFound,
+                format!("device <{}> not found", ns.device),
+            ));
+        }
+
+        let dir = self
+            .root
+            .join("subsystems")
+            .join(nqn)
+            .join("namespaces")
+            .join(ns.nsid.to_string());
+        mkdir(&dir)?;
+
+        // The device of an enabled namespace can not be changed.
+        let enabled = read(&dir, "enable") == "1";
+        if read(&dir, "device_path") != ns.device {
+            if enabled {
+                write(&dir, "enable", "0")?;
+            }
+            write(&dir, "device_path", &ns.device)?;
+            write(&dir, "enable", "1")?;
+        } else if !enabled {
+            write(&dir, "enable", "1")?;
+        }
+
+        Ok(())
+    }
+
+    fn port(&self, p: &Port) -> io::Result<()> {
+        let ports = self.root.join("ports");
+        let dir = match self.find_port(p)? {
+            Some(dir) => dir,
+            None => {
+                let id = ids(&ports)?.into_iter().max().unwrap_or_default() + 1;
+                let dir = ports.join(id.to_string());
+                mkdir(&dir)?;
+                mkdir(&dir.join("subsystems"))?;
+
+                let family = match p.address {
+                    IpAddr::V4(_) => "ipv4",
+                    IpAddr::V6(_) => "ipv6",
+                };
+                write(&dir, "addr_trtype", &p.transport.to_string())?;
+                write(&dir, "addr_adrfam", family)?;
+                write(&dir, "addr_traddr", &p.address.to_string())?;
+                write(&dir, "addr_trsvcid", &p.service.to_string())?;
+
+                tracing::info!(
+                    "Added nvmet port {} on {} {}:{}.",
+                    id,
+                    p.transport,
+                    p.address,
+                    p.service
+                );
+                dir
+            }
+        };
+
+        let links = dir.join("subsystems");
+        unlink_except(&links, &p.subsystems)?;
+        for nqn in &p.subsystems {
+            link(&self.root.join("subsystems").join(nqn), &links.join(nqn))?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes the ports, subsystems, namespaces and hosts not in the Target.
+    fn prune(&self, target: &Target) -> io::Result<()> {
+        if !self.available() {
+            return Ok(());
+        }
+
+        let ports = self.root.join("ports");
+        let kept: Vec<PathBuf> = target
+            .ports
+            .iter()
+            .filter_map(|p| self.find_port(p).ok().flatten())
+            .collect();
+        for id in ids(&ports)? {
+            let dir = ports.join(id.to_string());
+            if !kept.contains(&dir) {
+                unlink_except(&dir.join("subsystems"), &[])?;
+                rmdir(&dir)?;
+                tracing::info!("Removed nvmet port {}.", id);
+            }
+        }
+
+        let subsystems = self.root.join("subsystems");
+        for nqn in names(&subsystems)? {
+            let dir = subsystems.join(&nqn);
+            let Some(s) = target.subsystems.iter().find(|s| s.nqn == nqn) else {
+                for id in ids(&ports)? {
+                    let link = ports.join(id.to_string()).join("subsystems").join(&nqn);
+                    if fs::symlink_metadata(&link).is_ok() {
+                        fs::remove_file(&link)?;
+                    }
+                }
+                self.prune_namespaces(&dir, &[])?;
+                unlink_except(&dir.join("allowed_hosts"), &[])?;
+                rmdir(&dir)?;
+                tracing::info!("Removed nvmet subsystem <{}>.", nqn);
+                continue;
+            };
+
+            let nsids: Vec<String> = s.namespaces.iter().map(|n| n.nsid.to_string()).collect();
+            self.prune_namespaces(&dir, &nsids)?;
+        }
+
+        let hosts = self.root.join("hosts");
+        for host in names(&hosts)? {
+            if !target
+                .subsystems
+                .iter()
+                .any(|s| s.allowed_hosts.contains(&host))
+            {
+                rmdir(&hosts.join(host))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Configfs {
+    fn prune_namespaces(&self, subsystem: &Path, nsids: &[String]) -> io::Result<()> {
+        let namespaces = subsystem.join("namespaces");
+        for nsid in names(&namespaces)? {
+            if !nsids.contains(&nsid) {
+                let dir = namespaces.join(&nsid);
+                write(&dir, "enable", "0")?;
+                rmdir(&dir)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The port of the same transport, address and service.
+    fn find_port(&self, p: &Port) -> io::Result<Option<PathBuf>> {
+        let ports = self.root.join("ports");
+        for id in ids(&ports)? {
+            let dir = ports.join(id.to_string());
+            if read(&dir, "addr_trtype") == p.transport.to_string()
+                && read(&dir, "addr_traddr") == p.address.to_string()
+                && read(&dir, "addr_trsvcid") == p.service.to_string()
+            {
+                return Ok(Some(dir));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+fn bool_attr(b: bool) -> &'static str {
+    match b {
+        true => "1",
+        false => "0",
+    }
+}
+
+fn read(dir: &Path, attr: &str) -> String {
+    fs::read_to_string(dir.join(attr))
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default()
+}
+
+fn write(dir: &Path, attr: &str, value: &str) -> io::Result<()> {
+    if read(dir, attr) == value {
+        return Ok(());
+    }
+    fs::write(dir.join(attr), value).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("failed to write <{}>: {}", dir.join(attr).display(), e),
+        )
+    })
+}
+
+fn mkdir(dir: &Path) -> io::Result<()> {
+    match fs::create_dir(dir) {
+        Err(e) if e.kind() != ErrorKind::AlreadyExists => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Removes an object; the attributes and default groups of a directory
+/// mimicking configfs are removed first.
+fn rmdir(dir: &Path) -> io::Result<()> {
+    if fs::remove_dir(dir).is_ok() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        match entry.file_type()?.is_dir() {
+            true => {
+                let _ = fs::remove_dir(&path);
+            }
+            false => fs::remove_file(&path)?,
+        }
+    }
+
+    fs::remove_dir(dir)
+}
+
+fn link(target: &Path, link: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(link).is_ok() {
+        return Ok(());
+    }
+
+    symlink(target, link)
+}
+
+/// Removes the links in `dir` not named in `keep`.
+fn unlink_except(dir: &Path, keep: &[String]) -> io::Result<()> {
+    for name in names(dir)? {
+        if !keep.contains(&name) {
+            fs::remove_file(dir.join(name))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The names of the entries of a directory, none if it does not exist.
+fn names(dir: &Path) -> io::Result<Vec<String>> {
+    match fs::read_dir(dir) {
+        Ok(entries) => Ok(entries
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e),
+    }
+}
+
+/// The ids of the ports.
+fn ids(ports: &Path) -> io::Result<Vec<u16>> {
+    Ok(names(ports)?
+        .iter()
+        .filter_map(|n| n.parse().ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use yangtze_apis::v1alpha1::nvme_port::NvmeTransport;
+
+    use super::*;
+
+    const HOST: &str = "nqn.2014-08.org.nvmexpress:uuid:host1";
+
+    /// A plain directory mimicking the nvmet configfs tree, and a device.
+    fn tree() -> (tempfile::TempDir, Configfs, String) {
+        let dir = tempfile::tempdir().unwrap();
+        for d in ["subsystems", "hosts", "ports"] {
+            fs::create_dir(dir.path().join(d)).unwrap();
+        }
+        let device = dir.path().join("vol.img");
+        fs::write(&device, "").unwrap();
+
+        let configfs = Configfs::new(dir.path());
+        let device = device.to_string_lossy().to_string();
+        (dir, configfs, device)
+    }
+
+    fn subsystem(name: &str, device: &str, nsids: &[u32]) -> Subsystem {
+        Subsystem {
+            nqn: format!("nqn.2023-01.cn.xflops:{}", name),
+            allowed_hosts: vec![HOST.to_string()],
+            serial: Some(name.to_string()),
+            namespaces: nsids
+                .iter()
+                .map(|nsid| Namespace {
+                    nsid: *nsid,
+                    device: device.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    fn port(subsystems: &[&Subsystem]) -> Port {
+        Port {
+            transport: NvmeTransport::Tcp,
+            address: "192.168.0.10".parse().unwrap(),
+            service: 4420,
+            subsystems: subsystems.iter().map(|s| s.nqn.clone()).collect(),
+        }
+    }
+
+    fn apply(configfs: &Configfs, target: &Target) {
+        for s in &target.subsystems {
+            configfs.subsystem(s).unwrap();
+            for ns in &s.namespaces {
+                configfs.namespace(&s.nqn, ns).unwrap();
+            }
+        }
+        for p in &target.ports {
+            configfs.port(p).unwrap();
+        }
+        configfs.prune(target).unwrap();
+    }
+
+    #[test]
+    fn apply_layout() {
+        let (dir, configfs, device) = tree();
+        let vol1 = subsystem("vol1", &device, &[1]);
+        let target = Target {
+            ports: vec![port(&[&vol1])],
+            subsystems: vec![vol1],
+        };
+        apply(&configfs, &target);
+
+        let root = dir.path();
+        let s = root.join("subsystems/nqn.2023-01.cn.xflops:vol1");
+        assert_eq!(read(&s, "attr_allow_any_host"), "0");
+        assert_eq!(read(&s, "attr_serial"), "vol1");
+        assert!(root.join("hosts").join(HOST).is_dir());
+        assert_eq!(
+            fs::read_link(s.join("allowed_hosts").join(HOST)).unwrap(),
+            root.join("hosts").join(HOST)
+        );
+
+        let ns = s.join("namespaces/1");
+        assert_eq!(read(&ns, "device_path"), device);
+        assert_eq!(read(&ns, "enable"), "1");
+
+        let p = root.join("ports/1");
+        assert_eq!(read(&p, "addr_trtype"), "tcp");
+        assert_eq!(read(&p, "addr_adrfam"), "ipv4");
+        assert_eq!(read(&p, "addr_traddr"), "192.168.0.10");
+        assert_eq!(read(&p, "addr_trsvcid"), "4420");
+        assert_eq!(
+            fs::read_link(p.join("subsystems/nqn.2023-01.cn.xflops:vol1")).unwrap(),
+            s
+        );
+
+        // Applied again, nothing changes.
+        apply(&configfs, &target);
+        assert_eq!(names(&root.join("ports")).unwrap(), ["1"]);
+    }
+
+    #[test]
+    fn allow_any_host() {
+        let (dir, configfs, device) = tree();
+        let mut vol1 = subsystem("vol1", &device, &[]);
+        configfs.subsystem(&vol1).unwrap();
+
+        vol1.allowed_hosts.clear();
+        configfs.subsystem(&vol1).unwrap();
+
+        let s = dir.path().join("subsystems").join(&vol1.nqn);
+        assert_eq!(read(&s, "attr_allow_any_host"), "1");
+        assert!(names(&s.join("allowed_hosts")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn prune_keeps_unrelated_subsystem() {
+        let (dir, configfs, device) = tree();
+        let vol1 = subsystem("vol1", &device, &[1, 2]);
+        let vol2 = subsystem("vol2", &device, &[1]);
+        let vol3 = subsystem("vol3", &device, &[1]);
+        apply(
+            &configfs,
+            &Target {
+                ports: vec![port(&[&vol1, &vol2, &vol3])],
+                subsystems: vec![vol1, vol2, vol3],
+            },
+        );
+
+        // vol1 drops a namespace and vol3 is deleted; vol2 is left as it is.
+        let vol1 = subsystem("vol1", &device, &[1]);
+        let vol2 = subsystem("vol2", &device, &[1]);
+        configfs
+            .prune(&Target {
+                ports: vec![port(&[&vol1, &vol2])],
+                subsystems: vec![vol1, vol2],
+            })
+            .unwrap();
+
+        let root = dir.path();
+        let mut subsystems = names(&root.join("subsystems")).unwrap();
+        subsystems.sort();
+        assert_eq!(
+            subsystems,
+            ["nqn.2023-01.cn.xflops:vol1", "nqn.2023-01.cn.xflops:vol2"]
+        );
+        assert_eq!(
+            names(&root.join("subsystems/nqn.2023-01.cn.xflops:vol1/namespaces")).unwrap(),
+            ["1"]
+        );
+
+        let vol2 = root.join("subsystems/nqn.2023-01.cn.xflops:vol2");
+        assert_eq!(read(&vol2.join("namespaces/1"), "enable"), "1");
+        assert!(fs::symlink_metadata(vol2.join("allowed_hosts").join(HOST)).is_ok());
+        let mut links = names(&root.join("ports/1/subsystems")).unwrap();
+        links.sort();
+        assert_eq!(
+            links,
+            ["nqn.2023-01.cn.xflops:vol1", "nqn.2023-01.cn.xflops:vol2"]
+        );
+        assert!(root.join("hosts").join(HOST).is_dir());
+    }
+
+    #[test]
+    fn prune_everything() {
+        let (dir, configfs, device) = tree();
+        let vol1 = subsystem("vol1", &device, &[1]);
+        apply(
+            &configfs,
+            &Target {
+                ports: vec![port(&[&vol1])],
+                subsystems: vec![vol1],
+            },
+        );
+
+        configfs.prune(&Target::default()).unwrap();
+
+        let root = dir.path();
+        for d in ["subsystems", "hosts", "ports"] {
+            assert!(names(&root.join(d)).unwrap().is_empty(), "{}", d);
+        }
+    }
+
+    #[test]
+    fn missing_device() {
+        let (_dir, configfs, device) = tree();
+        let vol1 = subsystem("vol1", &device, &[]);
+        configfs.subsystem(&vol1).unwrap();
+
+        let ns = Namespace {
+            nsid: 1,
+            device: "/nonexistent/vol.img".to_string(),
+        };
+        assert_eq!(
+            configfs.namespace(&vol1.nqn, &ns).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn unavailable() {
+        let dir = tempfile::tempdir().unwrap();
+        let configfs = Configfs::new(&dir.path().join("nvmet"));
+
+        assert!(!configfs.available());
+        assert!(configfs.subsystem(&subsystem("vol1", "", &[])).is_err());
+        assert!(configfs.prune(&Target::default()).is_ok());
+    }
+}
@@ -0,0 +1,666 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use yangtze_apis::{
+    v1::{NamespaceName, YangtzeError},
+    v1alpha1::{
+        nvme_namespace::{self, NvmeNamespace},
+        nvme_port::{self, NvmePort, NvmeTransport},
+        nvme_subsystem::{
+            self, valid_nqn, NvmeBackend, NvmeState, NvmeStatus, NvmeSubsystem, NvmeSubsystemStatus,
+        },
+        storage_pool::{self, StoragePool},
+    },
+};
+use yangtze_client::YangtzeClient;
+
+mod configfs;
+mod spdk;
+
+/// The suffix of the file marking a volume as created by the agent.
+const MARKER: &str = ".yangtze";
+
+use configfs::Configfs;
+use spdk::Spdk;
+
+/// The NVMe-oF target of the Node, as built from the NVMe objects.
+#[derive(Debug, Default)]
+pub struct Target {
+    pub subsystems: Vec<Subsystem>,
+    pub ports: Vec<Port>,
+}
+
+#[derive(Debug)]
+pub struct Subsystem {
+    pub nqn: String,
+    pub allowed_hosts: Vec<String>,
+    pub serial: Option<String>,
+    pub namespaces: Vec<Namespace>,
+}
+
+#[derive(Debug)]
+pub struct Namespace {
+    pub nsid: u32,
+    pub device: String,
+}
+
+#[derive(Debug)]
+pub struct Port {
+    pub transport: NvmeTransport,
+    pub address: IpAddr,
+    pub service: u16,
+    /// The NQNs of the subsystems exposed.
+    pub subsystems: Vec<String>,
+}
+
+/// An NVMe-oF target the Target of its subsystems is applied to.
+pub trait Backend {
+    fn available(&self) -> bool;
+
+    fn subsystem(&self, s: &Subsystem) -> io::Result<()>;
+
+    fn namespace(&self, nqn: &str, ns: &Namespace) -> io::Result<()>;
+
+    fn port(&self, p: &Port) -> io::Result<()>;
+
+    /// Removes the objects not in the Target.
+    fn prune(&self, target: &Target) -> io::Result<()>;
+}
+
+/// Applies the NvmeSubsystems, NvmeNamespaces and NvmePorts of the Node to
+/// the target backend of each subsystem and reports the result in their
+/// status.
+pub struct Reconciler {
+    pub client: YangtzeClient,
+    pub namespace: String,
+    pub node: String,
+    /// The nvmet configfs tree, `/sys/kernel/config/nvmet` or a fake one.
+    pub root: PathBuf,
+    /// The JSON-RPC socket of SPDK, `/var/tmp/spdk.sock` or a fake one.
+    pub spdk_socket: PathBuf,
+    pub interval: u64,
+}
+
+impl Reconciler {
+    pub async fn run(self) {
+        // The volumes seen placed onto the Node, released once their
+        // NvmeNamespace is deleted.
+        let mut seen = HashSet::new();
+        loop {
+            if let Err(e) = self.reconcile(&mut seen).await {
+                tracing::error!("Failed to reconcile the NVMe-oF target: {}", e);
+            }
+            tokio::time::sleep(Duration::from_secs(self.interval)).await;
+        }
+    }
+
+    async fn reconcile(&self, seen: &mut HashSet<String>) -> Result<(), YangtzeError> {
+        let subsystems: Vec<NvmeSubsystem> = self
+            .list(&self.client(nvme_subsystem::VERSION_KIND.kind))
+            .await?
+            .into_iter()
+            .filter(|s: &NvmeSubsystem| s.spec.node == self.node)
+            .collect();
+        let ports: Vec<NvmePort> = self
+            .list(&self.client(nvme_port::VERSION_KIND.kind))
+            .await?
+            .into_iter()
+            .filter(|p: &NvmePort| p.spec.node == self.node)
+            .collect();
+        let all: Vec<NvmeNamespace> = self
+            .list(&self.client(nvme_namespace::VERSION_KIND.kind))
+            .await?;
+        let pools: Vec<StoragePool> = self
+            .list(&self.client(storage_pool::VERSION_KIND.kind))
+            .await?;
+        // The volumes of the StoragePools placed onto the Node.
+        let volumes: Vec<&str> = all
+            .iter()
+            .filter(|n| {
+                n.spec
+                    .placement
+                    .as_ref()
+                    .is_some_and(|p| p.node == self.node)
+            })
+            .map(|n| n.spec.device.as_str())
+            .collect();
+        let mut released = |volumes: &[&str]| {
+            release(&pools, seen, volumes).map_err(|e| {
+                YangtzeError::GeneralError(format!("Failed to release volumes: {}", e))
+            })
+        };
+        let namespaces: Vec<NvmeNamespace> = all
+            .iter()
+            .filter(|n| {
+                subsystems
+                    .iter()
+                    .any(|s| s.meta_data.name == n.spec.subsystem)
+            })
+            .cloned()
+            .collect();
+
+        let configfs = Configfs::new(&self.root);
+        let spdk = Spdk::new(&self.spdk_socket);
+        let backend = |b: NvmeBackend| -> &dyn Backend {
+            match b {
+                NvmeBackend::Nvmet => &configfs,
+                NvmeBackend::Spdk => &spdk,
+            }
+        };
+        let backends = [NvmeBackend::Nvmet, NvmeBackend::Spdk];
+        if subsystems.is_empty()
+            && ports.is_empty()
+            && !backends.iter().any(|b| backend(*b).available())
+        {
+            return released(&volumes);
+        }
+
+        let mut errors: HashMap<String, String> = HashMap::new();
+        let err = |e: io::Error| e.to_string();
+
+        // The subsystems and their namespaces.
+        let mut targets: HashMap<NvmeBackend, Target> = HashMap::new();
+        for s in &subsystems {
+            let key = format!("subsystem/{}", s.meta_data.name);
+            if !valid_nqn(&s.spec.nqn) {
+                errors.insert(key, format!("invalid NQN <{}>", s.spec.nqn));
+                continue;
+            }
+
+            let mut allowed_hosts = s.spec.allowed_hosts.clone();
+            if let Some(status) = &s.status {
+                allowed_hosts.extend(status.attached_hosts.iter().cloned());
+            }
+            allowed_hosts.sort();
+            allowed_hosts.dedup();
+            if let Some(h) = allowed_hosts.iter().find(|h| !valid_nqn(h)) {
+                errors.insert(key, format!("invalid host NQN <{}>", h));
+                continue;
+            }
+
+            let mut subsystem = Subsystem {
+                nqn: s.spec.nqn.clone(),
+                allowed_hosts,
+                serial: s.spec.serial.clone(),
+                namespaces: vec![],
+            };
+            // A subsystem or namespace failing to be refreshed is still in
+            // the Target, so that the one applied before is not pruned.
+            let target = backend(s.spec.backend);
+            let applied = match target.subsystem(&subsystem).map_err(err) {
+                Ok(()) => true,
+                Err(e) => {
+                    errors.insert(key, e);
+                    false
+                }
+            };
+
+            for n in namespaces
+                .iter()
+                .filter(|n| n.spec.subsystem == s.meta_data.name)
+            {
+                let namespace = Namespace {
+                    nsid: n.spec.nsid,
+                    device: n.spec.device.clone(),
+                };
+                let res = match (namespace.nsid, applied) {
+                    (0, _) => Err("the nsid starts from 1".to_string()),
+                    (_, false) => Err(format!("NvmeSubsystem <{}> failed", s.meta_data.name)),
+                    _ => allocate(n, &pools)
+                        .and_then(|_| target.namespace(&subsystem.nqn, &namespace))
+                        .map_err(err),
+                };
+                if let Err(e) = res {
+                    errors.insert(format!("namespace/{}", n.meta_data.name), e);
+                }
+                if namespace.nsid != 0 {
+                    subsystem.namespaces.push(namespace);
+                }
+            }
+
+            targets
+                .entry(s.spec.backend)
+                .or_default()
+                .subsystems
+                .push(subsystem);
+        }
+
+        // The ports exposing the subsystems.
+        for p in &ports {
+            let key = format!("port/{}", p.meta_data.name);
+            let address = match p.spec.address.parse::<IpAddr>() {
+                Ok(address) => address,
+                Err(_) => {
+                    errors.insert(key, format!("invalid address <{}>", p.spec.address));
+                    continue;
+                }
+            };
+
+            // The subsystems exposed by the port in each backend; a port
+            // without subsystems is a listener of nvmet. A failed subsystem
+            // is kept exposed as it was.
+            let mut exposed: HashMap<NvmeBackend, Vec<String>> = HashMap::new();
+            if p.spec.subsystems.is_empty() {
+                exposed.insert(NvmeBackend::Nvmet, vec![]);
+            }
+            for name in &p.spec.subsystems {
+                let in_target = |s: &NvmeSubsystem| {
+                    targets
+                        .get(&s.spec.backend)
+                        .is_some_and(|t| t.subsystems.iter().any(|t| t.nqn == s.spec.nqn))
+                };
+                match subsystems.iter().find(|s| &s.meta_data.name == name) {
+                    Some(s) if in_target(s) => {
+                        exposed
+                            .entry(s.spec.backend)
+                            .or_default()
+                            .push(s.spec.nqn.clone());
+                        if errors.contains_key(&format!("subsystem/{}", name)) {
+                            errors.insert(key.clone(), format!("NvmeSubsystem <{}> failed", name));
+                        }
+                    }
+                    Some(_) => {
+                        errors.insert(key.clone(), format!("NvmeSubsystem <{}> failed", name));
+                    }
+                    None => {
+                        errors.insert(
+                            key.clone(),
+                            format!("NvmeSubsystem <{}> not found on the Node", name),
+                        );
+                    }
+                }
+            }
+
+            for (b, nqns) in exposed {
+                let port = Port {
+                    transport: p.spec.transport,
+                    address,
+                    service: p.spec.service,
+                    subsystems: nqns,
+                };
+                if let Err(e) = backend(b).port(&port).map_err(err) {
+                    errors.insert(key.clone(), e);
+                }
+                targets.entry(b).or_default().ports.push(port);
+            }
+        }
+
+        for b in backends {
+            if !backend(b).available() {
+                continue;
+            }
+            let target = targets.remove(&b).unwrap_or_default();
+            backend(b)
+                .prune(&target)
+                .map_err(|e| YangtzeError::GeneralError(format!("Failed to prune {}: {}", b, e)))?;
+        }
+        // Once not served anymore.
+        released(&volumes)?;
+
+        for s in subsystems {
+            let key = format!("subsystem/{}", s.meta_data.name);
+            self.report(nvme_subsystem::VERSION_KIND.kind, s, errors.get(&key))
+                .await;
+        }
+        for n in namespaces {
+            let key = format!("namespace/{}", n.meta_data.name);
+            self.report(nvme_namespace::VERSION_KIND.kind, n, errors.get(&key))
+                .await;
+        }
+        for p in ports {
+            let key = format!("port/{}", p.meta_data.name);
+            self.report(nvme_port::VERSION_KIND.kind, p, errors.get(&key))
+                .await;
+        }
+
+        Ok(())
+    }
+
+    fn client(&self, kind: &str) -> YangtzeClient {
+        self.client.clone().version("v1alpha1").kind(kind)
+    }
+
+    async fn list<T: DeserializeOwned>(
+        &self,
+        client: &YangtzeClient,
+    ) -> Result<Vec<T>, YangtzeError> {
+        client
+            .list(NamespaceName {
+                namespace: Some(self.namespace.clone()),
+                name: None,
+            })
+            .await
+    }
+
+    /// Sets the status of an object to `Ready`, or `Error` with the reason.
+    async fn report<T>(&self, kind: &str, mut o: T, error: Option<&String>)
+    where
+        T: Applied + Serialize + DeserializeOwned + Display,
+    {
+        let next = NvmeStatus {
+            state: match error {
+                Some(_) => NvmeState::Error,
+                None => NvmeState::Ready,
+            },
+            reason: error.cloned(),
+        };
+        if o.applied().as_ref() == Some(&next) {
+            return;
+        }
+
+        tracing::info!(
+            "The {} <{}> is {} {}",
+            kind,
+            o,
+            next.state,
+            next.reason.clone().unwrap_or_default()
+        );
+        o.set_applied(next);
+
+        if let Err(e) = self.client(kind).update(o).await {
+            tracing::error!("Failed to report the status of a {}: {}", kind, e);
+        }
+    }
+}
+
+/// The objects whose state of being applied to the target is reported.
+trait Applied {
+    fn applied(&self) -> Option<NvmeStatus>;
+    fn set_applied(&mut self, status: NvmeStatus);
+}
+
+impl Applied for NvmeSubsystem {
+    fn applied(&self) -> Option<NvmeStatus> {
+        self.status.as_ref().map(|s| NvmeStatus {
+            state: s.state.clone(),
+            reason: s.reason.clone(),
+        })
+    }
+
+    /// Keeps the hosts maintained by the AttachmentController.
+    fn set_applied(&mut self, status: NvmeStatus) {
+        let attached_hosts = self
+            .status
+            .take()
+            .map(|s| s.attached_hosts)
+            .unwrap_or_default();
+        self.status = Some(NvmeSubsystemStatus {
+            state: status.state,
+            reason: status.reason,
+            attached_hosts,
+        });
+    }
+}
+
+impl Applied for NvmeNamespace {
+    fn applied(&self) -> Option<NvmeStatus> {
+        self.status.clone()
+    }
+
+    fn set_applied(&mut self, status: NvmeStatus) {
+        self.status = Some(status);
+    }
+}
+
+impl Applied for NvmePort {
+    fn applied(&self) -> Option<NvmeStatus> {
+        self.status.clone()
+    }
+
+    fn set_applied(&mut self, status: NvmeStatus) {
+        self.status = Some(status);
+    }
+}
+
+/// The directory the device of a volume of a StoragePool is mounted at,
+/// `<root>/<device>`, which the file of the volume must be in.
+fn mount(ns: &NvmeNamespace, pools: &[StoragePool]) -> io::Result<PathBuf> {
+    let (Some(pool), Some(placement)) = (&ns.spec.pool, &ns.spec.placement) else {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "the volume is not placed yet",
+        ));
+    };
+    let p = pools
+        .iter()
+        .find(|p| &p.meta_data.name == pool)
+        .ok_or(io::Error::new(
+            ErrorKind::NotFound,
+            format!("StoragePool <{}> not found", pool),
+        ))?;
+
+    let mount = Path::new(&p.spec.root).join(&placement.device);
+    if Path::new(&ns.spec.device).parent() != Some(mount.as_path()) {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "the volume <{}> is not in <{}>",
+                ns.spec.device,
+                mount.display()
+            ),
+        ));
+    }
+    if !mount.is_dir() {
+        return Err(io::Error::new(
+            ErrorKind::NotFound,
+            format!(
+                "the device <{}> of StoragePool <{}> is not mounted at <{}>",
+                placement.device,
+                pool,
+                mount.display()
+            ),
+        ));
+    }
+
+    Ok(mount)
+}
+
+/// Creates the file of a volume of a StoragePool in the directory its device
+/// is mounted at, if not found, and marks it as created by the agent.
+fn allocate(ns: &NvmeNamespace, pools: &[StoragePool]) -> io::Result<()> {
+    if ns.spec.pool.is_none() || ns.spec.size == 0 {
+        return Ok(());
+    }
+    mount(ns, pools)?;
+
+    let device = &ns.spec.device;
+    if Path::new(device).exists() {
+        return Ok(());
+    }
+
+    tracing::info!("Create the volume <{}> of {} bytes.", device, ns.spec.size);
+    let err =
+        |e: io::Error| io::Error::new(e.kind(), format!("failed to create <{}>: {}", device, e));
+    fs::write(marker(device), &ns.meta_data.name).map_err(err)?;
+    fs::File::create_new(device)
+        .and_then(|f| f.set_len(ns.spec.size))
+        .map_err(err)
+}
+
+/// Removes the files of the volumes seen before which are not one of
+/// `volumes`, i.e. whose NvmeNamespace is deleted; only the files created by
+/// the agent in the mounts of the StoragePools are removed.
+fn release(pools: &[StoragePool], seen: &mut HashSet<String>, volumes: &[&str]) -> io::Result<()> {
+    let mut deleted: Vec<String> = seen
+        .iter()
+        .filter(|v| !volumes.contains(&v.as_str()))
+        .cloned()
+        .collect();
+    deleted.sort();
+    seen.retain(|v| volumes.contains(&v.as_str()));
+    seen.extend(volumes.iter().map(|v| v.to_string()));
+
+    for volume in deleted {
+        let file = Path::new(&volume);
+        let pooled = file
+            .parent()
+            .and_then(Path::parent)
+            .is_some_and(|root| pools.iter().any(|p| Path::new(&p.spec.root) == root));
+        let marker = marker(&volume);
+        if !pooled || !marker.is_file() {
+            continue;
+        }
+
+        tracing::info!("Remove the volume <{}>.", volume);
+        let res = match fs::remove_file(file) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => fs::remove_file(&marker),
+        };
+        if let Err(e) = res {
+            // Retried by the next reconcile.
+            seen.insert(volume);
+            return Err(e);
+        }
+    }
+
+    Ok(())
+}
+
+/// The file marking a volume as created by the agent, e.g. `vol1.img.yangtze`.
+fn marker(volume: &str) -> PathBuf {
+    PathBuf::from(format!("{}{}", volume, MARKER))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn entries(dir: &Path) -> io::Result<Vec<PathBuf>> {
+        Ok(fs::read_dir(dir)?.flatten().map(|e| e.path()).collect())
+    }
+
+    fn pool(root: &Path) -> StoragePool {
+        serde_json::from_value(json!({
+            "meta_data": { "kind": "storagepool", "namespace": "default", "name": "fast" },
+            "spec": { "root": root },
+        }))
+        .unwrap()
+    }
+
+    fn volume(name: &str, device: &Path) -> NvmeNamespace {
+        serde_json::from_value(json!({
+            "meta_data": { "kind": "nvmenamespace", "namespace": "default", "name": name },
+            "spec": {
+                "subsystem": "fast-node1",
+                "nsid": 1,
+                "device": device,
+                "pool": "fast",
+                "size": 4096,
+                "placement": { "node": "node1", "device": "nvme0n1", "domain": "r1" },
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn allocate_in_mount() {
+        let root = tempfile::tempdir().unwrap();
+        let mount = root.path().join("nvme0n1");
+        fs::create_dir(&mount).unwrap();
+        let vol1 = volume("vol1", &mount.join("vol1.img"));
+
+        allocate(&vol1, &[pool(root.path())]).unwrap();
+        assert_eq!(fs::metadata(mount.join("vol1.img")).unwrap().len(), 4096);
+        assert!(mount.join("vol1.img.yangtze").is_file());
+        // Found, it is kept as it is.
+        allocate(&vol1, &[pool(root.path())]).unwrap();
+    }
+
+    #[test]
+    fn allocate_without_mount() {
+        let root = tempfile::tempdir().unwrap();
+        let vol1 = volume("vol1", &root.path().join("nvme0n1/vol1.img"));
+
+        let e = allocate(&vol1, &[pool(root.path())]).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::NotFound);
+        assert!(e.to_string().contains("is not mounted at"));
+    }
+
+    #[test]
+    fn allocate_out_of_mount() {
+        let root = tempfile::tempdir().unwrap();
+        let mount = root.path().join("nvme0n1");
+        fs::create_dir(&mount).unwrap();
+
+        for device in [
+            root.path().join("vol1.img"),
+            mount.join("../vol1.img"),
+            Path::new("/etc/vol1.img").to_path_buf(),
+        ] {
+            let e = allocate(&volume("vol1", &device), &[pool(root.path())]).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::InvalidInput);
+        }
+        assert!(entries(root.path()).unwrap() == [mount]);
+    }
+
+    #[test]
+    fn allocate_without_pool() {
+        let root = tempfile::tempdir().unwrap();
+        let vol1 = volume("vol1", &root.path().join("nvme0n1/vol1.img"));
+
+        assert_eq!(
+            allocate(&vol1, &[]).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn release_deleted_volumes() {
+        let root = tempfile::tempdir().unwrap();
+        let mount = root.path().join("nvme0n1");
+        fs::create_dir(&mount).unwrap();
+        let pools = [pool(root.path())];
+        for name in ["vol1", "vol2", "vol3"] {
+            allocate(&volume(name, &mount.join(format!("{}.img", name))), &pools).unwrap();
+        }
+        // Not created by the agent.
+        fs::write(mount.join("vol4.img"), "").unwrap();
+        fs::write(mount.join("README"), "").unwrap();
+
+        let path = |name: &str| mount.join(name).to_str().unwrap().to_string();
+        let (vol1, vol2, vol4) = (path("vol1.img"), path("vol2.img"), path("vol4.img"));
+        let mut seen = HashSet::new();
+        release(&pools, &mut seen, &[&vol1, &vol2, &vol4]).unwrap();
+        assert_eq!(entries(&mount).unwrap().len(), 8);
+
+        // vol3 was never seen, e.g. before the agent restarted.
+        release(&pools, &mut seen, &[&vol1]).unwrap();
+        let mut left = entries(&mount).unwrap();
+        left.sort();
+        let expected: Vec<PathBuf> = [
+            "README",
+            "vol1.img",
+            "vol1.img.yangtze",
+            "vol3.img",
+            "vol3.img.yangtze",
+            "vol4.img",
+        ]
+        .into_iter()
+        .map(|f| mount.join(f))
+        .collect();
+        assert_eq!(left, expected);
+        assert_eq!(seen, HashSet::from([vol1]));
+    }
+}
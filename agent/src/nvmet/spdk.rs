@@ -0,0 +1,615 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use yangtze_apis::v1alpha1::nvme_port::NvmeTransport;
+
+use super::{Backend, Namespace, Port, Subsystem, Target};
+
+/// The prefix of the bdevs created for the namespaces.
+const BDEV_PREFIX: &str = "yangtze_";
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The SPDK NVMe-oF target driven through its JSON-RPC Unix socket: a
+/// namespace is an AIO bdev of its device, a port is a listener of each of
+/// its subsystems.
+///
+/// The subsystems and the `yangtze_` bdevs are owned by the agent: the ones
+/// not in the Target are removed.
+pub struct Spdk {
+    socket: PathBuf,
+    id: AtomicU64,
+}
+
+impl Spdk {
+    pub fn new(socket: &Path) -> Self {
+        Spdk {
+            socket: socket.to_path_buf(),
+            id: AtomicU64::new(1),
+        }
+    }
+
+    /// Calls a method and returns its result.
+    fn call(&self, method: &str, params: Option<Value>) -> io::Result<Value> {
+        let mut stream = UnixStream::connect(&self.socket).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!(
+                    "SPDK is not available at <{}>: {}",
+                    self.socket.display(),
+                    e
+                ),
+            )
+        })?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+
+        let id = self.id.fetch_add(1, Ordering::Relaxed);
+        let mut req = json!({ "jsonrpc": "2.0", "method": method, "id": id });
+        if let Some(params) = params {
+            req["params"] = params;
+        }
+        stream.write_all(req.to_string().as_bytes())?;
+
+        // The responses are not delimited, but one JSON object each.
+        let res: Value = serde_json::Deserializer::from_reader(&stream)
+            .into_iter::<Value>()
+            .next()
+            .ok_or(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("no response to <{}>", method),
+            ))??;
+
+        if let Some(e) = res.get("error") {
+            return Err(io::Error::other(format!(
+                "{} failed: {}",
+                method,
+                e["message"].as_str().unwrap_or_default()
+            )));
+        }
+
+        Ok(res["result"].clone())
+    }
+
+    fn subsystems(&self) -> io::Result<Vec<Value>> {
+        let subsystems = self.call("nvmf_get_subsystems", None)?;
+
+        Ok(subsystems
+            .as_array()
+            .cloned()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|s| s["subtype"] == "NVMe")
+            .collect())
+    }
+
+    fn find(&self, nqn: &str) -> io::Result<Option<Value>> {
+        Ok(self.subsystems()?.into_iter().find(|s| s["nqn"] == nqn))
+    }
+
+    fn bdevs(&self) -> io::Result<Vec<Value>> {
+        Ok(self
+            .call("bdev_get_bdevs", None)?
+            .as_array()
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+impl Backend for Spdk {
+    fn available(&self) -> bool {
+        self.socket.exists()
+    }
+
+    fn subsystem(&self, s: &Subsystem) -> io::Result<()> {
+        let any = s.allowed_hosts.is_empty();
+        let current = match self.find(&s.nqn)? {
+            Some(current) => current,
+            None => {
+                let mut params = json!({ "nqn": s.nqn, "allow_any_host": any });
+                if let Some(serial) = &s.serial {
+                    params["serial_number"] = json!(serial);
+                }
+                self.call("nvmf_create_subsystem", Some(params))?;
+                tracing::info!("Created SPDK subsystem <{}>.", s.nqn);
+
+                json!({ "nqn": s.nqn, "allow_any_host": any, "hosts": [] })
+            }
+        };
+
+        let hosts: Vec<&str> = current["hosts"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|h| h["nqn"].as_str())
+            .collect();
+        for host in hosts
+            .iter()
+            .filter(|h| !s.allowed_hosts.iter().any(|a| a == *h))
+        {
+            let params = json!({ "nqn": s.nqn, "host": host });
+            self.call("nvmf_subsystem_remove_host", Some(params))?;
+        }
+        if current["allow_any_host"] != any {
+            let params = json!({ "nqn": s.nqn, "allow_any_host": any });
+            self.call("nvmf_subsystem_allow_any_host", Some(params))?;
+        }
+        for host in s
+            .allowed_hosts
+            .iter()
+            .filter(|h| !hosts.contains(&h.as_str()))
+        {
+            let params = json!({ "nqn": s.nqn, "host": host });
+            self.call("nvmf_subsystem_add_host", Some(params))?;
+        }
+
+        Ok(())
+    }
+
+    fn namespace(&self, nqn: &str, ns: &Namespace) -> io::Result<()> {
+        let name = bdev_name(nqn, ns.nsid);
+        let bdev = self.bdevs()?.into_iter().find(|b| b["name"] == name);
+        let filename = bdev
+            .as_ref()
+            .map(|b| b["driver_specific"]["aio"]["filename"].clone());
+
+        let current = self
+            .find(nqn)?
+            .and_then(|s| s["namespaces"].as_array().cloned())
+            .unwrap_or_default()
+            .into_iter()
+            .find(|n| n["nsid"] == ns.nsid);
+
+        // The device of a bdev can not be changed.
+        if bdev.is_some() && filename != Some(json!(ns.device)) {
+            if current.is_some() {
+                let params = json!({ "nqn": nqn, "nsid": ns.nsid });
+                self.call("nvmf_subsystem_remove_ns", Some(params))?;
+            }
+            self.call("bdev_aio_delete", Some(json!({ "name": name })))?;
+            return self.namespace(nqn, ns);
+        }
+        if bdev.is_none() {
+            let params = json!({ "name": name, "filename": ns.device });
+            self.call("bdev_aio_create", Some(params))?;
+        }
+
+        match current {
+            Some(n) if n["bdev_name"] == name => {}
+            Some(_) => {
+                let params = json!({ "nqn": nqn, "nsid": ns.nsid });
+                self.call("nvmf_subsystem_remove_ns", Some(params))?;
+                self.add_ns(nqn, ns, &name)?;
+            }
+            None => self.add_ns(nqn, ns, &name)?,
+        }
+
+        Ok(())
+    }
+
+    fn port(&self, p: &Port) -> io::Result<()> {
+        let trtype = trtype(p.transport);
+        let transports = self.call("nvmf_get_transports", None)?;
+        let exists =
+            transports.as_array().into_iter().flatten().any(|t| {
+                t["trtype"].as_str().map(|t| t.eq_ignore_ascii_case(trtype)) == Some(true)
+            });
+        if !exists {
+            let params = json!({ "trtype": trtype });
+            self.call("nvmf_create_transport", Some(params))?;
+        }
+
+        let address = listen_address(p);
+        for nqn in &p.subsystems {
+            let listening = self
+                .find(nqn)?
+                .and_then(|s| s["listen_addresses"].as_array().cloned())
+                .unwrap_or_default()
+                .iter()
+                .any(|l| same_address(l, &address));
+            if !listening {
+                let params = json!({ "nqn": nqn, "listen_address": address });
+                self.call("nvmf_subsystem_add_listener", Some(params))?;
+                tracing::info!(
+                    "SPDK subsystem <{}> listens on {} {}:{}.",
+                    nqn,
+                    p.transport,
+                    p.address,
+                    p.service
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn prune(&self, target: &Target) -> io::Result<()> {
+        for current in self.subsystems()? {
+            let nqn = current["nqn"].as_str().unwrap_or_default();
+            let Some(s) = target.subsystems.iter().find(|s| s.nqn == nqn) else {
+                self.call("nvmf_delete_subsystem", Some(json!({ "nqn": nqn })))?;
+                tracing::info!("Deleted SPDK subsystem <{}>.", nqn);
+                continue;
+            };
+
+            for l in current["listen_addresses"].as_array().into_iter().flatten() {
+                let listened = target
+                    .ports
+                    .iter()
+                    .filter(|p| p.subsystems.contains(&s.nqn))
+                    .any(|p| same_address(l, &listen_address(p)));
+                if !listened {
+                    let params = json!({ "nqn": nqn, "listen_address": l });
+                    self.call("nvmf_subsystem_remove_listener", Some(params))?;
+                }
+            }
+
+            for n in current["namespaces"].as_array().into_iter().flatten() {
+                if !s.namespaces.iter().any(|ns| n["nsid"] == ns.nsid) {
+                    let params = json!({ "nqn": nqn, "nsid": n["nsid"] });
+                    self.call("nvmf_subsystem_remove_ns", Some(params))?;
+                }
+            }
+        }
+
+        let names: Vec<String> = target
+            .subsystems
+            .iter()
+            .flat_map(|s| s.namespaces.iter().map(|n| bdev_name(&s.nqn, n.nsid)))
+            .collect();
+        for b in self.bdevs()? {
+            let name = b["name"].as_str().unwrap_or_default();
+            if name.starts_with(BDEV_PREFIX) && !names.iter().any(|n| n == name) {
+                self.call("bdev_aio_delete", Some(json!({ "name": name })))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Spdk {
+    fn add_ns(&self, nqn: &str, ns: &Namespace, bdev: &str) -> io::Result<()> {
+        let params = json!({
+            "nqn": nqn,
+            "namespace": { "nsid": ns.nsid, "bdev_name": bdev },
+        });
+        self.call("nvmf_subsystem_add_ns", Some(params))?;
+
+        Ok(())
+    }
+}
+
+/// The bdev of a namespace, e.g. `yangtze_nqn.2023-01.cn.xflops_vol1_n1`.
+fn bdev_name(nqn: &str, nsid: u32) -> String {
+    let nqn: String = nqn
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                true => c,
+                false => '_',
+            },
+        )
+        .collect();
+
+    format!("{}{}_n{}", BDEV_PREFIX, nqn, nsid)
+}
+
+fn trtype(transport: NvmeTransport) -> &'static str {
+    match transport {
+        NvmeTransport::Tcp => "TCP",
+        NvmeTransport::Rdma => "RDMA",
+    }
+}
+
+fn listen_address(p: &Port) -> Value {
+    let adrfam = match p.address {
+        IpAddr::V4(_) => "IPv4",
+        IpAddr::V6(_) => "IPv6",
+    };
+
+    json!({
+        "trtype": trtype(p.transport),
+        "adrfam": adrfam,
+        "traddr": p.address.to_string(),
+        "trsvcid": p.service.to_string(),
+    })
+}
+
+fn same_address(a: &Value, b: &Value) -> bool {
+    ["trtype", "traddr", "trsvcid"].iter().all(|k| {
+        a[k].as_str().map(str::to_ascii_lowercase) == b[k].as_str().map(str::to_ascii_lowercase)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use super::*;
+
+    const NQN: &str = "nqn.2023-01.cn.xflops:vol1";
+    const HOST: &str = "nqn.2014-08.org.nvmexpress:uuid:host1";
+
+    /// A fake SPDK target serving the JSON-RPC methods of the backend, which
+    /// records the methods called.
+    #[derive(Default)]
+    struct Mock {
+        subsystems: Vec<Value>,
+        bdevs: Vec<Value>,
+        transports: Vec<Value>,
+        calls: Vec<String>,
+    }
+
+    impl Mock {
+        fn serve(socket: &Path) -> Arc<Mutex<Mock>> {
+            let mock = Arc::new(Mutex::new(Mock::default()));
+            let listener = UnixListener::bind(socket).unwrap();
+            let served = mock.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let mut stream = stream.unwrap();
+                    let Some(Ok(req)) = serde_json::Deserializer::from_reader(&stream)
+                        .into_iter::<Value>()
+                        .next()
+                    else {
+                        continue;
+                    };
+                    let res = match served.lock().unwrap().handle(&req) {
+                        Ok(result) => {
+                            json!({ "jsonrpc": "2.0", "id": req["id"], "result": result })
+                        }
+                        Err(message) => json!({
+                            "jsonrpc": "2.0",
+                            "id": req["id"],
+                            "error": { "code": -32602, "message": message },
+                        }),
+                    };
+                    stream.write_all(res.to_string().as_bytes()).unwrap();
+                }
+            });
+
+            mock
+        }
+
+        fn subsystem(&mut self, nqn: &Value) -> Result<&mut Value, String> {
+            self.subsystems
+                .iter_mut()
+                .find(|s| s["nqn"] == *nqn)
+                .ok_or(format!("subsystem {} not found", nqn))
+        }
+
+        fn handle(&mut self, req: &Value) -> Result<Value, String> {
+            let method = req["method"].as_str().unwrap_or_default().to_string();
+            let p = &req["params"];
+            self.calls.push(method.clone());
+
+            match method.as_str() {
+                "nvmf_get_subsystems" => return Ok(json!(self.subsystems)),
+                "bdev_get_bdevs" => return Ok(json!(self.bdevs)),
+                "nvmf_get_transports" => return Ok(json!(self.transports)),
+                "nvmf_create_subsystem" => self.subsystems.push(json!({
+                    "nqn": p["nqn"],
+                    "subtype": "NVMe",
+                    "allow_any_host": p["allow_any_host"],
+                    "hosts": [],
+                    "namespaces": [],
+                    "listen_addresses": [],
+                })),
+                "nvmf_delete_subsystem" => self.subsystems.retain(|s| s["nqn"] != p["nqn"]),
+                "nvmf_subsystem_allow_any_host" => {
+                    self.subsystem(&p["nqn"])?["allow_any_host"] = p["allow_any_host"].clone()
+                }
+                "nvmf_subsystem_add_host" => self.subsystem(&p["nqn"])?["hosts"]
+                    .as_array_mut()
+                    .unwrap()
+                    .push(json!({ "nqn": p["host"] })),
+                "nvmf_subsystem_remove_host" => self.subsystem(&p["nqn"])?["hosts"]
+                    .as_array_mut()
+                    .unwrap()
+                    .retain(|h| h["nqn"] != p["host"]),
+                "bdev_aio_create" => self.bdevs.push(json!({
+                    "name": p["name"],
+                    "driver_specific": { "aio": { "filename": p["filename"] } },
+                })),
+                "bdev_aio_delete" => self.bdevs.retain(|b| b["name"] != p["name"]),
+                "nvmf_subsystem_add_ns" => {
+                    let bdev = &p["namespace"]["bdev_name"];
+                    if !self.bdevs.iter().any(|b| b["name"] == *bdev) {
+                        return Err(format!("bdev {} not found", bdev));
+                    }
+                    self.subsystem(&p["nqn"])?["namespaces"]
+                        .as_array_mut()
+                        .unwrap()
+                        .push(p["namespace"].clone())
+                }
+                "nvmf_subsystem_remove_ns" => self.subsystem(&p["nqn"])?["namespaces"]
+                    .as_array_mut()
+                    .unwrap()
+                    .retain(|n| n["nsid"] != p["nsid"]),
+                "nvmf_create_transport" => self.transports.push(json!({ "trtype": p["trtype"] })),
+                "nvmf_subsystem_add_listener" => {
+                    let trtype = p["listen_address"]["trtype"].as_str().unwrap_or_default();
+                    if !self.transports.iter().any(|t| t["trtype"] == trtype) {
+                        return Err(format!("transport {} not found", trtype));
+                    }
+                    self.subsystem(&p["nqn"])?["listen_addresses"]
+                        .as_array_mut()
+                        .unwrap()
+                        .push(p["listen_address"].clone())
+                }
+                "nvmf_subsystem_remove_listener" => self.subsystem(&p["nqn"])?["listen_addresses"]
+                    .as_array_mut()
+                    .unwrap()
+                    .retain(|l| !same_address(l, &p["listen_address"])),
+                _ => return Err(format!("method {} not found", method)),
+            }
+
+            Ok(json!(true))
+        }
+
+        /// The methods changing the target called since the last time.
+        fn changes(&mut self) -> Vec<String> {
+            self.calls
+                .drain(..)
+                .filter(|m| !m.contains("_get_"))
+                .collect()
+        }
+    }
+
+    fn target() -> Target {
+        Target {
+            subsystems: vec![Subsystem {
+                nqn: NQN.to_string(),
+                allowed_hosts: vec![HOST.to_string()],
+                serial: Some("YZ0001".to_string()),
+                namespaces: vec![Namespace {
+                    nsid: 1,
+                    device: "/dev/nvme0n1".to_string(),
+                }],
+            }],
+            ports: vec![Port {
+                transport: NvmeTransport::Tcp,
+                address: "192.168.0.10".parse().unwrap(),
+                service: 4420,
+                subsystems: vec![NQN.to_string()],
+            }],
+        }
+    }
+
+    /// Applies a Target in the order of the reconciler.
+    fn apply(spdk: &Spdk, target: &Target) {
+        for s in &target.subsystems {
+            spdk.subsystem(s).unwrap();
+            for ns in &s.namespaces {
+                spdk.namespace(&s.nqn, ns).unwrap();
+            }
+        }
+        for p in &target.ports {
+            spdk.port(p).unwrap();
+        }
+        spdk.prune(target).unwrap();
+    }
+
+    #[test]
+    fn apply_sequence() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("spdk.sock");
+        let mock = Mock::serve(&socket);
+        let spdk = Spdk::new(&socket);
+        assert!(spdk.available());
+
+        apply(&spdk, &target());
+        assert_eq!(
+            mock.lock().unwrap().changes(),
+            [
+                "nvmf_create_subsystem",
+                "nvmf_subsystem_add_host",
+                "bdev_aio_create",
+                "nvmf_subsystem_add_ns",
+                "nvmf_create_transport",
+                "nvmf_subsystem_add_listener",
+            ]
+        );
+
+        let m = mock.lock().unwrap();
+        assert_eq!(m.subsystems[0]["hosts"], json!([{ "nqn": HOST }]));
+        assert_eq!(
+            m.subsystems[0]["namespaces"],
+            json!([{ "nsid": 1, "bdev_name": bdev_name(NQN, 1) }])
+        );
+        assert_eq!(
+            m.subsystems[0]["listen_addresses"][0]["traddr"],
+            "192.168.0.10"
+        );
+    }
+
+    #[test]
+    fn reapply_is_idempotent() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("spdk.sock");
+        let mock = Mock::serve(&socket);
+        let spdk = Spdk::new(&socket);
+
+        apply(&spdk, &target());
+        mock.lock().unwrap().changes();
+
+        apply(&spdk, &target());
+        assert!(mock.lock().unwrap().changes().is_empty());
+    }
+
+    #[test]
+    fn changed_device_recreates_bdev() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("spdk.sock");
+        let mock = Mock::serve(&socket);
+        let spdk = Spdk::new(&socket);
+
+        apply(&spdk, &target());
+        mock.lock().unwrap().changes();
+
+        let mut target = target();
+        target.subsystems[0].namespaces[0].device = "/dev/nvme1n1".to_string();
+        apply(&spdk, &target);
+        assert_eq!(
+            mock.lock().unwrap().changes(),
+            [
+                "nvmf_subsystem_remove_ns",
+                "bdev_aio_delete",
+                "bdev_aio_create",
+                "nvmf_subsystem_add_ns",
+            ]
+        );
+    }
+
+    #[test]
+    fn prune_removes_what_is_not_in_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("spdk.sock");
+        let mock = Mock::serve(&socket);
+        let spdk = Spdk::new(&socket);
+
+        apply(&spdk, &target());
+        mock.lock()
+            .unwrap()
+            .bdevs
+            .push(json!({ "name": "Malloc0" }));
+        mock.lock().unwrap().changes();
+
+        spdk.prune(&Target::default()).unwrap();
+        let mut m = mock.lock().unwrap();
+        assert_eq!(m.changes(), ["nvmf_delete_subsystem", "bdev_aio_delete"]);
+        // The bdevs not created by the agent are kept.
+        assert_eq!(m.bdevs, [json!({ "name": "Malloc0" })]);
+    }
+
+    #[test]
+    fn unavailable_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let spdk = Spdk::new(&dir.path().join("spdk.sock"));
+
+        assert!(!spdk.available());
+        let e = spdk.subsystem(&target().subsystems[0]).unwrap_err();
+        assert!(e.to_string().starts_with("SPDK is not available"));
+    }
+}
@@ -0,0 +1,36 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs::OpenOptions;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// The bytes zeroed at both ends of a disk: the partition tables, including
+/// the backup GPT at the end, and the filesystem signatures.
+const WIPE_SIZE: u64 = 1 << 20;
+
+pub fn wipe(disk: &Path) -> io::Result<()> {
+    let mut f = OpenOptions::new().write(true).open(disk)?;
+    let size = f.seek(SeekFrom::End(0))?;
+    let zeros = vec![0; WIPE_SIZE.min(size) as usize];
+
+    f.seek(SeekFrom::Start(0))?;
+    f.write_all(&zeros)?;
+    f.seek(SeekFrom::Start(size - zeros.len() as u64))?;
+    f.write_all(&zeros)?;
+    f.sync_all()?;
+
+    tracing::info!("Wiped <{}>.", disk.display());
+
+    Ok(())
+}
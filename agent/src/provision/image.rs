@@ -0,0 +1,91 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::path::Path;
+
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Bytes;
+use hyper::Uri;
+use hyper_util::rt::TokioIo;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+/// Writes the raw image at `url`, `http://` or a local path, to `disk`;
+/// returns the bytes written.
+pub async fn write(url: &str, disk: &Path) -> Result<u64, String> {
+    let err = |e: std::io::Error| format!("<{}>: {}", disk.display(), e);
+    let mut out = OpenOptions::new()
+        .write(true)
+        .open(disk)
+        .await
+        .map_err(err)?;
+
+    let n = match url
+        .strip_prefix("file://")
+        .or(if url.contains("://") { None } else { Some(url) })
+    {
+        Some(path) => {
+            let mut f = File::open(path)
+                .await
+                .map_err(|e| format!("<{}>: {}", path, e))?;
+            tokio::io::copy(&mut f, &mut out).await.map_err(err)?
+        }
+        None => download(url, &mut out).await?,
+    };
+
+    out.sync_all().await.map_err(err)?;
+
+    Ok(n)
+}
+
+async fn download(url: &str, out: &mut File) -> Result<u64, String> {
+    let uri: Uri = url.parse().map_err(|e| format!("<{}>: {}", url, e))?;
+    if uri.scheme_str() != Some("http") {
+        return Err(format!("<{}>: only http images are supported", url));
+    }
+    let host = uri.host().ok_or(format!("<{}>: no host", url))?.to_string();
+    let port = uri.port_u16().unwrap_or(80);
+
+    let stream = TcpStream::connect((host.as_str(), port))
+        .await
+        .map_err(|e| e.to_string())?;
+    let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .map_err(|e| e.to_string())?;
+    tokio::spawn(async move {
+        if let Err(e) = conn.await {
+            tracing::error!("Failed to download the image: {}", e);
+        }
+    });
+
+    let req = hyper::Request::get(uri.path_and_query().map(|p| p.as_str()).unwrap_or("/"))
+        .header(hyper::header::HOST, host)
+        .body(Empty::<Bytes>::new())
+        .map_err(|e| e.to_string())?;
+    let mut res = sender.send_request(req).await.map_err(|e| e.to_string())?;
+    if !res.status().is_success() {
+        return Err(format!("<{}>: {}", url, res.status()));
+    }
+
+    let mut n = 0;
+    while let Some(frame) = res.frame().await {
+        let frame = frame.map_err(|e| e.to_string())?;
+        if let Some(data) = frame.data_ref() {
+            out.write_all(data).await.map_err(|e| e.to_string())?;
+            n += data.len() as u64;
+        }
+    }
+
+    Ok(n)
+}
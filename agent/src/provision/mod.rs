@@ -0,0 +1,196 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use uuid::Uuid;
+use yangtze_apis::{
+    v1::{now, NamespaceName, YangtzeError},
+    v1alpha1::provision::{self, ActionRequest, ActionResult, Provision, ProvisionAction},
+};
+use yangtze_client::YangtzeClient;
+
+use crate::inventory;
+use crate::node::Agent;
+
+mod disk;
+mod image;
+
+/// The actions being run, by the uuid of their Provision, their id and
+/// attempt; ids are only unique within a Provision.
+type Running = Arc<Mutex<HashSet<(Uuid, u64, u32)>>>;
+
+/// Runs the actions the ProvisionController requests from this Node.
+pub struct Executor {
+    pub client: YangtzeClient,
+    pub namespace: String,
+    pub node: String,
+    pub host_root: PathBuf,
+    pub interval: u64,
+    pub agent: Arc<Agent>,
+}
+
+impl Executor {
+    pub async fn run(self) {
+        let executor = Arc::new(self);
+        let client = executor
+            .client
+            .clone()
+            .version(provision::VERSION_KIND.version)
+            .kind(provision::VERSION_KIND.kind);
+
+        let running: Running = Arc::default();
+
+        loop {
+            if let Err(e) = executor.reconcile(&client, &running).await {
+                tracing::error!("Failed to reconcile the Provisions: {}", e);
+            }
+            tokio::time::sleep(Duration::from_secs(executor.interval)).await;
+        }
+    }
+
+    async fn reconcile(
+        self: &Arc<Self>,
+        client: &YangtzeClient,
+        running: &Running,
+    ) -> Result<(), YangtzeError> {
+        let provisions: Vec<Provision> = client
+            .list(NamespaceName {
+                namespace: Some(self.namespace.clone()),
+                name: None,
+            })
+            .await?;
+
+        for p in provisions.into_iter().filter(|p| p.spec.node == self.node) {
+            let (Some(uuid), Some(status)) = (p.meta_data.uuid, &p.status) else {
+                continue;
+            };
+            // The controller reboots the Node through its BMC.
+            let Some(action) = status
+                .action
+                .clone()
+                .filter(|a| a.action != ProvisionAction::Reboot)
+            else {
+                continue;
+            };
+            let done = status
+                .result
+                .as_ref()
+                .is_some_and(|r| r.id == action.id && r.attempt == action.attempt);
+            if done
+                || !running
+                    .lock()
+                    .unwrap()
+                    .insert((uuid, action.id, action.attempt))
+            {
+                continue;
+            }
+
+            tracing::info!(
+                "Run {} of Provision <{}>, attempt {}.",
+                action.action,
+                p,
+                action.attempt
+            );
+
+            let (executor, client, running) = (self.clone(), client.clone(), running.clone());
+            tokio::spawn(async move {
+                let result = executor.execute(&p, &action).await;
+                if let Err(e) = &result {
+                    tracing::error!("Failed to {} for Provision <{}>: {}", action.action, p, e);
+                }
+                if let Err(e) = report(&client, &p, &action, result.err()).await {
+                    tracing::error!(
+                        "Failed to report {} of Provision <{}>: {}",
+                        action.action,
+                        p,
+                        e
+                    );
+                }
+                running
+                    .lock()
+                    .unwrap()
+                    .remove(&(uuid, action.id, action.attempt));
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn execute(&self, p: &Provision, action: &ActionRequest) -> Result<(), String> {
+        match action.action {
+            ProvisionAction::Inspect => self
+                .agent
+                .inspect()
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            ProvisionAction::Wipe => disk::wipe(&self.disk(p)?).map_err(|e| e.to_string()),
+            ProvisionAction::WriteImage => {
+                let url = p
+                    .spec
+                    .image
+                    .clone()
+                    .ok_or("no image to write".to_string())?;
+                let n = image::write(&url, &self.disk(p)?).await?;
+                tracing::info!("Wrote {} bytes of <{}>.", n, url);
+                Ok(())
+            }
+            ProvisionAction::Reboot => Err("the Node is rebooted through its BMC".to_string()),
+        }
+    }
+
+    /// The disk of the Provision under the host root, the first NVMe device
+    /// by default.
+    fn disk(&self, p: &Provision) -> Result<PathBuf, String> {
+        let disk = match &p.spec.disk {
+            Some(disk) => disk.clone(),
+            None => inventory::collect(&self.host_root)
+                .nvmes
+                .first()
+                .map(|n| format!("/dev/{}", n.name))
+                .ok_or("no NVMe device to deploy to".to_string())?,
+        };
+
+        Ok(self.host_root.join(disk.trim_start_matches('/')))
+    }
+}
+
+/// Sets the result of an action in the latest Provision.
+async fn report(
+    client: &YangtzeClient,
+    p: &Provision,
+    action: &ActionRequest,
+    error: Option<String>,
+) -> Result<(), YangtzeError> {
+    let id = p.meta_data.uuid.ok_or(YangtzeError::InvalidConfig(format!(
+        "The id of <{}> is none.",
+        p
+    )))?;
+
+    let mut p = client.get::<Provision>(id.to_string()).await?;
+    if let Some(status) = p.status.as_mut() {
+        status.result = Some(ActionResult {
+            id: action.id,
+            attempt: action.attempt,
+            error,
+            finished: now(),
+        });
+    }
+    let _p = client.update(p).await?;
+
+    Ok(())
+}
@@ -0,0 +1,112 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use yangtze_apis::{
+    v1::{NamespaceName, YangtzeError},
+    v1alpha1::node::{self, Node},
+};
+use yangtze_client::YangtzeClient;
+
+/// Installs the FRR configuration rendered into the Node, and reloads FRR
+/// when it changes.
+pub struct Installer {
+    pub client: YangtzeClient,
+    pub namespace: String,
+    pub node: String,
+    /// The configuration file of FRR, e.g. a fake file for testing.
+    pub config: PathBuf,
+    /// The command reloading FRR, nothing if empty.
+    pub reload_command: String,
+    pub interval: u64,
+}
+
+impl Installer {
+    pub async fn run(self) {
+        // The configuration FRR was reloaded with, to retry failed reloads.
+        let mut applied = None;
+
+        loop {
+            match self.reconcile(&applied).await {
+                Ok(Some(config)) => applied = Some(config),
+                Ok(None) => {}
+                Err(e) => tracing::error!("Failed to install the FRR configuration: {}", e),
+            }
+            tokio::time::sleep(Duration::from_secs(self.interval)).await;
+        }
+    }
+
+    /// Returns the configuration newly applied, if any.
+    async fn reconcile(&self, applied: &Option<String>) -> Result<Option<String>, YangtzeError> {
+        let nn = NamespaceName {
+            namespace: Some(self.namespace.clone()),
+            name: Some(self.node.clone()),
+        };
+        let Some(node) = self
+            .client
+            .clone()
+            .version(node::VERSION_KIND.version)
+            .kind(node::VERSION_KIND.kind)
+            .list::<Node>(nn)
+            .await?
+            .pop()
+        else {
+            return Ok(None);
+        };
+        // The host is not a BGP speaker, FRR is left as is.
+        let Some(config) = node.spec.frr_config.clone() else {
+            return Ok(None);
+        };
+
+        let io_err = |e: std::io::Error| {
+            YangtzeError::GeneralError(format!("{}: {}", self.config.display(), e))
+        };
+        let changed = fs::read_to_string(&self.config).ok().as_deref() != Some(config.as_str());
+        if changed {
+            if let Some(dir) = self.config.parent() {
+                fs::create_dir_all(dir).map_err(io_err)?;
+            }
+            let mut tmp = self.config.as_os_str().to_owned();
+            tmp.push(".tmp");
+            fs::write(&tmp, &config).map_err(io_err)?;
+            fs::rename(&tmp, &self.config).map_err(io_err)?;
+            tracing::info!("Installed the FRR configuration of Node <{}>.", node);
+        } else if applied.as_ref() == Some(&config) {
+            return Ok(None);
+        }
+
+        self.reload().await?;
+        Ok(Some(config))
+    }
+
+    async fn reload(&self) -> Result<(), YangtzeError> {
+        let mut args = self.reload_command.split_whitespace();
+        let Some(cmd) = args.next() else {
+            return Ok(());
+        };
+        tracing::info!("Reload FRR by <{}>.", self.reload_command);
+        match tokio::process::Command::new(cmd).args(args).status().await {
+            Ok(s) if s.success() => Ok(()),
+            Ok(s) => Err(YangtzeError::GeneralError(format!(
+                "failed to reload FRR: {}",
+                s
+            ))),
+            Err(e) => Err(YangtzeError::GeneralError(format!(
+                "failed to reload FRR: {}",
+                e
+            ))),
+        }
+    }
+}
@@ -0,0 +1,156 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use super::device::Device;
+
+/// The lines kept of a console.
+const CAPACITY: usize = 200;
+
+/// The last lines of the console of an rshim device.
+#[derive(Default)]
+pub struct Console {
+    /// The number of lines read so far, and the last ones.
+    lines: Mutex<(u64, VecDeque<String>)>,
+}
+
+impl Console {
+    /// Starts a thread reading the console of `device`, which is reopened
+    /// when closed, e.g. by the reset of the DPU.
+    pub fn start(device: Device) -> Arc<Console> {
+        let console = Arc::new(Console::default());
+
+        let c = console.clone();
+        thread::spawn(move || loop {
+            match device.console() {
+                Ok(file) => {
+                    for line in BufReader::new(file).lines() {
+                        match line {
+                            Ok(line) => c.push(line.trim_end().to_string()),
+                            Err(e) => {
+                                tracing::debug!("Failed to read <{}>: {}", device.name, e);
+                                break;
+                            }
+                        }
+                    }
+                }
+                Err(e) => tracing::error!("Failed to open the console of <{}>: {}", device.name, e),
+            }
+            thread::sleep(Duration::from_secs(1));
+        });
+
+        console
+    }
+
+    fn push(&self, line: String) {
+        let mut lines = self.lines.lock().unwrap();
+        lines.0 += 1;
+        lines.1.push_back(line);
+        if lines.1.len() > CAPACITY {
+            lines.1.pop_front();
+        }
+    }
+
+    /// The number of lines read so far.
+    pub fn seq(&self) -> u64 {
+        self.lines.lock().unwrap().0
+    }
+
+    /// The last `n` lines.
+    pub fn tail(&self, n: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        lines
+            .1
+            .iter()
+            .skip(lines.1.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+
+    /// The lines read after the `seq`th line, as far as they are kept.
+    pub fn since(&self, seq: u64) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        let n = lines.0.saturating_sub(seq) as usize;
+        lines
+            .1
+            .iter()
+            .skip(lines.1.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use std::time::Instant;
+
+    use super::*;
+    use crate::rshim::device::fixtures::*;
+
+    /// Waits until the console has read `n` lines.
+    fn wait(console: &Console, n: u64) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while console.seq() < n {
+            assert!(Instant::now() < deadline, "read {} lines", console.seq());
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn keep_last_lines() {
+        let console = Console::default();
+        for i in 0..CAPACITY + 10 {
+            console.push(format!("line {}", i));
+        }
+
+        assert_eq!(console.seq(), CAPACITY as u64 + 10);
+        assert_eq!(console.tail(2), vec!["line 208", "line 209"]);
+        assert_eq!(console.since(console.seq() - 1), vec!["line 209"]);
+        assert_eq!(console.since(0).len(), CAPACITY);
+    }
+
+    #[test]
+    fn stream_console_fifo() {
+        let root = tempfile::tempdir().unwrap();
+        let path = rshim(root.path(), "rshim0");
+        mkfifo(&path.join("console"));
+        let device = Device::discover(root.path()).remove(0);
+        let console = Console::start(device);
+
+        let mut writer = OpenOptions::new()
+            .write(true)
+            .open(path.join("console"))
+            .unwrap();
+        writer.write_all(b"UEFI firmware\r\nBooting\r\n").unwrap();
+        wait(&console, 2);
+        assert_eq!(console.tail(5), vec!["UEFI firmware", "Booting"]);
+
+        // The DPU resets, closing the console, which is reopened.
+        let seq = console.seq();
+        drop(writer);
+        let mut writer = OpenOptions::new()
+            .write(true)
+            .open(path.join("console"))
+            .unwrap();
+        writer.write_all(b"Linux up\n").unwrap();
+        wait(&console, 3);
+        assert_eq!(console.since(seq), vec!["Linux up"]);
+    }
+}
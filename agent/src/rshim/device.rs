@@ -0,0 +1,217 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+/// An rshim device of a BlueField DPU, e.g. `/dev/rshim0`, with its `boot`,
+/// `console` and `misc` files.
+#[derive(Clone, Debug)]
+pub struct Device {
+    pub name: String,
+    path: PathBuf,
+}
+
+impl Device {
+    /// The rshim devices under `root`, which is `/dev` or a fake directory.
+    pub fn discover(root: &Path) -> Vec<Device> {
+        let mut devices: Vec<_> = fs::read_dir(root)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|e| e.file_name().to_string_lossy().to_string())
+                    .filter(|name| name.starts_with("rshim"))
+                    .map(|name| Device {
+                        path: root.join(&name),
+                        name,
+                    })
+                    .filter(|d| d.path.join("boot").exists())
+                    .collect()
+            })
+            .unwrap_or_default();
+        devices.sort_by(|a, b| a.name.cmp(&b.name));
+
+        devices
+    }
+
+    /// The `KEY VALUE` lines of `misc`, e.g. `BOOT_MODE 1` or
+    /// `DEV_NAME pcie-0000:03:00.0`.
+    pub fn misc(&self) -> io::Result<HashMap<String, String>> {
+        // Do not wait for a writer if `misc` is a pipe.
+        let mut file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(self.path.join("misc"))?;
+
+        let mut content = String::new();
+        match file.read_to_string(&mut content) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(content
+            .lines()
+            .filter_map(|l| l.trim().split_once(char::is_whitespace))
+            .map(|(k, v)| (k.to_string(), v.trim().to_string()))
+            .collect())
+    }
+
+    /// The PCI domain:bus:device of the DPU, from the `DEV_NAME` in `misc`.
+    pub fn pci_slot(&self) -> Option<String> {
+        let misc = self.misc().ok()?;
+        let addr = misc.get("DEV_NAME")?.strip_prefix("pcie-")?;
+
+        Some(
+            addr.rsplit_once('.')
+                .map(|(slot, _)| slot.to_string())
+                .unwrap_or(addr.to_string()),
+        )
+    }
+
+    /// Resets the DPU.
+    pub fn reset(&self) -> io::Result<()> {
+        // Fail instead of blocking if nobody reads the pipe.
+        let mut file = OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(self.path.join("misc"))?;
+
+        file.write_all(b"SW_RESET 1\n")
+    }
+
+    /// Pushes a BFB image into the boot stream, which resets the DPU and
+    /// boots it from the image; blocks until the whole image is written.
+    pub fn push(&self, image: &Path) -> io::Result<u64> {
+        let mut bfb = File::open(image)?;
+        let mut boot = OpenOptions::new()
+            .write(true)
+            .open(self.path.join("boot"))?;
+
+        io::copy(&mut bfb, &mut boot)
+    }
+
+    pub fn console(&self) -> io::Result<File> {
+        File::open(self.path.join("console"))
+    }
+}
+
+#[cfg(test)]
+pub mod fixtures {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    use super::*;
+
+    /// Creates a fake rshim device under `root` with a regular `boot`.
+    pub fn rshim(root: &Path, name: &str) -> PathBuf {
+        let path = root.join(name);
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("boot"), b"").unwrap();
+        path
+    }
+
+    /// Replaces a file of a fake rshim device with a FIFO, like the ones of
+    /// the rshim driver.
+    pub fn mkfifo(path: &Path) {
+        let _ = fs::remove_file(path);
+        let c = CString::new(path.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c.as_ptr(), 0o600) }, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::fixtures::*;
+    use super::*;
+
+    #[test]
+    fn discover_devices() {
+        let root = tempfile::tempdir().unwrap();
+        rshim(root.path(), "rshim1");
+        rshim(root.path(), "rshim0");
+        // Neither a boot stream, nor an rshim device.
+        fs::create_dir(root.path().join("rshim2")).unwrap();
+        fs::create_dir(root.path().join("tty0")).unwrap();
+
+        let names: Vec<_> = Device::discover(root.path())
+            .into_iter()
+            .map(|d| d.name)
+            .collect();
+        assert_eq!(names, vec!["rshim0", "rshim1"]);
+        assert!(Device::discover(&root.path().join("missing")).is_empty());
+    }
+
+    #[test]
+    fn read_pci_slot() {
+        let root = tempfile::tempdir().unwrap();
+        let path = rshim(root.path(), "rshim0");
+        fs::write(
+            path.join("misc"),
+            "BOOT_MODE 1\nDEV_NAME  pcie-0000:03:00.0\nDROP_MODE 0\n",
+        )
+        .unwrap();
+
+        let device = &Device::discover(root.path())[0];
+        assert_eq!(device.misc().unwrap()["BOOT_MODE"], "1");
+        assert_eq!(device.pci_slot().as_deref(), Some("0000:03:00"));
+    }
+
+    #[test]
+    fn push_to_boot_fifo() {
+        let root = tempfile::tempdir().unwrap();
+        let path = rshim(root.path(), "rshim0");
+        mkfifo(&path.join("boot"));
+        let image = root.path().join("image.bfb");
+        let bfb: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        fs::write(&image, &bfb).unwrap();
+
+        // The driver reads the boot stream while the image is written.
+        let boot = path.join("boot");
+        let reader = thread::spawn(move || fs::read(boot).unwrap());
+        let device = &Device::discover(root.path())[0];
+        assert_eq!(device.push(&image).unwrap(), bfb.len() as u64);
+        assert_eq!(reader.join().unwrap(), bfb);
+
+        assert_eq!(
+            device
+                .push(&root.path().join("missing.bfb"))
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn reset_through_misc() {
+        let root = tempfile::tempdir().unwrap();
+        let path = rshim(root.path(), "rshim0");
+        fs::write(path.join("misc"), "").unwrap();
+        let device = &Device::discover(root.path())[0];
+
+        device.reset().unwrap();
+        assert_eq!(
+            fs::read_to_string(path.join("misc")).unwrap(),
+            "SW_RESET 1\n"
+        );
+
+        // Nobody reads the pipe, so the reset fails instead of blocking.
+        mkfifo(&path.join("misc"));
+        assert!(device.reset().is_err());
+    }
+}
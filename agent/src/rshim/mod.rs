@@ -0,0 +1,395 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use yangtze_apis::{
+    v1::{now, NamespaceName, YangtzeError},
+    v1alpha1::xpu::{self, Xpu, XpuState, XpuStatus},
+};
+use yangtze_client::YangtzeClient;
+
+mod console;
+mod device;
+
+use console::Console;
+use device::Device;
+
+/// The console lines reported in the Xpu status.
+const CONSOLE_LINES: usize = 50;
+
+/// Seconds between the reports of the console alone, as a busy console
+/// would otherwise update the Xpu on each interval.
+const CONSOLE_INTERVAL: u64 = 60;
+
+/// The console lines telling the DPU booted from the BFB image.
+const READY_MARKERS: &[&str] = &["Installation finished", "Linux up"];
+
+/// A BFB image pushed into the boot stream of an Xpu.
+#[derive(Clone, Debug)]
+enum Push {
+    /// Writing the image, since the `seq`th console line.
+    Running(u64),
+    /// The image was written; waiting for the DPU to boot.
+    Done(u64),
+    Failed(String),
+}
+
+/// The pushes in progress or finished, by Xpu name.
+type Pushes = Arc<Mutex<HashMap<String, Push>>>;
+
+/// Provisions and resets the DPUs of the Node through their rshim devices,
+/// and reports their state and console in the Xpus.
+pub struct Manager {
+    pub client: YangtzeClient,
+    pub namespace: String,
+    pub node: String,
+    /// The directory of the rshim devices, `/dev` or a fake one.
+    pub root: PathBuf,
+    pub interval: u64,
+}
+
+impl Manager {
+    pub async fn run(self) {
+        let client = self
+            .client
+            .clone()
+            .version(xpu::VERSION_KIND.version)
+            .kind(xpu::VERSION_KIND.kind);
+
+        let mut consoles: HashMap<String, Arc<Console>> = HashMap::new();
+        let pushes: Pushes = Arc::default();
+        // When the console of each Xpu was last reported.
+        let mut reported: HashMap<String, u64> = HashMap::new();
+
+        loop {
+            if let Err(e) = self
+                .reconcile(&client, &mut consoles, &pushes, &mut reported)
+                .await
+            {
+                tracing::error!("Failed to reconcile the rshim devices: {}", e);
+            }
+            tokio::time::sleep(Duration::from_secs(self.interval)).await;
+        }
+    }
+
+    async fn reconcile(
+        &self,
+        client: &YangtzeClient,
+        consoles: &mut HashMap<String, Arc<Console>>,
+        pushes: &Pushes,
+        reported: &mut HashMap<String, u64>,
+    ) -> Result<(), YangtzeError> {
+        let devices = Device::discover(&self.root);
+        if devices.is_empty() {
+            return Ok(());
+        }
+
+        let xpus: Vec<Xpu> = client
+            .list::<Xpu>(NamespaceName {
+                namespace: Some(self.namespace.clone()),
+                name: None,
+            })
+            .await?
+            .into_iter()
+            .filter(|x: &Xpu| x.spec.node == self.node)
+            .collect();
+
+        for (x, device) in pair(xpus, devices) {
+            let console = consoles
+                .entry(device.name.clone())
+                .or_insert_with(|| {
+                    tracing::info!("Streaming the console of <{}>.", device.name);
+                    Console::start(device.clone())
+                })
+                .clone();
+
+            let console_due = reported
+                .get(&x.meta_data.name)
+                .is_none_or(|t| now().saturating_sub(*t) >= CONSOLE_INTERVAL);
+            let status = status(&x, &device, &console, pushes, console_due);
+            if x.status.as_ref() == Some(&status) {
+                continue;
+            }
+            if x.status.as_ref().map(|s| &s.console) != Some(&status.console) {
+                reported.insert(x.meta_data.name.clone(), now());
+            }
+
+            if x.status.as_ref().map(|s| &s.state) != Some(&status.state) {
+                tracing::info!(
+                    "Xpu <{}> is {} {}",
+                    x,
+                    status.state,
+                    status.reason.clone().unwrap_or_default()
+                );
+            }
+
+            let mut x = x;
+            x.status = Some(status);
+            client.update::<Xpu>(x).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The next status of an Xpu; starts the provisioning or the reset
+/// requested by its spec. The console is only refreshed if `console_due`,
+/// or along with a new state.
+fn status(
+    x: &Xpu,
+    device: &Device,
+    console: &Arc<Console>,
+    pushes: &Pushes,
+    console_due: bool,
+) -> XpuStatus {
+    let mut status = x.status.clone().unwrap_or(XpuStatus {
+        state: XpuState::Discovered,
+        firmware: String::new(),
+        representors: vec![],
+        reason: None,
+        last_transition: now(),
+        rshim: None,
+        bfb: None,
+        resets: 0,
+        retries: 0,
+        console: vec![],
+    });
+    status.rshim = Some(device.name.clone());
+    let state = status.state.clone();
+
+    let name = x.meta_data.name.clone();
+    let push = pushes.lock().unwrap().get(&name).cloned();
+
+    match push {
+        Some(Push::Running(_)) => {}
+        Some(Push::Failed(e)) => {
+            pushes.lock().unwrap().remove(&name);
+            transit(&mut status, XpuState::Error, Some(e));
+        }
+        Some(Push::Done(_)) if status.state != XpuState::Provisioning => {
+            // The controller gave up on the provisioning.
+            pushes.lock().unwrap().remove(&name);
+        }
+        Some(Push::Done(seq)) if ready(&console.since(seq)) => {
+            pushes.lock().unwrap().remove(&name);
+            transit(&mut status, XpuState::Ready, None);
+        }
+        Some(Push::Done(_)) => {}
+        None if x.spec.bfb.is_some()
+            && (x.spec.bfb != status.bfb || x.spec.retries > status.retries) =>
+        {
+            let image = x.spec.bfb.clone().unwrap_or_default();
+            tracing::info!("Pushing <{}> to <{}> of Xpu <{}>.", image, device.name, x);
+
+            status.bfb = x.spec.bfb.clone();
+            status.retries = x.spec.retries;
+            transit(&mut status, XpuState::Provisioning, None);
+
+            pushes
+                .lock()
+                .unwrap()
+                .insert(name.clone(), Push::Running(console.seq()));
+            start_push(name, device.clone(), PathBuf::from(image), pushes.clone());
+        }
+        // The agent restarted while the DPU was booting.
+        None if status.state == XpuState::Provisioning && ready(&console.tail(CONSOLE_LINES)) => {
+            transit(&mut status, XpuState::Ready, None);
+        }
+        None => {}
+    }
+
+    if x.spec.resets > status.resets {
+        status.resets = x.spec.resets;
+        match device.reset() {
+            Ok(()) => tracing::info!("Reset <{}> of Xpu <{}>.", device.name, x),
+            Err(e) => transit(
+                &mut status,
+                XpuState::Error,
+                Some(format!("failed to reset <{}>: {}", device.name, e)),
+            ),
+        }
+    }
+
+    if console_due || status.state != state {
+        status.console = console.tail(CONSOLE_LINES);
+    }
+
+    status
+}
+
+/// Writes the image in a blocking thread, as the boot stream is a pipe.
+fn start_push(name: String, device: Device, image: PathBuf, pushes: Pushes) {
+    tokio::task::spawn_blocking(move || {
+        let result = device.push(&image);
+
+        let mut pushes = pushes.lock().unwrap();
+        let seq = match pushes.get(&name) {
+            Some(Push::Running(seq)) => *seq,
+            _ => 0,
+        };
+        let push = match result {
+            Ok(n) => {
+                tracing::info!(
+                    "Pushed {} bytes of <{}> to <{}>.",
+                    n,
+                    image.display(),
+                    device.name
+                );
+                Push::Done(seq)
+            }
+            Err(e) => Push::Failed(format!(
+                "failed to push <{}> to <{}>: {}",
+                image.display(),
+                device.name,
+                e
+            )),
+        };
+        pushes.insert(name, push);
+    });
+}
+
+fn transit(status: &mut XpuStatus, state: XpuState, reason: Option<String>) {
+    status.state = state;
+    status.reason = reason;
+    status.last_transition = now();
+}
+
+fn ready(lines: &[String]) -> bool {
+    lines
+        .iter()
+        .any(|l| READY_MARKERS.iter().any(|m| l.contains(m)))
+}
+
+/// Pairs the Xpus with the rshim devices by PCI slot; a single Xpu and a
+/// single device are paired anyway, as fake devices have no `DEV_NAME`.
+fn pair(xpus: Vec<Xpu>, devices: Vec<Device>) -> Vec<(Xpu, Device)> {
+    if xpus.len() == 1 && devices.len() == 1 {
+        return xpus.into_iter().zip(devices).collect();
+    }
+
+    let slots: HashMap<String, Device> = devices
+        .into_iter()
+        .filter_map(|d| Some((d.pci_slot()?, d)))
+        .collect();
+
+    xpus.into_iter()
+        .filter_map(|x| {
+            let d = slots.get(&x.spec.pci_slot)?.clone();
+            Some((x, d))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Instant;
+
+    use serde_json::json;
+
+    use super::device::fixtures::*;
+    use super::*;
+
+    fn xpu(bfb: &str, retries: u64, status: Option<&XpuStatus>) -> Xpu {
+        serde_json::from_value(json!({
+            "meta_data": {"kind": "xpu", "namespace": "default", "name": "x1", "labels": [], "version": 0},
+            "spec": {"node": "n1", "bfb": bfb, "retries": retries},
+            "status": status,
+        }))
+        .unwrap()
+    }
+
+    /// Waits until the push of x1 is no longer running.
+    async fn pushed(pushes: &Pushes) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while matches!(pushes.lock().unwrap().get("x1"), Some(Push::Running(_))) {
+            assert!(Instant::now() < deadline, "the push is still running");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_failed_push() {
+        let root = tempfile::tempdir().unwrap();
+        let path = rshim(root.path(), "rshim0");
+        let device = Device::discover(root.path()).remove(0);
+        let console = Arc::new(Console::default());
+        let pushes: Pushes = Arc::default();
+        let image = root.path().join("image.bfb");
+        let bfb = image.to_str().unwrap();
+
+        // The image is missing, so the push fails.
+        let s = status(&xpu(bfb, 0, None), &device, &console, &pushes, true);
+        assert_eq!(s.state, XpuState::Provisioning);
+        pushed(&pushes).await;
+        let s = status(&xpu(bfb, 0, Some(&s)), &device, &console, &pushes, true);
+        assert_eq!(s.state, XpuState::Error);
+        assert!(s.reason.as_deref().unwrap().contains("image.bfb"));
+
+        // Not pushed again with the same image, until retried.
+        fs::write(&image, b"bfb").unwrap();
+        let s = status(&xpu(bfb, 0, Some(&s)), &device, &console, &pushes, true);
+        assert_eq!(s.state, XpuState::Error);
+        assert!(pushes.lock().unwrap().is_empty());
+
+        let s = status(&xpu(bfb, 1, Some(&s)), &device, &console, &pushes, true);
+        assert_eq!((s.state.clone(), s.retries), (XpuState::Provisioning, 1));
+        pushed(&pushes).await;
+        assert_eq!(fs::read(path.join("boot")).unwrap(), b"bfb");
+
+        // Waiting for the DPU to boot from the image.
+        let s = status(&xpu(bfb, 1, Some(&s)), &device, &console, &pushes, true);
+        assert_eq!(s.state, XpuState::Provisioning);
+        assert!(matches!(
+            pushes.lock().unwrap().get("x1"),
+            Some(Push::Done(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn refresh_console_when_due() {
+        let root = tempfile::tempdir().unwrap();
+        rshim(root.path(), "rshim0");
+        let device = Device::discover(root.path()).remove(0);
+        let console = Arc::new(Console::default());
+        let pushes: Pushes = Arc::default();
+
+        let mut last = XpuStatus {
+            state: XpuState::Ready,
+            firmware: String::new(),
+            representors: vec![],
+            reason: None,
+            last_transition: 0,
+            rshim: Some("rshim0".to_string()),
+            bfb: None,
+            resets: 0,
+            retries: 0,
+            console: vec!["old".to_string()],
+        };
+        let x: Xpu = serde_json::from_value(json!({
+            "meta_data": {"kind": "xpu", "namespace": "default", "name": "x1", "labels": [], "version": 0},
+            "spec": {"node": "n1"},
+            "status": last,
+        }))
+        .unwrap();
+
+        // The console alone does not change the status until due.
+        assert_eq!(status(&x, &device, &console, &pushes, false), last);
+        last.console = vec![];
+        assert_eq!(status(&x, &device, &console, &pushes, true), last);
+    }
+}
@@ -0,0 +1,99 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use yangtze_apis::{
+    v1::{now, Metadata, NamespaceName, YangtzeError},
+    v1alpha1::{
+        node::Dpu,
+        xpu::{self, Xpu, XpuMode, XpuSpec, XpuState, XpuStatus},
+    },
+};
+use yangtze_client::YangtzeClient;
+
+/// The name of the Xpu of a DPU, e.g. `node1-0000-03-00`.
+pub fn name(node: &str, dpu: &Dpu) -> String {
+    format!("{}-{}", node, dpu.name.replace(':', "-"))
+}
+
+/// Creates an Xpu for each DPU of the Node, and keeps its firmware and
+/// representors up to date; the state is left to the provisioning.
+pub async fn report(
+    client: &YangtzeClient,
+    namespace: &str,
+    node: &str,
+    dpus: &[Dpu],
+) -> Result<(), YangtzeError> {
+    let client = client
+        .clone()
+        .version(xpu::VERSION_KIND.version)
+        .kind(xpu::VERSION_KIND.kind);
+
+    for dpu in dpus {
+        let nn = NamespaceName {
+            namespace: Some(namespace.to_string()),
+            name: Some(name(node, dpu)),
+        };
+
+        match client.list::<Xpu>(nn).await?.pop() {
+            None => {
+                let x = Xpu {
+                    meta_data: Metadata {
+                        uuid: None,
+                        kind: xpu::VERSION_KIND.kind.to_string(),
+                        namespace: namespace.to_string(),
+                        name: name(node, dpu),
+                        labels: vec![],
+                        version: 0,
+                    },
+                    spec: XpuSpec {
+                        node: node.to_string(),
+                        model: dpu.model.clone(),
+                        mode: XpuMode::default(),
+                        pci_slot: dpu.name.clone(),
+                        bfb: None,
+                        resets: 0,
+                        retries: 0,
+                    },
+                    status: Some(XpuStatus {
+                        state: XpuState::Discovered,
+                        firmware: dpu.firmware.clone(),
+                        representors: dpu.representors.clone(),
+                        reason: None,
+                        last_transition: now(),
+                        rshim: None,
+                        bfb: None,
+                        resets: 0,
+                        retries: 0,
+                        console: vec![],
+                    }),
+                };
+                let x = client.create(x).await?;
+                tracing::info!("The DPU <{}> was registered as Xpu <{}>.", dpu.name, x);
+            }
+            Some(mut x) => {
+                let Some(status) = x.status.as_mut() else {
+                    continue;
+                };
+                if status.firmware == dpu.firmware && status.representors == dpu.representors {
+                    continue;
+                }
+
+                status.firmware = dpu.firmware.clone();
+                status.representors = dpu.representors.clone();
+                let _x = client.update(x).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
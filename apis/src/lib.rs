@@ -14,11 +14,47 @@
 pub mod v1;
 pub mod v1alpha1;
 
-use v1::VersionKind;
+use v1::{Column, VersionKind};
+
+/// Declares all kinds served by the yangtze-apiserver by their modules, so
+/// `KINDS` and `get_columns` come from the same list.
+macro_rules! kinds {
+    ($($m:ident),* $(,)?) => {
+        /// All kinds served by the yangtze-apiserver.
+        pub const KINDS: &[VersionKind] = &[$(v1alpha1::$m::VERSION_KIND),*];
+
+        /// The columns `yzctl` prints for the objects of a kind; the default
+        /// columns for the kinds which did not declare their own.
+        pub fn get_columns(kind: &str) -> &'static [Column] {
+            const COLUMNS: &[(&str, &[Column])] =
+                &[$((v1alpha1::$m::VERSION_KIND.kind, v1alpha1::$m::COLUMNS)),*];
+
+            match COLUMNS.iter().find(|(k, _)| *k == kind) {
+                Some((_, columns)) if !columns.is_empty() => columns,
+                _ => v1::DEFAULT_COLUMNS,
+            }
+        }
+    };
+}
+
+kinds!(
+    fabric,
+    node,
+    switch,
+    xpu,
+    subnet,
+    dhcp_lease,
+    boot_profile,
+    provision,
+    nvme_subsystem,
+    nvme_namespace,
+    nvme_port,
+    volume_attachment,
+    storage_pool,
+    vpc,
+    network_interface,
+);
 
 pub fn get_version_kind(vk: &str) -> Option<VersionKind> {
-    match vk {
-        "fabric" => Some(v1alpha1::fabric::VERSION_KIND),
-        _ => None,
-    }
+    KINDS.iter().find(|k| k.kind == vk).cloned()
 }
@@ -13,6 +13,7 @@
 
 use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display, Formatter};
+use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::error;
 
 use actix_web::{error, http::StatusCode, Result};
@@ -59,6 +60,14 @@ impl error::ResponseError for YangtzeError {
     }
 }
 
+/// The seconds since the Unix epoch, as stored in the timestamps of statuses.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct NamespaceName {
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -83,3 +92,32 @@ impl fmt::Display for VersionKind {
         write!(f, "{}/{}", self.version, self.kind)
     }
 }
+
+/// A column printed by `yzctl` for a kind; `path` is the dotted path of the
+/// field in the object, e.g. `status.state`.
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub name: &'static str,
+    pub path: &'static str,
+    /// Only printed in the wide output.
+    pub wide: bool,
+}
+
+/// The columns of the kinds which did not declare their own.
+pub const DEFAULT_COLUMNS: &[Column] = &[
+    Column {
+        name: "UUID",
+        path: "meta_data.uuid",
+        wide: false,
+    },
+    Column {
+        name: "NAMESPACE",
+        path: "meta_data.namespace",
+        wide: false,
+    },
+    Column {
+        name: "NAME",
+        path: "meta_data.name",
+        wide: false,
+    },
+];
@@ -0,0 +1,81 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt::{Display, Formatter};
+
+use crate::v1::{Column, Metadata, VersionKind};
+
+use serde::{Deserialize, Serialize};
+
+/// What a Node network boots: the iPXE script rendered for it loads the
+/// kernel and the initrd, and passes the OS image to the installer.
+///
+/// `{node}`, `{mac}` and `{image}` in the cmdline are replaced by the name
+/// of the Node, the MAC it boots from and the image.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BootProfileSpec {
+    /// The URL of the kernel, or a path served by the boot server.
+    pub kernel: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initrd: Option<String>,
+    #[serde(default)]
+    pub cmdline: String,
+    /// The URL of the OS image to install.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BootProfile {
+    pub meta_data: Metadata,
+    pub spec: BootProfileSpec,
+}
+
+impl Display for BootProfile {
+    fn fmt(&self, writer: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(writer, "{0}", self.meta_data.name)
+    }
+}
+
+pub const VERSION_KIND: VersionKind = VersionKind {
+    version: "v1alpha1",
+    kind: "bootprofile",
+};
+
+pub const COLUMNS: &[Column] = &[
+    Column {
+        name: "NAMESPACE",
+        path: "meta_data.namespace",
+        wide: false,
+    },
+    Column {
+        name: "NAME",
+        path: "meta_data.name",
+        wide: false,
+    },
+    Column {
+        name: "KERNEL",
+        path: "spec.kernel",
+        wide: false,
+    },
+    Column {
+        name: "IMAGE",
+        path: "spec.image",
+        wide: false,
+    },
+    Column {
+        name: "CMDLINE",
+        path: "spec.cmdline",
+        wide: true,
+    },
+];
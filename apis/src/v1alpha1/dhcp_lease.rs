@@ -0,0 +1,124 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::v1::{Column, Metadata, VersionKind};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DhcpLeaseState {
+    Bound,
+    Released,
+    /// The client found the address in use.
+    Declined,
+}
+
+impl fmt::Display for DhcpLeaseState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DhcpLeaseState::Bound => write!(f, "Bound"),
+            DhcpLeaseState::Released => write!(f, "Released"),
+            DhcpLeaseState::Declined => write!(f, "Declined"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhcpLeaseSpec {
+    pub subnet: String,
+    pub mac: String,
+    pub address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// The Node reserving the address, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhcpLeaseStatus {
+    pub state: DhcpLeaseState,
+    /// When the lease expires, in seconds since the epoch.
+    #[serde(default)]
+    pub expires: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DhcpLease {
+    pub meta_data: Metadata,
+    pub spec: DhcpLeaseSpec,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<DhcpLeaseStatus>,
+}
+
+impl Display for DhcpLease {
+    fn fmt(&self, writer: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(writer, "{0}", self.meta_data.name)
+    }
+}
+
+/// The name of the lease of a MAC in a Subnet, e.g. `net1-525400123456`.
+pub fn name(subnet: &str, mac: &str) -> String {
+    format!("{}-{}", subnet, mac.replace(':', "").to_lowercase())
+}
+
+pub const VERSION_KIND: VersionKind = VersionKind {
+    version: "v1alpha1",
+    kind: "dhcplease",
+};
+
+pub const COLUMNS: &[Column] = &[
+    Column {
+        name: "NAMESPACE",
+        path: "meta_data.namespace",
+        wide: false,
+    },
+    Column {
+        name: "NAME",
+        path: "meta_data.name",
+        wide: false,
+    },
+    Column {
+        name: "SUBNET",
+        path: "spec.subnet",
+        wide: false,
+    },
+    Column {
+        name: "MAC",
+        path: "spec.mac",
+        wide: false,
+    },
+    Column {
+        name: "ADDRESS",
+        path: "spec.address",
+        wide: false,
+    },
+    Column {
+        name: "STATE",
+        path: "status.state",
+        wide: false,
+    },
+    Column {
+        name: "HOSTNAME",
+        path: "spec.hostname",
+        wide: true,
+    },
+    Column {
+        name: "EXPIRES",
+        path: "status.expires",
+        wide: true,
+    },
+];
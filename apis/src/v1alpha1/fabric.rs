@@ -11,9 +11,11 @@
  * limitations under the License.
  */
 
+use std::collections::BTreeMap;
 use std::fmt::{self, Display, Formatter};
 
-use crate::v1::{Metadata, VersionKind};
+use crate::v1::{Column, Metadata, VersionKind};
+use crate::v1alpha1::topology::Topology;
 
 use serde::{Deserialize, Serialize};
 
@@ -39,18 +41,138 @@ impl fmt::Display for FabricState {
     }
 }
 
+/// The declared design of a Fabric, which its topology is checked against.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FabricDesign {
+    /// 2 for leaf/spine, 3 with super-spines.
+    pub tiers: u32,
+    /// The minimum number of upper-tier switches each leaf, and each spine
+    /// with super-spines, is connected to.
+    pub switch_uplinks: u32,
+    /// The minimum number of leaves each host is connected to.
+    pub host_uplinks: u32,
+}
+
+impl Default for FabricDesign {
+    fn default() -> Self {
+        FabricDesign {
+            tiers: 2,
+            switch_uplinks: 2,
+            host_uplinks: 2,
+        }
+    }
+}
+
+/// The pools which the Fabric allocates the settings of its switches and
+/// Vpcs from, unless they are set explicitly.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct FabricPools {
+    /// The ASNs of the switches, as <first>-<last>, e.g. 65000-65499.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asn: Option<String>,
+    /// The CIDR of the loopback addresses of the switches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loopback: Option<String>,
+    /// The CIDR split into a /31 per link between two switches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p2p: Option<String>,
+    /// The VNIs of the Vpcs, as <first>-<last>, e.g. 10000-19999.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vni: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FabricSpec {
     pub selector: String,
+    #[serde(default)]
+    pub design: FabricDesign,
+    #[serde(default)]
+    pub pools: FabricPools,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// What the Fabric allocated from its pools, the source of truth of which
+/// values are in use.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FabricAllocations {
+    /// The ASN of each switch.
+    #[serde(default)]
+    pub asns: BTreeMap<String, u32>,
+    /// The loopback address of each switch.
+    #[serde(default)]
+    pub loopbacks: BTreeMap<String, String>,
+    /// The /31 of each link between two switches, by <switch>:<port> of the
+    /// end which is first in its topology link; that end takes the first
+    /// address.
+    #[serde(default)]
+    pub links: BTreeMap<String, String>,
+    /// The VNI of each Vpc.
+    #[serde(default)]
+    pub vnis: BTreeMap<String, u32>,
+}
+
+impl FabricAllocations {
+    pub fn is_empty(&self) -> bool {
+        self.asns.is_empty()
+            && self.loopbacks.is_empty()
+            && self.links.is_empty()
+            && self.vnis.is_empty()
+    }
+}
+
+/// How much of a pool is in use.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolUsage {
+    /// The number of values in the pool.
+    pub total: u64,
+    /// The number of values in the pool not in use yet.
+    pub available: u64,
+}
+
+/// The usage of each pool of the Fabric which is set.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FabricUsage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asn: Option<PoolUsage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loopback: Option<PoolUsage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p2p: Option<PoolUsage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vni: Option<PoolUsage>,
+}
+
+impl FabricUsage {
+    /// The usage of all the pools.
+    pub fn sum(&self) -> PoolUsage {
+        [&self.asn, &self.loopback, &self.p2p, &self.vni]
+            .into_iter()
+            .flatten()
+            .fold(PoolUsage::default(), |sum, u| PoolUsage {
+                total: sum.total + u.total,
+                available: sum.available + u.available,
+            })
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct FabricStatus {
     pub state: FabricState,
+    /// The number of values in all the pools.
     #[serde(default)]
     pub total: u64,
+    /// The number of values in all the pools not allocated yet.
     #[serde(default)]
     pub available: u64,
+    /// The usage of each pool.
+    #[serde(default)]
+    pub usage: FabricUsage,
+    /// Why the Fabric is in its state, e.g. the topology errors.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topology: Option<Topology>,
+    #[serde(default, skip_serializing_if = "FabricAllocations::is_empty")]
+    pub allocations: FabricAllocations,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -71,3 +193,67 @@ pub const VERSION_KIND: VersionKind = VersionKind {
     version: "v1alpha1",
     kind: "fabric",
 };
+
+pub const COLUMNS: &[Column] = &[
+    Column {
+        name: "UUID",
+        path: "meta_data.uuid",
+        wide: false,
+    },
+    Column {
+        name: "NAMESPACE",
+        path: "meta_data.namespace",
+        wide: false,
+    },
+    Column {
+        name: "NAME",
+        path: "meta_data.name",
+        wide: false,
+    },
+    Column {
+        name: "STATE",
+        path: "status.state",
+        wide: false,
+    },
+    Column {
+        name: "REASON",
+        path: "status.reason",
+        wide: true,
+    },
+    Column {
+        name: "SELECTOR",
+        path: "spec.selector",
+        wide: true,
+    },
+    Column {
+        name: "TOTAL",
+        path: "status.total",
+        wide: true,
+    },
+    Column {
+        name: "AVAILABLE",
+        path: "status.available",
+        wide: true,
+    },
+    // The values of each pool not in use yet.
+    Column {
+        name: "ASNS",
+        path: "status.usage.asn.available",
+        wide: true,
+    },
+    Column {
+        name: "LOOPBACKS",
+        path: "status.usage.loopback.available",
+        wide: true,
+    },
+    Column {
+        name: "P2PS",
+        path: "status.usage.p2p.available",
+        wide: true,
+    },
+    Column {
+        name: "VNIS",
+        path: "status.usage.vni.available",
+        wide: true,
+    },
+];
@@ -11,4 +11,19 @@
  * limitations under the License.
  */
 
+pub mod boot_profile;
+pub mod dhcp_lease;
 pub mod fabric;
+pub mod network_interface;
+pub mod node;
+pub mod nvme_namespace;
+pub mod nvme_port;
+pub mod nvme_subsystem;
+pub mod provision;
+pub mod storage_pool;
+pub mod subnet;
+pub mod switch;
+pub mod topology;
+pub mod volume_attachment;
+pub mod vpc;
+pub mod xpu;
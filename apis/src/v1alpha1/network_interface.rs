@@ -0,0 +1,189 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::fmt::{self, Display, Formatter};
+
+use crate::v1::{Column, Metadata, VersionKind};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkInterfaceState {
+    /// Waiting for the address or for the agent of the Node.
+    Pending,
+    Ready,
+    Error,
+}
+
+impl fmt::Display for NetworkInterfaceState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetworkInterfaceState::Pending => write!(f, "Pending"),
+            NetworkInterfaceState::Ready => write!(f, "Ready"),
+            NetworkInterfaceState::Error => write!(f, "Error"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum InterfaceKind {
+    /// A NIC of the Node, bridged into the Vpc by the VLAN of the Subnet.
+    #[default]
+    Nic,
+    /// A SR-IOV virtual function of a NIC, tagged with the VLAN of the Subnet.
+    Vf,
+    /// A representor of a DPU, enslaved to the bridge of the Vpc.
+    Representor,
+}
+
+impl fmt::Display for InterfaceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InterfaceKind::Nic => write!(f, "nic"),
+            InterfaceKind::Vf => write!(f, "vf"),
+            InterfaceKind::Representor => write!(f, "representor"),
+        }
+    }
+}
+
+/// The MTU of the interfaces without one.
+pub const DEFAULT_MTU: u32 = 1500;
+
+/// Attaches an interface of a Node to a Subnet.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkInterfaceSpec {
+    pub node: String,
+    pub subnet: String,
+    #[serde(default)]
+    pub kind: InterfaceKind,
+    /// The NIC, the physical function of the VF or the representor, e.g. `ens1f0`.
+    pub interface: String,
+    /// The index of the VF of the physical function.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vf: Option<u32>,
+    /// The address in the Subnet, allocated from the Subnet if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// The MAC, the one of the NIC or representor, or generated for a VF, if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkInterfaceStatus {
+    pub state: NetworkInterfaceState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// The address allocated in the Subnet, reserved for the MAC by the DHCP server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vlan: Option<u16>,
+    /// The VNI of the Vpc of the Subnet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vni: Option<u32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NetworkInterface {
+    pub meta_data: Metadata,
+    pub spec: NetworkInterfaceSpec,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<NetworkInterfaceStatus>,
+}
+
+impl Display for NetworkInterface {
+    fn fmt(&self, writer: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(writer, "{0}", self.meta_data.name)
+    }
+}
+
+pub const VERSION_KIND: VersionKind = VersionKind {
+    version: "v1alpha1",
+    kind: "networkinterface",
+};
+
+pub const COLUMNS: &[Column] = &[
+    Column {
+        name: "NAMESPACE",
+        path: "meta_data.namespace",
+        wide: false,
+    },
+    Column {
+        name: "NAME",
+        path: "meta_data.name",
+        wide: false,
+    },
+    Column {
+        name: "NODE",
+        path: "spec.node",
+        wide: false,
+    },
+    Column {
+        name: "SUBNET",
+        path: "spec.subnet",
+        wide: false,
+    },
+    Column {
+        name: "ADDRESS",
+        path: "status.address",
+        wide: false,
+    },
+    Column {
+        name: "STATE",
+        path: "status.state",
+        wide: false,
+    },
+    Column {
+        name: "KIND",
+        path: "spec.kind",
+        wide: true,
+    },
+    Column {
+        name: "INTERFACE",
+        path: "spec.interface",
+        wide: true,
+    },
+    Column {
+        name: "MAC",
+        path: "status.mac",
+        wide: true,
+    },
+    Column {
+        name: "MTU",
+        path: "status.mtu",
+        wide: true,
+    },
+    Column {
+        name: "VLAN",
+        path: "status.vlan",
+        wide: true,
+    },
+    Column {
+        name: "VNI",
+        path: "status.vni",
+        wide: true,
+    },
+    Column {
+        name: "REASON",
+        path: "status.reason",
+        wide: true,
+    },
+];
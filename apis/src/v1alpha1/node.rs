@@ -0,0 +1,347 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::v1::{Column, Metadata, VersionKind};
+use crate::v1alpha1::xpu::RepresentorPort;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeState {
+    Ready,
+    NotReady,
+}
+
+impl fmt::Display for NodeState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NodeState::Ready => write!(f, "Ready"),
+            NodeState::NotReady => write!(f, "NotReady"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Nic {
+    pub name: String,
+    #[serde(default)]
+    pub mac: String,
+    #[serde(default)]
+    pub pci_address: String,
+    #[serde(default)]
+    pub driver: String,
+    /// The link speed in Mb/s, none if the link is down.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<u32>,
+    /// The number of SR-IOV VFs supported by the NIC, 0 if not capable.
+    #[serde(default)]
+    pub sriov_total_vfs: u32,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Dpu {
+    pub name: String,
+    #[serde(default)]
+    pub model: String,
+    /// The PCI addresses of the functions of the DPU on the host.
+    #[serde(default)]
+    pub pci_addresses: Vec<String>,
+    #[serde(default)]
+    pub firmware: String,
+    #[serde(default)]
+    pub representors: Vec<RepresentorPort>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct NvmeDevice {
+    pub name: String,
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub serial: String,
+    #[serde(default)]
+    pub firmware: String,
+    #[serde(default)]
+    pub pci_address: String,
+    /// The total size of the namespaces in bytes.
+    #[serde(default)]
+    pub size: u64,
+}
+
+/// The system information of DMI.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SystemInfo {
+    #[serde(default)]
+    pub vendor: String,
+    #[serde(default)]
+    pub product: String,
+    #[serde(default)]
+    pub serial: String,
+    #[serde(default)]
+    pub uuid: String,
+    #[serde(default)]
+    pub bios_version: String,
+}
+
+/// A neighbor of a NIC discovered by LLDP, e.g. the port of a switch.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LldpNeighbor {
+    /// The local NIC receiving the LLDP frames.
+    pub interface: String,
+    pub chassis_id: String,
+    pub port_id: String,
+    #[serde(default)]
+    pub port_description: String,
+    #[serde(default)]
+    pub system_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub management_address: Option<String>,
+}
+
+/// The requested power of a Node, applied through its BMC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerAction {
+    On,
+    Off,
+    /// Power cycles the Node once, leaving it on.
+    Cycle,
+}
+
+impl fmt::Display for PowerAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PowerAction::On => write!(f, "on"),
+            PowerAction::Off => write!(f, "off"),
+            PowerAction::Cycle => write!(f, "cycle"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerState {
+    On,
+    Off,
+    Unknown,
+}
+
+impl fmt::Display for PowerState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PowerState::On => write!(f, "On"),
+            PowerState::Off => write!(f, "Off"),
+            PowerState::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BmcDriver {
+    #[default]
+    Redfish,
+    Ipmi,
+}
+
+/// How to reach the BMC at `bmc_address`, e.g. `https://10.0.0.5` for
+/// Redfish or `10.0.0.5:623` for IPMI over LAN.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BmcSpec {
+    #[serde(default)]
+    pub driver: BmcDriver,
+    /// The name of the credentials of the BMC, which the controller reads
+    /// as `username:password` from the file of the name in its credentials
+    /// directory; anonymous if empty.
+    #[serde(default)]
+    pub credentials: String,
+    /// Accepts the self-signed certificates of Redfish.
+    #[serde(default)]
+    pub insecure: bool,
+}
+
+/// The power of a Node observed by the PowerController.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PowerStatus {
+    pub state: PowerState,
+    /// The last `spec.power` applied.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub applied: Option<PowerAction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// When the power state changed, in seconds since the epoch.
+    #[serde(default)]
+    pub last_transition: u64,
+}
+
+/// A fixed address for a MAC, served by the Subnet the address belongs to.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhcpReservation {
+    pub mac: String,
+    pub address: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct NodeSpec {
+    pub hostname: String,
+    #[serde(default)]
+    pub serial: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bmc_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bmc: Option<BmcSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub power: Option<PowerAction>,
+    /// The fixed addresses the DHCP server hands out to the NICs of the Node.
+    #[serde(default)]
+    pub reservations: Vec<DhcpReservation>,
+    /// The BootProfile the Node network boots with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boot_profile: Option<String>,
+    /// The BGP ASN of the Node peering with its leaves, i.e. BGP to the host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asn: Option<u32>,
+    /// The address of the loopback of the Node, its router id and VTEP.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loopback: Option<String>,
+    /// The FRR configuration of the Node rendered by the controller, and
+    /// installed by the agent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frr_config: Option<String>,
+}
+
+/// A step of a network boot seen by the boot servers, e.g. the bootloader
+/// fetched over TFTP or the iPXE script rendered.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BootAttempt {
+    /// In seconds since the epoch.
+    pub time: u64,
+    /// `tftp`, `http`, `ipxe`, or reported by the installer.
+    pub stage: String,
+    /// The file, script or profile of the step.
+    #[serde(default)]
+    pub detail: String,
+    /// The address of the client.
+    #[serde(default)]
+    pub address: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NodeStatus {
+    pub state: NodeState,
+    /// The seconds since the UNIX epoch of the last heartbeat of the agent.
+    #[serde(default)]
+    pub last_heartbeat: u64,
+    #[serde(default)]
+    pub system: SystemInfo,
+    #[serde(default)]
+    pub cpu_model: String,
+    #[serde(default)]
+    pub cpus: u32,
+    /// The memory of the node in bytes.
+    #[serde(default)]
+    pub memory: u64,
+    #[serde(default)]
+    pub nics: Vec<Nic>,
+    #[serde(default)]
+    pub dpus: Vec<Dpu>,
+    #[serde(default)]
+    pub nvmes: Vec<NvmeDevice>,
+    #[serde(default)]
+    pub neighbors: Vec<LldpNeighbor>,
+    /// The last network boot attempts, the oldest first.
+    #[serde(default)]
+    pub boots: Vec<BootAttempt>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub power: Option<PowerStatus>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Node {
+    pub meta_data: Metadata,
+    pub spec: NodeSpec,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<NodeStatus>,
+}
+
+impl Display for Node {
+    fn fmt(&self, writer: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(writer, "{0}", self.meta_data.name)
+    }
+}
+
+pub const VERSION_KIND: VersionKind = VersionKind {
+    version: "v1alpha1",
+    kind: "node",
+};
+
+pub const COLUMNS: &[Column] = &[
+    Column {
+        name: "NAMESPACE",
+        path: "meta_data.namespace",
+        wide: false,
+    },
+    Column {
+        name: "NAME",
+        path: "meta_data.name",
+        wide: false,
+    },
+    Column {
+        name: "STATE",
+        path: "status.state",
+        wide: false,
+    },
+    Column {
+        name: "POWER",
+        path: "status.power.state",
+        wide: false,
+    },
+    Column {
+        name: "SERIAL",
+        path: "spec.serial",
+        wide: false,
+    },
+    Column {
+        name: "BMC",
+        path: "spec.bmc_address",
+        wide: true,
+    },
+    Column {
+        name: "CPUS",
+        path: "status.cpus",
+        wide: true,
+    },
+    Column {
+        name: "MEMORY",
+        path: "status.memory",
+        wide: true,
+    },
+    Column {
+        name: "HEARTBEAT",
+        path: "status.last_heartbeat",
+        wide: true,
+    },
+    Column {
+        name: "ASN",
+        path: "spec.asn",
+        wide: true,
+    },
+    Column {
+        name: "LOOPBACK",
+        path: "spec.loopback",
+        wide: true,
+    },
+];
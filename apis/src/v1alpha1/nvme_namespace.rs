@@ -0,0 +1,130 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt::{Display, Formatter};
+
+use crate::v1::{Column, Metadata, VersionKind};
+use crate::v1alpha1::nvme_subsystem::NvmeStatus;
+
+use serde::{Deserialize, Serialize};
+
+/// Where the scheduler placed an NvmeNamespace of a StoragePool.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NvmePlacement {
+    pub node: String,
+    /// The NVMe device of the Node, e.g. `nvme0n1`.
+    pub device: String,
+    pub domain: String,
+}
+
+/// A namespace of an NvmeSubsystem, backed by a block device or a file of
+/// the Node of the subsystem; or a volume of `size` bytes of a StoragePool,
+/// for which the scheduler sets the subsystem, nsid and device.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NvmeNamespaceSpec {
+    /// The name of the NvmeSubsystem.
+    #[serde(default)]
+    pub subsystem: String,
+    /// The namespace id in the subsystem, from 1.
+    #[serde(default)]
+    pub nsid: u32,
+    /// e.g. `/dev/nvme0n1` or `/var/lib/yangtze/vol1.img`.
+    #[serde(default)]
+    pub device: String,
+    /// The name of the StoragePool to place the volume onto.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool: Option<String>,
+    /// The bytes of a volume of a StoragePool; the agent creates its file
+    /// in the mount of the device placed onto, and removes it once the
+    /// namespace is deleted.
+    #[serde(default)]
+    pub size: u64,
+    /// The volumes of the same group are placed in different failure
+    /// domains, e.g. the replicas of a volume.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anti_affinity: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placement: Option<NvmePlacement>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NvmeNamespace {
+    pub meta_data: Metadata,
+    pub spec: NvmeNamespaceSpec,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<NvmeStatus>,
+}
+
+impl Display for NvmeNamespace {
+    fn fmt(&self, writer: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(writer, "{0}", self.meta_data.name)
+    }
+}
+
+pub const VERSION_KIND: VersionKind = VersionKind {
+    version: "v1alpha1",
+    kind: "nvmenamespace",
+};
+
+pub const COLUMNS: &[Column] = &[
+    Column {
+        name: "NAMESPACE",
+        path: "meta_data.namespace",
+        wide: false,
+    },
+    Column {
+        name: "NAME",
+        path: "meta_data.name",
+        wide: false,
+    },
+    Column {
+        name: "SUBSYSTEM",
+        path: "spec.subsystem",
+        wide: false,
+    },
+    Column {
+        name: "NSID",
+        path: "spec.nsid",
+        wide: false,
+    },
+    Column {
+        name: "STATE",
+        path: "status.state",
+        wide: false,
+    },
+    Column {
+        name: "POOL",
+        path: "spec.pool",
+        wide: true,
+    },
+    Column {
+        name: "SIZE",
+        path: "spec.size",
+        wide: true,
+    },
+    Column {
+        name: "NODE",
+        path: "spec.placement.node",
+        wide: true,
+    },
+    Column {
+        name: "DEVICE",
+        path: "spec.device",
+        wide: true,
+    },
+    Column {
+        name: "REASON",
+        path: "status.reason",
+        wide: true,
+    },
+];
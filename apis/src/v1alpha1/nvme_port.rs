@@ -0,0 +1,117 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::v1::{Column, Metadata, VersionKind};
+use crate::v1alpha1::nvme_subsystem::NvmeStatus;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NvmeTransport {
+    #[default]
+    Tcp,
+    Rdma,
+}
+
+impl fmt::Display for NvmeTransport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NvmeTransport::Tcp => write!(f, "tcp"),
+            NvmeTransport::Rdma => write!(f, "rdma"),
+        }
+    }
+}
+
+fn default_service() -> u16 {
+    4420
+}
+
+/// A listener of the target of a Node exposing NvmeSubsystems.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NvmePortSpec {
+    pub node: String,
+    #[serde(default)]
+    pub transport: NvmeTransport,
+    /// The IPv4 or IPv6 address to listen on.
+    pub address: String,
+    #[serde(default = "default_service")]
+    pub service: u16,
+    /// The names of the NvmeSubsystems exposed.
+    #[serde(default)]
+    pub subsystems: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NvmePort {
+    pub meta_data: Metadata,
+    pub spec: NvmePortSpec,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<NvmeStatus>,
+}
+
+impl Display for NvmePort {
+    fn fmt(&self, writer: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(writer, "{0}", self.meta_data.name)
+    }
+}
+
+pub const VERSION_KIND: VersionKind = VersionKind {
+    version: "v1alpha1",
+    kind: "nvmeport",
+};
+
+pub const COLUMNS: &[Column] = &[
+    Column {
+        name: "NAMESPACE",
+        path: "meta_data.namespace",
+        wide: false,
+    },
+    Column {
+        name: "NAME",
+        path: "meta_data.name",
+        wide: false,
+    },
+    Column {
+        name: "NODE",
+        path: "spec.node",
+        wide: false,
+    },
+    Column {
+        name: "TRANSPORT",
+        path: "spec.transport",
+        wide: false,
+    },
+    Column {
+        name: "ADDRESS",
+        path: "spec.address",
+        wide: false,
+    },
+    Column {
+        name: "SERVICE",
+        path: "spec.service",
+        wide: false,
+    },
+    Column {
+        name: "STATE",
+        path: "status.state",
+        wide: false,
+    },
+    Column {
+        name: "REASON",
+        path: "status.reason",
+        wide: true,
+    },
+];
@@ -0,0 +1,154 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::v1::{Column, Metadata, VersionKind};
+
+use serde::{Deserialize, Serialize};
+
+/// The state of the NVMe-oF objects applied by the agent of their Node.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NvmeState {
+    Pending,
+    Ready,
+    Error,
+}
+
+impl fmt::Display for NvmeState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NvmeState::Pending => write!(f, "Pending"),
+            NvmeState::Ready => write!(f, "Ready"),
+            NvmeState::Error => write!(f, "Error"),
+        }
+    }
+}
+
+/// The target serving a subsystem on its Node.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NvmeBackend {
+    /// The Linux kernel target.
+    #[default]
+    Nvmet,
+    /// The SPDK target, e.g. on a DPU.
+    Spdk,
+}
+
+impl fmt::Display for NvmeBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NvmeBackend::Nvmet => write!(f, "nvmet"),
+            NvmeBackend::Spdk => write!(f, "spdk"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NvmeStatus {
+    pub state: NvmeState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// An NVMe-oF subsystem exported by the target of a Node.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NvmeSubsystemSpec {
+    /// The Node of the target.
+    pub node: String,
+    /// e.g. `nqn.2023-01.cn.xflops:vol1`.
+    pub nqn: String,
+    /// The host NQNs allowed to connect, any host if empty along with
+    /// `status.attached_hosts`.
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial: Option<String>,
+    #[serde(default)]
+    pub backend: NvmeBackend,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NvmeSubsystemStatus {
+    pub state: NvmeState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// The host NQNs of the VolumeAttachments of the namespaces, allowed
+    /// while attached; maintained by the AttachmentController.
+    #[serde(default)]
+    pub attached_hosts: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NvmeSubsystem {
+    pub meta_data: Metadata,
+    pub spec: NvmeSubsystemSpec,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<NvmeSubsystemStatus>,
+}
+
+impl Display for NvmeSubsystem {
+    fn fmt(&self, writer: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(writer, "{0}", self.meta_data.name)
+    }
+}
+
+/// Whether the NQN is well-formed, e.g. `nqn.2014-08.org.nvmexpress:uuid:...`.
+pub fn valid_nqn(nqn: &str) -> bool {
+    nqn.starts_with("nqn.") && nqn.len() <= 223 && !nqn.contains('/')
+}
+
+pub const VERSION_KIND: VersionKind = VersionKind {
+    version: "v1alpha1",
+    kind: "nvmesubsystem",
+};
+
+pub const COLUMNS: &[Column] = &[
+    Column {
+        name: "NAMESPACE",
+        path: "meta_data.namespace",
+        wide: false,
+    },
+    Column {
+        name: "NAME",
+        path: "meta_data.name",
+        wide: false,
+    },
+    Column {
+        name: "NODE",
+        path: "spec.node",
+        wide: false,
+    },
+    Column {
+        name: "NQN",
+        path: "spec.nqn",
+        wide: false,
+    },
+    Column {
+        name: "STATE",
+        path: "status.state",
+        wide: false,
+    },
+    Column {
+        name: "BACKEND",
+        path: "spec.backend",
+        wide: true,
+    },
+    Column {
+        name: "REASON",
+        path: "status.reason",
+        wide: true,
+    },
+];
@@ -0,0 +1,178 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::v1::{Column, Metadata, VersionKind};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProvisionPhase {
+    Registered,
+    Inspecting,
+    Available,
+    Deploying,
+    Deployed,
+    Deprovisioning,
+    /// An action failed after all its retries.
+    Failed,
+}
+
+impl fmt::Display for ProvisionPhase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProvisionPhase::Registered => write!(f, "Registered"),
+            ProvisionPhase::Inspecting => write!(f, "Inspecting"),
+            ProvisionPhase::Available => write!(f, "Available"),
+            ProvisionPhase::Deploying => write!(f, "Deploying"),
+            ProvisionPhase::Deployed => write!(f, "Deployed"),
+            ProvisionPhase::Deprovisioning => write!(f, "Deprovisioning"),
+            ProvisionPhase::Failed => write!(f, "Failed"),
+        }
+    }
+}
+
+/// The actions the agent of the Node runs for the phases.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProvisionAction {
+    Inspect,
+    Wipe,
+    WriteImage,
+    Reboot,
+}
+
+impl fmt::Display for ProvisionAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProvisionAction::Inspect => write!(f, "inspect"),
+            ProvisionAction::Wipe => write!(f, "wipe"),
+            ProvisionAction::WriteImage => write!(f, "write-image"),
+            ProvisionAction::Reboot => write!(f, "reboot"),
+        }
+    }
+}
+
+/// An action requested by the controller from the agent.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActionRequest {
+    /// Unique in the Provision, to match the result.
+    pub id: u64,
+    pub action: ProvisionAction,
+    /// The attempt of the action, from 1.
+    pub attempt: u32,
+    /// When the attempt started, in seconds since the epoch.
+    pub started: u64,
+}
+
+/// The result of an action reported by the agent.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActionResult {
+    pub id: u64,
+    pub attempt: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub finished: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProvisionSpec {
+    pub node: String,
+    /// The URL of the OS image to deploy; the Node is deprovisioned when
+    /// it is unset or changed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// The disk to deploy to, the first NVMe device by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProvisionStatus {
+    pub phase: ProvisionPhase,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// The image deployed on the Node.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// The index of the current action in the actions of the phase.
+    #[serde(default)]
+    pub step: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<ActionRequest>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<ActionResult>,
+    /// When the Provision entered its phase, in seconds since the epoch.
+    #[serde(default)]
+    pub last_transition: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Provision {
+    pub meta_data: Metadata,
+    pub spec: ProvisionSpec,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<ProvisionStatus>,
+}
+
+impl Display for Provision {
+    fn fmt(&self, writer: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(writer, "{0}", self.meta_data.name)
+    }
+}
+
+pub const VERSION_KIND: VersionKind = VersionKind {
+    version: "v1alpha1",
+    kind: "provision",
+};
+
+pub const COLUMNS: &[Column] = &[
+    Column {
+        name: "NAMESPACE",
+        path: "meta_data.namespace",
+        wide: false,
+    },
+    Column {
+        name: "NAME",
+        path: "meta_data.name",
+        wide: false,
+    },
+    Column {
+        name: "NODE",
+        path: "spec.node",
+        wide: false,
+    },
+    Column {
+        name: "PHASE",
+        path: "status.phase",
+        wide: false,
+    },
+    Column {
+        name: "ACTION",
+        path: "status.action.action",
+        wide: false,
+    },
+    Column {
+        name: "IMAGE",
+        path: "spec.image",
+        wide: true,
+    },
+    Column {
+        name: "REASON",
+        path: "status.reason",
+        wide: true,
+    },
+];
@@ -0,0 +1,174 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::v1::{Column, Metadata, VersionKind};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoragePoolState {
+    Initializing,
+    Ready,
+    Error,
+}
+
+impl fmt::Display for StoragePoolState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoragePoolState::Initializing => write!(f, "Initializing"),
+            StoragePoolState::Ready => write!(f, "Ready"),
+            StoragePoolState::Error => write!(f, "Error"),
+        }
+    }
+}
+
+fn default_failure_domain() -> String {
+    "rack".to_string()
+}
+
+fn default_root() -> String {
+    "/var/lib/yangtze/pools".to_string()
+}
+
+/// A pool of the NVMe devices reported by the agents of the selected Nodes,
+/// which the NvmeNamespaces requesting a size are placed onto.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StoragePoolSpec {
+    /// The labels of the Nodes, e.g. `storage=fast,zone=a`; all the Nodes
+    /// if empty.
+    #[serde(default)]
+    pub selector: String,
+    /// The label key of the failure domain of a Node, e.g. `rack` for
+    /// `rack=r1`; a Node without it is its own domain.
+    #[serde(default = "default_failure_domain")]
+    pub failure_domain: String,
+    /// Where each device is mounted on its Node, as `<root>/<device>`.
+    #[serde(default = "default_root")]
+    pub root: String,
+}
+
+/// An NVMe device of the pool.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolDevice {
+    pub node: String,
+    /// e.g. `nvme0n1`.
+    pub name: String,
+    pub domain: String,
+    pub capacity: u64,
+    pub allocated: u64,
+}
+
+impl PoolDevice {
+    pub fn available(&self) -> u64 {
+        self.capacity.saturating_sub(self.allocated)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoragePoolStatus {
+    pub state: StoragePoolState,
+    /// The bytes of the devices.
+    #[serde(default)]
+    pub capacity: u64,
+    /// The bytes of the NvmeNamespaces placed onto the devices.
+    #[serde(default)]
+    pub allocated: u64,
+    #[serde(default)]
+    pub available: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub devices: Vec<PoolDevice>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StoragePool {
+    pub meta_data: Metadata,
+    pub spec: StoragePoolSpec,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<StoragePoolStatus>,
+}
+
+impl Display for StoragePool {
+    fn fmt(&self, writer: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(writer, "{0}", self.meta_data.name)
+    }
+}
+
+/// Whether the labels of a Node match a selector, e.g. `storage=fast,zone=a`.
+pub fn selected(labels: &[String], selector: &str) -> bool {
+    selector
+        .split(',')
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .all(|l| labels.iter().any(|n| n == l))
+}
+
+/// The value of a label, e.g. `r1` of `rack=r1` by `rack`.
+pub fn label<'a>(labels: &'a [String], key: &str) -> Option<&'a str> {
+    labels.iter().find_map(|l| match l.split_once('=') {
+        Some((k, v)) if k == key => Some(v),
+        _ => None,
+    })
+}
+
+pub const VERSION_KIND: VersionKind = VersionKind {
+    version: "v1alpha1",
+    kind: "storagepool",
+};
+
+pub const COLUMNS: &[Column] = &[
+    Column {
+        name: "NAMESPACE",
+        path: "meta_data.namespace",
+        wide: false,
+    },
+    Column {
+        name: "NAME",
+        path: "meta_data.name",
+        wide: false,
+    },
+    Column {
+        name: "STATE",
+        path: "status.state",
+        wide: false,
+    },
+    Column {
+        name: "CAPACITY",
+        path: "status.capacity",
+        wide: false,
+    },
+    Column {
+        name: "AVAILABLE",
+        path: "status.available",
+        wide: false,
+    },
+    Column {
+        name: "ALLOCATED",
+        path: "status.allocated",
+        wide: true,
+    },
+    Column {
+        name: "SELECTOR",
+        path: "spec.selector",
+        wide: true,
+    },
+    Column {
+        name: "REASON",
+        path: "status.reason",
+        wide: true,
+    },
+];
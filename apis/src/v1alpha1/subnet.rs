@@ -0,0 +1,155 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::v1::{Column, Metadata, VersionKind};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubnetState {
+    Ready,
+    Error,
+}
+
+impl fmt::Display for SubnetState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SubnetState::Ready => write!(f, "Ready"),
+            SubnetState::Error => write!(f, "Error"),
+        }
+    }
+}
+
+/// The DHCP service of a Subnet; the PXE options are only sent to PXE clients.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhcpConfig {
+    /// The first address of the dynamic range.
+    pub start: String,
+    /// The last address of the dynamic range.
+    pub end: String,
+    /// The lease time in seconds.
+    #[serde(default = "default_lease_time")]
+    pub lease_time: u32,
+    /// The TFTP server of the PXE clients, i.e. `siaddr`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_server: Option<String>,
+    /// The bootloader of the legacy BIOS clients, e.g. `undionly.kpxe`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    /// The bootloader of the UEFI clients, e.g. `ipxe.efi`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub efi_filename: Option<String>,
+    /// The script chainloaded by iPXE clients, e.g. `http://10.0.0.1:8081/ipxe`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipxe_script: Option<String>,
+}
+
+fn default_lease_time() -> u32 {
+    3600
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubnetSpec {
+    /// e.g. `10.0.0.0/24`
+    pub cidr: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gateway: Option<String>,
+    #[serde(default)]
+    pub dns: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dhcp: Option<DhcpConfig>,
+    /// The Vpc of the Subnet, whose bridge the gateway is assigned to on
+    /// the attached hosts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vpc: Option<String>,
+    /// The VLAN of the Subnet on the uplinks, bridged into the Vpc.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vlan: Option<u16>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubnetStatus {
+    pub state: SubnetState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Subnet {
+    pub meta_data: Metadata,
+    pub spec: SubnetSpec,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<SubnetStatus>,
+}
+
+impl Display for Subnet {
+    fn fmt(&self, writer: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(writer, "{0}", self.meta_data.name)
+    }
+}
+
+pub const VERSION_KIND: VersionKind = VersionKind {
+    version: "v1alpha1",
+    kind: "subnet",
+};
+
+pub const COLUMNS: &[Column] = &[
+    Column {
+        name: "NAMESPACE",
+        path: "meta_data.namespace",
+        wide: false,
+    },
+    Column {
+        name: "NAME",
+        path: "meta_data.name",
+        wide: false,
+    },
+    Column {
+        name: "CIDR",
+        path: "spec.cidr",
+        wide: false,
+    },
+    Column {
+        name: "GATEWAY",
+        path: "spec.gateway",
+        wide: false,
+    },
+    Column {
+        name: "STATE",
+        path: "status.state",
+        wide: false,
+    },
+    Column {
+        name: "VPC",
+        path: "spec.vpc",
+        wide: true,
+    },
+    Column {
+        name: "VLAN",
+        path: "spec.vlan",
+        wide: true,
+    },
+    Column {
+        name: "DHCP-START",
+        path: "spec.dhcp.start",
+        wide: true,
+    },
+    Column {
+        name: "DHCP-END",
+        path: "spec.dhcp.end",
+        wide: true,
+    },
+];
@@ -0,0 +1,192 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+use crate::v1::{Column, Metadata, VersionKind};
+use crate::v1alpha1::node::LldpNeighbor;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SwitchState {
+    Discovered,
+    Ready,
+    Error,
+}
+
+impl fmt::Display for SwitchState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SwitchState::Discovered => write!(f, "Discovered"),
+            SwitchState::Ready => write!(f, "Ready"),
+            SwitchState::Error => write!(f, "Error"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SwitchRole {
+    Leaf,
+    Spine,
+    SuperSpine,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SwitchDriverKind {
+    /// Writes the `config_db.json` of the switch into a directory, e.g. to be
+    /// served to SONiC ZTP.
+    File,
+    /// Sets the OpenConfig of the switch over gNMI, and subscribes to its
+    /// state.
+    Gnmi,
+}
+
+/// How the controller configures a switch.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwitchDriverSpec {
+    pub kind: SwitchDriverKind,
+    /// The target of the driver, e.g. the directory of the `file` driver, or
+    /// the URI of the `gnmi` one like http://10.0.0.1:9339.
+    pub address: String,
+}
+
+/// A link between a port of the switch and a NIC of a Node, as seen by
+/// the LLDP of the Node.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwitchLink {
+    pub port: String,
+    pub node: String,
+    pub interface: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SwitchSpec {
+    /// The LLDP chassis id of the switch.
+    pub chassis_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fabric: Option<String>,
+    /// The tier of the switch in the Fabric, inferred from its links if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<SwitchRole>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub management_address: Option<String>,
+    /// The BGP ASN of the switch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asn: Option<u32>,
+    /// The address of the loopback, the router id and VTEP of the switch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loopback: Option<String>,
+    /// The switch is not configured by the controller if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub driver: Option<SwitchDriverSpec>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SwitchStatus {
+    pub state: SwitchState,
+    #[serde(default)]
+    pub system_name: String,
+    #[serde(default)]
+    pub links: Vec<SwitchLink>,
+    /// The LLDP neighbors of the switch ports read through its driver, where
+    /// `interface` is the local port.
+    #[serde(default)]
+    pub neighbors: Vec<LldpNeighbor>,
+    /// Why the configuration of the switch failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// The operational status of each port, e.g. UP, as subscribed to
+    /// through the driver.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub ports: BTreeMap<String, String>,
+    /// The state of the BGP session with each neighbor, e.g. ESTABLISHED.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sessions: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Switch {
+    pub meta_data: Metadata,
+    pub spec: SwitchSpec,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<SwitchStatus>,
+}
+
+impl Display for Switch {
+    fn fmt(&self, writer: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(writer, "{0}", self.meta_data.name)
+    }
+}
+
+pub const VERSION_KIND: VersionKind = VersionKind {
+    version: "v1alpha1",
+    kind: "switch",
+};
+
+pub const COLUMNS: &[Column] = &[
+    Column {
+        name: "NAMESPACE",
+        path: "meta_data.namespace",
+        wide: false,
+    },
+    Column {
+        name: "NAME",
+        path: "meta_data.name",
+        wide: false,
+    },
+    Column {
+        name: "STATE",
+        path: "status.state",
+        wide: false,
+    },
+    Column {
+        name: "CHASSIS",
+        path: "spec.chassis_id",
+        wide: false,
+    },
+    Column {
+        name: "ROLE",
+        path: "spec.role",
+        wide: false,
+    },
+    Column {
+        name: "FABRIC",
+        path: "spec.fabric",
+        wide: true,
+    },
+    Column {
+        name: "MANAGEMENT",
+        path: "spec.management_address",
+        wide: true,
+    },
+    Column {
+        name: "ASN",
+        path: "spec.asn",
+        wide: true,
+    },
+    Column {
+        name: "LOOPBACK",
+        path: "spec.loopback",
+        wide: true,
+    },
+    Column {
+        name: "REASON",
+        path: "status.reason",
+        wide: true,
+    },
+];
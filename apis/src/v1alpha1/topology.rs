@@ -0,0 +1,62 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Tier {
+    Host,
+    Leaf,
+    Spine,
+    SuperSpine,
+}
+
+impl fmt::Display for Tier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tier::Host => write!(f, "host"),
+            Tier::Leaf => write!(f, "leaf"),
+            Tier::Spine => write!(f, "spine"),
+            Tier::SuperSpine => write!(f, "super-spine"),
+        }
+    }
+}
+
+/// A Node or a Switch in the topology of a Fabric.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopologyNode {
+    pub name: String,
+    pub tier: Tier,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TopologyLink {
+    pub from: String,
+    pub from_port: String,
+    pub to: String,
+    pub to_port: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Topology {
+    #[serde(default)]
+    pub nodes: Vec<TopologyNode>,
+    #[serde(default)]
+    pub links: Vec<TopologyLink>,
+    /// The cabling errors and the violations of the design of the Fabric.
+    #[serde(default)]
+    pub errors: Vec<String>,
+}
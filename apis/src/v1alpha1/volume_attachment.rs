@@ -0,0 +1,141 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::v1::{Column, Metadata, VersionKind};
+use crate::v1alpha1::nvme_port::NvmeTransport;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VolumeAttachmentState {
+    /// Waiting for the host to be allowed by the subsystem.
+    Pending,
+    Attaching,
+    Attached,
+    Error,
+}
+
+impl fmt::Display for VolumeAttachmentState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VolumeAttachmentState::Pending => write!(f, "Pending"),
+            VolumeAttachmentState::Attaching => write!(f, "Attaching"),
+            VolumeAttachmentState::Attached => write!(f, "Attached"),
+            VolumeAttachmentState::Error => write!(f, "Error"),
+        }
+    }
+}
+
+/// A controller of the host connected to a port of the subsystem.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NvmePath {
+    /// e.g. `nvme1`.
+    pub controller: String,
+    pub transport: NvmeTransport,
+    /// e.g. `10.0.0.1:4420`.
+    pub address: String,
+    /// e.g. `live` or `connecting`.
+    pub state: String,
+}
+
+/// Attaches an NvmeNamespace to a Node over NVMe-oF.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VolumeAttachmentSpec {
+    /// The Node connecting to the namespace.
+    pub node: String,
+    /// The name of the NvmeNamespace.
+    pub namespace: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VolumeAttachmentStatus {
+    pub state: VolumeAttachmentState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// The NQN of the Node, allowed by the subsystem while attached.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_nqn: Option<String>,
+    /// The block device of the namespace on the Node, e.g. `/dev/nvme1n1`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    #[serde(default)]
+    pub paths: Vec<NvmePath>,
+    /// Whether more than one path is live.
+    #[serde(default)]
+    pub multipath: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VolumeAttachment {
+    pub meta_data: Metadata,
+    pub spec: VolumeAttachmentSpec,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<VolumeAttachmentStatus>,
+}
+
+impl Display for VolumeAttachment {
+    fn fmt(&self, writer: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(writer, "{0}", self.meta_data.name)
+    }
+}
+
+pub const VERSION_KIND: VersionKind = VersionKind {
+    version: "v1alpha1",
+    kind: "volumeattachment",
+};
+
+pub const COLUMNS: &[Column] = &[
+    Column {
+        name: "NAMESPACE",
+        path: "meta_data.namespace",
+        wide: false,
+    },
+    Column {
+        name: "NAME",
+        path: "meta_data.name",
+        wide: false,
+    },
+    Column {
+        name: "NODE",
+        path: "spec.node",
+        wide: false,
+    },
+    Column {
+        name: "VOLUME",
+        path: "spec.namespace",
+        wide: false,
+    },
+    Column {
+        name: "STATE",
+        path: "status.state",
+        wide: false,
+    },
+    Column {
+        name: "DEVICE",
+        path: "status.device",
+        wide: false,
+    },
+    Column {
+        name: "MULTIPATH",
+        path: "status.multipath",
+        wide: true,
+    },
+    Column {
+        name: "REASON",
+        path: "status.reason",
+        wide: true,
+    },
+];
@@ -0,0 +1,123 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::v1::{Column, Metadata, VersionKind};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VpcState {
+    Ready,
+    Error,
+}
+
+impl fmt::Display for VpcState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VpcState::Ready => write!(f, "Ready"),
+            VpcState::Error => write!(f, "Error"),
+        }
+    }
+}
+
+/// The largest VNI of the 24 bits of VXLAN.
+pub const MAX_VNI: u32 = 0xFF_FFFF;
+
+/// An isolated network of Subnets, realized on the hosts by a VRF and a
+/// bridge with a VXLAN device of the VNI.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VpcSpec {
+    /// The VXLAN network identifier, from 1 to 16777215; 0 to allocate it
+    /// from the pool of the Fabric.
+    #[serde(default)]
+    pub vni: u32,
+    /// The Fabric allocating the VNI.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fabric: Option<String>,
+    /// The routing table of the VRF, the VNI by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table: Option<u32>,
+}
+
+impl VpcSpec {
+    pub fn table(&self) -> u32 {
+        self.table.unwrap_or(self.vni)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VpcStatus {
+    pub state: VpcState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Vpc {
+    pub meta_data: Metadata,
+    pub spec: VpcSpec,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<VpcStatus>,
+}
+
+impl Display for Vpc {
+    fn fmt(&self, writer: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(writer, "{0}", self.meta_data.name)
+    }
+}
+
+pub const VERSION_KIND: VersionKind = VersionKind {
+    version: "v1alpha1",
+    kind: "vpc",
+};
+
+pub const COLUMNS: &[Column] = &[
+    Column {
+        name: "NAMESPACE",
+        path: "meta_data.namespace",
+        wide: false,
+    },
+    Column {
+        name: "NAME",
+        path: "meta_data.name",
+        wide: false,
+    },
+    Column {
+        name: "VNI",
+        path: "spec.vni",
+        wide: false,
+    },
+    Column {
+        name: "STATE",
+        path: "status.state",
+        wide: false,
+    },
+    Column {
+        name: "TABLE",
+        path: "spec.table",
+        wide: true,
+    },
+    Column {
+        name: "FABRIC",
+        path: "spec.fabric",
+        wide: true,
+    },
+    Column {
+        name: "REASON",
+        path: "status.reason",
+        wide: true,
+    },
+];
@@ -0,0 +1,179 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fmt::{self, Display, Formatter};
+
+use crate::v1::{Column, Metadata, VersionKind};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum XpuState {
+    Discovered,
+    Provisioning,
+    Ready,
+    Error,
+}
+
+impl fmt::Display for XpuState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            XpuState::Discovered => write!(f, "Discovered"),
+            XpuState::Provisioning => write!(f, "Provisioning"),
+            XpuState::Ready => write!(f, "Ready"),
+            XpuState::Error => write!(f, "Error"),
+        }
+    }
+}
+
+/// Whether the network of the host goes through the Arm cores of the DPU
+/// (embedded), or the host and the Arm cores are two separated hosts on the
+/// same NIC (separated).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum XpuMode {
+    #[default]
+    Embedded,
+    Separated,
+}
+
+/// A representor netdev of a PF or a VF of the DPU, e.g. `pf0vf1`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepresentorPort {
+    pub name: String,
+    /// The `phys_port_name` of the netdev, e.g. `p0`, `pf0hpf` or `pf0vf1`.
+    pub function: String,
+    #[serde(default)]
+    pub mac: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct XpuSpec {
+    /// The Node the DPU is plugged in.
+    pub node: String,
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub mode: XpuMode,
+    /// The PCI domain:bus:device of the DPU on the Node.
+    #[serde(default)]
+    pub pci_slot: String,
+    /// The path of the BFB image on the Node to provision the DPU with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bfb: Option<String>,
+    /// Increase to reset the DPU once more.
+    #[serde(default)]
+    pub resets: u64,
+    /// Increase to provision the DPU once more with the same image, e.g.
+    /// after a failure.
+    #[serde(default)]
+    pub retries: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct XpuStatus {
+    pub state: XpuState,
+    #[serde(default)]
+    pub firmware: String,
+    #[serde(default)]
+    pub representors: Vec<RepresentorPort>,
+    /// Why the Xpu is in its state, e.g. the error of the last provisioning.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// When the Xpu entered its state, in seconds since the epoch.
+    #[serde(default)]
+    pub last_transition: u64,
+    /// The rshim device of the DPU on the Node, e.g. `rshim0`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rshim: Option<String>,
+    /// The BFB image of the last provisioning.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bfb: Option<String>,
+    /// The resets done through rshim, see `XpuSpec::resets`.
+    #[serde(default)]
+    pub resets: u64,
+    /// The retries of the provisioning done, see `XpuSpec::retries`.
+    #[serde(default)]
+    pub retries: u64,
+    /// The last lines of the rshim console.
+    #[serde(default)]
+    pub console: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Xpu {
+    pub meta_data: Metadata,
+    pub spec: XpuSpec,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<XpuStatus>,
+}
+
+impl Display for Xpu {
+    fn fmt(&self, writer: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(writer, "{0}", self.meta_data.name)
+    }
+}
+
+pub const VERSION_KIND: VersionKind = VersionKind {
+    version: "v1alpha1",
+    kind: "xpu",
+};
+
+pub const COLUMNS: &[Column] = &[
+    Column {
+        name: "NAMESPACE",
+        path: "meta_data.namespace",
+        wide: false,
+    },
+    Column {
+        name: "NAME",
+        path: "meta_data.name",
+        wide: false,
+    },
+    Column {
+        name: "NODE",
+        path: "spec.node",
+        wide: false,
+    },
+    Column {
+        name: "MODEL",
+        path: "spec.model",
+        wide: false,
+    },
+    Column {
+        name: "MODE",
+        path: "spec.mode",
+        wide: false,
+    },
+    Column {
+        name: "STATE",
+        path: "status.state",
+        wide: false,
+    },
+    Column {
+        name: "RSHIM",
+        path: "status.rshim",
+        wide: true,
+    },
+    Column {
+        name: "FIRMWARE",
+        path: "status.firmware",
+        wide: true,
+    },
+    Column {
+        name: "REASON",
+        path: "status.reason",
+        wide: true,
+    },
+];
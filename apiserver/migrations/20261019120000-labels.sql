@@ -0,0 +1 @@
+ALTER TABLE objects ADD COLUMN IF NOT EXISTS labels TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,125 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use actix_web::{delete, get, patch, post, put, web, Responder};
+use std::sync::Arc;
+
+use yangtze_apis::{
+    v1::NamespaceName,
+    v1alpha1::boot_profile::{BootProfile, VERSION_KIND},
+};
+
+use crate::storage::{Object, Storage};
+use yangtze_apis::v1::YangtzeError;
+
+pub fn config(conf: &mut web::ServiceConfig) {
+    conf.service(get)
+        .service(list)
+        .service(create)
+        .service(delete)
+        .service(update);
+}
+
+#[get("/bootprofile/{id}")]
+pub async fn get(
+    id: web::Path<String>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.get(id.to_string()).await?;
+    let profile = BootProfile::try_from(obj)?;
+
+    Ok(web::Json(profile))
+}
+
+#[post("/bootprofile")]
+pub async fn list(
+    meta: web::Json<NamespaceName>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.list(VERSION_KIND.kind, meta.0).await?;
+    let profile: Vec<_> = obj
+        .iter()
+        .map(BootProfile::try_from)
+        .map_while(Result::ok)
+        .collect();
+
+    Ok(web::Json(profile))
+}
+
+#[delete("/bootprofile/{id}")]
+pub async fn delete(
+    id: web::Path<String>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.delete(id.to_string()).await?;
+    let profile = BootProfile::try_from(obj)?;
+
+    Ok(web::Json(profile))
+}
+
+#[put("/bootprofile")]
+pub async fn create(
+    profile: web::Json<BootProfile>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = Object::try_from(profile.0)?;
+    let obj = storage.create(obj).await?;
+    let profile = BootProfile::try_from(obj)?;
+
+    Ok(web::Json(profile))
+}
+
+#[patch("/bootprofile")]
+pub async fn update(
+    profile: web::Json<BootProfile>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = Object::try_from(profile.0)?;
+    let obj = storage.update(obj).await?;
+    let profile = BootProfile::try_from(obj)?;
+
+    Ok(web::Json(profile))
+}
+
+impl TryFrom<Object> for BootProfile {
+    type Error = YangtzeError;
+
+    fn try_from(o: Object) -> Result<Self, Self::Error> {
+        BootProfile::try_from(&o)
+    }
+}
+
+impl TryFrom<&Object> for BootProfile {
+    type Error = YangtzeError;
+
+    fn try_from(o: &Object) -> Result<Self, Self::Error> {
+        Ok(BootProfile {
+            meta_data: o.metadata.clone(),
+            spec: serde_json::from_str(o.spec.as_str())?,
+        })
+    }
+}
+
+impl TryFrom<BootProfile> for Object {
+    type Error = YangtzeError;
+
+    fn try_from(f: BootProfile) -> Result<Self, Self::Error> {
+        let _metadata = f.meta_data.clone();
+
+        Ok(Object {
+            metadata: f.meta_data.clone(),
+            spec: serde_json::to_string(&f.spec)?,
+            // A BootProfile has no status.
+            status: "null".to_string(),
+        })
+    }
+}
@@ -15,21 +15,20 @@ use std::sync::Arc;
 
 use yangtze_apis::{
     v1::NamespaceName,
-    v1alpha1::fabric::{Fabric, FabricState, FabricStatus, VERSION_KIND},
+    v1alpha1::fabric::{
+        Fabric, FabricAllocations, FabricState, FabricStatus, FabricUsage, VERSION_KIND,
+    },
 };
 
 use crate::storage::{Object, Storage};
 use yangtze_apis::v1::YangtzeError;
 
 pub fn config(conf: &mut web::ServiceConfig) {
-    let scope = web::scope(VERSION_KIND.version)
-        .service(get)
+    conf.service(get)
         .service(list)
         .service(create)
         .service(delete)
         .service(update);
-
-    conf.service(scope);
 }
 
 #[get("/fabric/{id}")]
@@ -79,6 +78,10 @@ pub async fn create(
             state: FabricState::Initializing,
             total: 0,
             available: 0,
+            usage: FabricUsage::default(),
+            reason: None,
+            topology: None,
+            allocations: FabricAllocations::default(),
         }),
         ..fabric.0
     };
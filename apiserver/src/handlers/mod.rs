@@ -11,4 +11,43 @@
  * limitations under the License.
  */
 
+use actix_web::web;
+
+pub mod boot_profile;
+pub mod dhcp_lease;
 pub mod fabric;
+pub mod network_interface;
+pub mod node;
+pub mod nvme_namespace;
+pub mod nvme_port;
+pub mod nvme_subsystem;
+pub mod provision;
+pub mod storage_pool;
+pub mod subnet;
+pub mod switch;
+pub mod volume_attachment;
+pub mod vpc;
+pub mod xpu;
+
+pub fn config(conf: &mut web::ServiceConfig) {
+    // All kinds of a version share one scope, as actix-web does not fall
+    // through to the next scope with the same prefix.
+    let scope = web::scope("v1alpha1")
+        .configure(fabric::config)
+        .configure(node::config)
+        .configure(switch::config)
+        .configure(xpu::config)
+        .configure(subnet::config)
+        .configure(dhcp_lease::config)
+        .configure(boot_profile::config)
+        .configure(provision::config)
+        .configure(nvme_subsystem::config)
+        .configure(nvme_namespace::config)
+        .configure(nvme_port::config)
+        .configure(volume_attachment::config)
+        .configure(storage_pool::config)
+        .configure(vpc::config)
+        .configure(network_interface::config);
+
+    conf.service(scope);
+}
@@ -0,0 +1,143 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use actix_web::{delete, get, patch, post, put, web, Responder};
+use std::sync::Arc;
+
+use yangtze_apis::{
+    v1::NamespaceName,
+    v1alpha1::node::{Node, NodeState, NodeStatus, SystemInfo, VERSION_KIND},
+};
+
+use crate::storage::{Object, Storage};
+use yangtze_apis::v1::YangtzeError;
+
+pub fn config(conf: &mut web::ServiceConfig) {
+    conf.service(get)
+        .service(list)
+        .service(create)
+        .service(delete)
+        .service(update);
+}
+
+#[get("/node/{id}")]
+pub async fn get(
+    id: web::Path<String>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.get(id.to_string()).await?;
+    let node = Node::try_from(obj)?;
+
+    Ok(web::Json(node))
+}
+
+#[post("/node")]
+pub async fn list(
+    meta: web::Json<NamespaceName>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.list(VERSION_KIND.kind, meta.0).await?;
+    let node: Vec<_> = obj
+        .iter()
+        .map(Node::try_from)
+        .map_while(Result::ok)
+        .collect();
+
+    Ok(web::Json(node))
+}
+
+#[delete("/node/{id}")]
+pub async fn delete(
+    id: web::Path<String>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.delete(id.to_string()).await?;
+    let node = Node::try_from(obj)?;
+
+    Ok(web::Json(node))
+}
+
+#[put("/node")]
+pub async fn create(
+    node: web::Json<Node>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    // The agent registers the node with the inventory of the host.
+    let node = Node {
+        status: node.0.status.or(Some(NodeStatus {
+            state: NodeState::NotReady,
+            last_heartbeat: 0,
+            system: SystemInfo::default(),
+            cpu_model: String::new(),
+            cpus: 0,
+            memory: 0,
+            nics: vec![],
+            dpus: vec![],
+            nvmes: vec![],
+            neighbors: vec![],
+            boots: vec![],
+            power: None,
+        })),
+        ..node.0
+    };
+    let obj = Object::try_from(node)?;
+    let obj = storage.create(obj).await?;
+    let node = Node::try_from(obj)?;
+
+    Ok(web::Json(node))
+}
+
+#[patch("/node")]
+pub async fn update(
+    node: web::Json<Node>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = Object::try_from(node.0)?;
+    let obj = storage.update(obj).await?;
+    let node = Node::try_from(obj)?;
+
+    Ok(web::Json(node))
+}
+
+impl TryFrom<Object> for Node {
+    type Error = YangtzeError;
+
+    fn try_from(o: Object) -> Result<Self, Self::Error> {
+        Node::try_from(&o)
+    }
+}
+
+impl TryFrom<&Object> for Node {
+    type Error = YangtzeError;
+
+    fn try_from(o: &Object) -> Result<Self, Self::Error> {
+        Ok(Node {
+            meta_data: o.metadata.clone(),
+            spec: serde_json::from_str(o.spec.as_str())?,
+            status: serde_json::from_str(o.status.as_str())?,
+        })
+    }
+}
+
+impl TryFrom<Node> for Object {
+    type Error = YangtzeError;
+
+    fn try_from(f: Node) -> Result<Self, Self::Error> {
+        let _metadata = f.meta_data.clone();
+
+        Ok(Object {
+            metadata: f.meta_data.clone(),
+            spec: serde_json::to_string(&f.spec)?,
+            status: serde_json::to_string(&f.status)?,
+        })
+    }
+}
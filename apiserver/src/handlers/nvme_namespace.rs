@@ -0,0 +1,135 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use actix_web::{delete, get, patch, post, put, web, Responder};
+use std::sync::Arc;
+
+use yangtze_apis::{
+    v1::NamespaceName,
+    v1alpha1::{
+        nvme_namespace::{NvmeNamespace, VERSION_KIND},
+        nvme_subsystem::{NvmeState, NvmeStatus},
+    },
+};
+
+use crate::storage::{Object, Storage};
+use yangtze_apis::v1::YangtzeError;
+
+pub fn config(conf: &mut web::ServiceConfig) {
+    conf.service(get)
+        .service(list)
+        .service(create)
+        .service(delete)
+        .service(update);
+}
+
+#[get("/nvmenamespace/{id}")]
+pub async fn get(
+    id: web::Path<String>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.get(id.to_string()).await?;
+    let namespace = NvmeNamespace::try_from(obj)?;
+
+    Ok(web::Json(namespace))
+}
+
+#[post("/nvmenamespace")]
+pub async fn list(
+    meta: web::Json<NamespaceName>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.list(VERSION_KIND.kind, meta.0).await?;
+    let namespace: Vec<_> = obj
+        .iter()
+        .map(NvmeNamespace::try_from)
+        .map_while(Result::ok)
+        .collect();
+
+    Ok(web::Json(namespace))
+}
+
+#[delete("/nvmenamespace/{id}")]
+pub async fn delete(
+    id: web::Path<String>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.delete(id.to_string()).await?;
+    let namespace = NvmeNamespace::try_from(obj)?;
+
+    Ok(web::Json(namespace))
+}
+
+#[put("/nvmenamespace")]
+pub async fn create(
+    namespace: web::Json<NvmeNamespace>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let namespace = NvmeNamespace {
+        status: namespace.0.status.or(Some(NvmeStatus {
+            state: NvmeState::Pending,
+            reason: None,
+        })),
+        ..namespace.0
+    };
+    let obj = Object::try_from(namespace)?;
+    let obj = storage.create(obj).await?;
+    let namespace = NvmeNamespace::try_from(obj)?;
+
+    Ok(web::Json(namespace))
+}
+
+#[patch("/nvmenamespace")]
+pub async fn update(
+    namespace: web::Json<NvmeNamespace>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = Object::try_from(namespace.0)?;
+    let obj = storage.update(obj).await?;
+    let namespace = NvmeNamespace::try_from(obj)?;
+
+    Ok(web::Json(namespace))
+}
+
+impl TryFrom<Object> for NvmeNamespace {
+    type Error = YangtzeError;
+
+    fn try_from(o: Object) -> Result<Self, Self::Error> {
+        NvmeNamespace::try_from(&o)
+    }
+}
+
+impl TryFrom<&Object> for NvmeNamespace {
+    type Error = YangtzeError;
+
+    fn try_from(o: &Object) -> Result<Self, Self::Error> {
+        Ok(NvmeNamespace {
+            meta_data: o.metadata.clone(),
+            spec: serde_json::from_str(o.spec.as_str())?,
+            status: serde_json::from_str(o.status.as_str())?,
+        })
+    }
+}
+
+impl TryFrom<NvmeNamespace> for Object {
+    type Error = YangtzeError;
+
+    fn try_from(f: NvmeNamespace) -> Result<Self, Self::Error> {
+        let _metadata = f.meta_data.clone();
+
+        Ok(Object {
+            metadata: f.meta_data.clone(),
+            spec: serde_json::to_string(&f.spec)?,
+            status: serde_json::to_string(&f.status)?,
+        })
+    }
+}
@@ -0,0 +1,133 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use actix_web::{delete, get, patch, post, put, web, Responder};
+use std::sync::Arc;
+
+use yangtze_apis::{
+    v1::NamespaceName,
+    v1alpha1::nvme_subsystem::{NvmeState, NvmeSubsystem, NvmeSubsystemStatus, VERSION_KIND},
+};
+
+use crate::storage::{Object, Storage};
+use yangtze_apis::v1::YangtzeError;
+
+pub fn config(conf: &mut web::ServiceConfig) {
+    conf.service(get)
+        .service(list)
+        .service(create)
+        .service(delete)
+        .service(update);
+}
+
+#[get("/nvmesubsystem/{id}")]
+pub async fn get(
+    id: web::Path<String>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.get(id.to_string()).await?;
+    let subsystem = NvmeSubsystem::try_from(obj)?;
+
+    Ok(web::Json(subsystem))
+}
+
+#[post("/nvmesubsystem")]
+pub async fn list(
+    meta: web::Json<NamespaceName>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.list(VERSION_KIND.kind, meta.0).await?;
+    let subsystem: Vec<_> = obj
+        .iter()
+        .map(NvmeSubsystem::try_from)
+        .map_while(Result::ok)
+        .collect();
+
+    Ok(web::Json(subsystem))
+}
+
+#[delete("/nvmesubsystem/{id}")]
+pub async fn delete(
+    id: web::Path<String>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.delete(id.to_string()).await?;
+    let subsystem = NvmeSubsystem::try_from(obj)?;
+
+    Ok(web::Json(subsystem))
+}
+
+#[put("/nvmesubsystem")]
+pub async fn create(
+    subsystem: web::Json<NvmeSubsystem>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let subsystem = NvmeSubsystem {
+        status: subsystem.0.status.or(Some(NvmeSubsystemStatus {
+            state: NvmeState::Pending,
+            reason: None,
+            attached_hosts: vec![],
+        })),
+        ..subsystem.0
+    };
+    let obj = Object::try_from(subsystem)?;
+    let obj = storage.create(obj).await?;
+    let subsystem = NvmeSubsystem::try_from(obj)?;
+
+    Ok(web::Json(subsystem))
+}
+
+#[patch("/nvmesubsystem")]
+pub async fn update(
+    subsystem: web::Json<NvmeSubsystem>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = Object::try_from(subsystem.0)?;
+    let obj = storage.update(obj).await?;
+    let subsystem = NvmeSubsystem::try_from(obj)?;
+
+    Ok(web::Json(subsystem))
+}
+
+impl TryFrom<Object> for NvmeSubsystem {
+    type Error = YangtzeError;
+
+    fn try_from(o: Object) -> Result<Self, Self::Error> {
+        NvmeSubsystem::try_from(&o)
+    }
+}
+
+impl TryFrom<&Object> for NvmeSubsystem {
+    type Error = YangtzeError;
+
+    fn try_from(o: &Object) -> Result<Self, Self::Error> {
+        Ok(NvmeSubsystem {
+            meta_data: o.metadata.clone(),
+            spec: serde_json::from_str(o.spec.as_str())?,
+            status: serde_json::from_str(o.status.as_str())?,
+        })
+    }
+}
+
+impl TryFrom<NvmeSubsystem> for Object {
+    type Error = YangtzeError;
+
+    fn try_from(f: NvmeSubsystem) -> Result<Self, Self::Error> {
+        let _metadata = f.meta_data.clone();
+
+        Ok(Object {
+            metadata: f.meta_data.clone(),
+            spec: serde_json::to_string(&f.spec)?,
+            status: serde_json::to_string(&f.status)?,
+        })
+    }
+}
@@ -0,0 +1,137 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use actix_web::{delete, get, patch, post, put, web, Responder};
+use std::sync::Arc;
+
+use yangtze_apis::{
+    v1::NamespaceName,
+    v1alpha1::provision::{Provision, ProvisionPhase, ProvisionStatus, VERSION_KIND},
+};
+
+use crate::storage::{Object, Storage};
+use yangtze_apis::v1::YangtzeError;
+
+pub fn config(conf: &mut web::ServiceConfig) {
+    conf.service(get)
+        .service(list)
+        .service(create)
+        .service(delete)
+        .service(update);
+}
+
+#[get("/provision/{id}")]
+pub async fn get(
+    id: web::Path<String>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.get(id.to_string()).await?;
+    let provision = Provision::try_from(obj)?;
+
+    Ok(web::Json(provision))
+}
+
+#[post("/provision")]
+pub async fn list(
+    meta: web::Json<NamespaceName>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.list(VERSION_KIND.kind, meta.0).await?;
+    let provision: Vec<_> = obj
+        .iter()
+        .map(Provision::try_from)
+        .map_while(Result::ok)
+        .collect();
+
+    Ok(web::Json(provision))
+}
+
+#[delete("/provision/{id}")]
+pub async fn delete(
+    id: web::Path<String>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.delete(id.to_string()).await?;
+    let provision = Provision::try_from(obj)?;
+
+    Ok(web::Json(provision))
+}
+
+#[put("/provision")]
+pub async fn create(
+    provision: web::Json<Provision>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let provision = Provision {
+        status: provision.0.status.or(Some(ProvisionStatus {
+            phase: ProvisionPhase::Registered,
+            reason: None,
+            image: None,
+            step: 0,
+            action: None,
+            result: None,
+            last_transition: 0,
+        })),
+        ..provision.0
+    };
+    let obj = Object::try_from(provision)?;
+    let obj = storage.create(obj).await?;
+    let provision = Provision::try_from(obj)?;
+
+    Ok(web::Json(provision))
+}
+
+#[patch("/provision")]
+pub async fn update(
+    provision: web::Json<Provision>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = Object::try_from(provision.0)?;
+    let obj = storage.update(obj).await?;
+    let provision = Provision::try_from(obj)?;
+
+    Ok(web::Json(provision))
+}
+
+impl TryFrom<Object> for Provision {
+    type Error = YangtzeError;
+
+    fn try_from(o: Object) -> Result<Self, Self::Error> {
+        Provision::try_from(&o)
+    }
+}
+
+impl TryFrom<&Object> for Provision {
+    type Error = YangtzeError;
+
+    fn try_from(o: &Object) -> Result<Self, Self::Error> {
+        Ok(Provision {
+            meta_data: o.metadata.clone(),
+            spec: serde_json::from_str(o.spec.as_str())?,
+            status: serde_json::from_str(o.status.as_str())?,
+        })
+    }
+}
+
+impl TryFrom<Provision> for Object {
+    type Error = YangtzeError;
+
+    fn try_from(f: Provision) -> Result<Self, Self::Error> {
+        let _metadata = f.meta_data.clone();
+
+        Ok(Object {
+            metadata: f.meta_data.clone(),
+            spec: serde_json::to_string(&f.spec)?,
+            status: serde_json::to_string(&f.status)?,
+        })
+    }
+}
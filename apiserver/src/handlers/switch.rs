@@ -0,0 +1,138 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use actix_web::{delete, get, patch, post, put, web, Responder};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use yangtze_apis::{
+    v1::NamespaceName,
+    v1alpha1::switch::{Switch, SwitchState, SwitchStatus, VERSION_KIND},
+};
+
+use crate::storage::{Object, Storage};
+use yangtze_apis::v1::YangtzeError;
+
+pub fn config(conf: &mut web::ServiceConfig) {
+    conf.service(get)
+        .service(list)
+        .service(create)
+        .service(delete)
+        .service(update);
+}
+
+#[get("/switch/{id}")]
+pub async fn get(
+    id: web::Path<String>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.get(id.to_string()).await?;
+    let switch = Switch::try_from(obj)?;
+
+    Ok(web::Json(switch))
+}
+
+#[post("/switch")]
+pub async fn list(
+    meta: web::Json<NamespaceName>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.list(VERSION_KIND.kind, meta.0).await?;
+    let switch: Vec<_> = obj
+        .iter()
+        .map(Switch::try_from)
+        .map_while(Result::ok)
+        .collect();
+
+    Ok(web::Json(switch))
+}
+
+#[delete("/switch/{id}")]
+pub async fn delete(
+    id: web::Path<String>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.delete(id.to_string()).await?;
+    let switch = Switch::try_from(obj)?;
+
+    Ok(web::Json(switch))
+}
+
+#[put("/switch")]
+pub async fn create(
+    switch: web::Json<Switch>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let switch = Switch {
+        status: switch.0.status.or(Some(SwitchStatus {
+            state: SwitchState::Discovered,
+            system_name: String::new(),
+            links: vec![],
+            neighbors: vec![],
+            reason: None,
+            ports: BTreeMap::new(),
+            sessions: BTreeMap::new(),
+        })),
+        ..switch.0
+    };
+    let obj = Object::try_from(switch)?;
+    let obj = storage.create(obj).await?;
+    let switch = Switch::try_from(obj)?;
+
+    Ok(web::Json(switch))
+}
+
+#[patch("/switch")]
+pub async fn update(
+    switch: web::Json<Switch>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = Object::try_from(switch.0)?;
+    let obj = storage.update(obj).await?;
+    let switch = Switch::try_from(obj)?;
+
+    Ok(web::Json(switch))
+}
+
+impl TryFrom<Object> for Switch {
+    type Error = YangtzeError;
+
+    fn try_from(o: Object) -> Result<Self, Self::Error> {
+        Switch::try_from(&o)
+    }
+}
+
+impl TryFrom<&Object> for Switch {
+    type Error = YangtzeError;
+
+    fn try_from(o: &Object) -> Result<Self, Self::Error> {
+        Ok(Switch {
+            meta_data: o.metadata.clone(),
+            spec: serde_json::from_str(o.spec.as_str())?,
+            status: serde_json::from_str(o.status.as_str())?,
+        })
+    }
+}
+
+impl TryFrom<Switch> for Object {
+    type Error = YangtzeError;
+
+    fn try_from(f: Switch) -> Result<Self, Self::Error> {
+        let _metadata = f.meta_data.clone();
+
+        Ok(Object {
+            metadata: f.meta_data.clone(),
+            spec: serde_json::to_string(&f.spec)?,
+            status: serde_json::to_string(&f.status)?,
+        })
+    }
+}
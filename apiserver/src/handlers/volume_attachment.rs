@@ -0,0 +1,138 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use actix_web::{delete, get, patch, post, put, web, Responder};
+use std::sync::Arc;
+
+use yangtze_apis::{
+    v1::NamespaceName,
+    v1alpha1::volume_attachment::{
+        VolumeAttachment, VolumeAttachmentState, VolumeAttachmentStatus, VERSION_KIND,
+    },
+};
+
+use crate::storage::{Object, Storage};
+use yangtze_apis::v1::YangtzeError;
+
+pub fn config(conf: &mut web::ServiceConfig) {
+    conf.service(get)
+        .service(list)
+        .service(create)
+        .service(delete)
+        .service(update);
+}
+
+#[get("/volumeattachment/{id}")]
+pub async fn get(
+    id: web::Path<String>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.get(id.to_string()).await?;
+    let attachment = VolumeAttachment::try_from(obj)?;
+
+    Ok(web::Json(attachment))
+}
+
+#[post("/volumeattachment")]
+pub async fn list(
+    meta: web::Json<NamespaceName>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.list(VERSION_KIND.kind, meta.0).await?;
+    let attachment: Vec<_> = obj
+        .iter()
+        .map(VolumeAttachment::try_from)
+        .map_while(Result::ok)
+        .collect();
+
+    Ok(web::Json(attachment))
+}
+
+#[delete("/volumeattachment/{id}")]
+pub async fn delete(
+    id: web::Path<String>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.delete(id.to_string()).await?;
+    let attachment = VolumeAttachment::try_from(obj)?;
+
+    Ok(web::Json(attachment))
+}
+
+#[put("/volumeattachment")]
+pub async fn create(
+    attachment: web::Json<VolumeAttachment>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let attachment = VolumeAttachment {
+        status: attachment.0.status.or(Some(VolumeAttachmentStatus {
+            state: VolumeAttachmentState::Pending,
+            reason: None,
+            host_nqn: None,
+            device: None,
+            paths: vec![],
+            multipath: false,
+        })),
+        ..attachment.0
+    };
+    let obj = Object::try_from(attachment)?;
+    let obj = storage.create(obj).await?;
+    let attachment = VolumeAttachment::try_from(obj)?;
+
+    Ok(web::Json(attachment))
+}
+
+#[patch("/volumeattachment")]
+pub async fn update(
+    attachment: web::Json<VolumeAttachment>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = Object::try_from(attachment.0)?;
+    let obj = storage.update(obj).await?;
+    let attachment = VolumeAttachment::try_from(obj)?;
+
+    Ok(web::Json(attachment))
+}
+
+impl TryFrom<Object> for VolumeAttachment {
+    type Error = YangtzeError;
+
+    fn try_from(o: Object) -> Result<Self, Self::Error> {
+        VolumeAttachment::try_from(&o)
+    }
+}
+
+impl TryFrom<&Object> for VolumeAttachment {
+    type Error = YangtzeError;
+
+    fn try_from(o: &Object) -> Result<Self, Self::Error> {
+        Ok(VolumeAttachment {
+            meta_data: o.metadata.clone(),
+            spec: serde_json::from_str(o.spec.as_str())?,
+            status: serde_json::from_str(o.status.as_str())?,
+        })
+    }
+}
+
+impl TryFrom<VolumeAttachment> for Object {
+    type Error = YangtzeError;
+
+    fn try_from(f: VolumeAttachment) -> Result<Self, Self::Error> {
+        let _metadata = f.meta_data.clone();
+
+        Ok(Object {
+            metadata: f.meta_data.clone(),
+            spec: serde_json::to_string(&f.spec)?,
+            status: serde_json::to_string(&f.status)?,
+        })
+    }
+}
@@ -0,0 +1,132 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use actix_web::{delete, get, patch, post, put, web, Responder};
+use std::sync::Arc;
+
+use yangtze_apis::{
+    v1::NamespaceName,
+    v1alpha1::vpc::{Vpc, VpcState, VpcStatus, VERSION_KIND},
+};
+
+use crate::storage::{Object, Storage};
+use yangtze_apis::v1::YangtzeError;
+
+pub fn config(conf: &mut web::ServiceConfig) {
+    conf.service(get)
+        .service(list)
+        .service(create)
+        .service(delete)
+        .service(update);
+}
+
+#[get("/vpc/{id}")]
+pub async fn get(
+    id: web::Path<String>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.get(id.to_string()).await?;
+    let vpc = Vpc::try_from(obj)?;
+
+    Ok(web::Json(vpc))
+}
+
+#[post("/vpc")]
+pub async fn list(
+    meta: web::Json<NamespaceName>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.list(VERSION_KIND.kind, meta.0).await?;
+    let vpc: Vec<_> = obj
+        .iter()
+        .map(Vpc::try_from)
+        .map_while(Result::ok)
+        .collect();
+
+    Ok(web::Json(vpc))
+}
+
+#[delete("/vpc/{id}")]
+pub async fn delete(
+    id: web::Path<String>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = storage.delete(id.to_string()).await?;
+    let vpc = Vpc::try_from(obj)?;
+
+    Ok(web::Json(vpc))
+}
+
+#[put("/vpc")]
+pub async fn create(
+    vpc: web::Json<Vpc>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let vpc = Vpc {
+        status: vpc.0.status.or(Some(VpcStatus {
+            state: VpcState::Ready,
+            reason: None,
+        })),
+        ..vpc.0
+    };
+    let obj = Object::try_from(vpc)?;
+    let obj = storage.create(obj).await?;
+    let vpc = Vpc::try_from(obj)?;
+
+    Ok(web::Json(vpc))
+}
+
+#[patch("/vpc")]
+pub async fn update(
+    vpc: web::Json<Vpc>,
+    storage: web::Data<Arc<dyn Storage>>,
+) -> actix_web::Result<impl Responder> {
+    let obj = Object::try_from(vpc.0)?;
+    let obj = storage.update(obj).await?;
+    let vpc = Vpc::try_from(obj)?;
+
+    Ok(web::Json(vpc))
+}
+
+impl TryFrom<Object> for Vpc {
+    type Error = YangtzeError;
+
+    fn try_from(o: Object) -> Result<Self, Self::Error> {
+        Vpc::try_from(&o)
+    }
+}
+
+impl TryFrom<&Object> for Vpc {
+    type Error = YangtzeError;
+
+    fn try_from(o: &Object) -> Result<Self, Self::Error> {
+        Ok(Vpc {
+            meta_data: o.metadata.clone(),
+            spec: serde_json::from_str(o.spec.as_str())?,
+            status: serde_json::from_str(o.status.as_str())?,
+        })
+    }
+}
+
+impl TryFrom<Vpc> for Object {
+    type Error = YangtzeError;
+
+    fn try_from(f: Vpc) -> Result<Self, Self::Error> {
+        let _metadata = f.meta_data.clone();
+
+        Ok(Object {
+            metadata: f.meta_data.clone(),
+            spec: serde_json::to_string(&f.spec)?,
+            status: serde_json::to_string(&f.status)?,
+        })
+    }
+}
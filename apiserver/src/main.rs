@@ -29,7 +29,7 @@ async fn main() -> Result<(), YangtzeError> {
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(storage.clone()))
-            .configure(handlers::fabric::config)
+            .configure(handlers::config)
     })
     .bind(("127.0.0.1", 8080))
     .map_err(|e| YangtzeError::GeneralError(e.to_string()))?
@@ -114,8 +114,9 @@ impl Storage for PostgresStorage {
             name,
             version,
             spec,
-            status)
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
+            status,
+            labels)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
         RETURNING *";
 
         let obj: Object = sqlx::query_as(query)
@@ -126,6 +127,7 @@ impl Storage for PostgresStorage {
             .bind(o.metadata.version)
             .bind(&o.spec)
             .bind(&o.status)
+            .bind(&o.metadata.labels)
             .fetch_one(&self.pool)
             .await
             .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
@@ -134,13 +136,14 @@ impl Storage for PostgresStorage {
     }
 
     async fn update(&self, o: Object) -> Result<Object, YangtzeError> {
-        let query = "UPDATE objects SET spec=$3, status=$4, version=version+1 WHERE id=$1 AND version <= $2 RETURNING *";
+        let query = "UPDATE objects SET spec=$3, status=$4, labels=$5, version=version+1 WHERE id=$1 AND version <= $2 RETURNING *";
 
         let obj: Object = sqlx::query_as(query)
             .bind(o.metadata.uuid)
             .bind(o.metadata.version)
             .bind(&o.spec)
             .bind(&o.status)
+            .bind(&o.metadata.labels)
             .fetch_one(&self.pool)
             .await
             .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
@@ -158,10 +161,91 @@ impl<'r> FromRow<'r, PgRow> for Object {
                 namespace: row.try_get("namespace")?,
                 name: row.try_get("name")?,
                 version: row.try_get("version")?,
-                labels: vec![],
+                labels: row.try_get("labels")?,
             },
             spec: row.try_get("spec")?,
             status: row.try_get("status")?,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use actix_web::{test, web, App};
+    use serde_json::{json, Value};
+    use sqlx::Executor;
+
+    use super::*;
+    use crate::handlers;
+
+    const MIGRATIONS: &[&str] = &[
+        include_str!("../../migrations/20231202213300-init.sql"),
+        include_str!("../../migrations/20261019120000-labels.sql"),
+    ];
+
+    #[actix_web::test]
+    #[ignore = "needs a Postgres database at DATABASE_URL"]
+    async fn persist_labels() {
+        let url = std::env::var("DATABASE_URL").unwrap();
+        let storage = PostgresStorage::new(url).await.unwrap();
+        for sql in MIGRATIONS {
+            // Fails if applied already, e.g. by a previous run.
+            let _ = storage.pool.execute(*sql).await;
+        }
+        let storage: Arc<dyn Storage> = Arc::new(storage);
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(storage))
+                .configure(handlers::config),
+        )
+        .await;
+
+        let name = format!("n-{}", Uuid::new_v4());
+        let req = test::TestRequest::put()
+            .uri("/v1alpha1/node")
+            .set_json(json!({
+                "meta_data": {"kind": "node", "namespace": "default", "name": name, "labels": ["storage=fast", "zone=a"]},
+                "spec": {"hostname": name, "serial": "s1"},
+            }))
+            .to_request();
+        let node: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            node["meta_data"]["labels"],
+            json!(["storage=fast", "zone=a"])
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/v1alpha1/node")
+            .set_json(json!({"namespace": "default", "name": name}))
+            .to_request();
+        let nodes: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(
+            nodes[0]["meta_data"]["labels"],
+            json!(["storage=fast", "zone=a"])
+        );
+
+        let mut node = nodes[0].clone();
+        node["meta_data"]["labels"] = json!(["zone=b"]);
+        let req = test::TestRequest::patch()
+            .uri("/v1alpha1/node")
+            .set_json(&node)
+            .to_request();
+        let node: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(node["meta_data"]["labels"], json!(["zone=b"]));
+
+        let id = node["meta_data"]["uuid"].as_str().unwrap();
+        let req = test::TestRequest::get()
+            .uri(&format!("/v1alpha1/node/{}", id))
+            .to_request();
+        let node: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(node["meta_data"]["labels"], json!(["zone=b"]));
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/v1alpha1/node/{}", id))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+    }
+}
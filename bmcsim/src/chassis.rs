@@ -0,0 +1,49 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Mutex;
+
+/// The power of the simulated system, shared by the Redfish and IPMI
+/// servers.
+pub struct Chassis {
+    power: Mutex<bool>,
+}
+
+impl Chassis {
+    pub fn new(power: bool) -> Self {
+        Chassis {
+            power: Mutex::new(power),
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        *self.power.lock().unwrap()
+    }
+
+    pub fn set(&self, on: bool) {
+        let mut power = self.power.lock().unwrap();
+        if *power != on {
+            tracing::info!("The system is powered {}.", if on { "on" } else { "off" });
+        }
+        *power = on;
+    }
+
+    /// Restarts the system if it is on, as a power cycle or a reset does.
+    pub fn restart(&self) -> bool {
+        let power = self.power.lock().unwrap();
+        if *power {
+            tracing::info!("The system is restarted.");
+        }
+        *power
+    }
+}
@@ -0,0 +1,255 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use md5::{Digest, Md5};
+use tokio::net::UdpSocket;
+
+use yangtze_apis::v1::YangtzeError;
+
+use crate::chassis::Chassis;
+
+const RMCP_HEADER: [u8; 4] = [0x06, 0x00, 0xff, 0x07];
+
+const AUTH_NONE: u8 = 0x00;
+const AUTH_MD5: u8 = 0x02;
+const AUTH_PASSWORD: u8 = 0x04;
+
+const NETFN_CHASSIS: u8 = 0x00;
+const NETFN_APP: u8 = 0x06;
+
+const BMC_ADDRESS: u8 = 0x20;
+
+const CC_OK: u8 = 0x00;
+const CC_INVALID_SESSION: u8 = 0x87;
+const CC_INVALID_USER: u8 = 0x81;
+const CC_INVALID_COMMAND: u8 = 0xc1;
+const CC_UNSUPPORTED: u8 = 0xd5;
+
+/// Serves the chassis commands of IPMI 1.5 over LAN, authenticated by
+/// straight password or MD5.
+pub struct Server {
+    pub listen: SocketAddr,
+    pub username: String,
+    pub password: String,
+    /// Whether MD5 is offered besides the straight password.
+    pub md5: bool,
+    pub chassis: Arc<Chassis>,
+}
+
+/// A session by id, activated or only challenged.
+struct Session {
+    challenge: [u8; 16],
+    active: bool,
+}
+
+struct Request<'a> {
+    auth_type: u8,
+    seq: u32,
+    session: u32,
+    /// The IPMI message, from the responder address to the checksum.
+    msg: &'a [u8],
+    auth_code: Option<&'a [u8]>,
+}
+
+impl Server {
+    pub async fn run(self) -> Result<(), YangtzeError> {
+        let socket = UdpSocket::bind(self.listen)
+            .await
+            .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+        self.serve(socket).await
+    }
+
+    /// Serves on a bound socket, e.g. of a free port in tests.
+    pub async fn serve(self, socket: UdpSocket) -> Result<(), YangtzeError> {
+        let err = |e: std::io::Error| YangtzeError::GeneralError(e.to_string());
+        let listen = socket.local_addr().map_err(err)?;
+        tracing::info!("IPMI is listening on <{}>.", listen);
+
+        let mut sessions = HashMap::new();
+        let mut buf = [0; 1024];
+        loop {
+            let (n, peer) = socket.recv_from(&mut buf).await.map_err(err)?;
+            let Some(req) = parse(&buf[..n]) else {
+                continue;
+            };
+            if req.msg.len() < 7 {
+                continue;
+            }
+
+            let (netfn, cmd) = (req.msg[1] >> 2, req.msg[5]);
+            let (cc, data) = self.handle(&mut sessions, &req, netfn, cmd);
+
+            let mut msg = vec![req.msg[3], ((netfn | 1) << 2) | (req.msg[4] & 0x03)];
+            msg.push(checksum(&msg));
+            msg.extend_from_slice(&[BMC_ADDRESS, req.msg[4], cmd, cc]);
+            msg.extend_from_slice(&data);
+            msg.push(checksum(&msg[3..]));
+
+            let mut packet = RMCP_HEADER.to_vec();
+            packet.push(AUTH_NONE);
+            packet.extend_from_slice(&req.seq.to_le_bytes());
+            packet.extend_from_slice(&req.session.to_le_bytes());
+            packet.push(msg.len() as u8);
+            packet.extend_from_slice(&msg);
+
+            if let Err(e) = socket.send_to(&packet, peer).await {
+                tracing::error!("Failed to reply to <{}>: {}", peer, e);
+            }
+        }
+    }
+
+    fn handle(
+        &self,
+        sessions: &mut HashMap<u32, Session>,
+        req: &Request,
+        netfn: u8,
+        cmd: u8,
+    ) -> (u8, Vec<u8>) {
+        let data = &req.msg[6..req.msg.len() - 1];
+
+        match (netfn, cmd) {
+            // Get Channel Authentication Capabilities
+            (NETFN_APP, 0x38) => {
+                let auth = ((self.md5 as u8) << AUTH_MD5) | (1 << AUTH_PASSWORD);
+                (CC_OK, vec![0x01, auth, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00])
+            }
+            // Get Session Challenge
+            (NETFN_APP, 0x39) => {
+                if data.len() < 17 || data[1..17] != pad(&self.username) {
+                    return (CC_INVALID_USER, vec![]);
+                }
+                let id = random() | 1;
+                let challenge: Vec<u8> = (0..4).flat_map(|_| random().to_le_bytes()).collect();
+                let mut res = id.to_le_bytes().to_vec();
+                res.extend_from_slice(&challenge);
+                sessions.insert(
+                    id,
+                    Session {
+                        challenge: challenge.try_into().unwrap_or_default(),
+                        active: false,
+                    },
+                );
+                (CC_OK, res)
+            }
+            // Activate Session
+            (NETFN_APP, 0x3a) => {
+                let Some(session) = sessions.get_mut(&req.session) else {
+                    return (CC_INVALID_SESSION, vec![]);
+                };
+                if data.len() < 22 || data[2..18] != session.challenge || !self.authentic(req) {
+                    return (CC_INVALID_SESSION, vec![]);
+                }
+                session.active = true;
+                tracing::info!("IPMI session <{:08x}> was activated.", req.session);
+
+                let mut res = vec![req.auth_type];
+                res.extend_from_slice(&req.session.to_le_bytes());
+                res.extend_from_slice(&(random() | 1).to_le_bytes());
+                res.push(data[1]);
+                (CC_OK, res)
+            }
+            _ if !sessions.get(&req.session).is_some_and(|s| s.active) => {
+                (CC_INVALID_SESSION, vec![])
+            }
+            // Set Session Privilege Level
+            (NETFN_APP, 0x3b) => (CC_OK, data.first().copied().into_iter().collect()),
+            // Close Session
+            (NETFN_APP, 0x3c) => {
+                sessions.remove(&req.session);
+                (CC_OK, vec![])
+            }
+            // Get Chassis Status
+            (NETFN_CHASSIS, 0x01) => (CC_OK, vec![self.chassis.is_on() as u8, 0, 0, 0]),
+            // Chassis Control
+            (NETFN_CHASSIS, 0x02) => match data.first() {
+                Some(0x00) | Some(0x05) => {
+                    self.chassis.set(false);
+                    (CC_OK, vec![])
+                }
+                Some(0x01) => {
+                    self.chassis.set(true);
+                    (CC_OK, vec![])
+                }
+                Some(0x02) | Some(0x03) if self.chassis.restart() => (CC_OK, vec![]),
+                _ => (CC_UNSUPPORTED, vec![]),
+            },
+            _ => (CC_INVALID_COMMAND, vec![]),
+        }
+    }
+
+    fn authentic(&self, req: &Request) -> bool {
+        let password = pad(&self.password);
+        match (req.auth_type, req.auth_code) {
+            (AUTH_PASSWORD, Some(code)) => code == password,
+            (AUTH_MD5, Some(code)) if self.md5 => {
+                let mut md5 = Md5::new();
+                md5.update(password);
+                md5.update(req.session.to_le_bytes());
+                md5.update(req.msg);
+                md5.update(req.seq.to_le_bytes());
+                md5.update(password);
+                code == md5.finalize().as_slice()
+            }
+            _ => false,
+        }
+    }
+}
+
+fn parse(packet: &[u8]) -> Option<Request<'_>> {
+    if packet.len() < 14 || packet[..4] != RMCP_HEADER {
+        return None;
+    }
+    let auth_type = packet[4];
+    let seq = u32::from_le_bytes(packet[5..9].try_into().ok()?);
+    let session = u32::from_le_bytes(packet[9..13].try_into().ok()?);
+    let (auth_code, start) = match auth_type {
+        AUTH_NONE => (None, 13),
+        _ => (Some(packet.get(13..29)?), 29),
+    };
+    let len = *packet.get(start)? as usize;
+
+    Some(Request {
+        auth_type,
+        seq,
+        session,
+        msg: packet.get(start + 1..start + 1 + len)?,
+        auth_code,
+    })
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    0u8.wrapping_sub(bytes.iter().fold(0u8, |a, b| a.wrapping_add(*b)))
+}
+
+fn pad(s: &str) -> [u8; 16] {
+    let mut padded = [0; 16];
+    let len = s.len().min(16);
+    padded[..len].copy_from_slice(&s.as_bytes()[..len]);
+
+    padded
+}
+
+/// Good enough for the ids and challenges of a simulator.
+fn random() -> u32 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+
+    (nanos as u32).wrapping_mul(0x9e37_79b9) ^ (nanos >> 32) as u32
+}
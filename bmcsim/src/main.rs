@@ -0,0 +1,82 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use clap::Parser;
+
+use yangtze_apis::v1::YangtzeError;
+use yangtze_bmcsim::{chassis, ipmi, redfish};
+
+#[derive(Parser)]
+#[command(name = "yangtze-bmcsim")]
+#[command(version = "0.1.0")]
+#[command(about = "A BMC simulator serving Redfish and IPMI over LAN", long_about = None)]
+struct Cli {
+    #[arg(long, default_value = "127.0.0.1:8000")]
+    redfish_listen: SocketAddr,
+
+    /// The address to serve IPMI over LAN on, e.g. 127.0.0.1:623
+    #[arg(long)]
+    ipmi_listen: Option<SocketAddr>,
+
+    /// Offers only the straight password authentication over IPMI
+    #[arg(long)]
+    ipmi_without_md5: bool,
+
+    #[arg(long, default_value = "admin")]
+    username: String,
+
+    #[arg(long, default_value = "password")]
+    password: String,
+
+    /// Whether the simulated system is powered on at start
+    #[arg(long)]
+    power_on: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), YangtzeError> {
+    let subscriber = tracing_subscriber::FmtSubscriber::new();
+    // use that subscriber to process traces emitted after this point
+    tracing::subscriber::set_global_default(subscriber)
+        .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+
+    let cli = Cli::parse();
+
+    let chassis = Arc::new(chassis::Chassis::new(cli.power_on));
+
+    let redfish = redfish::Server {
+        listen: cli.redfish_listen,
+        username: cli.username.clone(),
+        password: cli.password.clone(),
+        chassis: chassis.clone(),
+    };
+
+    match cli.ipmi_listen {
+        Some(listen) => {
+            let ipmi = ipmi::Server {
+                listen,
+                username: cli.username,
+                password: cli.password,
+                md5: !cli.ipmi_without_md5,
+                chassis,
+            };
+            tokio::try_join!(redfish.run(), ipmi.run())?;
+        }
+        None => redfish.run().await?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,192 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use actix_web::{dev, get, post, web, App, HttpRequest, HttpResponse, HttpServer, Responder};
+use base64::Engine;
+use serde_json::{json, Value};
+
+use yangtze_apis::v1::YangtzeError;
+
+use crate::chassis::Chassis;
+
+const SYSTEM_ID: &str = "1";
+
+const RESET_TYPES: &[&str] = &[
+    "On",
+    "ForceOff",
+    "GracefulShutdown",
+    "ForceRestart",
+    "GracefulRestart",
+    "PowerCycle",
+];
+
+/// Serves one ComputerSystem over Redfish with HTTP basic authentication:
+///
+/// * `GET /redfish/v1`: the service root
+/// * `GET /redfish/v1/Systems`: the systems
+/// * `GET /redfish/v1/Systems/1`: the system and its power state
+/// * `POST /redfish/v1/Systems/1/Actions/ComputerSystem.Reset`: applies a
+///   `ResetType`
+pub struct Server {
+    pub listen: SocketAddr,
+    pub username: String,
+    pub password: String,
+    pub chassis: Arc<Chassis>,
+}
+
+struct State {
+    authorization: String,
+    chassis: Arc<Chassis>,
+}
+
+impl Server {
+    pub async fn run(self) -> Result<(), YangtzeError> {
+        let listener = std::net::TcpListener::bind(self.listen)
+            .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+        self.serve(listener)?
+            .await
+            .map_err(|e| YangtzeError::GeneralError(e.to_string()))
+    }
+
+    /// The server on a bound listener, e.g. of a free port in tests.
+    pub fn serve(self, listener: std::net::TcpListener) -> Result<dev::Server, YangtzeError> {
+        let listen = listener
+            .local_addr()
+            .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+        tracing::info!("Redfish is listening on <{}>.", listen);
+
+        let credentials = format!("{}:{}", self.username, self.password);
+        let state = web::Data::new(State {
+            authorization: format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(credentials)
+            ),
+            chassis: self.chassis,
+        });
+
+        Ok(HttpServer::new(move || {
+            App::new()
+                .app_data(state.clone())
+                .service(root)
+                .service(systems)
+                .service(system)
+                .service(reset)
+        })
+        .listen(listener)
+        .map_err(|e| YangtzeError::GeneralError(e.to_string()))?
+        .run())
+    }
+}
+
+#[get("/redfish/v1")]
+async fn root() -> impl Responder {
+    HttpResponse::Ok().json(json!({
+        "@odata.id": "/redfish/v1",
+        "Id": "RootService",
+        "RedfishVersion": "1.6.0",
+        "Systems": { "@odata.id": "/redfish/v1/Systems" },
+    }))
+}
+
+#[get("/redfish/v1/Systems")]
+async fn systems(req: HttpRequest, state: web::Data<State>) -> impl Responder {
+    if let Some(res) = unauthorized(&req, &state) {
+        return res;
+    }
+
+    HttpResponse::Ok().json(json!({
+        "@odata.id": "/redfish/v1/Systems",
+        "Members@odata.count": 1,
+        "Members": [{ "@odata.id": format!("/redfish/v1/Systems/{}", SYSTEM_ID) }],
+    }))
+}
+
+#[get("/redfish/v1/Systems/{id}")]
+async fn system(
+    id: web::Path<String>,
+    req: HttpRequest,
+    state: web::Data<State>,
+) -> impl Responder {
+    if let Some(res) = unauthorized(&req, &state) {
+        return res;
+    }
+    if id.as_str() != SYSTEM_ID {
+        return HttpResponse::NotFound().finish();
+    }
+
+    let path = format!("/redfish/v1/Systems/{}", SYSTEM_ID);
+    HttpResponse::Ok().json(json!({
+        "@odata.id": path,
+        "Id": SYSTEM_ID,
+        "Name": "Simulated System",
+        "PowerState": if state.chassis.is_on() { "On" } else { "Off" },
+        "Actions": {
+            "#ComputerSystem.Reset": {
+                "target": format!("{}/Actions/ComputerSystem.Reset", path),
+                "ResetType@Redfish.AllowableValues": RESET_TYPES,
+            },
+        },
+    }))
+}
+
+#[post("/redfish/v1/Systems/{id}/Actions/ComputerSystem.Reset")]
+async fn reset(
+    id: web::Path<String>,
+    body: web::Json<Value>,
+    req: HttpRequest,
+    state: web::Data<State>,
+) -> impl Responder {
+    if let Some(res) = unauthorized(&req, &state) {
+        return res;
+    }
+    if id.as_str() != SYSTEM_ID {
+        return HttpResponse::NotFound().finish();
+    }
+
+    let chassis = &state.chassis;
+    match body["ResetType"].as_str() {
+        Some("On") => chassis.set(true),
+        Some("ForceOff") | Some("GracefulShutdown") => chassis.set(false),
+        Some("PowerCycle") | Some("ForceRestart") | Some("GracefulRestart") => {
+            if !chassis.restart() {
+                return HttpResponse::Conflict().json(error("The system is off."));
+            }
+        }
+        _ => return HttpResponse::BadRequest().json(error("Unsupported ResetType.")),
+    }
+
+    HttpResponse::NoContent().finish()
+}
+
+fn unauthorized(req: &HttpRequest, state: &State) -> Option<HttpResponse> {
+    let authorization = req
+        .headers()
+        .get(actix_web::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok());
+    if authorization == Some(state.authorization.as_str()) {
+        return None;
+    }
+
+    Some(
+        HttpResponse::Unauthorized()
+            .insert_header(("WWW-Authenticate", "Basic realm=\"Redfish\""))
+            .json(error("Invalid credentials.")),
+    )
+}
+
+fn error(message: &str) -> Value {
+    json!({ "error": { "code": "Base.1.0.GeneralError", "message": message } })
+}
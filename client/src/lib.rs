@@ -11,22 +11,17 @@
  * limitations under the License.
  */
 
-use std::fmt;
-use std::fmt::{Display, Formatter};
 use url::Url;
 
-use http_body_util::{BodyExt, Empty, Full};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Buf;
 use hyper::body::Bytes;
-use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use hyper::header::CONTENT_TYPE;
 use hyper::http::StatusCode;
-use hyper::{body::Buf, Request};
-use hyper::{Method, Response};
-use hyper_tls::HttpsConnector;
+use hyper::Method;
 use hyper_util::rt::TokioIo;
 use serde::de::DeserializeOwned;
-use serde::Deserialize;
 use serde::Serialize;
-use thiserror::Error;
 use tokio::net::TcpStream;
 
 use yangtze_apis::v1::{NamespaceName, YangtzeError};
@@ -74,9 +69,7 @@ impl YangtzeClient {
     }
 
     pub async fn get<T: DeserializeOwned>(&self, id: String) -> Result<T, YangtzeError> {
-        let body = self
-            .execute_request::<T>(Method::GET, Some(id), None)
-            .await?;
+        let body = self.execute_request(Method::GET, Some(id), None).await?;
         serde_json::from_reader(body.reader())
             .map_err(|e| YangtzeError::RestfulError(e.to_string()))
     }
@@ -87,7 +80,7 @@ impl YangtzeClient {
     ) -> Result<Vec<T>, YangtzeError> {
         let input = serde_json::to_string(&nn)?;
         let body = self
-            .execute_request::<T>(Method::POST, None, Some(input))
+            .execute_request(Method::POST, None, Some(input))
             .await?;
 
         serde_json::from_reader(body.reader())
@@ -96,18 +89,14 @@ impl YangtzeClient {
 
     pub async fn create<T: DeserializeOwned + Serialize>(&self, o: T) -> Result<T, YangtzeError> {
         let input = serde_json::to_string(&o)?;
-        let body = self
-            .execute_request::<T>(Method::PUT, None, Some(input))
-            .await?;
+        let body = self.execute_request(Method::PUT, None, Some(input)).await?;
 
         serde_json::from_reader(body.reader())
             .map_err(|e| YangtzeError::RestfulError(e.to_string()))
     }
 
     pub async fn delete<T: DeserializeOwned>(&self, id: String) -> Result<T, YangtzeError> {
-        let body = self
-            .execute_request::<T>(Method::DELETE, Some(id), None)
-            .await?;
+        let body = self.execute_request(Method::DELETE, Some(id), None).await?;
         serde_json::from_reader(body.reader())
             .map_err(|e| YangtzeError::RestfulError(e.to_string()))
     }
@@ -115,7 +104,7 @@ impl YangtzeClient {
     pub async fn update<T: DeserializeOwned + Serialize>(&self, o: T) -> Result<T, YangtzeError> {
         let input = serde_json::to_string(&o)?;
         let body = self
-            .execute_request::<T>(Method::PATCH, None, Some(input))
+            .execute_request(Method::PATCH, None, Some(input))
             .await?;
 
         serde_json::from_reader(body.reader())
@@ -136,7 +125,7 @@ impl YangtzeClient {
         url
     }
 
-    async fn execute_request<T: DeserializeOwned>(
+    async fn execute_request(
         &self,
         method: Method,
         path: Option<String>,
@@ -154,7 +143,7 @@ impl YangtzeClient {
             None => format!("{}://{}/{}", schema, self.address, self.base_url()),
         };
 
-        let body = data.unwrap_or(String::new());
+        let body = data.unwrap_or_default();
 
         let req = hyper::Request::builder()
             .method(method)
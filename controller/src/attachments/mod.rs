@@ -0,0 +1,98 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use async_trait::async_trait;
+
+use yangtze_apis::{
+    v1::{NamespaceName, VersionKind, YangtzeError},
+    v1alpha1::{
+        nvme_namespace::{self, NvmeNamespace},
+        nvme_subsystem::{self, NvmeState, NvmeSubsystem, NvmeSubsystemStatus},
+        volume_attachment::{self, VolumeAttachment},
+    },
+};
+use yangtze_client::YangtzeClient;
+
+use crate::framework::Controller;
+
+/// Allows the hosts of the VolumeAttachments of the namespaces of an
+/// NvmeSubsystem, and revokes them once detached, i.e. the attachment is
+/// deleted.
+#[derive(Clone)]
+pub struct AttachmentController {}
+
+#[async_trait]
+impl Controller<NvmeSubsystem> for AttachmentController {
+    async fn execute(&self, client: YangtzeClient, s: NvmeSubsystem) -> Result<(), YangtzeError> {
+        let nn = NamespaceName {
+            namespace: Some(s.meta_data.namespace.clone()),
+            name: None,
+        };
+
+        let namespaces: Vec<String> = client
+            .clone()
+            .version(nvme_namespace::VERSION_KIND.version)
+            .kind(nvme_namespace::VERSION_KIND.kind)
+            .list::<NvmeNamespace>(nn.clone())
+            .await?
+            .into_iter()
+            .filter(|n| n.spec.subsystem == s.meta_data.name)
+            .map(|n| n.meta_data.name)
+            .collect();
+
+        let mut hosts: Vec<String> = client
+            .clone()
+            .version(volume_attachment::VERSION_KIND.version)
+            .kind(volume_attachment::VERSION_KIND.kind)
+            .list::<VolumeAttachment>(nn)
+            .await?
+            .into_iter()
+            .filter(|a| namespaces.contains(&a.spec.namespace))
+            .filter_map(|a| a.status.and_then(|s| s.host_nqn))
+            .collect();
+        hosts.sort();
+        hosts.dedup();
+
+        let attached_hosts = s
+            .status
+            .as_ref()
+            .map(|s| s.attached_hosts.clone())
+            .unwrap_or_default();
+        if hosts == attached_hosts {
+            return Ok(());
+        }
+
+        for h in hosts.iter().filter(|h| !attached_hosts.contains(h)) {
+            tracing::info!("Allow host <{}> by NvmeSubsystem <{}>.", h, s);
+        }
+        for h in attached_hosts.iter().filter(|h| !hosts.contains(h)) {
+            tracing::info!("Revoke host <{}> from NvmeSubsystem <{}>.", h, s);
+        }
+
+        let mut s = s;
+        s.status
+            .get_or_insert(NvmeSubsystemStatus {
+                state: NvmeState::Pending,
+                reason: None,
+                attached_hosts: vec![],
+            })
+            .attached_hosts = hosts;
+        let _s = client.update::<NvmeSubsystem>(s).await?;
+
+        Ok(())
+    }
+
+    fn get_version_kind(&self) -> VersionKind {
+        nvme_subsystem::VERSION_KIND.clone()
+    }
+}
@@ -0,0 +1,381 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use md5::{Digest, Md5};
+use tokio::net::UdpSocket;
+
+use yangtze_apis::{
+    v1::YangtzeError,
+    v1alpha1::node::{PowerAction, PowerState},
+};
+
+use super::{Credentials, Driver};
+
+/// RMCP version 1.0, no ack, class IPMI.
+const RMCP_HEADER: [u8; 4] = [0x06, 0x00, 0xff, 0x07];
+
+const AUTH_NONE: u8 = 0x00;
+const AUTH_MD5: u8 = 0x02;
+const AUTH_PASSWORD: u8 = 0x04;
+
+const NETFN_CHASSIS: u8 = 0x00;
+const NETFN_APP: u8 = 0x06;
+
+const CMD_CHASSIS_STATUS: u8 = 0x01;
+const CMD_CHASSIS_CONTROL: u8 = 0x02;
+const CMD_AUTH_CAPABILITIES: u8 = 0x38;
+const CMD_SESSION_CHALLENGE: u8 = 0x39;
+const CMD_ACTIVATE_SESSION: u8 = 0x3a;
+const CMD_SET_PRIVILEGE: u8 = 0x3b;
+const CMD_CLOSE_SESSION: u8 = 0x3c;
+
+const CHASSIS_DOWN: u8 = 0x00;
+const CHASSIS_UP: u8 = 0x01;
+const CHASSIS_CYCLE: u8 = 0x02;
+
+const BMC_ADDRESS: u8 = 0x20;
+const CONSOLE_ADDRESS: u8 = 0x81;
+const PRIVILEGE_ADMIN: u8 = 0x04;
+const CURRENT_CHANNEL: u8 = 0x0e;
+
+const DEFAULT_PORT: u16 = 623;
+const TIMEOUT: Duration = Duration::from_secs(2);
+const RETRIES: usize = 3;
+
+/// Controls the chassis through IPMI 1.5 over LAN.
+pub struct Ipmi {
+    address: String,
+    username: [u8; 16],
+    password: [u8; 16],
+}
+
+impl Ipmi {
+    pub fn new(address: &str, credentials: &Credentials) -> Result<Self, YangtzeError> {
+        let address = match address.rsplit_once(':') {
+            Some((_, port)) if port.parse::<u16>().is_ok() => address.to_string(),
+            _ => format!("{}:{}", address, DEFAULT_PORT),
+        };
+
+        Ok(Ipmi {
+            address,
+            username: pad(&credentials.username)?,
+            password: pad(&credentials.password)?,
+        })
+    }
+
+    /// Runs a command in a new session with administrator privilege.
+    async fn command(&self, netfn: u8, cmd: u8, data: &[u8]) -> Result<Vec<u8>, YangtzeError> {
+        let mut session = self.open().await?;
+        let res = session.command(netfn, cmd, data).await;
+
+        let id = session.id.to_le_bytes();
+        if let Err(e) = session.command(NETFN_APP, CMD_CLOSE_SESSION, &id).await {
+            tracing::debug!("Failed to close the IPMI session: {}", e);
+        }
+
+        res
+    }
+
+    async fn open(&self) -> Result<Session, YangtzeError> {
+        let err = |e: std::io::Error| {
+            YangtzeError::GeneralError(format!("IPMI <{}>: {}", self.address, e))
+        };
+        let socket = UdpSocket::bind("0.0.0.0:0").await.map_err(err)?;
+        socket.connect(&self.address).await.map_err(err)?;
+
+        let mut session = Session {
+            address: self.address.clone(),
+            socket,
+            auth_type: AUTH_NONE,
+            id: 0,
+            seq: 0,
+            rq_seq: 0,
+            password: self.password,
+        };
+
+        let caps = session
+            .command(
+                NETFN_APP,
+                CMD_AUTH_CAPABILITIES,
+                &[CURRENT_CHANNEL, PRIVILEGE_ADMIN],
+            )
+            .await?;
+        let supported = *caps.get(1).unwrap_or(&0);
+        let anonymous = self.username == [0; 16] && self.password == [0; 16];
+        // The password is never sent in the clear, nor left out.
+        let auth_type = match anonymous {
+            true => [AUTH_MD5, AUTH_PASSWORD, AUTH_NONE]
+                .into_iter()
+                .find(|t| supported & (1 << t) != 0)
+                .ok_or(session.error("no supported authentication type"))?,
+            false if supported & (1 << AUTH_MD5) != 0 => AUTH_MD5,
+            false => {
+                return Err(
+                    session.error("MD5 authentication is not offered, refuse to send the password")
+                )
+            }
+        };
+
+        let mut data = vec![auth_type];
+        data.extend_from_slice(&self.username);
+        let challenge = session
+            .command(NETFN_APP, CMD_SESSION_CHALLENGE, &data)
+            .await?;
+        if challenge.len() < 20 {
+            return Err(session.error("short session challenge"));
+        }
+
+        session.auth_type = auth_type;
+        session.id = u32::from_le_bytes(challenge[0..4].try_into().unwrap_or_default());
+
+        let outbound = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or_default()
+            | 1;
+        let mut data = vec![auth_type, PRIVILEGE_ADMIN];
+        data.extend_from_slice(&challenge[4..20]);
+        data.extend_from_slice(&outbound.to_le_bytes());
+        let activated = session
+            .command(NETFN_APP, CMD_ACTIVATE_SESSION, &data)
+            .await?;
+        if activated.len() < 9 {
+            return Err(session.error("short session activation"));
+        }
+
+        session.id = u32::from_le_bytes(activated[1..5].try_into().unwrap_or_default());
+        session.seq = u32::from_le_bytes(activated[5..9].try_into().unwrap_or_default());
+
+        session
+            .command(NETFN_APP, CMD_SET_PRIVILEGE, &[PRIVILEGE_ADMIN])
+            .await?;
+
+        Ok(session)
+    }
+}
+
+#[async_trait]
+impl Driver for Ipmi {
+    async fn power_state(&self) -> Result<PowerState, YangtzeError> {
+        let status = self.command(NETFN_CHASSIS, CMD_CHASSIS_STATUS, &[]).await?;
+
+        Ok(match status.first() {
+            Some(s) if s & 0x01 != 0 => PowerState::On,
+            Some(_) => PowerState::Off,
+            None => PowerState::Unknown,
+        })
+    }
+
+    async fn set_power(&self, action: PowerAction) -> Result<(), YangtzeError> {
+        let control = match action {
+            PowerAction::On => CHASSIS_UP,
+            PowerAction::Off => CHASSIS_DOWN,
+            // A power cycle has no effect on a chassis which is off.
+            PowerAction::Cycle => match self.power_state().await? {
+                PowerState::Off => CHASSIS_UP,
+                _ => CHASSIS_CYCLE,
+            },
+        };
+
+        self.command(NETFN_CHASSIS, CMD_CHASSIS_CONTROL, &[control])
+            .await?;
+
+        Ok(())
+    }
+}
+
+struct Session {
+    address: String,
+    socket: UdpSocket,
+    auth_type: u8,
+    id: u32,
+    /// The session sequence number of the next request.
+    seq: u32,
+    /// The sequence number of the next request message.
+    rq_seq: u8,
+    password: [u8; 16],
+}
+
+impl Session {
+    /// Sends a request and returns the data of its response.
+    async fn command(&mut self, netfn: u8, cmd: u8, data: &[u8]) -> Result<Vec<u8>, YangtzeError> {
+        let rq_seq = self.rq_seq;
+        self.rq_seq = (self.rq_seq + 1) & 0x3f;
+
+        let mut msg = vec![BMC_ADDRESS, netfn << 2];
+        msg.push(checksum(&msg));
+        msg.extend_from_slice(&[CONSOLE_ADDRESS, rq_seq << 2, cmd]);
+        msg.extend_from_slice(data);
+        msg.push(checksum(&msg[3..]));
+
+        let mut buf = [0; 1024];
+        for _ in 0..RETRIES {
+            let packet = self.packet(&msg);
+            if self.id != 0 && self.seq != 0 {
+                self.seq = self.seq.wrapping_add(1);
+            }
+            self.socket
+                .send(&packet)
+                .await
+                .map_err(|e| self.error(&e.to_string()))?;
+
+            let n = match tokio::time::timeout(TIMEOUT, self.socket.recv(&mut buf)).await {
+                Ok(n) => n.map_err(|e| self.error(&e.to_string()))?,
+                Err(_) => continue,
+            };
+
+            let Some(res) = response(&buf[..n]) else {
+                continue;
+            };
+            if res.len() < 8 || res[5] != cmd || res[4] >> 2 != rq_seq {
+                continue;
+            }
+
+            return match res[6] {
+                0x00 => Ok(res[7..res.len() - 1].to_vec()),
+                code => {
+                    Err(self.error(&format!("command 0x{:02x} failed with 0x{:02x}", cmd, code)))
+                }
+            };
+        }
+
+        Err(self.error(&format!("command 0x{:02x} timed out", cmd)))
+    }
+
+    fn packet(&self, msg: &[u8]) -> Vec<u8> {
+        let mut packet = RMCP_HEADER.to_vec();
+        packet.push(self.auth_type);
+        packet.extend_from_slice(&self.seq.to_le_bytes());
+        packet.extend_from_slice(&self.id.to_le_bytes());
+        match self.auth_type {
+            AUTH_PASSWORD => packet.extend_from_slice(&self.password),
+            AUTH_MD5 => {
+                let mut md5 = Md5::new();
+                md5.update(self.password);
+                md5.update(self.id.to_le_bytes());
+                md5.update(msg);
+                md5.update(self.seq.to_le_bytes());
+                md5.update(self.password);
+                packet.extend_from_slice(&md5.finalize());
+            }
+            _ => {}
+        }
+        packet.push(msg.len() as u8);
+        packet.extend_from_slice(msg);
+
+        packet
+    }
+
+    fn error(&self, reason: &str) -> YangtzeError {
+        YangtzeError::GeneralError(format!("IPMI <{}>: {}", self.address, reason))
+    }
+}
+
+/// The IPMI message of a response packet.
+fn response(packet: &[u8]) -> Option<&[u8]> {
+    if packet.len() < 14 || packet[..4] != RMCP_HEADER {
+        return None;
+    }
+    let start = match packet[4] {
+        AUTH_NONE => 13,
+        _ => 29,
+    };
+    let len = *packet.get(start)? as usize;
+
+    packet.get(start + 1..start + 1 + len)
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    0u8.wrapping_sub(bytes.iter().fold(0u8, |a, b| a.wrapping_add(*b)))
+}
+
+fn pad(s: &str) -> Result<[u8; 16], YangtzeError> {
+    if s.len() > 16 {
+        return Err(YangtzeError::InvalidConfig(
+            "IPMI credentials are up to 16 bytes".to_string(),
+        ));
+    }
+    let mut padded = [0; 16];
+    padded[..s.len()].copy_from_slice(s.as_bytes());
+
+    Ok(padded)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use yangtze_bmcsim::{chassis::Chassis, ipmi::Server};
+
+    use super::*;
+
+    /// Serves a bmcsim BMC on a free port, and connects the driver to it.
+    async fn serve(md5: bool, password: &str) -> (Ipmi, Arc<Chassis>) {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = socket.local_addr().unwrap();
+        let chassis = Arc::new(Chassis::new(false));
+        let server = Server {
+            listen: address,
+            username: "admin".to_string(),
+            password: "password".to_string(),
+            md5,
+            chassis: chassis.clone(),
+        };
+        tokio::spawn(server.serve(socket));
+
+        let credentials = Credentials {
+            username: "admin".to_string(),
+            password: password.to_string(),
+        };
+        (
+            Ipmi::new(&address.to_string(), &credentials).unwrap(),
+            chassis,
+        )
+    }
+
+    #[tokio::test]
+    async fn control_power() {
+        let (ipmi, chassis) = serve(true, "password").await;
+        assert_eq!(ipmi.power_state().await.unwrap(), PowerState::Off);
+
+        ipmi.set_power(PowerAction::On).await.unwrap();
+        assert!(chassis.is_on());
+        assert_eq!(ipmi.power_state().await.unwrap(), PowerState::On);
+
+        ipmi.set_power(PowerAction::Cycle).await.unwrap();
+        assert_eq!(ipmi.power_state().await.unwrap(), PowerState::On);
+
+        ipmi.set_power(PowerAction::Off).await.unwrap();
+        assert_eq!(ipmi.power_state().await.unwrap(), PowerState::Off);
+
+        // Cycling a Node which is off powers it on.
+        ipmi.set_power(PowerAction::Cycle).await.unwrap();
+        assert_eq!(ipmi.power_state().await.unwrap(), PowerState::On);
+    }
+
+    #[tokio::test]
+    async fn reject_wrong_password() {
+        let (ipmi, _) = serve(true, "wrong").await;
+        assert!(ipmi.power_state().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn refuse_downgrade() {
+        let (ipmi, _) = serve(false, "password").await;
+        let e = ipmi.power_state().await.unwrap_err();
+        assert!(e.to_string().contains("MD5"), "{}", e);
+    }
+}
@@ -0,0 +1,123 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs;
+use std::path::Path;
+
+use async_trait::async_trait;
+
+use yangtze_apis::{
+    v1::YangtzeError,
+    v1alpha1::node::{BmcDriver, BmcSpec, PowerAction, PowerState},
+};
+
+mod ipmi;
+mod redfish;
+
+/// The out-of-band power control of a Node through its BMC.
+#[async_trait]
+pub trait Driver: Send + Sync {
+    async fn power_state(&self) -> Result<PowerState, YangtzeError>;
+
+    /// Applies the action; cycling a Node which is off powers it on.
+    async fn set_power(&self, action: PowerAction) -> Result<(), YangtzeError>;
+}
+
+/// The username and password of a BMC.
+#[derive(Clone, Debug, Default)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl Credentials {
+    /// Reads the credentials of a name from `<directory>/<name>`, whose
+    /// first line is `username:password`.
+    pub fn read(directory: &Path, name: &str) -> Result<Self, YangtzeError> {
+        if name.is_empty() {
+            return Ok(Credentials::default());
+        }
+        if name.contains('/') || name.starts_with('.') {
+            return Err(YangtzeError::InvalidConfig(format!(
+                "invalid BMC credentials <{}>",
+                name
+            )));
+        }
+
+        let path = directory.join(name);
+        let content = fs::read_to_string(&path).map_err(|e| {
+            YangtzeError::InvalidConfig(format!(
+                "BMC credentials <{}> at <{}>: {}",
+                name,
+                path.display(),
+                e
+            ))
+        })?;
+        let line = content.lines().next().unwrap_or_default();
+        let (username, password) =
+            line.split_once(':')
+                .ok_or(YangtzeError::InvalidConfig(format!(
+                    "BMC credentials <{}> are not username:password",
+                    name
+                )))?;
+
+        Ok(Credentials {
+            username: username.to_string(),
+            password: password.to_string(),
+        })
+    }
+}
+
+pub fn new(
+    address: &str,
+    bmc: &BmcSpec,
+    credentials: &Credentials,
+) -> Result<Box<dyn Driver>, YangtzeError> {
+    match bmc.driver {
+        BmcDriver::Redfish => Ok(Box::new(redfish::Redfish::new(address, bmc, credentials)?)),
+        BmcDriver::Ipmi => Ok(Box::new(ipmi::Ipmi::new(address, credentials)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_credentials() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("rack1"), "admin:pa:ss\n").unwrap();
+
+        let c = Credentials::read(dir.path(), "rack1").unwrap();
+        assert_eq!(
+            (c.username.as_str(), c.password.as_str()),
+            ("admin", "pa:ss")
+        );
+    }
+
+    #[test]
+    fn anonymous_credentials() {
+        let c = Credentials::read(Path::new("/nonexistent"), "").unwrap();
+        assert!(c.username.is_empty() && c.password.is_empty());
+    }
+
+    #[test]
+    fn invalid_credentials() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("rack1"), "admin\n").unwrap();
+
+        for name in ["rack1", "rack2", "../rack1", ".hidden", "a/b"] {
+            assert!(Credentials::read(dir.path(), name).is_err(), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,315 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use base64::Engine;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::{Method, Request, Uri};
+use hyper_util::rt::TokioIo;
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+use serde_json::{json, Value};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+
+use yangtze_apis::{
+    v1::YangtzeError,
+    v1alpha1::node::{BmcSpec, PowerAction, PowerState},
+};
+
+use super::{Credentials, Driver};
+
+/// Controls the first ComputerSystem of a Redfish service.
+pub struct Redfish {
+    uri: Uri,
+    authorization: String,
+    tls: Option<TlsConnector>,
+}
+
+impl Redfish {
+    pub fn new(
+        address: &str,
+        bmc: &BmcSpec,
+        credentials: &Credentials,
+    ) -> Result<Self, YangtzeError> {
+        let address = match address.contains("://") {
+            true => address.to_string(),
+            false => format!("https://{}", address),
+        };
+        let uri: Uri = address
+            .parse()
+            .map_err(|e| YangtzeError::InvalidConfig(format!("<{}>: {}", address, e)))?;
+
+        let tls = match uri.scheme_str() {
+            Some("http") => None,
+            Some("https") => Some(connector(bmc.insecure)),
+            _ => {
+                return Err(YangtzeError::InvalidConfig(format!(
+                    "<{}>: unsupported scheme",
+                    address
+                )))
+            }
+        };
+
+        let credentials = format!("{}:{}", credentials.username, credentials.password);
+        let authorization = format!(
+            "Basic {}",
+            base64::engine::general_purpose::STANDARD.encode(credentials)
+        );
+
+        Ok(Redfish {
+            uri,
+            authorization,
+            tls,
+        })
+    }
+
+    /// The first system of the service.
+    async fn system(&self) -> Result<Value, YangtzeError> {
+        let systems = self
+            .request(Method::GET, "/redfish/v1/Systems", None)
+            .await?;
+        let path =
+            systems["Members"][0]["@odata.id"]
+                .as_str()
+                .ok_or(YangtzeError::GeneralError(format!(
+                    "No system in the Redfish service <{}>",
+                    self.uri
+                )))?;
+
+        self.request(Method::GET, path, None).await
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<Value, YangtzeError> {
+        let err = |e: &dyn std::fmt::Display| {
+            YangtzeError::GeneralError(format!("<{}{}>: {}", self.uri, path, e))
+        };
+
+        let host = self.uri.host().unwrap_or_default();
+        let port = self
+            .uri
+            .port_u16()
+            .unwrap_or(if self.tls.is_some() { 443 } else { 80 });
+        let stream = TcpStream::connect((host, port))
+            .await
+            .map_err(|e| err(&e))?;
+
+        let body = match body {
+            Some(body) => Full::new(Bytes::from(body.to_string())),
+            None => Full::new(Bytes::new()),
+        };
+        let req = Request::builder()
+            .method(method)
+            .uri(path)
+            .header(hyper::header::HOST, host)
+            .header(hyper::header::AUTHORIZATION, &self.authorization)
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .header(hyper::header::ACCEPT, "application/json")
+            .body(body)
+            .map_err(|e| err(&e))?;
+
+        let (status, bytes) = match &self.tls {
+            None => send(stream, req).await,
+            Some(tls) => {
+                let name = ServerName::try_from(host).map_err(|e| err(&e))?;
+                let stream = tls.connect(name, stream).await.map_err(|e| err(&e))?;
+                send(stream, req).await
+            }
+        }
+        .map_err(|e| err(&e))?;
+
+        if !status.is_success() {
+            return Err(err(&status));
+        }
+        if bytes.is_empty() {
+            return Ok(Value::Null);
+        }
+
+        serde_json::from_slice(&bytes).map_err(|e| err(&e))
+    }
+}
+
+#[async_trait]
+impl Driver for Redfish {
+    async fn power_state(&self) -> Result<PowerState, YangtzeError> {
+        let system = self.system().await?;
+
+        Ok(match system["PowerState"].as_str() {
+            Some("On") | Some("PoweringOn") => PowerState::On,
+            Some("Off") | Some("PoweringOff") => PowerState::Off,
+            _ => PowerState::Unknown,
+        })
+    }
+
+    async fn set_power(&self, action: PowerAction) -> Result<(), YangtzeError> {
+        let system = self.system().await?;
+        let reset = &system["Actions"]["#ComputerSystem.Reset"];
+        let allowed = |t: &str| match reset["ResetType@Redfish.AllowableValues"].as_array() {
+            Some(values) => values.iter().any(|v| v == t),
+            None => true,
+        };
+
+        let reset_type = match action {
+            PowerAction::On => "On",
+            PowerAction::Off => "ForceOff",
+            PowerAction::Cycle if system["PowerState"] == "Off" => "On",
+            PowerAction::Cycle if allowed("PowerCycle") => "PowerCycle",
+            PowerAction::Cycle => "ForceRestart",
+        };
+
+        let target = match reset["target"].as_str() {
+            Some(target) => target.to_string(),
+            None => format!(
+                "{}/Actions/ComputerSystem.Reset",
+                system["@odata.id"].as_str().unwrap_or_default()
+            ),
+        };
+
+        self.request(
+            Method::POST,
+            &target,
+            Some(json!({ "ResetType": reset_type })),
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+async fn send<S>(
+    stream: S,
+    req: Request<Full<Bytes>>,
+) -> Result<(hyper::StatusCode, Bytes), hyper::Error>
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let (mut sender, conn) = hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+    tokio::spawn(async move {
+        if let Err(e) = conn.await {
+            tracing::debug!("The connection to the BMC was closed: {}", e);
+        }
+    });
+
+    let res = sender.send_request(req).await?;
+    let status = res.status();
+    let body = res.into_body().collect().await?.to_bytes();
+
+    Ok((status, body))
+}
+
+fn connector(insecure: bool) -> TlsConnector {
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+
+    let mut config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    if insecure {
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(NoVerifier));
+    }
+
+    TlsConnector::from(Arc::new(config))
+}
+
+/// Accepts any certificate, as most BMCs use self-signed ones.
+struct NoVerifier;
+
+impl ServerCertVerifier for NoVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use yangtze_bmcsim::{chassis::Chassis, redfish::Server};
+
+    use super::*;
+
+    /// Serves a bmcsim BMC on a free port, and connects the driver to it.
+    async fn serve(password: &str) -> (Redfish, Arc<Chassis>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let chassis = Arc::new(Chassis::new(false));
+        let server = Server {
+            listen: address,
+            username: "admin".to_string(),
+            password: "password".to_string(),
+            chassis: chassis.clone(),
+        };
+        tokio::spawn(server.serve(listener).unwrap());
+
+        let bmc = serde_json::from_value(json!({"driver": "redfish"})).unwrap();
+        let credentials = Credentials {
+            username: "admin".to_string(),
+            password: password.to_string(),
+        };
+        (
+            Redfish::new(&format!("http://{}", address), &bmc, &credentials).unwrap(),
+            chassis,
+        )
+    }
+
+    #[tokio::test]
+    async fn control_power() {
+        let (redfish, chassis) = serve("password").await;
+        assert_eq!(redfish.power_state().await.unwrap(), PowerState::Off);
+
+        redfish.set_power(PowerAction::On).await.unwrap();
+        assert!(chassis.is_on());
+        assert_eq!(redfish.power_state().await.unwrap(), PowerState::On);
+
+        redfish.set_power(PowerAction::Cycle).await.unwrap();
+        assert_eq!(redfish.power_state().await.unwrap(), PowerState::On);
+
+        redfish.set_power(PowerAction::Off).await.unwrap();
+        assert_eq!(redfish.power_state().await.unwrap(), PowerState::Off);
+
+        // Cycling a Node which is off powers it on.
+        redfish.set_power(PowerAction::Cycle).await.unwrap();
+        assert_eq!(redfish.power_state().await.unwrap(), PowerState::On);
+    }
+
+    #[tokio::test]
+    async fn reject_wrong_password() {
+        let (redfish, _) = serve("wrong").await;
+        assert!(redfish.power_state().await.is_err());
+    }
+}
@@ -0,0 +1,424 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+use std::net::Ipv4Addr;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use ipnet::Ipv4Net;
+
+use yangtze_apis::v1alpha1::{
+    fabric::{Fabric, FabricAllocations, FabricPools, FabricUsage, PoolUsage},
+    switch::Switch,
+    topology::TopologyLink,
+    vpc::{Vpc, MAX_VNI},
+};
+
+/// The pools of a Fabric, parsed from its spec.
+pub struct Pools {
+    asn: Option<RangeInclusive<u32>>,
+    loopback: Option<Ipv4Net>,
+    p2p: Option<Ipv4Net>,
+    vni: Option<RangeInclusive<u32>>,
+}
+
+/// The objects of a Fabric which are allocated to.
+pub struct Owners<'a> {
+    /// The switches of the Fabric.
+    pub switches: &'a [Switch],
+    /// The links between the switches of the Fabric.
+    pub links: &'a [TopologyLink],
+    /// All the Vpcs of the namespace, as their VNIs are unique.
+    pub vpcs: &'a [Vpc],
+    /// All the Fabrics of the namespace, as their ledgers hold the VNIs
+    /// not written to their Vpcs yet.
+    pub fabrics: &'a [Fabric],
+    pub fabric: &'a str,
+}
+
+/// What a Fabric allocated, and how much of its pools is in use.
+pub struct Allocated {
+    pub allocations: FabricAllocations,
+    pub usage: FabricUsage,
+    pub errors: Vec<String>,
+}
+
+fn parse_range(name: &str, s: &str, max: u32) -> Result<RangeInclusive<u32>, String> {
+    let invalid = || format!("invalid {} pool <{}>, expect <first>-<last>", name, s);
+    let (first, last) = s.split_once('-').ok_or_else(invalid)?;
+    let first: u32 = first.trim().parse().map_err(|_| invalid())?;
+    let last: u32 = last.trim().parse().map_err(|_| invalid())?;
+    if first == 0 || first > last || last > max {
+        return Err(invalid());
+    }
+
+    Ok(first..=last)
+}
+
+fn parse_cidr(name: &str, s: &str, max_prefix: u8) -> Result<Ipv4Net, String> {
+    let net: Ipv4Net = s
+        .parse()
+        .map_err(|e| format!("invalid {} pool <{}>: {}", name, s, e))?;
+    if net.prefix_len() > max_prefix {
+        return Err(format!(
+            "invalid {} pool <{}>, expect a prefix of at most {}",
+            name, s, max_prefix
+        ));
+    }
+
+    Ok(net.trunc())
+}
+
+fn pool_usage<V>(total: u64, used: &BTreeSet<V>, contains: impl Fn(&V) -> bool) -> PoolUsage {
+    let in_use = used.iter().filter(|v| contains(v)).count() as u64;
+    PoolUsage {
+        total,
+        available: total.saturating_sub(in_use),
+    }
+}
+
+fn range_size(r: &RangeInclusive<u32>) -> u64 {
+    (*r.end() - *r.start()) as u64 + 1
+}
+
+/// The number of the addresses of `hosts()`.
+fn hosts_size(net: &Ipv4Net) -> u64 {
+    match net.prefix_len() {
+        31 | 32 => 1 << (32 - net.prefix_len()),
+        p => (1 << (32 - p)) - 2,
+    }
+}
+
+/// Parses the values of a ledger, dropping the invalid ones.
+fn parse_ledger<V: FromStr>(ledger: &BTreeMap<String, String>) -> BTreeMap<String, V> {
+    ledger
+        .iter()
+        .filter_map(|(k, v)| v.parse().ok().map(|v| (k.clone(), v)))
+        .collect()
+}
+
+fn format_ledger<V: Display>(ledger: BTreeMap<String, V>) -> BTreeMap<String, String> {
+    ledger
+        .into_iter()
+        .map(|(k, v)| (k, v.to_string()))
+        .collect()
+}
+
+/// Keeps the previous allocation of each owner unless its value is set
+/// explicitly to another one, and allocates the lowest free value of the
+/// pool to the others. The allocations of the owners gone are reclaimed.
+/// Returns the allocations and all the values in use.
+fn assign<V: Ord + Clone>(
+    what: &str,
+    owners: &[(String, Option<V>)],
+    previous: &BTreeMap<String, V>,
+    mut used: BTreeSet<V>,
+    pool: Option<impl Iterator<Item = V>>,
+    errors: &mut Vec<String>,
+) -> (BTreeMap<String, V>, BTreeSet<V>) {
+    let mut allocations = BTreeMap::new();
+    let mut pending = vec![];
+    for (owner, explicit) in owners {
+        match (explicit, previous.get(owner)) {
+            (Some(v), Some(p)) if v == p => {
+                allocations.insert(owner.clone(), v.clone());
+            }
+            (Some(v), _) => {
+                used.insert(v.clone());
+            }
+            (None, Some(p)) => {
+                allocations.insert(owner.clone(), p.clone());
+            }
+            (None, None) => pending.push(owner),
+        }
+    }
+    used.extend(allocations.values().cloned());
+
+    let Some(mut pool) = pool else {
+        return (allocations, used);
+    };
+    for owner in pending {
+        // The values skipped are in use, so the pool is walked only once.
+        match pool.by_ref().find(|v| !used.contains(v)) {
+            Some(v) => {
+                used.insert(v.clone());
+                allocations.insert(owner.clone(), v);
+            }
+            None => {
+                errors.push(format!("the {} pool is exhausted", what));
+                break;
+            }
+        }
+    }
+
+    (allocations, used)
+}
+
+impl Pools {
+    pub fn parse(spec: &FabricPools) -> Result<Self, String> {
+        Ok(Pools {
+            asn: spec
+                .asn
+                .as_deref()
+                .map(|s| parse_range("ASN", s, u32::MAX))
+                .transpose()?,
+            loopback: spec
+                .loopback
+                .as_deref()
+                .map(|s| parse_cidr("loopback", s, 32))
+                .transpose()?,
+            p2p: spec
+                .p2p
+                .as_deref()
+                .map(|s| parse_cidr("p2p", s, 31))
+                .transpose()?,
+            vni: spec
+                .vni
+                .as_deref()
+                .map(|s| parse_range("VNI", s, MAX_VNI))
+                .transpose()?,
+        })
+    }
+
+    /// Allocates from the pools to the owners, given the previous
+    /// allocations of the Fabric.
+    pub fn allocate(&self, owners: &Owners, previous: &FabricAllocations) -> Allocated {
+        let mut errors = vec![];
+        let mut usage = FabricUsage::default();
+
+        let switches: Vec<_> = owners
+            .switches
+            .iter()
+            .map(|sw| (sw.meta_data.name.clone(), sw.spec.asn))
+            .collect();
+        let (asns, used) = assign(
+            "ASN",
+            &switches,
+            &previous.asns,
+            BTreeSet::new(),
+            self.asn.clone(),
+            &mut errors,
+        );
+        usage.asn = self
+            .asn
+            .as_ref()
+            .map(|r| pool_usage(range_size(r), &used, |v| r.contains(v)));
+
+        let switches: Vec<_> = owners
+            .switches
+            .iter()
+            .map(|sw| {
+                let loopback = sw.spec.loopback.as_deref().and_then(|l| l.parse().ok());
+                (sw.meta_data.name.clone(), loopback)
+            })
+            .collect();
+        let (loopbacks, used) = assign::<Ipv4Addr>(
+            "loopback",
+            &switches,
+            &parse_ledger(&previous.loopbacks),
+            BTreeSet::new(),
+            self.loopback.map(|n| n.hosts()),
+            &mut errors,
+        );
+        usage.loopback = self
+            .loopback
+            .as_ref()
+            .map(|n| pool_usage(hosts_size(n), &used, |v| n.contains(v)));
+
+        let links: Vec<_> = owners
+            .links
+            .iter()
+            .map(|l| (format!("{}:{}", l.from, l.from_port), None))
+            .collect();
+        let (links, used) = assign::<Ipv4Net>(
+            "p2p",
+            &links,
+            &parse_ledger(&previous.links),
+            BTreeSet::new(),
+            self.p2p.map(|n| n.subnets(31).into_iter().flatten()),
+            &mut errors,
+        );
+        usage.p2p = self
+            .p2p
+            .as_ref()
+            .map(|n| pool_usage(1 << (31 - n.prefix_len()), &used, |v| n.contains(v)));
+
+        // The VNIs of the Vpcs of other Fabrics are in use as well, and so
+        // are the ones in their ledgers not written to their Vpcs yet.
+        let (vpcs, others): (Vec<&Vpc>, Vec<&Vpc>) = owners
+            .vpcs
+            .iter()
+            .partition(|v| v.spec.fabric.as_deref() == Some(owners.fabric));
+        let vpcs: Vec<_> = vpcs
+            .iter()
+            .map(|v| {
+                let vni = Some(v.spec.vni).filter(|vni| *vni > 0);
+                (v.meta_data.name.clone(), vni)
+            })
+            .collect();
+        let (vnis, used) = assign(
+            "VNI",
+            &vpcs,
+            &previous.vnis,
+            others
+                .iter()
+                .map(|v| v.spec.vni)
+                .filter(|vni| *vni > 0)
+                .chain(
+                    owners
+                        .fabrics
+                        .iter()
+                        .filter(|f| f.meta_data.name != owners.fabric)
+                        .filter_map(|f| f.status.as_ref())
+                        .flat_map(|s| s.allocations.vnis.values().copied()),
+                )
+                .collect(),
+            self.vni.clone(),
+            &mut errors,
+        );
+        usage.vni = self
+            .vni
+            .as_ref()
+            .map(|r| pool_usage(range_size(r), &used, |v| r.contains(v)));
+
+        Allocated {
+            allocations: FabricAllocations {
+                asns,
+                loopbacks: format_ledger(loopbacks),
+                links: format_ledger(links),
+                vnis,
+            },
+            usage,
+            errors,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn pools(vni: &str) -> Pools {
+        Pools::parse(&FabricPools {
+            asn: Some("65000-65009".to_string()),
+            loopback: Some("10.255.0.0/29".to_string()),
+            p2p: None,
+            vni: Some(vni.to_string()),
+        })
+        .unwrap()
+    }
+
+    fn switch(name: &str) -> Switch {
+        serde_json::from_value(json!({
+            "meta_data": {"kind": "switch", "namespace": "default", "name": name, "labels": [], "version": 0},
+            "spec": {"chassis_id": name, "fabric": "f1"},
+        }))
+        .unwrap()
+    }
+
+    fn vpc(name: &str, fabric: &str, vni: u32) -> Vpc {
+        serde_json::from_value(json!({
+            "meta_data": {"kind": "vpc", "namespace": "default", "name": name, "labels": [], "version": 0},
+            "spec": {"fabric": fabric, "vni": vni},
+        }))
+        .unwrap()
+    }
+
+    fn fabric(name: &str, vnis: &[(&str, u32)]) -> Fabric {
+        let vnis: BTreeMap<_, _> = vnis.iter().map(|(v, n)| (v.to_string(), *n)).collect();
+        serde_json::from_value(json!({
+            "meta_data": {"kind": "fabric", "namespace": "default", "name": name, "labels": [], "version": 0},
+            "spec": {"selector": ""},
+            "status": {"state": "ready", "allocations": {"vnis": vnis}},
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn skip_vnis_of_other_fabrics() {
+        let vpcs = [
+            vpc("a", "f1", 0),
+            vpc("b", "f2", 100),
+            vpc("c", "f2", 0),
+            vpc("d", "f1", 0),
+        ];
+        // f2 allocated 101 to c, which is not written to c yet.
+        let fabrics = [fabric("f1", &[]), fabric("f2", &[("b", 100), ("c", 101)])];
+        let owners = Owners {
+            switches: &[],
+            links: &[],
+            vpcs: &vpcs,
+            fabrics: &fabrics,
+            fabric: "f1",
+        };
+        let allocated = pools("100-103").allocate(&owners, &FabricAllocations::default());
+
+        assert!(allocated.errors.is_empty());
+        assert_eq!(allocated.allocations.vnis.get("a"), Some(&102));
+        assert_eq!(allocated.allocations.vnis.get("d"), Some(&103));
+
+        let allocated = pools("100-102").allocate(&owners, &FabricAllocations::default());
+        assert_eq!(allocated.errors, vec!["the VNI pool is exhausted"]);
+    }
+
+    #[test]
+    fn report_usage_per_pool() {
+        let switches = [switch("leaf1"), switch("leaf2")];
+        let vpcs = [vpc("a", "f1", 0)];
+        let fabrics = [fabric("f2", &[("b", 100)])];
+        let owners = Owners {
+            switches: &switches,
+            links: &[],
+            vpcs: &vpcs,
+            fabrics: &fabrics,
+            fabric: "f1",
+        };
+        let allocated = pools("100-199").allocate(&owners, &FabricAllocations::default());
+
+        assert!(allocated.errors.is_empty());
+        let usage = allocated.usage;
+        assert_eq!(
+            usage.asn,
+            Some(PoolUsage {
+                total: 10,
+                available: 8
+            })
+        );
+        assert_eq!(
+            usage.loopback,
+            Some(PoolUsage {
+                total: 6,
+                available: 4
+            })
+        );
+        assert_eq!(usage.p2p, None);
+        assert_eq!(
+            usage.vni,
+            Some(PoolUsage {
+                total: 100,
+                available: 98
+            })
+        );
+        // The sum reported as the total and available of the Fabric.
+        assert_eq!(
+            usage.sum(),
+            PoolUsage {
+                total: 116,
+                available: 110
+            }
+        );
+    }
+}
@@ -14,13 +14,25 @@
 use async_trait::async_trait;
 
 use yangtze_apis::{
-    v1::{VersionKind, YangtzeError},
-    v1alpha1::fabric::{self, Fabric, FabricState, FabricStatus},
+    v1::{NamespaceName, VersionKind, YangtzeError},
+    v1alpha1::{
+        fabric::{self, Fabric, FabricAllocations, FabricState, FabricStatus, FabricUsage},
+        switch::{self, Switch},
+        vpc::{self, Vpc},
+    },
 };
 use yangtze_client::YangtzeClient;
 
 use crate::framework::Controller;
 
+mod allocator;
+mod topology;
+
+/// Builds the topology of a Fabric, and allocates from its pools to its
+/// switches and Vpcs. The allocations are recorded in the status of the
+/// Fabric before they are set to the objects, so that a reconcile racing
+/// with another one fails on the version of the Fabric instead of
+/// allocating a value twice.
 #[derive(Clone)]
 pub struct FabricController {}
 
@@ -37,19 +49,156 @@ impl Controller<Fabric> for FabricController {
             .get::<Fabric>(f.meta_data.uuid.unwrap().to_string())
             .await?;
 
-        f.status = match f.status {
-            Some(status) => Some(FabricStatus {
-                state: FabricState::Ready,
-                ..status
-            }),
-            None => Some(FabricStatus {
+        let Some(status) = &f.status else {
+            f.status = Some(FabricStatus {
                 state: FabricState::Initializing,
                 total: 0,
                 available: 0,
-            }),
+                usage: FabricUsage::default(),
+                reason: None,
+                topology: None,
+                allocations: FabricAllocations::default(),
+            });
+            let _f = client.update::<Fabric>(f).await?;
+            return Ok(());
+        };
+        if matches!(status.state, FabricState::Deleting | FabricState::Deleted) {
+            return Ok(());
+        }
+
+        let nn = NamespaceName {
+            namespace: Some(f.meta_data.namespace.clone()),
+            name: None,
+        };
+        let switches: Vec<Switch> = client
+            .clone()
+            .version(switch::VERSION_KIND.version)
+            .kind(switch::VERSION_KIND.kind)
+            .list(nn.clone())
+            .await?
+            .into_iter()
+            .filter(|sw: &Switch| sw.spec.fabric.as_deref() == Some(&f.meta_data.name))
+            .collect();
+        let vpcs: Vec<Vpc> = client
+            .clone()
+            .version(vpc::VERSION_KIND.version)
+            .kind(vpc::VERSION_KIND.kind)
+            .list(nn.clone())
+            .await?;
+        let fabrics: Vec<Fabric> = client
+            .clone()
+            .version(fabric::VERSION_KIND.version)
+            .kind(fabric::VERSION_KIND.kind)
+            .list(nn)
+            .await?;
+
+        let topology = topology::build(&f.spec.design, &switches);
+        let mut errors = topology.errors.clone();
+
+        let allocated = allocator::Pools::parse(&f.spec.pools).map(|pools| {
+            let links: Vec<_> = topology
+                .links
+                .iter()
+                .filter(|l| switches.iter().any(|sw| sw.meta_data.name == l.from))
+                .cloned()
+                .collect();
+            let owners = allocator::Owners {
+                switches: &switches,
+                links: &links,
+                vpcs: &vpcs,
+                fabrics: &fabrics,
+                fabric: &f.meta_data.name,
+            };
+            pools.allocate(&owners, &status.allocations)
+        });
+        let (allocations, usage) = match allocated {
+            Ok(allocated) => {
+                errors.extend(allocated.errors);
+                (allocated.allocations, allocated.usage)
+            }
+            // The allocations are kept until the pools are fixed.
+            Err(e) => {
+                errors.push(e);
+                (status.allocations.clone(), status.usage.clone())
+            }
+        };
+
+        let (state, reason) = match errors.is_empty() {
+            true => (FabricState::Ready, None),
+            false => (FabricState::Error, Some(errors.join("; "))),
+        };
+        let sum = usage.sum();
+        let status = FabricStatus {
+            state,
+            total: sum.total,
+            available: sum.available,
+            usage,
+            reason,
+            topology: Some(topology),
+            allocations,
+        };
+
+        if f.status.as_ref() != Some(&status) {
+            if status.state == FabricState::Error {
+                tracing::info!(
+                    "Fabric <{}> has errors: {}",
+                    f,
+                    status.reason.clone().unwrap_or_default()
+                );
+            }
+
+            f.status = Some(status);
+            f = client.update::<Fabric>(f).await?;
+        }
+
+        let Some(allocations) = f.status.as_ref().map(|s| &s.allocations) else {
+            return Ok(());
         };
+        for mut sw in switches {
+            let name = &sw.meta_data.name;
+            let asn = sw.spec.asn.or(allocations.asns.get(name).copied());
+            let loopback = sw
+                .spec
+                .loopback
+                .clone()
+                .or(allocations.loopbacks.get(name).cloned());
+            if sw.spec.asn == asn && sw.spec.loopback == loopback {
+                continue;
+            }
 
-        let _f = client.update::<Fabric>(f).await?;
+            tracing::info!(
+                "Allocate ASN <{}> and loopback <{}> of Fabric <{}> to Switch <{}>.",
+                asn.map(|a| a.to_string()).unwrap_or_default(),
+                loopback.clone().unwrap_or_default(),
+                f,
+                sw
+            );
+            sw.spec.asn = asn;
+            sw.spec.loopback = loopback;
+            let _sw = client
+                .clone()
+                .version(switch::VERSION_KIND.version)
+                .kind(switch::VERSION_KIND.kind)
+                .update::<Switch>(sw)
+                .await?;
+        }
+        for mut v in vpcs {
+            if v.spec.vni != 0 || v.spec.fabric.as_deref() != Some(&f.meta_data.name) {
+                continue;
+            }
+            let Some(vni) = allocations.vnis.get(&v.meta_data.name) else {
+                continue;
+            };
+
+            tracing::info!("Allocate VNI <{}> of Fabric <{}> to Vpc <{}>.", vni, f, v);
+            v.spec.vni = *vni;
+            let _v = client
+                .clone()
+                .version(vpc::VERSION_KIND.version)
+                .kind(vpc::VERSION_KIND.kind)
+                .update::<Vpc>(v)
+                .await?;
+        }
 
         Ok(())
     }
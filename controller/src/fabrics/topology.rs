@@ -0,0 +1,460 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use yangtze_apis::v1alpha1::{
+    fabric::FabricDesign,
+    switch::{Switch, SwitchRole},
+    topology::{Tier, Topology, TopologyLink, TopologyNode},
+};
+
+/// Builds the topology of a Fabric from the LLDP neighbors of its switches
+/// and the links to the hosts, and checks it against the design.
+pub fn build(design: &FabricDesign, switches: &[Switch]) -> Topology {
+    let mut errors = vec![];
+
+    let chassis: BTreeMap<&str, &str> = switches
+        .iter()
+        .map(|sw| (sw.spec.chassis_id.as_str(), sw.meta_data.name.as_str()))
+        .collect();
+
+    // The peer of each switch port, as seen by the LLDP of the switch.
+    let mut peers: BTreeMap<(String, String), (String, String)> = BTreeMap::new();
+    let mut host_links = vec![];
+    for sw in switches {
+        let name = &sw.meta_data.name;
+        let Some(status) = &sw.status else {
+            continue;
+        };
+
+        for nb in &status.neighbors {
+            let port = (name.clone(), nb.interface.clone());
+            let Some(peer) = chassis.get(nb.chassis_id.as_str()) else {
+                let remote = match nb.system_name.as_str() {
+                    "" => &nb.chassis_id,
+                    s => s,
+                };
+                errors.push(format!(
+                    "{}:{} is cabled to {} outside of the fabric",
+                    name, nb.interface, remote
+                ));
+                continue;
+            };
+
+            let peer = (peer.to_string(), nb.port_id.clone());
+            if let Some(other) = peers.insert(port.clone(), peer.clone()) {
+                if other != peer {
+                    errors.push(format!(
+                        "{}:{} sees more than one neighbor: {}:{} and {}:{}",
+                        port.0, port.1, other.0, other.1, peer.0, peer.1
+                    ));
+                }
+            }
+        }
+
+        for l in &status.links {
+            host_links.push(TopologyLink {
+                from: l.node.clone(),
+                from_port: l.interface.clone(),
+                to: name.clone(),
+                to_port: l.port.clone(),
+            });
+        }
+    }
+
+    // Both ends of a link have to see each other.
+    let mut links = BTreeSet::new();
+    for (port, peer) in &peers {
+        match peers.get(peer) {
+            Some(back) if back == port => {}
+            Some(back) => errors.push(format!(
+                "{}:{} sees {}:{}, but {}:{} sees {}:{}",
+                port.0, port.1, peer.0, peer.1, peer.0, peer.1, back.0, back.1
+            )),
+            None => {}
+        }
+
+        let (a, b) = if port < peer {
+            (port, peer)
+        } else {
+            (peer, port)
+        };
+        links.insert(TopologyLink {
+            from: a.0.clone(),
+            from_port: a.1.clone(),
+            to: b.0.clone(),
+            to_port: b.1.clone(),
+        });
+    }
+
+    let tiers = tiers(switches, &links, &host_links);
+    let tier = |name: &str| tiers.get(name).copied().unwrap_or(Tier::Host);
+
+    for l in &links {
+        let (a, b) = (
+            tier(&l.from).min(tier(&l.to)),
+            tier(&l.from).max(tier(&l.to)),
+        );
+        let allowed = matches!(
+            (a, b),
+            (Tier::Leaf, Tier::Spine) | (Tier::Spine, Tier::SuperSpine) | (Tier::Leaf, Tier::Leaf)
+        );
+        if !allowed {
+            errors.push(format!(
+                "{} ({}) and {} ({}) should not be cabled",
+                l.from,
+                tier(&l.from),
+                l.to,
+                tier(&l.to)
+            ));
+        }
+    }
+
+    for l in &host_links {
+        if tier(&l.to) != Tier::Leaf {
+            errors.push(format!(
+                "host {} is cabled to {} ({}) instead of a leaf",
+                l.from,
+                l.to,
+                tier(&l.to)
+            ));
+        }
+    }
+
+    // The redundancy of the uplinks.
+    let mut uplinks: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for l in links.iter().chain(host_links.iter()) {
+        let (a, b) = (l.from.as_str(), l.to.as_str());
+        if tier(a) < tier(b) {
+            uplinks.entry(a).or_default().insert(b);
+        } else if tier(b) < tier(a) {
+            uplinks.entry(b).or_default().insert(a);
+        }
+    }
+
+    let super_spines = tiers.values().any(|t| *t == Tier::SuperSpine);
+    if design.tiers < 3 && super_spines {
+        errors.push(format!(
+            "the fabric is designed with {} tiers, but has super-spines",
+            design.tiers
+        ));
+    }
+
+    let mut nodes: Vec<_> = tiers
+        .iter()
+        .map(|(name, tier)| TopologyNode {
+            name: name.clone(),
+            tier: *tier,
+        })
+        .chain(
+            host_links
+                .iter()
+                .map(|l| l.from.clone())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .map(|name| TopologyNode {
+                    name,
+                    tier: Tier::Host,
+                }),
+        )
+        .collect();
+    nodes.sort_by(|a, b| b.tier.cmp(&a.tier).then(a.name.cmp(&b.name)));
+
+    for n in &nodes {
+        let (expected, upper) = match n.tier {
+            Tier::Host => (design.host_uplinks, Tier::Leaf),
+            Tier::Leaf => (design.switch_uplinks, Tier::Spine),
+            Tier::Spine if design.tiers >= 3 => (design.switch_uplinks, Tier::SuperSpine),
+            _ => continue,
+        };
+
+        let count = uplinks.get(n.name.as_str()).map(|u| u.len()).unwrap_or(0) as u32;
+        if count < expected {
+            errors.push(format!(
+                "{} {} is connected to {} {}s, expect at least {}",
+                n.tier, n.name, count, upper, expected
+            ));
+        }
+    }
+
+    let mut links: Vec<_> = links.into_iter().chain(host_links).collect();
+    links.sort();
+
+    Topology {
+        nodes,
+        links,
+        errors,
+    }
+}
+
+/// The tier of each switch: its declared role, or inferred from its links,
+/// i.e. the switches with hosts are leaves, the ones above them spines, and
+/// the ones above the spines super-spines.
+fn tiers(
+    switches: &[Switch],
+    links: &BTreeSet<TopologyLink>,
+    host_links: &[TopologyLink],
+) -> BTreeMap<String, Tier> {
+    let mut tiers: BTreeMap<String, Tier> = switches
+        .iter()
+        .filter_map(|sw| {
+            let tier = match sw.spec.role? {
+                SwitchRole::Leaf => Tier::Leaf,
+                SwitchRole::Spine => Tier::Spine,
+                SwitchRole::SuperSpine => Tier::SuperSpine,
+            };
+            Some((sw.meta_data.name.clone(), tier))
+        })
+        .collect();
+
+    for l in host_links {
+        tiers.entry(l.to.clone()).or_insert(Tier::Leaf);
+    }
+
+    for (lower, upper) in [(Tier::Leaf, Tier::Spine), (Tier::Spine, Tier::SuperSpine)] {
+        for l in links {
+            for (a, b) in [(&l.from, &l.to), (&l.to, &l.from)] {
+                if tiers.get(a) == Some(&lower) && !tiers.contains_key(b) {
+                    tiers.insert(b.clone(), upper);
+                }
+            }
+        }
+    }
+
+    for sw in switches {
+        tiers.entry(sw.meta_data.name.clone()).or_insert(Tier::Leaf);
+    }
+
+    tiers
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// A switch whose chassis id is its name, seeing `neighbors` as
+    /// (local port, peer, peer port), and cabled to `hosts` as
+    /// (port, node, interface).
+    fn switch(
+        name: &str,
+        role: Option<&str>,
+        neighbors: &[(&str, &str, &str)],
+        hosts: &[(&str, &str, &str)],
+    ) -> Switch {
+        let neighbors: Vec<_> = neighbors
+            .iter()
+            .map(|(port, peer, peer_port)| {
+                json!({"interface": port, "chassis_id": peer, "port_id": peer_port, "system_name": peer})
+            })
+            .collect();
+        let links: Vec<_> = hosts
+            .iter()
+            .map(|(port, node, interface)| json!({"port": port, "node": node, "interface": interface}))
+            .collect();
+        serde_json::from_value(json!({
+            "meta_data": {"kind": "switch", "namespace": "default", "name": name, "labels": [], "version": 0},
+            "spec": {"chassis_id": name, "fabric": "f1", "role": role},
+            "status": {"state": "ready", "neighbors": neighbors, "links": links},
+        }))
+        .unwrap()
+    }
+
+    /// Two leaves cabled to two spines, with h1 on both leaves.
+    fn leaf_spine() -> Vec<Switch> {
+        vec![
+            switch(
+                "leaf1",
+                None,
+                &[
+                    ("Ethernet0", "spine1", "Ethernet0"),
+                    ("Ethernet4", "spine2", "Ethernet0"),
+                ],
+                &[("Ethernet8", "h1", "eth0")],
+            ),
+            switch(
+                "leaf2",
+                None,
+                &[
+                    ("Ethernet0", "spine1", "Ethernet4"),
+                    ("Ethernet4", "spine2", "Ethernet4"),
+                ],
+                &[("Ethernet8", "h1", "eth1")],
+            ),
+            switch(
+                "spine1",
+                None,
+                &[
+                    ("Ethernet0", "leaf1", "Ethernet0"),
+                    ("Ethernet4", "leaf2", "Ethernet0"),
+                ],
+                &[],
+            ),
+            switch(
+                "spine2",
+                None,
+                &[
+                    ("Ethernet0", "leaf1", "Ethernet4"),
+                    ("Ethernet4", "leaf2", "Ethernet4"),
+                ],
+                &[],
+            ),
+        ]
+    }
+
+    fn tier_of(t: &Topology, name: &str) -> Tier {
+        t.nodes.iter().find(|n| n.name == name).unwrap().tier
+    }
+
+    #[test]
+    fn infer_tiers_of_leaf_spine() {
+        let t = build(&FabricDesign::default(), &leaf_spine());
+
+        assert!(t.errors.is_empty(), "{:?}", t.errors);
+        assert_eq!(tier_of(&t, "leaf1"), Tier::Leaf);
+        assert_eq!(tier_of(&t, "spine2"), Tier::Spine);
+        assert_eq!(tier_of(&t, "h1"), Tier::Host);
+        // The spines first, the hosts last.
+        assert_eq!(t.nodes[0].name, "spine1");
+        assert_eq!(t.nodes.last().unwrap().name, "h1");
+        // Each switch link once, seen from both ends, and the host links.
+        assert_eq!(t.links.len(), 6);
+        assert!(t.links.contains(&TopologyLink {
+            from: "leaf2".to_string(),
+            from_port: "Ethernet4".to_string(),
+            to: "spine2".to_string(),
+            to_port: "Ethernet4".to_string(),
+        }));
+    }
+
+    #[test]
+    fn check_redundancy() {
+        let mut switches = leaf_spine();
+        switches[1] = switch(
+            "leaf2",
+            None,
+            &[("Ethernet0", "spine1", "Ethernet4")],
+            &[("Ethernet8", "h2", "eth0")],
+        );
+        switches[3] = switch("spine2", None, &[("Ethernet0", "leaf1", "Ethernet4")], &[]);
+
+        let t = build(&FabricDesign::default(), &switches);
+        assert_eq!(
+            t.errors,
+            vec![
+                "leaf leaf2 is connected to 1 spines, expect at least 2",
+                "host h1 is connected to 1 leafs, expect at least 2",
+                "host h2 is connected to 1 leafs, expect at least 2",
+            ]
+        );
+
+        let design = FabricDesign {
+            switch_uplinks: 1,
+            host_uplinks: 1,
+            ..Default::default()
+        };
+        assert!(build(&design, &switches).errors.is_empty());
+    }
+
+    #[test]
+    fn report_miscabling() {
+        let mut switches = leaf_spine();
+        // spine1 sees leaf2 on the port leaf1 sees it on.
+        switches[2] = switch(
+            "spine1",
+            None,
+            &[
+                ("Ethernet0", "leaf2", "Ethernet0"),
+                ("Ethernet4", "leaf2", "Ethernet0"),
+            ],
+            &[],
+        );
+        // A port cabled outside the fabric, and a host on a spine.
+        switches[3] = switch(
+            "spine2",
+            Some("spine"),
+            &[
+                ("Ethernet0", "leaf1", "Ethernet4"),
+                ("Ethernet4", "leaf2", "Ethernet4"),
+                ("Ethernet8", "router", "ge-0/0/0"),
+            ],
+            &[("Ethernet12", "h2", "eth0")],
+        );
+
+        let t = build(
+            &FabricDesign {
+                host_uplinks: 1,
+                ..Default::default()
+            },
+            &switches,
+        );
+        assert!(t
+            .errors
+            .contains(&"spine2:Ethernet8 is cabled to router outside of the fabric".to_string()));
+        assert!(t.errors.contains(
+            &"leaf1:Ethernet0 sees spine1:Ethernet0, but spine1:Ethernet0 sees leaf2:Ethernet0"
+                .to_string()
+        ));
+        assert!(t
+            .errors
+            .contains(&"host h2 is cabled to spine2 (spine) instead of a leaf".to_string()));
+    }
+
+    #[test]
+    fn check_tiers() {
+        let mut switches = leaf_spine();
+        // Spines cabled together, and a super-spine in a 2-tier design.
+        switches[2] = switch(
+            "spine1",
+            None,
+            &[
+                ("Ethernet0", "leaf1", "Ethernet0"),
+                ("Ethernet4", "leaf2", "Ethernet0"),
+                ("Ethernet8", "spine2", "Ethernet8"),
+            ],
+            &[],
+        );
+        switches[3] = switch(
+            "spine2",
+            None,
+            &[
+                ("Ethernet0", "leaf1", "Ethernet4"),
+                ("Ethernet4", "leaf2", "Ethernet4"),
+                ("Ethernet8", "spine1", "Ethernet8"),
+            ],
+            &[],
+        );
+        switches.push(switch("super1", Some("super-spine"), &[], &[]));
+
+        let t = build(&FabricDesign::default(), &switches);
+        assert_eq!(
+            t.errors,
+            vec![
+                "spine1 (spine) and spine2 (spine) should not be cabled",
+                "the fabric is designed with 2 tiers, but has super-spines",
+            ]
+        );
+
+        // With 3 tiers, the spines need super-spine uplinks.
+        let design = FabricDesign {
+            tiers: 3,
+            ..Default::default()
+        };
+        let t = build(&design, &switches);
+        assert!(t.errors.contains(
+            &"spine spine1 is connected to 0 super-spines, expect at least 2".to_string()
+        ));
+    }
+}
@@ -0,0 +1,403 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::net::Ipv4Addr;
+
+use async_trait::async_trait;
+use ipnet::Ipv4Net;
+use md5::{Digest, Md5};
+
+use yangtze_apis::{
+    v1::{NamespaceName, VersionKind, YangtzeError},
+    v1alpha1::{
+        network_interface::{
+            self, InterfaceKind, NetworkInterface, NetworkInterfaceState, NetworkInterfaceStatus,
+            DEFAULT_MTU,
+        },
+        node::{self, Node},
+        subnet::{self, Subnet},
+        vpc::{self, Vpc},
+    },
+};
+use yangtze_client::YangtzeClient;
+
+use crate::framework::Controller;
+
+/// Allocates the addresses of the NetworkInterfaces from their Subnets, and
+/// resolves their MAC, MTU, VLAN and VNI for the agents.
+#[derive(Clone)]
+pub struct NetworkInterfaceController {}
+
+#[async_trait]
+impl Controller<NetworkInterface> for NetworkInterfaceController {
+    async fn execute(
+        &self,
+        client: YangtzeClient,
+        i: NetworkInterface,
+    ) -> Result<(), YangtzeError> {
+        let status = match resolve(&client, &i).await {
+            Ok(status) => status,
+            Err(YangtzeError::InvalidConfig(reason)) => NetworkInterfaceStatus {
+                state: NetworkInterfaceState::Error,
+                reason: Some(reason),
+                address: None,
+                mac: None,
+                mtu: None,
+                vlan: None,
+                vni: None,
+            },
+            Err(e) => return Err(e),
+        };
+
+        // The agent owns the state once the interface is resolved.
+        if let Some(current) = &i.status {
+            let resolved = status.state != NetworkInterfaceState::Error;
+            if current.address == status.address
+                && current.mac == status.mac
+                && current.mtu == status.mtu
+                && current.vlan == status.vlan
+                && current.vni == status.vni
+                && (resolved || current.reason == status.reason)
+            {
+                return Ok(());
+            }
+        }
+
+        match (&status.address, &status.reason) {
+            (Some(address), _) => tracing::info!(
+                "Allocate <{}> of Subnet <{}> to NetworkInterface <{}>.",
+                address,
+                i.spec.subnet,
+                i
+            ),
+            (None, Some(reason)) => {
+                tracing::info!("NetworkInterface <{}> is invalid: {}.", i, reason)
+            }
+            (None, None) => {}
+        }
+
+        let mut i = i;
+        i.status = Some(status);
+        let _i = client.update::<NetworkInterface>(i).await?;
+
+        Ok(())
+    }
+
+    fn get_version_kind(&self) -> VersionKind {
+        network_interface::VERSION_KIND.clone()
+    }
+}
+
+/// The status of a NetworkInterface to be programmed by the agent; the
+/// InvalidConfig errors are reported in its status.
+async fn resolve(
+    client: &YangtzeClient,
+    i: &NetworkInterface,
+) -> Result<NetworkInterfaceStatus, YangtzeError> {
+    let invalid = |e: String| YangtzeError::InvalidConfig(e);
+    let nn = |name: Option<&str>| NamespaceName {
+        namespace: Some(i.meta_data.namespace.clone()),
+        name: name.map(str::to_string),
+    };
+
+    let subnet = client
+        .clone()
+        .version(subnet::VERSION_KIND.version)
+        .kind(subnet::VERSION_KIND.kind)
+        .list::<Subnet>(nn(Some(&i.spec.subnet)))
+        .await?
+        .pop()
+        .ok_or(invalid(format!("Subnet <{}> not found", i.spec.subnet)))?;
+
+    let vni = match &subnet.spec.vpc {
+        Some(name) => {
+            let vpc = client
+                .clone()
+                .version(vpc::VERSION_KIND.version)
+                .kind(vpc::VERSION_KIND.kind)
+                .list::<Vpc>(nn(Some(name)))
+                .await?
+                .pop()
+                .ok_or(invalid(format!("Vpc <{}> not found", name)))?;
+            Some(vpc.spec.vni)
+        }
+        None => None,
+    };
+
+    let node = client
+        .clone()
+        .version(node::VERSION_KIND.version)
+        .kind(node::VERSION_KIND.kind)
+        .list::<Node>(nn(Some(&i.spec.node)))
+        .await?
+        .pop()
+        .ok_or(invalid(format!("Node <{}> not found", i.spec.node)))?;
+    let mac = mac(i, &node)?;
+
+    // The addresses taken by the other interfaces and the Nodes.
+    let mut used = vec![];
+    for other in client
+        .clone()
+        .version(network_interface::VERSION_KIND.version)
+        .kind(network_interface::VERSION_KIND.kind)
+        .list::<NetworkInterface>(nn(None))
+        .await?
+        .into_iter()
+        .filter(|o| o.meta_data.name != i.meta_data.name && o.spec.subnet == i.spec.subnet)
+    {
+        let address = other.status.and_then(|s| s.address);
+        used.extend(other.spec.address.into_iter().chain(address));
+    }
+    for n in client
+        .clone()
+        .version(node::VERSION_KIND.version)
+        .kind(node::VERSION_KIND.kind)
+        .list::<Node>(nn(None))
+        .await?
+    {
+        used.extend(n.spec.reservations.into_iter().map(|r| r.address));
+    }
+    let used: Vec<Ipv4Addr> = used.iter().filter_map(|a| a.parse().ok()).collect();
+
+    let current = i.status.as_ref().and_then(|s| s.address.as_deref());
+    let address = match (&i.spec.address, current) {
+        (Some(a), _) => {
+            let a = a
+                .parse::<Ipv4Addr>()
+                .map_err(|e| invalid(format!("invalid address <{}>: {}", a, e)))?;
+            available(&subnet, &used, a)?;
+            a
+        }
+        (None, Some(a)) => match a.parse::<Ipv4Addr>() {
+            Ok(a) if available(&subnet, &used, a).is_ok() => a,
+            _ => allocate(&subnet, &used)?,
+        },
+        (None, None) => allocate(&subnet, &used)?,
+    };
+
+    Ok(NetworkInterfaceStatus {
+        state: NetworkInterfaceState::Pending,
+        reason: None,
+        address: Some(address.to_string()),
+        mac: Some(mac),
+        mtu: Some(i.spec.mtu.unwrap_or(DEFAULT_MTU)),
+        vlan: subnet.spec.vlan,
+        vni,
+    })
+}
+
+/// The MAC of the interface: the one of the NIC, or one generated from the
+/// name of the interface for a VF; the MAC of the host function behind a
+/// representor is not known by the DPU, so it has to be specified.
+fn mac(i: &NetworkInterface, node: &Node) -> Result<String, YangtzeError> {
+    if let Some(mac) = &i.spec.mac {
+        return Ok(mac.to_lowercase());
+    }
+
+    let nics = node
+        .status
+        .as_ref()
+        .map(|s| s.nics.as_slice())
+        .unwrap_or(&[]);
+    let nic = nics.iter().find(|n| n.name == i.spec.interface);
+    match i.spec.kind {
+        InterfaceKind::Nic => nic
+            .map(|n| n.mac.to_lowercase())
+            .ok_or(YangtzeError::InvalidConfig(format!(
+                "NIC <{}> not found on Node <{}>",
+                i.spec.interface, node.meta_data.name
+            ))),
+        InterfaceKind::Vf => {
+            let vf = i.spec.vf.ok_or(YangtzeError::InvalidConfig(
+                "the index of the VF is required".to_string(),
+            ))?;
+            if let Some(n) = nic.filter(|n| vf >= n.sriov_total_vfs) {
+                return Err(YangtzeError::InvalidConfig(format!(
+                    "NIC <{}> has {} VFs only",
+                    n.name, n.sriov_total_vfs
+                )));
+            }
+
+            // A locally administered unicast MAC.
+            let mut md5 = Md5::new();
+            md5.update(format!("{}/{}", i.meta_data.namespace, i.meta_data.name));
+            let digest = md5.finalize();
+            let mut mac = vec!["02".to_string()];
+            mac.extend(digest[..5].iter().map(|b| format!("{:02x}", b)));
+            Ok(mac.join(":"))
+        }
+        InterfaceKind::Representor => Err(YangtzeError::InvalidConfig(
+            "the MAC of the function behind the representor is required".to_string(),
+        )),
+    }
+}
+
+/// Checks that an address of the Subnet is neither the gateway nor in the
+/// dynamic range of the DHCP, nor used.
+fn available(subnet: &Subnet, used: &[Ipv4Addr], a: Ipv4Addr) -> Result<(), YangtzeError> {
+    let invalid = |e: String| YangtzeError::InvalidConfig(e);
+    let net: Ipv4Net = subnet
+        .spec
+        .cidr
+        .parse()
+        .map_err(|e| invalid(format!("Subnet <{}>: {}", subnet, e)))?;
+
+    // Every address of a /31 or /32 is a host.
+    let host = net.contains(&a)
+        && (net.prefix_len() >= 31 || (a != net.network() && a != net.broadcast()));
+    if !host {
+        return Err(invalid(format!("<{}> is not a host of {}", a, net)));
+    }
+    if subnet.spec.gateway.as_deref().and_then(|g| g.parse().ok()) == Some(a) {
+        return Err(invalid(format!("<{}> is the gateway of {}", a, net)));
+    }
+    if let Some(dhcp) = &subnet.spec.dhcp {
+        let start = dhcp.start.parse::<Ipv4Addr>().ok();
+        let end = dhcp.end.parse::<Ipv4Addr>().ok();
+        if let (Some(start), Some(end)) = (start, end) {
+            if start <= a && a <= end {
+                return Err(invalid(format!(
+                    "<{}> is in the DHCP range {}-{}",
+                    a, start, end
+                )));
+            }
+        }
+    }
+    if used.contains(&a) {
+        return Err(invalid(format!("<{}> is already in use", a)));
+    }
+
+    Ok(())
+}
+
+/// The first available address of the Subnet.
+fn allocate(subnet: &Subnet, used: &[Ipv4Addr]) -> Result<Ipv4Addr, YangtzeError> {
+    let net: Ipv4Net = subnet
+        .spec
+        .cidr
+        .parse()
+        .map_err(|e| YangtzeError::InvalidConfig(format!("Subnet <{}>: {}", subnet, e)))?;
+
+    net.hosts()
+        .find(|a| available(subnet, used, *a).is_ok())
+        .ok_or(YangtzeError::InvalidConfig(format!(
+            "no available address in Subnet <{}>",
+            subnet
+        )))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn subnet(cidr: &str) -> Subnet {
+        serde_json::from_value(json!({
+            "meta_data": {"kind": "subnet", "namespace": "default", "name": "s1", "labels": [], "version": 0},
+            "spec": {
+                "cidr": cidr,
+                "gateway": "10.0.0.1",
+                "dhcp": {"start": "10.0.0.100", "end": "10.0.0.199", "lease_time": 3600},
+            },
+        }))
+        .unwrap()
+    }
+
+    fn interface(
+        kind: &str,
+        interface: &str,
+        vf: Option<u32>,
+        mac: Option<&str>,
+    ) -> NetworkInterface {
+        serde_json::from_value(json!({
+            "meta_data": {"kind": "networkinterface", "namespace": "default", "name": "i1", "labels": [], "version": 0},
+            "spec": {"node": "n1", "subnet": "s1", "kind": kind, "interface": interface, "vf": vf, "mac": mac},
+        }))
+        .unwrap()
+    }
+
+    fn node() -> Node {
+        serde_json::from_value(json!({
+            "meta_data": {"kind": "node", "namespace": "default", "name": "n1", "labels": [], "version": 0},
+            "spec": {"hostname": "n1", "serial": "s1"},
+            "status": {
+                "state": "ready",
+                "nics": [{"name": "eth0", "mac": "52:54:00:AA:BB:CC", "sriov_total_vfs": 4}],
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn available_addresses() {
+        let s = subnet("10.0.0.0/24");
+        let used = [Ipv4Addr::new(10, 0, 0, 5)];
+        let check = |a: [u8; 4]| available(&s, &used, Ipv4Addr::from(a)).map_err(|e| e.to_string());
+
+        assert!(check([10, 0, 0, 2]).is_ok());
+        assert!(check([10, 0, 0, 254]).is_ok());
+        for a in [[10, 0, 0, 0], [10, 0, 0, 255], [10, 0, 1, 2]] {
+            assert!(check(a).unwrap_err().contains("is not a host of"));
+        }
+        assert!(check([10, 0, 0, 1]).unwrap_err().contains("is the gateway"));
+        assert!(check([10, 0, 0, 150])
+            .unwrap_err()
+            .contains("in the DHCP range"));
+        assert!(check([10, 0, 0, 5]).unwrap_err().contains("already in use"));
+
+        // The point-to-point links have no network nor broadcast address.
+        let s = subnet("10.0.2.0/31");
+        assert!(available(&s, &[], Ipv4Addr::new(10, 0, 2, 0)).is_ok());
+        assert!(available(&s, &[], Ipv4Addr::new(10, 0, 2, 1)).is_ok());
+    }
+
+    #[test]
+    fn allocate_first_available() {
+        let s = subnet("10.0.0.0/24");
+        let used = [Ipv4Addr::new(10, 0, 0, 2), Ipv4Addr::new(10, 0, 0, 3)];
+        assert_eq!(allocate(&s, &used).unwrap(), Ipv4Addr::new(10, 0, 0, 4));
+
+        // All but the gateway are in the DHCP range.
+        let mut s = subnet("10.0.0.0/30");
+        s.spec.dhcp.as_mut().unwrap().start = "10.0.0.2".to_string();
+        assert!(allocate(&s, &[]).is_err());
+
+        // Checking an address does not enumerate the hosts of a large Subnet.
+        let s = subnet("10.0.0.0/8");
+        let used: Vec<Ipv4Addr> = (2..100).map(|i| Ipv4Addr::new(10, 0, 0, i)).collect();
+        assert_eq!(allocate(&s, &used).unwrap(), Ipv4Addr::new(10, 0, 0, 200));
+    }
+
+    #[test]
+    fn mac_of_interfaces() {
+        let n = node();
+
+        let nic = interface("nic", "eth0", None, None);
+        assert_eq!(mac(&nic, &n).unwrap(), "52:54:00:aa:bb:cc");
+        assert!(mac(&interface("nic", "eth9", None, None), &n).is_err());
+
+        let given = interface("representor", "pf0hpf", None, Some("02:AA:00:00:00:01"));
+        assert_eq!(mac(&given, &n).unwrap(), "02:aa:00:00:00:01");
+        assert!(mac(&interface("representor", "pf0hpf", None, None), &n).is_err());
+
+        // Stable, locally administered and unicast.
+        let vf = interface("vf", "eth0", Some(1), None);
+        let generated = mac(&vf, &n).unwrap();
+        assert_eq!(generated, mac(&vf, &n).unwrap());
+        assert!(generated.starts_with("02:"));
+        assert_eq!(generated.split(':').count(), 6);
+
+        assert!(mac(&interface("vf", "eth0", Some(4), None), &n).is_err());
+        assert!(mac(&interface("vf", "eth0", None, None), &n).is_err());
+    }
+}
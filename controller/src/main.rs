@@ -11,12 +11,23 @@
  * limitations under the License.
  */
 
+use std::path::PathBuf;
+
 use yangtze_apis::v1::YangtzeError;
 use yangtze_client::YangtzeConfig;
 
+mod attachments;
+mod bmc;
 mod fabrics;
 mod framework;
+mod interfaces;
+mod nodes;
+mod pools;
+mod power;
+mod provisions;
+mod routing;
 mod switches;
+mod xpus;
 
 #[tokio::main]
 async fn main() -> Result<(), YangtzeError> {
@@ -32,7 +43,32 @@ async fn main() -> Result<(), YangtzeError> {
     let mut rt = framework::runtime(config);
 
     rt = rt.register(fabrics::FabricController {}).await;
-    rt = rt.register(switches::SwitchController {}).await;
+    rt = rt.register(switches::SwitchController::default()).await;
+    rt = rt.register(switches::DiscoveryController {}).await;
+    rt = rt.register(routing::RoutingController {}).await;
+    rt = rt
+        .register(nodes::NodeController { grace_period: 40 })
+        .await;
+    rt = rt
+        .register(power::PowerController {
+            credentials: PathBuf::from("/etc/yangtze/bmc"),
+        })
+        .await;
+    rt = rt.register(attachments::AttachmentController {}).await;
+    rt = rt.register(pools::StoragePoolController {}).await;
+    rt = rt.register(pools::SchedulerController {}).await;
+    rt = rt.register(interfaces::NetworkInterfaceController {}).await;
+    rt = rt
+        .register(provisions::ProvisionController {
+            action_timeout: 1800,
+            max_attempts: 3,
+        })
+        .await;
+    rt = rt
+        .register(xpus::XpuController {
+            provision_timeout: 3600,
+        })
+        .await;
 
     rt.run().await;
 
@@ -0,0 +1,61 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use async_trait::async_trait;
+
+use yangtze_apis::{
+    v1::{now, VersionKind, YangtzeError},
+    v1alpha1::node::{self, Node, NodeState},
+};
+use yangtze_client::YangtzeClient;
+
+use crate::framework::Controller;
+
+/// Marks the Nodes `NotReady` when their agent stopped sending heartbeats.
+#[derive(Clone)]
+pub struct NodeController {
+    /// Seconds without heartbeat before a Node is `NotReady`.
+    pub grace_period: u64,
+}
+
+#[async_trait]
+impl Controller<Node> for NodeController {
+    async fn execute(&self, client: YangtzeClient, n: Node) -> Result<(), YangtzeError> {
+        let status = match &n.status {
+            Some(status) if status.state == NodeState::Ready => status,
+            _ => return Ok(()),
+        };
+
+        if now().saturating_sub(status.last_heartbeat) <= self.grace_period {
+            return Ok(());
+        }
+
+        tracing::warn!(
+            "No heartbeat from Node <{}> since {}, mark it NotReady.",
+            n.meta_data,
+            status.last_heartbeat
+        );
+
+        let mut n = n;
+        if let Some(status) = n.status.as_mut() {
+            status.state = NodeState::NotReady;
+        }
+        let _n = client.update::<Node>(n).await?;
+
+        Ok(())
+    }
+
+    fn get_version_kind(&self) -> VersionKind {
+        node::VERSION_KIND.clone()
+    }
+}
@@ -0,0 +1,162 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use async_trait::async_trait;
+
+use yangtze_apis::{
+    v1::{NamespaceName, VersionKind, YangtzeError},
+    v1alpha1::{
+        node::{self, Node, NodeState},
+        nvme_namespace::{self, NvmeNamespace},
+        storage_pool::{
+            self, label, selected, PoolDevice, StoragePool, StoragePoolState, StoragePoolStatus,
+        },
+    },
+};
+use yangtze_client::YangtzeClient;
+
+use crate::framework::Controller;
+
+mod scheduler;
+
+pub use scheduler::SchedulerController;
+
+/// The devices of a StoragePool and the NvmeNamespaces of its namespace.
+pub struct Inventory {
+    pub devices: Vec<PoolDevice>,
+    /// The Nodes of the devices which are not ready.
+    pub unready: Vec<String>,
+    pub namespaces: Vec<NvmeNamespace>,
+}
+
+/// Collects the NVMe devices reported by the agents of the Nodes selected by
+/// a StoragePool, with the bytes of the NvmeNamespaces placed onto them.
+pub async fn inventory(client: &YangtzeClient, p: &StoragePool) -> Result<Inventory, YangtzeError> {
+    let nn = NamespaceName {
+        namespace: Some(p.meta_data.namespace.clone()),
+        name: None,
+    };
+
+    let nodes: Vec<Node> = client
+        .clone()
+        .version(node::VERSION_KIND.version)
+        .kind(node::VERSION_KIND.kind)
+        .list(nn.clone())
+        .await?;
+    let namespaces: Vec<NvmeNamespace> = client
+        .clone()
+        .version(nvme_namespace::VERSION_KIND.version)
+        .kind(nvme_namespace::VERSION_KIND.kind)
+        .list(nn)
+        .await?;
+
+    let mut devices = vec![];
+    let mut unready = vec![];
+    for n in nodes
+        .iter()
+        .filter(|n| selected(&n.meta_data.labels, &p.spec.selector))
+    {
+        let Some(status) = &n.status else {
+            continue;
+        };
+        if status.state != NodeState::Ready {
+            unready.push(n.meta_data.name.clone());
+        }
+
+        let domain = label(&n.meta_data.labels, &p.spec.failure_domain)
+            .unwrap_or(&n.meta_data.name)
+            .to_string();
+        for d in status.nvmes.iter().filter(|d| d.size > 0) {
+            devices.push(PoolDevice {
+                node: n.meta_data.name.clone(),
+                name: d.name.clone(),
+                domain: domain.clone(),
+                capacity: d.size,
+                allocated: 0,
+            });
+        }
+    }
+
+    for ns in namespaces
+        .iter()
+        .filter(|ns| ns.spec.pool.as_deref() == Some(&p.meta_data.name))
+    {
+        let Some(placement) = &ns.spec.placement else {
+            continue;
+        };
+        if let Some(d) = devices
+            .iter_mut()
+            .find(|d| d.node == placement.node && d.name == placement.device)
+        {
+            d.allocated += ns.spec.size;
+        }
+    }
+
+    devices.sort_by(|a, b| (&a.node, &a.name).cmp(&(&b.node, &b.name)));
+
+    Ok(Inventory {
+        devices,
+        unready,
+        namespaces,
+    })
+}
+
+/// Tracks the capacity of the StoragePools.
+#[derive(Clone)]
+pub struct StoragePoolController {}
+
+#[async_trait]
+impl Controller<StoragePool> for StoragePoolController {
+    async fn execute(&self, client: YangtzeClient, p: StoragePool) -> Result<(), YangtzeError> {
+        let inventory = inventory(&client, &p).await?;
+        let devices = inventory.devices;
+
+        let (state, reason) = match devices.is_empty() {
+            true => (
+                StoragePoolState::Error,
+                Some("no NVMe device on the selected Nodes".to_string()),
+            ),
+            false => (StoragePoolState::Ready, None),
+        };
+        let status = StoragePoolStatus {
+            state,
+            capacity: devices.iter().map(|d| d.capacity).sum(),
+            allocated: devices.iter().map(|d| d.allocated).sum(),
+            available: devices.iter().map(|d| d.available()).sum(),
+            reason,
+            devices,
+        };
+
+        if p.status.as_ref() == Some(&status) {
+            return Ok(());
+        }
+
+        tracing::info!(
+            "StoragePool <{}> is {}: {} of {} bytes available.",
+            p,
+            status.state,
+            status.available,
+            status.capacity
+        );
+
+        let mut p = p;
+        p.status = Some(status);
+        let _p = client.update::<StoragePool>(p).await?;
+
+        Ok(())
+    }
+
+    fn get_version_kind(&self) -> VersionKind {
+        storage_pool::VERSION_KIND.clone()
+    }
+}
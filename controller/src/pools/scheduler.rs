@@ -0,0 +1,378 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use async_trait::async_trait;
+
+use yangtze_apis::{
+    v1::{Metadata, NamespaceName, VersionKind, YangtzeError},
+    v1alpha1::{
+        nvme_namespace::{self, NvmeNamespace, NvmePlacement},
+        nvme_port::{self, NvmePort},
+        nvme_subsystem::{self, NvmeState, NvmeStatus, NvmeSubsystem, NvmeSubsystemSpec},
+        storage_pool::{self, PoolDevice, StoragePool},
+    },
+};
+use yangtze_client::YangtzeClient;
+
+use crate::framework::Controller;
+use crate::pools::inventory;
+
+/// Places the NvmeNamespaces requesting a size of a StoragePool onto the
+/// device with the most available bytes, out of the failure domains of the
+/// volumes of the same anti-affinity group. The volumes of a pool on a Node
+/// are the namespaces of the NvmeSubsystem `<pool>-<node>`, which is created
+/// on demand and exposed by adding it to the NvmePorts of the Node.
+#[derive(Clone)]
+pub struct SchedulerController {}
+
+#[async_trait]
+impl Controller<NvmeNamespace> for SchedulerController {
+    async fn execute(&self, client: YangtzeClient, ns: NvmeNamespace) -> Result<(), YangtzeError> {
+        let Some(pool) = ns.spec.pool.clone() else {
+            return Ok(());
+        };
+        if ns.spec.placement.is_some() {
+            return Ok(());
+        }
+
+        if ns.spec.size == 0 {
+            return pending(
+                &client,
+                ns,
+                "a volume of a StoragePool needs a size".to_string(),
+            )
+            .await;
+        }
+
+        let p = client
+            .clone()
+            .version(storage_pool::VERSION_KIND.version)
+            .kind(storage_pool::VERSION_KIND.kind)
+            .list::<StoragePool>(NamespaceName {
+                namespace: Some(ns.meta_data.namespace.clone()),
+                name: Some(pool.clone()),
+            })
+            .await?
+            .pop();
+        let Some(p) = p else {
+            return pending(&client, ns, format!("StoragePool <{}> not found", pool)).await;
+        };
+
+        let inventory = inventory(&client, &p).await?;
+
+        // The failure domains of the other volumes of the group.
+        let excluded: Vec<&str> = match &ns.spec.anti_affinity {
+            Some(group) => inventory
+                .namespaces
+                .iter()
+                .filter(|o| o.meta_data.name != ns.meta_data.name)
+                .filter(|o| o.spec.pool.as_deref() == Some(&pool))
+                .filter(|o| o.spec.anti_affinity.as_ref() == Some(group))
+                .filter_map(|o| o.spec.placement.as_ref())
+                .map(|p| p.domain.as_str())
+                .collect(),
+            None => vec![],
+        };
+
+        let device = choose(
+            &inventory.devices,
+            &inventory.unready,
+            &excluded,
+            ns.spec.size,
+        );
+        let Some(device) = device else {
+            let reason = match excluded.is_empty() {
+                true => format!(
+                    "no device of StoragePool <{}> has {} bytes available",
+                    pool, ns.spec.size
+                ),
+                false => format!(
+                    "no device of StoragePool <{}> has {} bytes available out of the failure domains {}",
+                    pool,
+                    ns.spec.size,
+                    excluded.join(",")
+                ),
+            };
+            return pending(&client, ns, reason).await;
+        };
+
+        let subsystem = subsystem(&client, &p, device).await?;
+        expose(&client, &subsystem).await?;
+        let nsid = inventory
+            .namespaces
+            .iter()
+            .filter(|o| o.spec.subsystem == subsystem.meta_data.name)
+            .map(|o| o.spec.nsid)
+            .max()
+            .unwrap_or_default()
+            + 1;
+
+        tracing::info!(
+            "Place NvmeNamespace <{}> of StoragePool <{}> onto <{}/{}>.",
+            ns,
+            pool,
+            device.node,
+            device.name
+        );
+
+        let mut ns = ns;
+        ns.spec.device = format!(
+            "{}/{}/{}.img",
+            p.spec.root.trim_end_matches('/'),
+            device.name,
+            ns.meta_data.name
+        );
+        ns.spec.subsystem = subsystem.meta_data.name;
+        ns.spec.nsid = nsid;
+        ns.spec.placement = Some(NvmePlacement {
+            node: device.node.clone(),
+            device: device.name.clone(),
+            domain: device.domain.clone(),
+        });
+        ns.status = Some(NvmeStatus {
+            state: NvmeState::Pending,
+            reason: None,
+        });
+        let _ns = client.update::<NvmeNamespace>(ns).await?;
+
+        Ok(())
+    }
+
+    fn get_version_kind(&self) -> VersionKind {
+        nvme_namespace::VERSION_KIND.clone()
+    }
+}
+
+/// The ready device out of the excluded failure domains with the most bytes
+/// available, at least `size`.
+fn choose<'a>(
+    devices: &'a [PoolDevice],
+    unready: &[String],
+    excluded: &[&str],
+    size: u64,
+) -> Option<&'a PoolDevice> {
+    devices
+        .iter()
+        .filter(|d| !unready.contains(&d.node))
+        .filter(|d| !excluded.contains(&d.domain.as_str()))
+        .filter(|d| d.available() >= size)
+        .min_by(|a, b| {
+            b.available()
+                .cmp(&a.available())
+                .then_with(|| (&a.node, &a.name).cmp(&(&b.node, &b.name)))
+        })
+}
+
+/// Keeps an NvmeNamespace pending with the reason it is not placed.
+async fn pending(
+    client: &YangtzeClient,
+    ns: NvmeNamespace,
+    reason: String,
+) -> Result<(), YangtzeError> {
+    let status = NvmeStatus {
+        state: NvmeState::Pending,
+        reason: Some(reason),
+    };
+    if ns.status.as_ref() == Some(&status) {
+        return Ok(());
+    }
+
+    tracing::info!(
+        "NvmeNamespace <{}> is not placed: {}",
+        ns,
+        status.reason.clone().unwrap_or_default()
+    );
+
+    let mut ns = ns;
+    ns.status = Some(status);
+    let _ns = client.update::<NvmeNamespace>(ns).await?;
+
+    Ok(())
+}
+
+/// Gets or creates the NvmeSubsystem of the volumes of a pool on a Node.
+async fn subsystem(
+    client: &YangtzeClient,
+    p: &StoragePool,
+    device: &PoolDevice,
+) -> Result<NvmeSubsystem, YangtzeError> {
+    let client = client
+        .clone()
+        .version(nvme_subsystem::VERSION_KIND.version)
+        .kind(nvme_subsystem::VERSION_KIND.kind);
+    let name = format!("{}-{}", p.meta_data.name, device.node);
+
+    let found = client
+        .list::<NvmeSubsystem>(NamespaceName {
+            namespace: Some(p.meta_data.namespace.clone()),
+            name: Some(name.clone()),
+        })
+        .await?
+        .pop();
+    if let Some(s) = found {
+        if s.spec.node != device.node {
+            return Err(YangtzeError::InvalidConfig(format!(
+                "NvmeSubsystem <{}> of StoragePool <{}> is not on Node <{}>.",
+                s, p, device.node
+            )));
+        }
+        return Ok(s);
+    }
+
+    tracing::info!("Create NvmeSubsystem <{}> of StoragePool <{}>.", name, p);
+
+    client
+        .create(NvmeSubsystem {
+            meta_data: Metadata {
+                uuid: None,
+                kind: nvme_subsystem::VERSION_KIND.kind.to_string(),
+                namespace: p.meta_data.namespace.clone(),
+                name,
+                labels: vec![],
+                version: 0,
+            },
+            spec: NvmeSubsystemSpec {
+                node: device.node.clone(),
+                nqn: format!(
+                    "nqn.2023-01.cn.xflops:{}:{}:{}",
+                    p.meta_data.namespace, p.meta_data.name, device.node
+                ),
+                allowed_hosts: vec![],
+                serial: None,
+                backend: Default::default(),
+            },
+            status: None,
+        })
+        .await
+}
+
+/// Adds an NvmeSubsystem to the NvmePorts of its Node not exposing it yet.
+async fn expose(client: &YangtzeClient, s: &NvmeSubsystem) -> Result<(), YangtzeError> {
+    let client = client
+        .clone()
+        .version(nvme_port::VERSION_KIND.version)
+        .kind(nvme_port::VERSION_KIND.kind);
+
+    let ports: Vec<NvmePort> = client
+        .list::<NvmePort>(NamespaceName {
+            namespace: Some(s.meta_data.namespace.clone()),
+            name: None,
+        })
+        .await?
+        .into_iter()
+        .filter(|p| p.spec.node == s.spec.node)
+        .collect();
+    if ports.is_empty() {
+        tracing::warn!(
+            "No NvmePort on Node <{}> to expose NvmeSubsystem <{}>.",
+            s.spec.node,
+            s
+        );
+        return Ok(());
+    }
+
+    for mut p in ports {
+        if p.spec.subsystems.contains(&s.meta_data.name) {
+            continue;
+        }
+
+        tracing::info!("Expose NvmeSubsystem <{}> by NvmePort <{}>.", s, p);
+        p.spec.subsystems.push(s.meta_data.name.clone());
+        let _p = client.update::<NvmePort>(p).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIB: u64 = 1 << 30;
+
+    fn device(node: &str, name: &str, domain: &str, capacity: u64, allocated: u64) -> PoolDevice {
+        PoolDevice {
+            node: node.to_string(),
+            name: name.to_string(),
+            domain: domain.to_string(),
+            capacity: capacity * GIB,
+            allocated: allocated * GIB,
+        }
+    }
+
+    fn devices() -> Vec<PoolDevice> {
+        vec![
+            device("node1", "nvme0n1", "r1", 1000, 900),
+            device("node2", "nvme0n1", "r1", 1000, 200),
+            device("node3", "nvme0n1", "r2", 500, 0),
+        ]
+    }
+
+    fn chosen(d: Option<&PoolDevice>) -> Option<&str> {
+        d.map(|d| d.node.as_str())
+    }
+
+    #[test]
+    fn choose_most_available() {
+        assert_eq!(
+            chosen(choose(&devices(), &[], &[], 100 * GIB)),
+            Some("node2")
+        );
+    }
+
+    #[test]
+    fn choose_fitting_device() {
+        let mut devices = devices();
+        devices[1].allocated = 950 * GIB;
+
+        assert_eq!(chosen(choose(&devices, &[], &[], 100 * GIB)), Some("node3"));
+        assert_eq!(chosen(choose(&devices, &[], &[], 500 * GIB)), Some("node3"));
+    }
+
+    #[test]
+    fn reject_oversize_volume() {
+        assert_eq!(chosen(choose(&devices(), &[], &[], 801 * GIB)), None);
+        assert_eq!(
+            chosen(choose(&devices(), &[], &[], 800 * GIB)),
+            Some("node2")
+        );
+    }
+
+    #[test]
+    fn skip_excluded_domains() {
+        assert_eq!(chosen(choose(&devices(), &[], &["r1"], GIB)), Some("node3"));
+        assert_eq!(chosen(choose(&devices(), &[], &["r1", "r2"], GIB)), None);
+    }
+
+    #[test]
+    fn skip_unready_nodes() {
+        let unready = ["node2".to_string()];
+
+        assert_eq!(
+            chosen(choose(&devices(), &unready, &[], GIB)),
+            Some("node3")
+        );
+    }
+
+    #[test]
+    fn break_ties_by_name() {
+        let devices = vec![
+            device("node2", "nvme1n1", "r1", 100, 0),
+            device("node2", "nvme0n1", "r1", 100, 0),
+            device("node1", "nvme1n1", "r1", 100, 0),
+        ];
+
+        let d = choose(&devices, &[], &[], GIB).unwrap();
+        assert_eq!((d.node.as_str(), d.name.as_str()), ("node1", "nvme1n1"));
+    }
+}
@@ -0,0 +1,256 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+
+use yangtze_apis::{
+    v1::{now, NamespaceName, VersionKind, YangtzeError},
+    v1alpha1::node::{self, Node, PowerAction, PowerState, PowerStatus},
+};
+use yangtze_client::YangtzeClient;
+
+use crate::bmc::{self, Credentials, Driver};
+use crate::framework::Controller;
+
+/// Applies `spec.power` of the Nodes through their BMC and reports the
+/// observed power; `on` and `off` are kept, `cycle` is applied once.
+#[derive(Clone)]
+pub struct PowerController {
+    /// The directory of the credentials of the BMCs, by name.
+    pub credentials: PathBuf,
+}
+
+#[async_trait]
+impl Controller<Node> for PowerController {
+    async fn execute(&self, client: YangtzeClient, n: Node) -> Result<(), YangtzeError> {
+        let (Some(address), Some(bmc), Some(status)) = (
+            n.spec.bmc_address.clone(),
+            n.spec.bmc.clone(),
+            n.status.as_ref(),
+        ) else {
+            return Ok(());
+        };
+
+        let last = status.power.clone().unwrap_or(PowerStatus {
+            state: PowerState::Unknown,
+            applied: None,
+            reason: None,
+            last_transition: 0,
+        });
+        let mut power = last.clone();
+
+        // Replaced by the Node as updated before a power cycle.
+        let mut n = n;
+        let driver = Credentials::read(&self.credentials, &bmc.credentials)
+            .and_then(|c| bmc::new(&address, &bmc, &c));
+        let res = match driver {
+            Ok(driver) => {
+                self.apply(&client, &mut n, driver.as_ref(), &mut power)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        match res {
+            Ok(()) => power.reason = None,
+            Err(e) => {
+                tracing::error!("Failed to control the power of Node <{}>: {}", n, e);
+                power.state = PowerState::Unknown;
+                power.reason = Some(e.to_string());
+            }
+        }
+
+        if power.state != last.state {
+            power.last_transition = now();
+        }
+        let Some(status) = n.status.as_mut() else {
+            return Ok(());
+        };
+        if status.power.as_ref() == Some(&power) {
+            return Ok(());
+        }
+
+        status.power = Some(power);
+        let _n = client.update::<Node>(n).await?;
+
+        Ok(())
+    }
+
+    fn get_version_kind(&self) -> VersionKind {
+        node::VERSION_KIND.clone()
+    }
+}
+
+impl PowerController {
+    async fn apply(
+        &self,
+        client: &YangtzeClient,
+        n: &mut Node,
+        driver: &dyn Driver,
+        power: &mut PowerStatus,
+    ) -> Result<(), YangtzeError> {
+        power.state = driver.power_state().await?;
+
+        let Some(action) = n.spec.power else {
+            return Ok(());
+        };
+        let required = required(action, power);
+
+        if required && action == PowerAction::Cycle {
+            // Persisted before the BMC is called, so that a failure to
+            // report the status after does not cycle the Node again.
+            let claimed;
+            (*n, claimed) = self.claim(client, n, power).await?;
+            if !claimed {
+                *power = n
+                    .status
+                    .as_ref()
+                    .and_then(|s| s.power.clone())
+                    .unwrap_or(power.clone());
+                return Ok(());
+            }
+        }
+
+        if required {
+            tracing::info!("Power {} Node <{}>, which is {}.", action, n, power.state);
+            driver.set_power(action).await?;
+            power.state = driver.power_state().await?;
+        }
+        power.applied = Some(action);
+
+        Ok(())
+    }
+
+    /// Records a power cycle as applied on the latest Node; returns the Node
+    /// updated, and whether the cycle is still to be applied, i.e. it was
+    /// not applied or cancelled meanwhile.
+    async fn claim(
+        &self,
+        client: &YangtzeClient,
+        n: &Node,
+        power: &PowerStatus,
+    ) -> Result<(Node, bool), YangtzeError> {
+        let mut latest = client
+            .list::<Node>(NamespaceName {
+                namespace: Some(n.meta_data.namespace.clone()),
+                name: Some(n.meta_data.name.clone()),
+            })
+            .await?
+            .pop()
+            .ok_or(YangtzeError::GeneralError(format!(
+                "Node <{}> not found",
+                n
+            )))?;
+
+        if !claim(&mut latest, power) {
+            return Ok((latest, false));
+        }
+        let latest = client.update::<Node>(latest).await?;
+
+        Ok((latest, true))
+    }
+}
+
+/// Whether the action is to be applied to a Node of the observed power.
+fn required(action: PowerAction, power: &PowerStatus) -> bool {
+    match action {
+        PowerAction::On => power.state != PowerState::On,
+        PowerAction::Off => power.state != PowerState::Off,
+        PowerAction::Cycle => power.applied != Some(PowerAction::Cycle),
+    }
+}
+
+/// Records a power cycle as applied on the latest Node, unless it was
+/// applied or cancelled meanwhile; returns whether it was recorded.
+fn claim(latest: &mut Node, power: &PowerStatus) -> bool {
+    let Some(status) = latest.status.as_mut() else {
+        return false;
+    };
+    let applied = status.power.as_ref().and_then(|p| p.applied);
+    if latest.spec.power != Some(PowerAction::Cycle) || applied == Some(PowerAction::Cycle) {
+        return false;
+    }
+
+    let mut claimed = power.clone();
+    claimed.applied = Some(PowerAction::Cycle);
+    status.power = Some(claimed);
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn power(state: PowerState, applied: Option<PowerAction>) -> PowerStatus {
+        PowerStatus {
+            state,
+            applied,
+            reason: None,
+            last_transition: 0,
+        }
+    }
+
+    fn node(action: Option<&str>, applied: Option<&str>) -> Node {
+        serde_json::from_value(json!({
+            "meta_data": {"kind": "node", "namespace": "default", "name": "n1", "labels": [], "version": 0},
+            "spec": {"hostname": "n1", "serial": "s1", "power": action},
+            "status": {"state": "ready", "power": {"state": "on", "applied": applied, "last_transition": 0}},
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn require_action() {
+        let on = power(PowerState::On, None);
+        assert!(!required(PowerAction::On, &on));
+        assert!(required(PowerAction::Off, &on));
+        assert!(required(PowerAction::Cycle, &on));
+        assert!(required(
+            PowerAction::On,
+            &power(PowerState::Unknown, Some(PowerAction::On))
+        ));
+
+        // A cycle is applied once, whatever the power.
+        let cycled = power(PowerState::Off, Some(PowerAction::Cycle));
+        assert!(!required(PowerAction::Cycle, &cycled));
+    }
+
+    #[test]
+    fn claim_cycle_once() {
+        let observed = power(PowerState::On, None);
+
+        let mut n = node(Some("cycle"), None);
+        assert!(claim(&mut n, &observed));
+        let claimed = n.status.as_ref().unwrap().power.clone().unwrap();
+        assert_eq!(claimed.applied, Some(PowerAction::Cycle));
+        assert_eq!(claimed.state, PowerState::On);
+
+        // Applied by another reconcile meanwhile.
+        assert!(!claim(&mut n, &observed));
+
+        // Cancelled meanwhile.
+        let mut n = node(Some("on"), None);
+        assert!(!claim(&mut n, &observed));
+        let mut n = node(None, None);
+        assert!(!claim(&mut n, &observed));
+        assert_eq!(n.status.unwrap().power.unwrap().applied, None);
+
+        // Requested again, e.g. by a Provision, after a cycle.
+        let mut n = node(Some("cycle"), Some("cycle"));
+        assert!(!claim(&mut n, &observed));
+    }
+}
@@ -0,0 +1,469 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use async_trait::async_trait;
+
+use yangtze_apis::{
+    v1::{now, NamespaceName, VersionKind, YangtzeError},
+    v1alpha1::{
+        node::{self, Node, PowerAction},
+        provision::{
+            self, ActionRequest, ActionResult, Provision, ProvisionAction, ProvisionPhase,
+            ProvisionStatus,
+        },
+    },
+};
+use yangtze_client::YangtzeClient;
+
+use crate::framework::Controller;
+
+/// Drives the Provisions through their phases; the agent of the Node runs
+/// the actions of each phase, but the reboot which is a power cycle through
+/// the BMC of the Node. The actions are retried when they fail or time out.
+#[derive(Clone)]
+pub struct ProvisionController {
+    /// Seconds an attempt of an action may take.
+    pub action_timeout: u64,
+    /// The attempts of an action before the Provision is `Failed`.
+    pub max_attempts: u32,
+}
+
+/// The actions of a phase, in order.
+fn actions(phase: ProvisionPhase) -> &'static [ProvisionAction] {
+    match phase {
+        ProvisionPhase::Inspecting => &[ProvisionAction::Inspect],
+        ProvisionPhase::Deploying => &[
+            ProvisionAction::Wipe,
+            ProvisionAction::WriteImage,
+            ProvisionAction::Reboot,
+        ],
+        ProvisionPhase::Deprovisioning => &[ProvisionAction::Wipe],
+        _ => &[],
+    }
+}
+
+#[async_trait]
+impl Controller<Provision> for ProvisionController {
+    async fn execute(&self, client: YangtzeClient, p: Provision) -> Result<(), YangtzeError> {
+        let now = now();
+        let status = p.status.clone().unwrap_or(ProvisionStatus {
+            phase: ProvisionPhase::Registered,
+            reason: None,
+            image: None,
+            step: 0,
+            action: None,
+            result: None,
+            last_transition: now,
+        });
+
+        let mut next = status.clone();
+        match status.phase {
+            ProvisionPhase::Registered => {
+                if self.node(&client, &p).await?.is_none() {
+                    next.reason = Some(format!("Node <{}> not found", p.spec.node));
+                } else {
+                    self.enter(&mut next, ProvisionPhase::Inspecting, now);
+                }
+            }
+            ProvisionPhase::Available if p.spec.image.is_some() => {
+                self.enter(&mut next, ProvisionPhase::Deploying, now);
+            }
+            ProvisionPhase::Deployed if p.spec.image != status.image => {
+                self.enter(&mut next, ProvisionPhase::Deprovisioning, now);
+            }
+            ProvisionPhase::Inspecting
+            | ProvisionPhase::Deploying
+            | ProvisionPhase::Deprovisioning => {
+                if rebooting(&next) {
+                    let node = self.node(&client, &p).await?;
+                    rebooted(&mut next, node.as_ref(), now);
+                }
+                self.progress(&p, &mut next, now);
+            }
+            _ => {}
+        }
+
+        // A reboot, or its retry, is requested before it is recorded, so
+        // that the Node is not cycled again by a stale request.
+        if rebooting(&next) && next.action != status.action {
+            self.cycle(&client, &p).await?;
+        }
+
+        if p.status.as_ref() == Some(&next) {
+            return Ok(());
+        }
+
+        if next.phase != status.phase {
+            tracing::info!(
+                "Provision <{}> of Node <{}> is {}.",
+                p,
+                p.spec.node,
+                next.phase
+            );
+        }
+
+        let mut p = p;
+        p.status = Some(next);
+        let _p = client.update::<Provision>(p).await?;
+
+        Ok(())
+    }
+
+    fn get_version_kind(&self) -> VersionKind {
+        provision::VERSION_KIND.clone()
+    }
+}
+
+impl ProvisionController {
+    async fn node(
+        &self,
+        client: &YangtzeClient,
+        p: &Provision,
+    ) -> Result<Option<Node>, YangtzeError> {
+        Ok(client
+            .clone()
+            .version(node::VERSION_KIND.version)
+            .kind(node::VERSION_KIND.kind)
+            .list::<Node>(NamespaceName {
+                namespace: Some(p.meta_data.namespace.clone()),
+                name: Some(p.spec.node.clone()),
+            })
+            .await?
+            .pop())
+    }
+
+    /// Requests a power cycle of the Node through its BMC; a cycle applied
+    /// before is cleared so that the PowerController applies it again.
+    async fn cycle(&self, client: &YangtzeClient, p: &Provision) -> Result<(), YangtzeError> {
+        // The reboot fails, for the Node is not found.
+        let Some(mut n) = self.node(client, p).await? else {
+            return Ok(());
+        };
+
+        tracing::info!("Power cycle Node <{}> for Provision <{}>.", n, p);
+        n.spec.power = Some(PowerAction::Cycle);
+        if let Some(power) = n.status.as_mut().and_then(|s| s.power.as_mut()) {
+            power.applied = None;
+        }
+        let _n = client
+            .clone()
+            .version(node::VERSION_KIND.version)
+            .kind(node::VERSION_KIND.kind)
+            .update::<Node>(n)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Enters a phase, and requests its first action if any.
+    fn enter(&self, status: &mut ProvisionStatus, phase: ProvisionPhase, now: u64) {
+        status.phase = phase;
+        status.reason = None;
+        status.step = 0;
+        status.last_transition = now;
+
+        match actions(phase).first() {
+            Some(action) => self.request(status, *action, 1, now),
+            None => status.action = None,
+        }
+    }
+
+    fn request(
+        &self,
+        status: &mut ProvisionStatus,
+        action: ProvisionAction,
+        attempt: u32,
+        now: u64,
+    ) {
+        // The last id is the one of the current action, or of the last result.
+        let id = status
+            .action
+            .as_ref()
+            .map(|a| a.id)
+            .unwrap_or_default()
+            .max(status.result.as_ref().map(|r| r.id).unwrap_or_default())
+            + 1;
+        status.action = Some(ActionRequest {
+            id,
+            action,
+            attempt,
+            started: now,
+        });
+    }
+
+    /// Moves to the next action of the phase, or to the next phase, when the
+    /// agent reported the result of the current action.
+    fn progress(&self, p: &Provision, status: &mut ProvisionStatus, now: u64) {
+        let Some(action) = status.action.clone() else {
+            return;
+        };
+
+        let result = status
+            .result
+            .as_ref()
+            .filter(|r| r.id == action.id && r.attempt == action.attempt);
+
+        let error = match result {
+            Some(r) => r.error.clone(),
+            None if now.saturating_sub(action.started) > self.action_timeout => Some(format!(
+                "{} did not finish in {}s",
+                action.action, self.action_timeout
+            )),
+            None => return,
+        };
+
+        if let Some(e) = error {
+            if action.attempt >= self.max_attempts {
+                status.phase = ProvisionPhase::Failed;
+                status.reason = Some(format!(
+                    "{} failed after {} attempts: {}",
+                    action.action, action.attempt, e
+                ));
+                status.last_transition = now;
+                status.action = None;
+            } else {
+                tracing::warn!(
+                    "Retry {} of Provision <{}>, attempt {}: {}",
+                    action.action,
+                    p,
+                    action.attempt + 1,
+                    e
+                );
+                status.reason = Some(e);
+                self.request(status, action.action, action.attempt + 1, now);
+            }
+            return;
+        }
+
+        status.step += 1;
+        if let Some(next) = actions(status.phase).get(status.step) {
+            status.reason = None;
+            self.request(status, *next, 1, now);
+            return;
+        }
+
+        match status.phase {
+            ProvisionPhase::Deploying => {
+                status.image = p.spec.image.clone();
+                self.enter(status, ProvisionPhase::Deployed, now);
+            }
+            ProvisionPhase::Deprovisioning => {
+                status.image = None;
+                self.enter(status, ProvisionPhase::Available, now);
+            }
+            _ => self.enter(status, ProvisionPhase::Available, now),
+        }
+    }
+}
+
+/// Whether the current action is a reboot without result.
+fn rebooting(status: &ProvisionStatus) -> bool {
+    status.action.as_ref().is_some_and(|a| {
+        a.action == ProvisionAction::Reboot
+            && status
+                .result
+                .as_ref()
+                .is_none_or(|r| r.id != a.id || r.attempt != a.attempt)
+    })
+}
+
+/// Records the result of a reboot once the PowerController cycled the Node.
+fn rebooted(status: &mut ProvisionStatus, node: Option<&Node>, now: u64) {
+    let Some(action) = &status.action else {
+        return;
+    };
+
+    let error = match node {
+        None => Some("the Node is not found".to_string()),
+        Some(n) if n.spec.bmc.is_none() || n.spec.bmc_address.is_none() => {
+            Some(format!("Node <{}> has no BMC to reboot it", n))
+        }
+        Some(n) => {
+            let applied = n
+                .status
+                .as_ref()
+                .and_then(|s| s.power.as_ref())
+                .and_then(|p| p.applied);
+            if n.spec.power != Some(PowerAction::Cycle) || applied != Some(PowerAction::Cycle) {
+                return;
+            }
+            None
+        }
+    };
+
+    status.result = Some(ActionResult {
+        id: action.id,
+        attempt: action.attempt,
+        error,
+        finished: now,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const CONTROLLER: ProvisionController = ProvisionController {
+        action_timeout: 600,
+        max_attempts: 2,
+    };
+
+    fn provision() -> Provision {
+        serde_json::from_value(json!({
+            "meta_data": {"kind": "provision", "namespace": "default", "name": "p1", "labels": [], "version": 0},
+            "spec": {"node": "n1", "image": "http://mirror/ubuntu.img"},
+        }))
+        .unwrap()
+    }
+
+    fn node(bmc: bool, applied: Option<&str>) -> Node {
+        let (address, bmc) = match bmc {
+            true => (json!("10.0.1.1"), json!({"driver": "redfish"})),
+            false => (json!(null), json!(null)),
+        };
+        serde_json::from_value(json!({
+            "meta_data": {"kind": "node", "namespace": "default", "name": "n1", "labels": [], "version": 0},
+            "spec": {"hostname": "n1", "serial": "s1", "bmc_address": address, "bmc": bmc, "power": "cycle"},
+            "status": {"state": "ready", "power": {"state": "on", "applied": applied, "last_transition": 0}},
+        }))
+        .unwrap()
+    }
+
+    fn status(phase: ProvisionPhase, now: u64) -> ProvisionStatus {
+        let mut status = ProvisionStatus {
+            phase: ProvisionPhase::Registered,
+            reason: None,
+            image: None,
+            step: 0,
+            action: None,
+            result: None,
+            last_transition: 0,
+        };
+        CONTROLLER.enter(&mut status, phase, now);
+        status
+    }
+
+    /// Reports the result of the current action as the agent would.
+    fn report(status: &mut ProvisionStatus, error: Option<&str>, now: u64) {
+        let action = status.action.as_ref().unwrap();
+        status.result = Some(ActionResult {
+            id: action.id,
+            attempt: action.attempt,
+            error: error.map(str::to_string),
+            finished: now,
+        });
+    }
+
+    fn action(status: &ProvisionStatus) -> (ProvisionAction, u32) {
+        let action = status.action.as_ref().unwrap();
+        (action.action, action.attempt)
+    }
+
+    #[test]
+    fn deploy_through_actions() {
+        let p = provision();
+        let mut status = status(ProvisionPhase::Deploying, 100);
+        assert_eq!(status.last_transition, 100);
+        assert_eq!(action(&status), (ProvisionAction::Wipe, 1));
+
+        // No result yet.
+        CONTROLLER.progress(&p, &mut status, 200);
+        assert_eq!(action(&status), (ProvisionAction::Wipe, 1));
+
+        report(&mut status, None, 200);
+        CONTROLLER.progress(&p, &mut status, 200);
+        assert_eq!(action(&status), (ProvisionAction::WriteImage, 1));
+        assert_eq!(status.action.as_ref().unwrap().started, 200);
+
+        report(&mut status, None, 300);
+        CONTROLLER.progress(&p, &mut status, 300);
+        assert_eq!(action(&status), (ProvisionAction::Reboot, 1));
+        assert!(rebooting(&status));
+
+        report(&mut status, None, 400);
+        assert!(!rebooting(&status));
+        CONTROLLER.progress(&p, &mut status, 400);
+        assert_eq!(status.phase, ProvisionPhase::Deployed);
+        assert_eq!(status.image, p.spec.image);
+        assert_eq!(status.last_transition, 400);
+        assert!(status.action.is_none());
+    }
+
+    #[test]
+    fn retry_failed_action() {
+        let p = provision();
+        let mut status = status(ProvisionPhase::Inspecting, 100);
+        let id = status.action.as_ref().unwrap().id;
+
+        report(&mut status, Some("no inventory"), 200);
+        CONTROLLER.progress(&p, &mut status, 200);
+        assert_eq!(action(&status), (ProvisionAction::Inspect, 2));
+        assert!(status.action.as_ref().unwrap().id > id);
+        assert_eq!(status.reason.as_deref(), Some("no inventory"));
+
+        report(&mut status, None, 300);
+        CONTROLLER.progress(&p, &mut status, 300);
+        assert_eq!(status.phase, ProvisionPhase::Available);
+        assert!(status.reason.is_none());
+    }
+
+    #[test]
+    fn time_out_and_fail() {
+        let p = provision();
+        let mut status = status(ProvisionPhase::Deprovisioning, 100);
+
+        CONTROLLER.progress(&p, &mut status, 700);
+        assert_eq!(action(&status), (ProvisionAction::Wipe, 1));
+
+        CONTROLLER.progress(&p, &mut status, 701);
+        assert_eq!(action(&status), (ProvisionAction::Wipe, 2));
+        assert_eq!(
+            status.reason.as_deref(),
+            Some("wipe did not finish in 600s")
+        );
+
+        CONTROLLER.progress(&p, &mut status, 1302);
+        assert_eq!(status.phase, ProvisionPhase::Failed);
+        assert_eq!(
+            status.reason.as_deref(),
+            Some("wipe failed after 2 attempts: wipe did not finish in 600s")
+        );
+        assert_eq!(status.last_transition, 1302);
+        assert!(status.action.is_none());
+    }
+
+    #[test]
+    fn reboot_through_bmc() {
+        let mut status = status(ProvisionPhase::Deploying, 100);
+        status.step = 2;
+        CONTROLLER.request(&mut status, ProvisionAction::Reboot, 1, 100);
+
+        // Not cycled by the PowerController yet.
+        rebooted(&mut status, Some(&node(true, None)), 200);
+        assert!(status.result.is_none());
+
+        rebooted(&mut status, Some(&node(true, Some("cycle"))), 200);
+        let result = status.result.clone().unwrap();
+        assert_eq!(result.id, status.action.as_ref().unwrap().id);
+        assert!(result.error.is_none());
+
+        status.result = None;
+        rebooted(&mut status, Some(&node(false, None)), 200);
+        assert_eq!(
+            status.result.unwrap().error.as_deref(),
+            Some("Node <n1> has no BMC to reboot it")
+        );
+    }
+}
@@ -0,0 +1,307 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::fmt::Write;
+use std::net::Ipv4Addr;
+
+use yangtze_apis::v1alpha1::switch::SwitchRole;
+
+use crate::switches::intent::Resolved;
+
+/// The global administrator of the route targets of the VNIs: as the ASNs
+/// of the VTEPs differ with eBGP, the automatic route targets do not match.
+/// A private 2-byte ASN, so that any VNI fits in the local administrator.
+pub const RT_ASN: u32 = 64512;
+
+/// The peer group of the sessions to the upper tiers, or to the lower tier
+/// of switches.
+pub const FABRIC: &str = "fabric";
+/// The peer group of the sessions of a leaf to the hosts speaking BGP.
+pub const HOSTS: &str = "hosts";
+
+/// The grammar the rendered configurations are checked against.
+const GRAMMAR: &str = include_str!("grammar.txt");
+
+pub fn route_target(vni: u32) -> String {
+    format!("{}:{}", RT_ASN, vni)
+}
+
+/// A BGP speaker of the Fabric.
+pub struct Speaker<'a> {
+    pub hostname: &'a str,
+    pub asn: u32,
+    pub router_id: Ipv4Addr,
+    /// The interfaces of the unnumbered sessions, by peer group.
+    pub groups: Vec<(&'static str, Vec<String>)>,
+    /// The VNIs of a VTEP, advertised with their route targets; the spines
+    /// only relay the EVPN routes.
+    pub vnis: Option<Vec<u32>>,
+}
+
+/// The speaker of a switch: the leaves peer with the hosts speaking BGP, and
+/// advertise the VNIs of their VLANs.
+pub fn switch(sw: &Resolved) -> Speaker<'_> {
+    let mut groups = vec![(
+        FABRIC,
+        sw.fabric_ports.iter().map(|p| p.name.clone()).collect(),
+    )];
+    let hosts: Vec<String> = sw
+        .host_ports
+        .iter()
+        .filter(|p| p.peer_asn.is_some())
+        .map(|p| p.name.clone())
+        .collect();
+    if !hosts.is_empty() {
+        groups.push((HOSTS, hosts));
+    }
+
+    Speaker {
+        hostname: &sw.hostname,
+        asn: sw.asn,
+        router_id: sw.loopback,
+        groups,
+        vnis: match sw.role {
+            SwitchRole::Leaf => Some(sw.vlans.values().copied().collect()),
+            _ => None,
+        },
+    }
+}
+
+/// The speaker of a host peering with its leaves, and a VTEP of all the VNIs.
+pub fn host(
+    hostname: &str,
+    asn: u32,
+    router_id: Ipv4Addr,
+    uplinks: Vec<String>,
+    vnis: Vec<u32>,
+) -> Speaker<'_> {
+    Speaker {
+        hostname,
+        asn,
+        router_id,
+        groups: vec![(FABRIC, uplinks)],
+        vnis: Some(vnis),
+    }
+}
+
+/// Renders the FRR configuration of a speaker: eBGP sessions unnumbered
+/// over its links, the loopback advertised, and the L2VPN EVPN address
+/// family with the route targets of the VNIs.
+pub fn render(s: &Speaker) -> String {
+    let mut c = String::new();
+    let _ = writeln!(c, "frr defaults datacenter");
+    let _ = writeln!(c, "hostname {}", s.hostname);
+    let _ = writeln!(c, "log syslog informational");
+    let _ = writeln!(c, "!");
+
+    let _ = writeln!(c, "router bgp {}", s.asn);
+    let _ = writeln!(c, " bgp router-id {}", s.router_id);
+    let _ = writeln!(c, " no bgp ebgp-requires-policy");
+    let _ = writeln!(c, " bgp bestpath as-path multipath-relax");
+    for (group, interfaces) in &s.groups {
+        let _ = writeln!(c, " neighbor {} peer-group", group);
+        let _ = writeln!(c, " neighbor {} remote-as external", group);
+        for i in interfaces {
+            let _ = writeln!(c, " neighbor {} interface peer-group {}", i, group);
+        }
+    }
+    let _ = writeln!(c, " !");
+
+    let _ = writeln!(c, " address-family ipv4 unicast");
+    let _ = writeln!(c, "  network {}/32", s.router_id);
+    let _ = writeln!(c, " exit-address-family");
+    let _ = writeln!(c, " !");
+
+    let _ = writeln!(c, " address-family l2vpn evpn");
+    for (group, _) in &s.groups {
+        let _ = writeln!(c, "  neighbor {} activate", group);
+    }
+    if let Some(vnis) = &s.vnis {
+        let _ = writeln!(c, "  advertise-all-vni");
+        for vni in vnis {
+            let _ = writeln!(c, "  vni {}", vni);
+            let _ = writeln!(c, "   route-target import {}", route_target(*vni));
+            let _ = writeln!(c, "   route-target export {}", route_target(*vni));
+            let _ = writeln!(c, "  exit-vni");
+        }
+    }
+    let _ = writeln!(c, " exit-address-family");
+    let _ = writeln!(c, "exit");
+    let _ = writeln!(c, "!");
+
+    c
+}
+
+/// Checks each command of a configuration against the grammar, in the node
+/// of the commands before it.
+pub fn check(config: &str) -> Result<(), String> {
+    let grammar = grammar()?;
+
+    let mut node = "config";
+    for (n, line) in config.lines().enumerate() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.is_empty() || tokens == ["!"] {
+            continue;
+        }
+
+        let command = grammar
+            .iter()
+            .find(|c| c.node == node && matches(&c.tokens, &tokens))
+            .ok_or(format!(
+                "line {}: unknown command <{}> in node <{}>",
+                n + 1,
+                line.trim(),
+                node
+            ))?;
+        if let Some(next) = command.next {
+            node = next;
+        }
+    }
+
+    Ok(())
+}
+
+/// A command of the grammar.
+struct Command {
+    node: &'static str,
+    tokens: Vec<&'static str>,
+    /// The node entered by the command.
+    next: Option<&'static str>,
+}
+
+fn grammar() -> Result<Vec<Command>, String> {
+    let mut commands = vec![];
+    for line in GRAMMAR.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (node, rest) = line
+            .split_once(':')
+            .ok_or(format!("invalid grammar <{}>", line))?;
+        let (command, next) = match rest.split_once("->") {
+            Some((command, next)) => (command, Some(next.trim())),
+            None => (rest, None),
+        };
+        commands.push(Command {
+            node: node.trim(),
+            tokens: command.split_whitespace().collect(),
+            next,
+        });
+    }
+
+    Ok(commands)
+}
+
+fn matches(grammar: &[&str], tokens: &[&str]) -> bool {
+    grammar.len() == tokens.len() && grammar.iter().zip(tokens).all(|(g, t)| token(g, t))
+}
+
+fn token(g: &str, t: &str) -> bool {
+    if let Some(alternatives) = g.strip_prefix('<').and_then(|g| g.strip_suffix('>')) {
+        return alternatives.split('|').any(|a| token(a, t));
+    }
+    if let Some((min, max)) = g
+        .strip_prefix('(')
+        .and_then(|g| g.strip_suffix(')'))
+        .and_then(|g| g.split_once('-'))
+    {
+        return match (min.parse::<u64>(), max.parse::<u64>(), t.parse::<u64>()) {
+            (Ok(min), Ok(max), Ok(v)) => min <= v && v <= max,
+            _ => false,
+        };
+    }
+
+    match g {
+        "WORD" => !t.is_empty(),
+        "A.B.C.D" => t.parse::<Ipv4Addr>().is_ok(),
+        "A.B.C.D/M" => t.split_once('/').is_some_and(|(a, m)| {
+            a.parse::<Ipv4Addr>().is_ok() && m.parse::<u8>().is_ok_and(|m| m <= 32)
+        }),
+        "ASN:NN_OR_IP-ADDRESS:NN" => t.rsplit_once(':').is_some_and(|(admin, nn)| {
+            (admin.parse::<u32>().is_ok() || admin.parse::<Ipv4Addr>().is_ok())
+                && nn.parse::<u32>().is_ok()
+        }),
+        _ => g == t,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::switches::intent::fixtures;
+
+    /// Checks the rendered configuration and compares it with its golden
+    /// file; set `UPDATE_GOLDEN` to rewrite the golden file instead.
+    fn assert_golden(s: &Speaker, name: &str) {
+        let path = fixtures::golden(name);
+        let rendered = render(s);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::write(&path, &rendered).unwrap();
+        }
+
+        assert_eq!(check(&rendered), Ok(()));
+        assert_eq!(rendered, fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn render_leaf() {
+        assert_golden(&switch(&fixtures::leaf()), "frr/leaf1.conf");
+    }
+
+    #[test]
+    fn render_spine() {
+        assert_golden(&switch(&fixtures::spine()), "frr/spine1.conf");
+    }
+
+    #[test]
+    fn render_host() {
+        let host = host(
+            "h1",
+            65201,
+            Ipv4Addr::new(10, 255, 1, 1),
+            vec!["eth0".to_string(), "eth1".to_string()],
+            vec![100, 200],
+        );
+
+        assert_golden(&host, "frr/h1.conf");
+    }
+
+    #[test]
+    fn check_unknown_command() {
+        let config =
+            "router bgp 65001\n neighbor fabric peer-group\n bgp listen range 10.0.0.0/8\n";
+
+        assert_eq!(
+            check(config),
+            Err("line 3: unknown command <bgp listen range 10.0.0.0/8> in node <bgp>".to_string())
+        );
+    }
+
+    #[test]
+    fn check_command_out_of_node() {
+        // A VNI is only valid in the EVPN address family.
+        let config = "router bgp 65001\n address-family ipv4 unicast\n  vni 100\n";
+
+        assert!(check(config).unwrap_err().starts_with("line 3:"));
+    }
+
+    #[test]
+    fn check_invalid_arguments() {
+        assert!(check("router bgp 4294967296\n").is_err());
+        assert!(check("router bgp 65001\n bgp router-id 10.0.0.256\n").is_err());
+        assert!(check("hostname\n").is_err());
+    }
+}
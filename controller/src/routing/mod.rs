@@ -0,0 +1,140 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::net::Ipv4Addr;
+
+use async_trait::async_trait;
+
+use yangtze_apis::{
+    v1::{NamespaceName, VersionKind, YangtzeError},
+    v1alpha1::{
+        fabric::{self, Fabric},
+        node::{self, Node},
+        switch::{self, Switch},
+        vpc::{self, Vpc},
+    },
+};
+use yangtze_client::YangtzeClient;
+
+use crate::framework::Controller;
+use crate::switches::intent;
+
+pub mod frr;
+
+/// Renders the FRR configurations of the Nodes of a Fabric speaking BGP to
+/// their leaves, which are installed by their agents.
+#[derive(Clone)]
+pub struct RoutingController {}
+
+#[async_trait]
+impl Controller<Fabric> for RoutingController {
+    async fn execute(&self, client: YangtzeClient, f: Fabric) -> Result<(), YangtzeError> {
+        let Some(topology) = f.status.as_ref().and_then(|s| s.topology.as_ref()) else {
+            return Ok(());
+        };
+        let nn = NamespaceName {
+            namespace: Some(f.meta_data.namespace.clone()),
+            name: None,
+        };
+
+        let switches: Vec<String> = client
+            .clone()
+            .version(switch::VERSION_KIND.version)
+            .kind(switch::VERSION_KIND.kind)
+            .list::<Switch>(nn.clone())
+            .await?
+            .into_iter()
+            .filter(|sw| sw.spec.fabric.as_deref() == Some(&f.meta_data.name))
+            .map(|sw| sw.meta_data.name)
+            .collect();
+        let nodes: Vec<Node> = client
+            .clone()
+            .version(node::VERSION_KIND.version)
+            .kind(node::VERSION_KIND.kind)
+            .list(nn.clone())
+            .await?;
+        let vpcs: Vec<Vpc> = client
+            .clone()
+            .version(vpc::VERSION_KIND.version)
+            .kind(vpc::VERSION_KIND.kind)
+            .list(nn)
+            .await?;
+        let vnis = intent::vnis(&vpcs);
+
+        for mut n in nodes {
+            let mut uplinks: Vec<String> = topology
+                .links
+                .iter()
+                .filter(|l| l.from == n.meta_data.name && switches.contains(&l.to))
+                .map(|l| l.from_port.clone())
+                .collect();
+            if uplinks.is_empty() {
+                continue;
+            }
+            uplinks.sort();
+            uplinks.dedup();
+
+            let config = match (n.spec.asn, &n.spec.loopback) {
+                (None, _) => None,
+                (Some(asn), loopback) => {
+                    let rendered = loopback
+                        .as_deref()
+                        .ok_or("the Node has no loopback".to_string())
+                        .and_then(|l| {
+                            l.parse::<Ipv4Addr>()
+                                .map_err(|e| format!("invalid loopback: {}", e))
+                        })
+                        .and_then(|router_id| {
+                            let hostname = match n.spec.hostname.as_str() {
+                                "" => n.meta_data.name.as_str(),
+                                h => h,
+                            };
+                            let speaker =
+                                frr::host(hostname, asn, router_id, uplinks, vnis.clone());
+                            let config = frr::render(&speaker);
+                            frr::check(&config).map(|_| config)
+                        });
+                    match rendered {
+                        Ok(config) => Some(config),
+                        Err(e) => {
+                            tracing::error!(
+                                "Failed to render the FRR configuration of Node <{}>: {}",
+                                n,
+                                e
+                            );
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            if n.spec.frr_config == config {
+                continue;
+            }
+
+            tracing::info!("Render the FRR configuration of Node <{}>.", n);
+            n.spec.frr_config = config;
+            let _n = client
+                .clone()
+                .version(node::VERSION_KIND.version)
+                .kind(node::VERSION_KIND.kind)
+                .update::<Node>(n)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    fn get_version_kind(&self) -> VersionKind {
+        fabric::VERSION_KIND.clone()
+    }
+}
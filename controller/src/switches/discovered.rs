@@ -10,3 +10,217 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use async_trait::async_trait;
+
+use yangtze_apis::{
+    v1::{Metadata, NamespaceName, VersionKind, YangtzeError},
+    v1alpha1::{
+        node::{self, LldpNeighbor, Node},
+        switch::{self, Switch, SwitchLink, SwitchSpec, SwitchState, SwitchStatus},
+    },
+};
+use yangtze_client::YangtzeClient;
+
+use crate::framework::Controller;
+
+/// Builds the links between the Nodes and the switch ports from the LLDP
+/// neighbors reported by the agents, and creates the Switches not known yet.
+#[derive(Clone)]
+pub struct DiscoveryController {}
+
+#[async_trait]
+impl Controller<Node> for DiscoveryController {
+    async fn execute(&self, client: YangtzeClient, n: Node) -> Result<(), YangtzeError> {
+        let neighbors = n
+            .status
+            .as_ref()
+            .map(|s| s.neighbors.clone())
+            .unwrap_or_default();
+
+        // The neighbors of the Node by chassis id.
+        let mut chassis: BTreeMap<String, Vec<LldpNeighbor>> = BTreeMap::new();
+        for nb in neighbors {
+            chassis.entry(nb.chassis_id.clone()).or_default().push(nb);
+        }
+
+        let client = client
+            .version(switch::VERSION_KIND.version)
+            .kind(switch::VERSION_KIND.kind);
+        let switches = client
+            .list::<Switch>(NamespaceName {
+                namespace: Some(n.meta_data.namespace.clone()),
+                name: None,
+            })
+            .await?;
+
+        // The Switches are matched by chassis id, their names are only taken.
+        let mut names = BTreeSet::new();
+        for sw in switches {
+            names.insert(sw.meta_data.name.clone());
+            let nbs = chassis.remove(&sw.spec.chassis_id).unwrap_or_default();
+            update_links(&client, &n, sw, &nbs).await?;
+        }
+
+        // The remaining chassis are not known yet.
+        for (chassis_id, nbs) in chassis {
+            let sw = discovered(&n.meta_data.namespace, &chassis_id, &nbs, &names);
+            names.insert(sw.meta_data.name.clone());
+            tracing::info!("Discovered Switch <{}> from Node <{}>.", sw.meta_data, n);
+            let sw = client.create(sw).await?;
+            update_links(&client, &n, sw, &nbs).await?;
+        }
+
+        Ok(())
+    }
+
+    fn get_version_kind(&self) -> VersionKind {
+        node::VERSION_KIND.clone()
+    }
+}
+
+/// Replaces the links of the Node on the Switch by the ones of `nbs`.
+async fn update_links(
+    client: &YangtzeClient,
+    n: &Node,
+    mut sw: Switch,
+    nbs: &[LldpNeighbor],
+) -> Result<(), YangtzeError> {
+    let status = sw.status.get_or_insert(SwitchStatus {
+        state: SwitchState::Discovered,
+        system_name: String::new(),
+        links: vec![],
+        neighbors: vec![],
+        reason: None,
+        ports: BTreeMap::new(),
+        sessions: BTreeMap::new(),
+    });
+
+    let mut links: Vec<_> = status
+        .links
+        .iter()
+        .filter(|l| l.node != n.meta_data.name)
+        .cloned()
+        .collect();
+    links.extend(nbs.iter().map(|nb| SwitchLink {
+        port: nb.port_id.clone(),
+        node: n.meta_data.name.clone(),
+        interface: nb.interface.clone(),
+    }));
+    links.sort_by(|a, b| a.port.cmp(&b.port));
+
+    let system_name = nbs
+        .iter()
+        .map(|nb| nb.system_name.clone())
+        .find(|s| !s.is_empty())
+        .unwrap_or(status.system_name.clone());
+
+    if links == status.links && system_name == status.system_name {
+        return Ok(());
+    }
+
+    status.links = links;
+    status.system_name = system_name;
+    let _sw = client.update(sw).await?;
+
+    Ok(())
+}
+
+/// A Switch for a chassis not known yet, named by its system name, or by its
+/// chassis id if not set; a name in `taken` gets the chassis id as suffix.
+fn discovered(
+    namespace: &str,
+    chassis_id: &str,
+    nbs: &[LldpNeighbor],
+    taken: &BTreeSet<String>,
+) -> Switch {
+    let system_name = nbs
+        .iter()
+        .map(|nb| nb.system_name.as_str())
+        .find(|s| !s.is_empty())
+        .unwrap_or_default();
+
+    let chassis = sanitize(chassis_id);
+    let mut name = match system_name {
+        "" => chassis.clone(),
+        s => sanitize(s),
+    };
+    if taken.contains(&name) {
+        name = format!("{}-{}", name, chassis);
+    }
+
+    Switch {
+        meta_data: Metadata {
+            uuid: None,
+            kind: switch::VERSION_KIND.kind.to_string(),
+            namespace: namespace.to_string(),
+            name,
+            labels: vec![],
+            version: 0,
+        },
+        spec: SwitchSpec {
+            chassis_id: chassis_id.to_string(),
+            fabric: None,
+            role: None,
+            management_address: nbs.iter().find_map(|nb| nb.management_address.clone()),
+            asn: None,
+            loopback: None,
+            driver: None,
+        },
+        status: Some(SwitchStatus {
+            state: SwitchState::Discovered,
+            system_name: system_name.to_string(),
+            links: vec![],
+            neighbors: vec![],
+            reason: None,
+            ports: BTreeMap::new(),
+            sessions: BTreeMap::new(),
+        }),
+    }
+}
+
+fn sanitize(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn neighbor(chassis_id: &str, system_name: &str) -> LldpNeighbor {
+        LldpNeighbor {
+            interface: "eth0".to_string(),
+            chassis_id: chassis_id.to_string(),
+            port_id: "Ethernet0".to_string(),
+            port_description: String::new(),
+            system_name: system_name.to_string(),
+            management_address: Some("10.0.0.1".to_string()),
+        }
+    }
+
+    #[test]
+    fn name_by_system_name() {
+        let nbs = [neighbor("00:11:22:33:44:55", "Leaf.1")];
+        let sw = discovered("default", "00:11:22:33:44:55", &nbs, &BTreeSet::new());
+        assert_eq!(sw.meta_data.name, "leaf-1");
+        assert_eq!(sw.spec.chassis_id, "00:11:22:33:44:55");
+        assert_eq!(sw.spec.management_address.as_deref(), Some("10.0.0.1"));
+
+        let nbs = [neighbor("00:11:22:33:44:55", "")];
+        let sw = discovered("default", "00:11:22:33:44:55", &nbs, &BTreeSet::new());
+        assert_eq!(sw.meta_data.name, "00-11-22-33-44-55");
+    }
+
+    #[test]
+    fn disambiguate_taken_name() {
+        let taken = BTreeSet::from(["leaf-1".to_string()]);
+        let nbs = [neighbor("00:11:22:33:44:66", "leaf-1")];
+        let sw = discovered("default", "00:11:22:33:44:66", &nbs, &taken);
+        assert_eq!(sw.meta_data.name, "leaf-1-00-11-22-33-44-66");
+    }
+}
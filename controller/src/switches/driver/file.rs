@@ -0,0 +1,186 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+use yangtze_apis::{v1::YangtzeError, v1alpha1::node::LldpNeighbor};
+
+use crate::routing::frr;
+use crate::switches::driver::{StateUpdate, SwitchDriver};
+use crate::switches::intent::Resolved;
+use crate::switches::{openconfig, sonic};
+
+/// A SONiC switch whose configuration is the files `config_db.json` and
+/// `frr.conf` in `<directory>/<switch>`, e.g. served to SONiC ZTP, or read
+/// back as a fake switch; its LLDP neighbors are read from `lldp.json` in
+/// the OpenConfig JSON of `/lldp/interfaces`.
+pub struct File {
+    path: PathBuf,
+}
+
+impl File {
+    pub fn new(directory: &str, switch: &str) -> Self {
+        File {
+            path: Path::new(directory).join(switch),
+        }
+    }
+}
+
+/// Replaces the file at `path` by `content` unless it is the same; returns
+/// whether it changed.
+fn replace(path: &Path, content: &str) -> Result<bool, YangtzeError> {
+    let io_err =
+        |e: std::io::Error| YangtzeError::GeneralError(format!("{}: {}", path.display(), e));
+
+    if fs::read_to_string(path).ok().as_deref() == Some(content) {
+        return Ok(false);
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(io_err)?;
+    }
+    // Replaced at once, so that the switch never reads a partial file.
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, content).map_err(io_err)?;
+    fs::rename(&tmp, path).map_err(io_err)?;
+
+    Ok(true)
+}
+
+#[async_trait]
+impl SwitchDriver for File {
+    async fn running(&self) -> Result<Value, YangtzeError> {
+        let config_db = fs::read_to_string(self.path.join("config_db.json"))
+            .ok()
+            .and_then(|c| serde_json::from_str::<Value>(&c).ok())
+            .unwrap_or(Value::Null);
+        let frr = fs::read_to_string(self.path.join("frr.conf")).ok();
+
+        Ok(json!({ "config_db": config_db, "frr": frr }))
+    }
+
+    async fn apply(&self, intent: &Resolved) -> Result<bool, YangtzeError> {
+        let frr = frr::render(&frr::switch(intent));
+        frr::check(&frr).map_err(YangtzeError::InvalidConfig)?;
+        let config_db = serde_json::to_string_pretty(&sonic::render(intent))? + "\n";
+
+        let db_changed = replace(&self.path.join("config_db.json"), &config_db)?;
+        let frr_changed = replace(&self.path.join("frr.conf"), &frr)?;
+
+        Ok(db_changed || frr_changed)
+    }
+
+    async fn neighbors(&self) -> Result<Vec<LldpNeighbor>, YangtzeError> {
+        let path = self.path.join("lldp.json");
+        let lldp = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Value::Null,
+            Err(e) => {
+                return Err(YangtzeError::GeneralError(format!(
+                    "{}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+
+        Ok(openconfig::neighbors(&lldp))
+    }
+
+    async fn subscribe(&self) -> Result<Option<mpsc::Receiver<StateUpdate>>, YangtzeError> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::switches::intent::fixtures;
+
+    #[tokio::test]
+    async fn apply_writes_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let driver = File::new(dir.path().to_str().unwrap(), "leaf1");
+        let leaf = fixtures::leaf();
+
+        assert!(driver.apply(&leaf).await.unwrap());
+        assert!(!driver.apply(&leaf).await.unwrap());
+
+        let written = fs::read_to_string(dir.path().join("leaf1/config_db.json")).unwrap();
+        let golden = fs::read_to_string(fixtures::golden("sonic/leaf1.json")).unwrap();
+        assert_eq!(written, golden);
+        assert!(!dir.path().join("leaf1/config_db.json.tmp").exists());
+
+        let running = driver.running().await.unwrap();
+        assert_eq!(
+            running["config_db"]["DEVICE_METADATA"]["localhost"]["hostname"],
+            "leaf1"
+        );
+        assert!(running["frr"]
+            .as_str()
+            .unwrap()
+            .contains("router bgp 65001"));
+    }
+
+    #[tokio::test]
+    async fn read_neighbors() {
+        let dir = tempfile::tempdir().unwrap();
+        let driver = File::new(dir.path().to_str().unwrap(), "leaf1");
+        assert!(driver.neighbors().await.unwrap().is_empty());
+
+        fs::create_dir_all(dir.path().join("leaf1")).unwrap();
+        fs::copy(
+            fixtures::golden("lldp/leaf1.json"),
+            dir.path().join("leaf1/lldp.json"),
+        )
+        .unwrap();
+        let neighbors = driver.neighbors().await.unwrap();
+        let ports: Vec<_> = neighbors
+            .iter()
+            .map(|nb| {
+                (
+                    nb.interface.as_str(),
+                    nb.system_name.as_str(),
+                    nb.port_id.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            ports,
+            vec![
+                ("Ethernet0", "spine1", "Ethernet0"),
+                ("Ethernet4", "spine2", "Ethernet0"),
+            ]
+        );
+        assert_eq!(neighbors[0].chassis_id, "52:54:00:00:01:01");
+        assert_eq!(
+            neighbors[0].management_address.as_deref(),
+            Some("10.0.0.11")
+        );
+    }
+
+    #[tokio::test]
+    async fn running_of_new_switch() {
+        let dir = tempfile::tempdir().unwrap();
+        let driver = File::new(dir.path().to_str().unwrap(), "spine1");
+
+        let running = driver.running().await.unwrap();
+        assert!(running["config_db"].is_null());
+        assert!(running["frr"].is_null());
+    }
+}
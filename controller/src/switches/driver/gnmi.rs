@@ -0,0 +1,401 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tonic::transport::{Channel, Endpoint};
+
+use yangtze_apis::{v1::YangtzeError, v1alpha1::node::LldpNeighbor};
+use yangtze_gnmi::{
+    client::GnmiClient,
+    proto::{
+        get_request::DataType, subscribe_request, subscribe_response, subscription_list,
+        typed_value, Encoding, GetRequest, Path, SetRequest, SubscribeRequest, Subscription,
+        SubscriptionList, SubscriptionMode, TypedValue, Update,
+    },
+};
+
+use crate::switches::driver::{StateUpdate, SwitchDriver};
+use crate::switches::intent::Resolved;
+use crate::switches::openconfig;
+
+/// The depth of the channel of the state updates of a switch.
+const UPDATES: usize = 64;
+
+/// A switch configured with OpenConfig over gNMI.
+pub struct Gnmi {
+    channel: Channel,
+}
+
+impl Gnmi {
+    pub fn new(address: &str) -> Result<Self, YangtzeError> {
+        let endpoint = Endpoint::from_shared(address.to_string()).map_err(|e| {
+            YangtzeError::InvalidConfig(format!("invalid gNMI target <{}>: {}", address, e))
+        })?;
+
+        // Connected on the first request, and reconnected when it is lost.
+        Ok(Gnmi {
+            channel: endpoint.connect_lazy(),
+        })
+    }
+
+    fn client(&self) -> GnmiClient<Channel> {
+        GnmiClient::new(self.channel.clone())
+    }
+}
+
+fn path(s: &str) -> Result<Path, YangtzeError> {
+    Path::parse(s).map_err(YangtzeError::GeneralError)
+}
+
+fn status_err(e: tonic::Status) -> YangtzeError {
+    YangtzeError::RestfulError(format!("gNMI: {}", e.message()))
+}
+
+/// The JSON of a value, or a string of a scalar.
+fn json(v: Option<&TypedValue>) -> Value {
+    match v.and_then(|v| v.value.as_ref()) {
+        Some(typed_value::Value::JsonIetfVal(b)) | Some(typed_value::Value::JsonVal(b)) => {
+            serde_json::from_slice(b).unwrap_or(Value::Null)
+        }
+        Some(typed_value::Value::StringVal(s)) | Some(typed_value::Value::AsciiVal(s)) => {
+            Value::String(s.clone())
+        }
+        Some(typed_value::Value::IntVal(i)) => Value::from(*i),
+        Some(typed_value::Value::UintVal(u)) => Value::from(*u),
+        Some(typed_value::Value::BoolVal(b)) => Value::Bool(*b),
+        _ => Value::Null,
+    }
+}
+
+/// Parses an update of the subscribed state.
+fn state_update(path: &Path, v: Option<&TypedValue>) -> Option<StateUpdate> {
+    let value = match json(v) {
+        Value::String(s) => s,
+        _ => return None,
+    };
+    let key = |elem: &str, key: &str| {
+        path.elem
+            .iter()
+            .find(|e| e.name == elem)
+            .and_then(|e| e.key.get(key).cloned())
+    };
+
+    match path.elem.last().map(|e| e.name.as_str()) {
+        Some("oper-status") => Some(StateUpdate::Port {
+            name: key("interface", "name")?,
+            status: value,
+        }),
+        Some("session-state") => Some(StateUpdate::Session {
+            neighbor: key("neighbor", "neighbor-address")?,
+            state: value,
+        }),
+        _ => None,
+    }
+}
+
+#[async_trait]
+impl SwitchDriver for Gnmi {
+    /// The configured OpenConfig subtrees by path, null if not configured.
+    async fn running(&self) -> Result<Value, YangtzeError> {
+        let mut running = serde_json::Map::new();
+        // One request per subtree, as a missing one fails the whole request.
+        for p in [openconfig::INTERFACES, openconfig::VLANS, openconfig::BGP] {
+            let request = GetRequest {
+                path: vec![path(p)?],
+                r#type: DataType::Config as i32,
+                encoding: Encoding::JsonIetf as i32,
+                ..Default::default()
+            };
+            let value = match self.client().get(request).await {
+                Ok(response) => response
+                    .into_inner()
+                    .notification
+                    .iter()
+                    .flat_map(|n| n.update.iter())
+                    .map(|u| json(u.val.as_ref()))
+                    .next()
+                    .unwrap_or(Value::Null),
+                Err(e) if e.code() == tonic::Code::NotFound => Value::Null,
+                Err(e) => return Err(status_err(e)),
+            };
+            running.insert(p.to_string(), value);
+        }
+
+        Ok(Value::Object(running))
+    }
+
+    /// Replaces the entries missing some rendered leaves, and deletes the
+    /// ones of the controller not rendered any more, at once.
+    async fn apply(&self, intent: &Resolved) -> Result<bool, YangtzeError> {
+        let running = self.running().await?;
+        let config = openconfig::render(intent);
+
+        let delete = openconfig::stale(&running, &config)
+            .iter()
+            .map(|p| path(p))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut replace = vec![];
+        for (p, config) in config {
+            if openconfig::configured(&running, &p)
+                .is_some_and(|r| openconfig::contains(r, &config))
+            {
+                continue;
+            }
+            replace.push(Update {
+                path: Some(path(&p)?),
+                val: Some(TypedValue {
+                    value: Some(typed_value::Value::JsonIetfVal(serde_json::to_vec(
+                        &config,
+                    )?)),
+                }),
+                ..Default::default()
+            });
+        }
+        if replace.is_empty() && delete.is_empty() {
+            return Ok(false);
+        }
+
+        let request = SetRequest {
+            delete,
+            replace,
+            ..Default::default()
+        };
+        self.client().set(request).await.map_err(status_err)?;
+
+        Ok(true)
+    }
+
+    async fn neighbors(&self) -> Result<Vec<LldpNeighbor>, YangtzeError> {
+        let request = GetRequest {
+            path: vec![path(openconfig::LLDP)?],
+            r#type: DataType::State as i32,
+            encoding: Encoding::JsonIetf as i32,
+            ..Default::default()
+        };
+        let lldp = match self.client().get(request).await {
+            Ok(response) => response
+                .into_inner()
+                .notification
+                .iter()
+                .flat_map(|n| n.update.iter())
+                .map(|u| json(u.val.as_ref()))
+                .next()
+                .unwrap_or(Value::Null),
+            Err(e) if e.code() == tonic::Code::NotFound => Value::Null,
+            Err(e) => return Err(status_err(e)),
+        };
+
+        Ok(openconfig::neighbors(&lldp))
+    }
+
+    async fn subscribe(&self) -> Result<Option<mpsc::Receiver<StateUpdate>>, YangtzeError> {
+        let mut subscription = vec![];
+        for p in [openconfig::OPER_STATUS, openconfig::SESSION_STATE] {
+            subscription.push(Subscription {
+                path: Some(path(p)?),
+                mode: SubscriptionMode::OnChange as i32,
+                ..Default::default()
+            });
+        }
+        let request = SubscribeRequest {
+            request: Some(subscribe_request::Request::Subscribe(SubscriptionList {
+                subscription,
+                mode: subscription_list::Mode::Stream as i32,
+                encoding: Encoding::JsonIetf as i32,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        // The requests stay open, as closing them may end the subscription.
+        let requests = futures::stream::iter([request]).chain(futures::stream::pending());
+        let mut responses = self
+            .client()
+            .subscribe(requests)
+            .await
+            .map_err(status_err)?
+            .into_inner();
+
+        let (tx, rx) = mpsc::channel(UPDATES);
+        tokio::spawn(async move {
+            loop {
+                let response = match responses.message().await {
+                    Ok(Some(r)) => r,
+                    Ok(None) => break,
+                    Err(e) => {
+                        tracing::error!("The gNMI subscription ended: {}", e.message());
+                        break;
+                    }
+                };
+                let Some(subscribe_response::Response::Update(n)) = response.response else {
+                    continue;
+                };
+                let prefix = n.prefix.unwrap_or_default();
+                for u in &n.update {
+                    let path = prefix.join(&u.path.clone().unwrap_or_default());
+                    let Some(update) = state_update(&path, u.val.as_ref()) else {
+                        continue;
+                    };
+                    if tx.send(update).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(Some(rx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    use tokio::net::TcpListener;
+    use yangtze_gnmi::server::GnmiServer;
+    use yangtze_gnmisim::target::Target;
+
+    use super::*;
+    use crate::switches::intent::fixtures;
+
+    /// Serves a gnmisim target on a free port, reporting the ports and BGP
+    /// neighbors of `down` down, and connects the driver to it.
+    async fn serve(down: &[&str]) -> Gnmi {
+        serve_target(Target::new(down.iter().map(|d| d.to_string()).collect())).await
+    }
+
+    async fn serve_target(target: Target) -> Gnmi {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let incoming = futures::stream::unfold(listener, |l| async move {
+            Some((l.accept().await.map(|(s, _)| s), l))
+        });
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(GnmiServer::new(target))
+                .serve_with_incoming(incoming),
+        );
+
+        Gnmi::new(&format!("http://{}", address)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn apply_once() {
+        let gnmi = serve(&[]).await;
+        let leaf = fixtures::leaf();
+        let running = gnmi.running().await.unwrap();
+        assert_eq!(running[openconfig::INTERFACES], Value::Null);
+
+        assert!(gnmi.apply(&leaf).await.unwrap());
+        assert!(!gnmi.apply(&leaf).await.unwrap());
+
+        let running = gnmi.running().await.unwrap();
+        for (p, config) in openconfig::render(&leaf) {
+            assert_eq!(openconfig::configured(&running, &p), Some(&config), "{}", p);
+        }
+
+        // The leaves the switch adds, e.g. its defaults, are left alone.
+        let request = SetRequest {
+            update: vec![Update {
+                path: Some(path("/interfaces/interface[name=Ethernet0]/config").unwrap()),
+                val: Some(TypedValue {
+                    value: Some(typed_value::Value::JsonIetfVal(
+                        serde_json::to_vec(&serde_json::json!({ "loopback-mode": false })).unwrap(),
+                    )),
+                }),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        gnmi.client().set(request).await.unwrap();
+        assert!(!gnmi.apply(&leaf).await.unwrap());
+
+        // Only the changed entries are replaced, and the VLANs not intended
+        // any more are deleted.
+        let mut leaf = leaf;
+        leaf.vlans.insert(30, 300);
+        assert!(gnmi.apply(&leaf).await.unwrap());
+        assert!(!gnmi.apply(&leaf).await.unwrap());
+        let vlan = format!("{}/vlan[vlan-id=30]", openconfig::VLANS);
+        let running = gnmi.running().await.unwrap();
+        assert!(openconfig::configured(&running, &vlan).is_some());
+        let ethernet0 = "/interfaces/interface[name=Ethernet0]/config/loopback-mode";
+        assert_eq!(
+            openconfig::configured(&running, ethernet0),
+            Some(&Value::Bool(false))
+        );
+
+        leaf.vlans.remove(&30);
+        assert!(gnmi.apply(&leaf).await.unwrap());
+        assert!(!gnmi.apply(&leaf).await.unwrap());
+        let running = gnmi.running().await.unwrap();
+        assert_eq!(openconfig::configured(&running, &vlan), None);
+    }
+
+    #[tokio::test]
+    async fn read_neighbors() {
+        let gnmi = serve(&[]).await;
+        assert!(gnmi.neighbors().await.unwrap().is_empty());
+
+        let lldp = std::fs::read_to_string(fixtures::golden("lldp/leaf1.json")).unwrap();
+        let gnmi =
+            serve_target(Target::new(vec![]).lldp(serde_json::from_str(&lldp).unwrap())).await;
+        let neighbors = gnmi.neighbors().await.unwrap();
+        let ports: Vec<_> = neighbors
+            .iter()
+            .map(|nb| {
+                (
+                    nb.interface.as_str(),
+                    nb.chassis_id.as_str(),
+                    nb.port_id.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            ports,
+            vec![
+                ("Ethernet0", "52:54:00:00:01:01", "Ethernet0"),
+                ("Ethernet4", "52:54:00:00:01:02", "Ethernet0"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn subscribe_oper_status() {
+        let gnmi = serve(&["Ethernet8"]).await;
+        let mut updates = gnmi.subscribe().await.unwrap().unwrap();
+        gnmi.apply(&fixtures::leaf()).await.unwrap();
+
+        let mut ports = BTreeMap::new();
+        while ports.len() < 3 {
+            let update = tokio::time::timeout(Duration::from_secs(5), updates.recv())
+                .await
+                .expect("no oper-status update")
+                .unwrap();
+            if let StateUpdate::Port { name, status } = update {
+                ports.insert(name, status);
+            }
+        }
+        assert_eq!(
+            ports,
+            BTreeMap::from([
+                ("Ethernet0".to_string(), "UP".to_string()),
+                ("Ethernet8".to_string(), "DOWN".to_string()),
+                ("Loopback0".to_string(), "UP".to_string()),
+            ])
+        );
+    }
+}
@@ -0,0 +1,65 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use async_trait::async_trait;
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use yangtze_apis::{
+    v1::YangtzeError,
+    v1alpha1::{
+        node::LldpNeighbor,
+        switch::{SwitchDriverKind, SwitchDriverSpec},
+    },
+};
+
+use crate::switches::intent::Resolved;
+
+mod file;
+mod gnmi;
+
+/// A change of the operational state of a switch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StateUpdate {
+    /// The operational status of a port, e.g. UP.
+    Port { name: String, status: String },
+    /// The state of the BGP session with a neighbor, e.g. ESTABLISHED.
+    Session { neighbor: String, state: String },
+}
+
+/// The southbound of a switch, which renders the intent in the dialect of
+/// the switch.
+#[async_trait]
+pub trait SwitchDriver: Send + Sync {
+    /// Reads the configuration the switch is running, in the dialect of the
+    /// driver.
+    async fn running(&self) -> Result<Value, YangtzeError>;
+
+    /// Configures the switch as intended; returns whether its running
+    /// configuration changed.
+    async fn apply(&self, intent: &Resolved) -> Result<bool, YangtzeError>;
+
+    /// The LLDP neighbors of the switch ports, where `interface` is the
+    /// local port.
+    async fn neighbors(&self) -> Result<Vec<LldpNeighbor>, YangtzeError>;
+
+    /// Subscribes to the operational state of the switch, none if it does
+    /// not report any; the channel closes when the subscription ends.
+    async fn subscribe(&self) -> Result<Option<mpsc::Receiver<StateUpdate>>, YangtzeError>;
+}
+
+pub fn new(switch: &str, spec: &SwitchDriverSpec) -> Result<Box<dyn SwitchDriver>, YangtzeError> {
+    match spec.kind {
+        SwitchDriverKind::File => Ok(Box::new(file::File::new(&spec.address, switch))),
+        SwitchDriverKind::Gnmi => Ok(Box::new(gnmi::Gnmi::new(&spec.address)?)),
+    }
+}
@@ -0,0 +1,271 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+
+use ipnet::Ipv4Net;
+
+use yangtze_apis::v1alpha1::{
+    fabric::Fabric,
+    node::Node,
+    subnet::Subnet,
+    switch::{Switch, SwitchRole},
+    topology::Tier,
+    vpc::{Vpc, MAX_VNI},
+};
+
+/// The state of a Fabric which the configurations of its switches and hosts
+/// are rendered from.
+pub struct Intent<'a> {
+    pub fabric: &'a Fabric,
+    /// The switches of the Fabric.
+    pub switches: &'a [Switch],
+    pub nodes: &'a [Node],
+    pub vpcs: &'a [Vpc],
+    pub subnets: &'a [Subnet],
+}
+
+/// A port and what it is cabled to.
+pub struct Port {
+    pub name: String,
+    pub peer: String,
+    pub peer_port: String,
+    /// The ASN of the peer, if it speaks BGP.
+    pub peer_asn: Option<u32>,
+    /// The address of the port on the /31 allocated to its link, if any.
+    pub address: Option<String>,
+}
+
+/// A switch with the settings its configurations are rendered from.
+pub struct Resolved {
+    pub hostname: String,
+    pub role: SwitchRole,
+    pub asn: u32,
+    pub loopback: Ipv4Addr,
+    /// The ports cabled to the other switches of the Fabric.
+    pub fabric_ports: Vec<Port>,
+    /// The ports cabled to the Nodes.
+    pub host_ports: Vec<Port>,
+    /// The VNI of each VLAN of the Subnets on a leaf, one VLAN per Vpc.
+    pub vlans: BTreeMap<u16, u32>,
+}
+
+impl Intent<'_> {
+    pub fn resolve(&self, sw: &Switch) -> Result<Resolved, String> {
+        let name = &sw.meta_data.name;
+        let topology = self
+            .fabric
+            .status
+            .as_ref()
+            .and_then(|s| s.topology.as_ref());
+
+        let tier = topology
+            .and_then(|t| t.nodes.iter().find(|n| &n.name == name))
+            .map(|n| n.tier);
+        let role = match (&sw.spec.role, tier) {
+            (Some(role), _) => *role,
+            (None, Some(Tier::Leaf)) => SwitchRole::Leaf,
+            (None, Some(Tier::Spine)) => SwitchRole::Spine,
+            (None, Some(Tier::SuperSpine)) => SwitchRole::SuperSpine,
+            _ => return Err("the role of the switch is unknown".to_string()),
+        };
+        let asn = sw.spec.asn.ok_or("the switch has no ASN")?;
+        let loopback: Ipv4Addr = sw
+            .spec
+            .loopback
+            .as_deref()
+            .ok_or("the switch has no loopback")?
+            .parse()
+            .map_err(|e| format!("invalid loopback: {}", e))?;
+
+        let hostname = match sw.status.as_ref().map(|s| s.system_name.as_str()) {
+            Some("") | None => name.clone(),
+            Some(s) => s.to_string(),
+        };
+
+        let links = self.fabric.status.as_ref().map(|s| &s.allocations.links);
+        let mut fabric_ports = vec![];
+        for l in topology.map(|t| t.links.as_slice()).unwrap_or(&[]) {
+            let (port, peer, peer_port, first) = match (&l.from == name, &l.to == name) {
+                (true, false) => (&l.from_port, &l.to, &l.to_port, true),
+                (false, true) => (&l.to_port, &l.from, &l.from_port, false),
+                _ => continue,
+            };
+            let Some(p) = self.switches.iter().find(|s| &s.meta_data.name == peer) else {
+                continue;
+            };
+            // The first end of the link takes the first address of its /31.
+            let address = links
+                .and_then(|links| links.get(&format!("{}:{}", l.from, l.from_port)))
+                .and_then(|net| net.parse::<Ipv4Net>().ok())
+                .map(|net| match first {
+                    true => format!("{}/31", net.network()),
+                    false => format!("{}/31", net.broadcast()),
+                });
+            fabric_ports.push(Port {
+                name: port.clone(),
+                peer: peer.clone(),
+                peer_port: peer_port.clone(),
+                peer_asn: p.spec.asn,
+                address,
+            });
+        }
+        let host_ports: Vec<Port> = sw
+            .status
+            .iter()
+            .flat_map(|s| s.links.iter())
+            .map(|l| Port {
+                name: l.port.clone(),
+                peer: l.node.clone(),
+                peer_port: l.interface.clone(),
+                peer_asn: self
+                    .nodes
+                    .iter()
+                    .find(|n| n.meta_data.name == l.node)
+                    .and_then(|n| n.spec.asn),
+                address: None,
+            })
+            .collect();
+
+        let vlans = match role {
+            SwitchRole::Leaf => self.vlans()?,
+            _ => BTreeMap::new(),
+        };
+
+        Ok(Resolved {
+            hostname,
+            role,
+            asn,
+            loopback,
+            fabric_ports,
+            host_ports,
+            vlans,
+        })
+    }
+
+    /// Maps the VLAN of each Subnet to the VNI of its Vpc, which can be
+    /// mapped to a single VLAN only.
+    fn vlans(&self) -> Result<BTreeMap<u16, u32>, String> {
+        let mut vlans: BTreeMap<u16, u32> = BTreeMap::new();
+        for s in self.subnets {
+            let (Some(vpc), Some(vlan)) = (&s.spec.vpc, s.spec.vlan) else {
+                continue;
+            };
+            let Some(v) = self.vpcs.iter().find(|v| &v.meta_data.name == vpc) else {
+                continue;
+            };
+            let vni = v.spec.vni;
+            if vni == 0 || vni > MAX_VNI {
+                continue;
+            }
+
+            match vlans.get(&vlan) {
+                Some(other) if *other != vni => {
+                    return Err(format!(
+                        "VLAN {} of Subnet <{}> is mapped to VNI {} already",
+                        vlan, s, other
+                    ))
+                }
+                Some(_) => continue,
+                None => {}
+            }
+            if let Some((other, _)) = vlans.iter().find(|(_, n)| **n == vni) {
+                return Err(format!(
+                    "VNI {} of Vpc <{}> is mapped to VLAN {} already",
+                    vni, v, other
+                ));
+            }
+            vlans.insert(vlan, vni);
+        }
+
+        Ok(vlans)
+    }
+}
+
+/// The valid VNIs of the Vpcs.
+pub fn vnis(vpcs: &[Vpc]) -> Vec<u32> {
+    let mut vnis: Vec<u32> = vpcs
+        .iter()
+        .map(|v| v.spec.vni)
+        .filter(|vni| *vni > 0 && *vni <= MAX_VNI)
+        .collect();
+    vnis.sort();
+    vnis.dedup();
+    vnis
+}
+
+/// A leaf and a spine cabled to each other, for the tests of the renderers.
+#[cfg(test)]
+pub mod fixtures {
+    use super::*;
+
+    /// The directory of the golden files.
+    pub fn golden(name: &str) -> std::path::PathBuf {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    /// leaf1, with a /31 to spine1, and h1 trunked to two Vpcs.
+    pub fn leaf() -> Resolved {
+        Resolved {
+            hostname: "leaf1".to_string(),
+            role: SwitchRole::Leaf,
+            asn: 65001,
+            loopback: Ipv4Addr::new(10, 255, 0, 1),
+            fabric_ports: vec![Port {
+                name: "Ethernet0".to_string(),
+                peer: "spine1".to_string(),
+                peer_port: "Ethernet4".to_string(),
+                peer_asn: Some(65100),
+                address: Some("10.0.0.0/31".to_string()),
+            }],
+            host_ports: vec![Port {
+                name: "Ethernet8".to_string(),
+                peer: "h1".to_string(),
+                peer_port: "eth0".to_string(),
+                peer_asn: Some(65201),
+                address: None,
+            }],
+            vlans: BTreeMap::from([(10, 100), (20, 200)]),
+        }
+    }
+
+    /// spine1, with a /31 to leaf1 and an unnumbered link to leaf2.
+    pub fn spine() -> Resolved {
+        Resolved {
+            hostname: "spine1".to_string(),
+            role: SwitchRole::Spine,
+            asn: 65100,
+            loopback: Ipv4Addr::new(10, 255, 0, 100),
+            fabric_ports: vec![
+                Port {
+                    name: "Ethernet4".to_string(),
+                    peer: "leaf1".to_string(),
+                    peer_port: "Ethernet0".to_string(),
+                    peer_asn: Some(65001),
+                    address: Some("10.0.0.1/31".to_string()),
+                },
+                Port {
+                    name: "Ethernet8".to_string(),
+                    peer: "leaf2".to_string(),
+                    peer_port: "Ethernet0".to_string(),
+                    peer_asn: Some(65002),
+                    address: None,
+                },
+            ],
+            host_ports: vec![],
+            vlans: BTreeMap::new(),
+        }
+    }
+}
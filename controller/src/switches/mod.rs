@@ -11,23 +11,250 @@
  * limitations under the License.
  */
 
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
 use async_trait::async_trait;
+use tokio::task::JoinHandle;
 
 use yangtze_apis::{
-    v1::{VersionKind, YangtzeError},
-    v1alpha1::fabric::{self, Fabric},
+    v1::{NamespaceName, VersionKind, YangtzeError},
+    v1alpha1::{
+        fabric::{self, Fabric},
+        node::{self, Node},
+        subnet::{self, Subnet},
+        switch::{self, Switch, SwitchDriverSpec, SwitchState},
+        vpc::{self, Vpc},
+    },
 };
 use yangtze_client::YangtzeClient;
 
 use crate::framework::Controller;
 
-#[derive(Clone)]
-pub struct SwitchController {}
+mod discovered;
+mod driver;
+pub(crate) mod intent;
+mod openconfig;
+mod sonic;
+
+pub use discovered::DiscoveryController;
+
+/// The namespace, Fabric and name of a watched switch.
+type WatchKey = (String, String, String);
+
+/// The subscription to the operational state of a switch, and the latest
+/// state received.
+struct Watch {
+    driver: SwitchDriverSpec,
+    task: JoinHandle<()>,
+    ports: BTreeMap<String, String>,
+    sessions: BTreeMap<String, String>,
+}
+
+/// Configures the switches of a Fabric with a driver as intended, and
+/// reports the operational state they are subscribed to.
+#[derive(Clone, Default)]
+pub struct SwitchController {
+    watches: Arc<Mutex<HashMap<WatchKey, Watch>>>,
+}
+
+impl SwitchController {
+    /// Subscribes to the state of a switch unless it already is through its
+    /// driver; returns its latest ports and sessions.
+    async fn watch(
+        &self,
+        key: WatchKey,
+        spec: &SwitchDriverSpec,
+        driver: &dyn driver::SwitchDriver,
+    ) -> (BTreeMap<String, String>, BTreeMap<String, String>) {
+        if let Some(w) = self.watches.lock().unwrap().get(&key) {
+            if &w.driver == spec && !w.task.is_finished() {
+                return (w.ports.clone(), w.sessions.clone());
+            }
+        }
+
+        let mut rx = match driver.subscribe().await {
+            Ok(Some(rx)) => rx,
+            Ok(None) => return Default::default(),
+            Err(e) => {
+                tracing::error!("Failed to subscribe to Switch <{}>: {}", key.2, e);
+                return Default::default();
+            }
+        };
+        // Hold the lock until the Watch is inserted, so the task finds it.
+        let mut watches = self.watches.lock().unwrap();
+        let shared = self.watches.clone();
+        let watched = key.clone();
+        let task = tokio::spawn(async move {
+            while let Some(update) = rx.recv().await {
+                let mut watches = shared.lock().unwrap();
+                let Some(w) = watches.get_mut(&watched) else {
+                    return;
+                };
+                match update {
+                    driver::StateUpdate::Port { name, status } => w.ports.insert(name, status),
+                    driver::StateUpdate::Session { neighbor, state } => {
+                        w.sessions.insert(neighbor, state)
+                    }
+                };
+            }
+        });
+
+        let watch = Watch {
+            driver: spec.clone(),
+            task,
+            ports: BTreeMap::new(),
+            sessions: BTreeMap::new(),
+        };
+        if let Some(old) = watches.insert(key, watch) {
+            old.task.abort();
+        }
+        Default::default()
+    }
+
+    /// Ends the watches of the switches no longer in the Fabric with a driver.
+    fn unwatch(&self, namespace: &str, fabric: &str, switches: &[Switch]) {
+        self.watches.lock().unwrap().retain(|(ns, f, name), w| {
+            let watched = ns != namespace
+                || f != fabric
+                || switches
+                    .iter()
+                    .any(|sw| &sw.meta_data.name == name && sw.spec.driver.is_some());
+            if !watched {
+                w.task.abort();
+            }
+            watched
+        });
+    }
+}
 
 #[async_trait]
 impl Controller<Fabric> for SwitchController {
-    async fn execute(&self, _client: YangtzeClient, _f: Fabric) -> Result<(), YangtzeError> {
-        tracing::info!("switch controller");
+    async fn execute(&self, client: YangtzeClient, f: Fabric) -> Result<(), YangtzeError> {
+        let nn = NamespaceName {
+            namespace: Some(f.meta_data.namespace.clone()),
+            name: None,
+        };
+
+        let switches: Vec<Switch> = client
+            .clone()
+            .version(switch::VERSION_KIND.version)
+            .kind(switch::VERSION_KIND.kind)
+            .list(nn.clone())
+            .await?
+            .into_iter()
+            .filter(|sw: &Switch| sw.spec.fabric.as_deref() == Some(&f.meta_data.name))
+            .collect();
+        self.unwatch(&f.meta_data.namespace, &f.meta_data.name, &switches);
+        if !switches.iter().any(|sw| sw.spec.driver.is_some()) {
+            return Ok(());
+        }
+
+        let nodes: Vec<Node> = client
+            .clone()
+            .version(node::VERSION_KIND.version)
+            .kind(node::VERSION_KIND.kind)
+            .list(nn.clone())
+            .await?;
+        let vpcs: Vec<Vpc> = client
+            .clone()
+            .version(vpc::VERSION_KIND.version)
+            .kind(vpc::VERSION_KIND.kind)
+            .list(nn.clone())
+            .await?;
+        let subnets: Vec<Subnet> = client
+            .clone()
+            .version(subnet::VERSION_KIND.version)
+            .kind(subnet::VERSION_KIND.kind)
+            .list(nn)
+            .await?;
+
+        let intent = intent::Intent {
+            fabric: &f,
+            switches: &switches,
+            nodes: &nodes,
+            vpcs: &vpcs,
+            subnets: &subnets,
+        };
+        let resolved: Vec<_> = switches.iter().map(|sw| intent.resolve(sw)).collect();
+
+        for (mut sw, resolved) in switches.into_iter().zip(resolved) {
+            let Some(spec) = sw.spec.driver.clone() else {
+                continue;
+            };
+
+            let (applied, neighbors, ports, sessions) = match driver::new(&sw.meta_data.name, &spec)
+            {
+                Ok(driver) => {
+                    let applied = match resolved {
+                        Ok(intent) => driver.apply(&intent).await,
+                        Err(e) => Err(YangtzeError::InvalidConfig(e)),
+                    };
+                    // The topology of the Fabric is built from the neighbors.
+                    let neighbors = match driver.neighbors().await {
+                        Ok(neighbors) => Some(neighbors),
+                        Err(e) => {
+                            tracing::error!(
+                                "Failed to read the LLDP neighbors of Switch <{}>: {}",
+                                sw.meta_data.name,
+                                e
+                            );
+                            None
+                        }
+                    };
+                    let key = (
+                        f.meta_data.namespace.clone(),
+                        f.meta_data.name.clone(),
+                        sw.meta_data.name.clone(),
+                    );
+                    let (ports, sessions) = self.watch(key, &spec, driver.as_ref()).await;
+                    (applied, neighbors, ports, sessions)
+                }
+                Err(e) => (Err(e), None, BTreeMap::new(), BTreeMap::new()),
+            };
+            let (state, reason) = match applied {
+                Ok(changed) => {
+                    if changed {
+                        tracing::info!("Applied the configuration of Switch <{}>.", sw);
+                    }
+                    (SwitchState::Ready, None)
+                }
+                Err(e) => (SwitchState::Error, Some(e.to_string())),
+            };
+
+            let Some(status) = sw.status.as_mut() else {
+                continue;
+            };
+            // The last neighbors read are kept if they could not be read.
+            let neighbors = neighbors.unwrap_or(status.neighbors.clone());
+            if status.state == state
+                && status.neighbors == neighbors
+                && status.reason == reason
+                && status.ports == ports
+                && status.sessions == sessions
+            {
+                continue;
+            }
+            if let Some(reason) = &reason {
+                tracing::error!(
+                    "Failed to configure Switch <{}>: {}",
+                    sw.meta_data.name,
+                    reason
+                );
+            }
+            status.state = state;
+            status.reason = reason;
+            status.neighbors = neighbors;
+            status.ports = ports;
+            status.sessions = sessions;
+            let _sw = client
+                .clone()
+                .version(switch::VERSION_KIND.version)
+                .kind(switch::VERSION_KIND.kind)
+                .update::<Switch>(sw)
+                .await?;
+        }
+
         Ok(())
     }
 
@@ -0,0 +1,335 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+
+use serde_json::{json, Value};
+
+use yangtze_apis::v1alpha1::node::LldpNeighbor;
+use yangtze_gnmi::proto::Path;
+
+use crate::routing::frr::{FABRIC, HOSTS};
+use crate::switches::intent::{Port, Resolved};
+use crate::switches::sonic::PORT_MTU;
+
+/// The OpenConfig subtrees configured on a switch, entry by entry.
+pub const INTERFACES: &str = "/interfaces";
+pub const VLANS: &str = "/network-instances/network-instance[name=default]/vlans";
+pub const BGP: &str =
+    "/network-instances/network-instance[name=default]/protocols/protocol[identifier=BGP][name=BGP]/bgp";
+
+/// The LLDP state of the switch ports.
+pub const LLDP: &str = "/lldp/interfaces";
+
+/// The leaves of the operational state subscribed to.
+pub const OPER_STATUS: &str = "/interfaces/interface[name=*]/state/oper-status";
+pub const SESSION_STATE: &str = "/network-instances/network-instance[name=default]/protocols/protocol[identifier=BGP][name=BGP]/bgp/neighbors/neighbor[neighbor-address=*]/state/session-state";
+
+/// The neighbors of the ports in the RFC 7951 JSON of `/lldp/interfaces`,
+/// where `interface` is the local port.
+pub fn neighbors(lldp: &Value) -> Vec<LldpNeighbor> {
+    let field = |v: &Value, name: &str| {
+        v.get(format!("openconfig-lldp:{}", name))
+            .or(v.get(name))
+            .cloned()
+            .unwrap_or(Value::Null)
+    };
+    let text = |v: &Value, name: &str| field(v, name).as_str().unwrap_or_default().to_string();
+
+    let mut res = vec![];
+    for i in field(lldp, "interface").as_array().into_iter().flatten() {
+        let neighbors = field(&field(i, "neighbors"), "neighbor");
+        for nb in neighbors.as_array().into_iter().flatten() {
+            let state = field(nb, "state");
+            let (chassis_id, port_id) = (text(&state, "chassis-id"), text(&state, "port-id"));
+            if chassis_id.is_empty() || port_id.is_empty() {
+                continue;
+            }
+            res.push(LldpNeighbor {
+                interface: text(i, "name"),
+                chassis_id,
+                port_id,
+                port_description: text(&state, "port-description"),
+                system_name: text(&state, "system-name"),
+                management_address: Some(text(&state, "management-address"))
+                    .filter(|a| !a.is_empty()),
+            });
+        }
+    }
+    res.sort_by(|a, b| a.interface.cmp(&b.interface));
+
+    res
+}
+
+/// The RFC 7951 JSON of each entry, by its keyed path.
+pub type Config = BTreeMap<String, Value>;
+
+/// The lists whose entries are all configured by the controller, as their
+/// subtree, their path in it and their key; the entries not rendered are
+/// removed.
+const OWNED: [(&str, &str, &str); 3] = [
+    (VLANS, "vlan", "vlan-id"),
+    (BGP, "peer-groups/peer-group", "peer-group-name"),
+    (BGP, "neighbors/neighbor", "neighbor-address"),
+];
+
+/// Renders the OpenConfig of a switch: its ports, its loopback and BGP
+/// sessions over the fabric links, and on the leaves the VLANs of the
+/// Subnets trunked to the hosts.
+pub fn render(sw: &Resolved) -> Config {
+    let vlans: Vec<u16> = sw.vlans.keys().copied().collect();
+    let mut config = Config::new();
+
+    let loopback = json!({
+        "name": "Loopback0",
+        "config": {
+            "name": "Loopback0",
+            "type": "iana-if-type:softwareLoopback",
+            "enabled": true,
+        },
+        "subinterfaces": subinterfaces(&sw.loopback.to_string(), 32),
+    });
+    config.insert(interface("Loopback0"), loopback);
+    for p in &sw.fabric_ports {
+        config.insert(interface(&p.name), port(p));
+    }
+    for p in &sw.host_ports {
+        let mut port = port(p);
+        if !vlans.is_empty() {
+            port["openconfig-if-ethernet:ethernet"] = json!({
+                "openconfig-vlan:switched-vlan": {
+                    "config": {
+                        "interface-mode": "TRUNK",
+                        "trunk-vlans": vlans,
+                    },
+                },
+            });
+        }
+        config.insert(interface(&p.name), port);
+    }
+
+    for id in &vlans {
+        config.insert(
+            format!("{}/vlan[vlan-id={}]", VLANS, id),
+            json!({
+                "vlan-id": id,
+                "config": {
+                    "vlan-id": id,
+                    "name": format!("Vlan{}", id),
+                    "status": "ACTIVE",
+                },
+            }),
+        );
+    }
+
+    config.insert(
+        format!("{}/global", BGP),
+        json!({
+            "config": {
+                "as": sw.asn,
+                "router-id": sw.loopback.to_string(),
+            },
+            "afi-safis": afi_safis(&["IPV4_UNICAST", "L2VPN_EVPN"]),
+        }),
+    );
+    let mut groups = vec![FABRIC];
+    if sw.host_ports.iter().any(|p| p.peer_asn.is_some()) {
+        groups.push(HOSTS);
+    }
+    for g in groups {
+        config.insert(
+            format!("{}/peer-groups/peer-group[peer-group-name={}]", BGP, g),
+            json!({
+                "peer-group-name": g,
+                "config": { "peer-group-name": g },
+                "afi-safis": afi_safis(&["L2VPN_EVPN"]),
+            }),
+        );
+    }
+    let neighbors = sw.fabric_ports.iter().map(|p| (p, FABRIC)).chain(
+        sw.host_ports
+            .iter()
+            .filter(|p| p.peer_asn.is_some())
+            .map(|p| (p, HOSTS)),
+    );
+    for (p, group) in neighbors {
+        let neighbor = neighbor(p, group);
+        config.insert(
+            format!(
+                "{}/neighbors/neighbor[neighbor-address={}]",
+                BGP,
+                neighbor["neighbor-address"].as_str().unwrap_or_default()
+            ),
+            neighbor,
+        );
+    }
+
+    config
+}
+
+fn interface(name: &str) -> String {
+    format!("{}/interface[name={}]", INTERFACES, name)
+}
+
+/// An Ethernet port, with the address of its subinterface if any.
+fn port(p: &Port) -> Value {
+    let mut port = json!({
+        "name": p.name,
+        "config": {
+            "name": p.name,
+            "type": "iana-if-type:ethernetCsmacd",
+            "mtu": PORT_MTU,
+            "description": format!("{}:{}", p.peer, p.peer_port),
+            "enabled": true,
+        },
+    });
+    if let Some((ip, prefix)) = p.address.as_deref().and_then(|a| a.split_once('/')) {
+        port["subinterfaces"] = subinterfaces(ip, prefix.parse().unwrap_or(31));
+    }
+
+    port
+}
+
+/// The member of an object, whether its name is qualified with its module
+/// or not.
+fn member<'a>(v: &'a Value, name: &str) -> Option<&'a Value> {
+    v.as_object()?
+        .iter()
+        .find(|(k, _)| k.rsplit(':').next() == Some(name))
+        .map(|(_, v)| v)
+}
+
+/// The value of a key of a list entry, as in a path.
+fn key(entry: &Value, key: &str) -> Option<String> {
+    match member(entry, key)? {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// The running configuration at a path, in the subtrees read by path.
+pub fn configured<'a>(running: &'a Value, path: &str) -> Option<&'a Value> {
+    let (subtree, rest) = [INTERFACES, VLANS, BGP]
+        .into_iter()
+        .find_map(|s| Some((s, path.strip_prefix(s)?)))?;
+    let mut node = running.get(subtree)?;
+    for e in Path::parse(rest).ok()?.elem {
+        node = member(node, &e.name)?;
+        if !e.key.is_empty() {
+            node = node.as_array()?.iter().find(|entry| {
+                e.key
+                    .iter()
+                    .all(|(k, v)| key(entry, k).as_deref() == Some(v))
+            })?;
+        }
+    }
+
+    Some(node)
+}
+
+/// Whether the running configuration has all the rendered leaves; it may
+/// have more, e.g. the defaults of the switch.
+pub fn contains(running: &Value, config: &Value) -> bool {
+    match (running, config) {
+        (Value::Object(_), Value::Object(config)) => config.iter().all(|(k, c)| {
+            let name = k.rsplit(':').next().unwrap_or(k);
+            member(running, name).is_some_and(|r| contains(r, c))
+        }),
+        (Value::Array(running), Value::Array(config)) => {
+            running.len() == config.len()
+                && config
+                    .iter()
+                    .all(|c| running.iter().any(|r| contains(r, c)))
+        }
+        (running, config) => running == config,
+    }
+}
+
+/// The paths of the running entries of the lists owned by the controller
+/// which are not rendered any more.
+pub fn stale(running: &Value, config: &Config) -> Vec<String> {
+    let mut res = vec![];
+    for (subtree, list, k) in OWNED {
+        let entries = configured(running, &format!("{}/{}", subtree, list));
+        for entry in entries.and_then(|e| e.as_array()).into_iter().flatten() {
+            let Some(value) = key(entry, k) else {
+                continue;
+            };
+            let path = format!("{}/{}[{}={}]", subtree, list, k, value);
+            if !config.contains_key(&path) {
+                res.push(path);
+            }
+        }
+    }
+
+    res
+}
+
+fn subinterfaces(ip: &str, prefix: u8) -> Value {
+    json!({
+        "subinterface": [{
+            "index": 0,
+            "config": { "index": 0 },
+            "openconfig-if-ip:ipv4": {
+                "addresses": {
+                    "address": [{
+                        "ip": ip,
+                        "config": { "ip": ip, "prefix-length": prefix },
+                    }],
+                },
+            },
+        }],
+    })
+}
+
+fn afi_safis(names: &[&str]) -> Value {
+    let afi_safis: Vec<Value> = names
+        .iter()
+        .map(|n| {
+            let name = format!("openconfig-bgp-types:{}", n);
+            json!({
+                "afi-safi-name": name,
+                "config": { "afi-safi-name": name, "enabled": true },
+            })
+        })
+        .collect();
+
+    json!({ "afi-safi": afi_safis })
+}
+
+/// A BGP neighbor on a port: the other end of its /31 if any, else
+/// unnumbered over the port.
+fn neighbor(p: &Port, group: &str) -> Value {
+    let address = p
+        .address
+        .as_deref()
+        .and_then(|a| a.split_once('/'))
+        .and_then(|(ip, _)| ip.parse::<Ipv4Addr>().ok())
+        .map(|ip| Ipv4Addr::from(u32::from(ip) ^ 1).to_string())
+        .unwrap_or(p.name.clone());
+
+    let mut config = json!({
+        "neighbor-address": address,
+        "peer-group": group,
+        "description": p.peer,
+    });
+    if let Some(asn) = p.peer_asn {
+        config["peer-as"] = json!(asn);
+    }
+
+    json!({
+        "neighbor-address": address,
+        "config": config,
+    })
+}
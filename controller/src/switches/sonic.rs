@@ -0,0 +1,205 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::collections::BTreeMap;
+
+use yangtze_apis::v1alpha1::switch::SwitchRole;
+
+use crate::switches::intent::Resolved;
+
+/// The MTU of the ports of the switches.
+pub const PORT_MTU: u32 = 9100;
+
+/// The name of the VXLAN tunnel of the leaves.
+const VTEP: &str = "vtep";
+
+/// The fields of the entries of a SONiC `config_db.json`, by table and key.
+pub type ConfigDb = BTreeMap<String, BTreeMap<String, BTreeMap<String, String>>>;
+
+/// Renders the `config_db.json` fragments of a switch: its ports, its
+/// loopback and BGP sessions unnumbered over the fabric links, and on the
+/// leaves the VLANs of the Subnets mapped to the VNIs of their Vpcs.
+pub fn render(sw: &Resolved) -> ConfigDb {
+    let mut db = ConfigDb::new();
+
+    let device_type = match sw.role {
+        SwitchRole::Leaf => "LeafRouter",
+        SwitchRole::Spine | SwitchRole::SuperSpine => "SpineRouter",
+    };
+    entry(
+        &mut db,
+        "DEVICE_METADATA",
+        "localhost",
+        &[
+            ("hostname", sw.hostname.clone()),
+            ("bgp_asn", sw.asn.to_string()),
+            ("type", device_type.to_string()),
+            // The BGP sessions are configured by the tables, i.e. frrcfgd.
+            ("docker_routing_config_mode", "unified".to_string()),
+            ("frr_mgmt_framework_config", "true".to_string()),
+        ],
+    );
+
+    entry(&mut db, "LOOPBACK_INTERFACE", "Loopback0", &[]);
+    entry(
+        &mut db,
+        "LOOPBACK_INTERFACE",
+        &format!("Loopback0|{}/32", sw.loopback),
+        &[],
+    );
+    entry(
+        &mut db,
+        "BGP_GLOBALS",
+        "default",
+        &[
+            ("local_asn", sw.asn.to_string()),
+            ("router_id", sw.loopback.to_string()),
+        ],
+    );
+
+    for p in sw.fabric_ports.iter().chain(sw.host_ports.iter()) {
+        entry(
+            &mut db,
+            "PORT",
+            &p.name,
+            &[
+                ("admin_status", "up".to_string()),
+                ("mtu", PORT_MTU.to_string()),
+                ("description", format!("{}:{}", p.peer, p.peer_port)),
+            ],
+        );
+    }
+
+    for p in &sw.fabric_ports {
+        entry(
+            &mut db,
+            "INTERFACE",
+            &p.name,
+            &[("ipv6_use_link_local_only", "enable".to_string())],
+        );
+        if let Some(address) = &p.address {
+            entry(
+                &mut db,
+                "INTERFACE",
+                &format!("{}|{}", p.name, address),
+                &[],
+            );
+        }
+
+        let mut fields = vec![
+            ("name", p.peer.clone()),
+            ("peer_type", "external".to_string()),
+            ("admin_status", "up".to_string()),
+        ];
+        if let Some(asn) = p.peer_asn {
+            fields.push(("asn", asn.to_string()));
+        }
+        entry(
+            &mut db,
+            "BGP_NEIGHBOR",
+            &format!("default|{}", p.name),
+            &fields,
+        );
+    }
+
+    if sw.role != SwitchRole::Leaf {
+        return db;
+    }
+
+    entry(
+        &mut db,
+        "VXLAN_TUNNEL",
+        VTEP,
+        &[("src_ip", sw.loopback.to_string())],
+    );
+    entry(
+        &mut db,
+        "VXLAN_EVPN_NVO",
+        "nvo",
+        &[("source_vtep", VTEP.to_string())],
+    );
+
+    for (vlan, vni) in &sw.vlans {
+        let vlan_name = format!("Vlan{}", vlan);
+        let vrf = format!("Vrf{}", vni);
+
+        entry(&mut db, "VRF", &vrf, &[]);
+        entry(&mut db, "VLAN", &vlan_name, &[("vlanid", vlan.to_string())]);
+        for p in &sw.host_ports {
+            entry(
+                &mut db,
+                "VLAN_MEMBER",
+                &format!("{}|{}", vlan_name, p.name),
+                &[("tagging_mode", "tagged".to_string())],
+            );
+        }
+        entry(&mut db, "VLAN_INTERFACE", &vlan_name, &[("vrf_name", vrf)]);
+        entry(
+            &mut db,
+            "VXLAN_TUNNEL_MAP",
+            &format!("{}|map_{}_{}", VTEP, vni, vlan_name),
+            &[("vlan", vlan_name.clone()), ("vni", vni.to_string())],
+        );
+    }
+
+    db
+}
+
+fn entry(db: &mut ConfigDb, table: &str, key: &str, fields: &[(&str, String)]) {
+    let e = db
+        .entry(table.to_string())
+        .or_default()
+        .entry(key.to_string())
+        .or_default();
+    for (k, v) in fields {
+        e.insert(k.to_string(), v.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::switches::intent::fixtures;
+
+    /// Compares the rendered config_db with its golden file; set
+    /// `UPDATE_GOLDEN` to rewrite the golden file instead.
+    fn assert_golden(sw: &Resolved, name: &str) {
+        let path = fixtures::golden(name);
+        let rendered = serde_json::to_string_pretty(&render(sw)).unwrap() + "\n";
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            fs::write(&path, &rendered).unwrap();
+        }
+
+        assert_eq!(rendered, fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn render_leaf() {
+        assert_golden(&fixtures::leaf(), "sonic/leaf1.json");
+    }
+
+    #[test]
+    fn render_spine() {
+        assert_golden(&fixtures::spine(), "sonic/spine1.json");
+    }
+
+    #[test]
+    fn spine_has_no_overlay() {
+        let db = render(&fixtures::spine());
+
+        for table in ["VXLAN_TUNNEL", "VLAN", "VLAN_MEMBER", "VRF"] {
+            assert!(!db.contains_key(table), "{}", table);
+        }
+    }
+}
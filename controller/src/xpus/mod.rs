@@ -1,4 +1,4 @@
-/* 
+/*
  * Copyright 2023 The xflops Authors.
  * Licensed under the Apache License, Version 2.0 (the "License");
  * you may not use this file except in compliance with the License.
@@ -9,4 +9,163 @@
  * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
  * See the License for the specific language governing permissions and
  * limitations under the License.
- */
\ No newline at end of file
+ */
+
+use async_trait::async_trait;
+
+use yangtze_apis::{
+    v1::{now, NamespaceName, VersionKind, YangtzeError},
+    v1alpha1::{
+        node::{self, Node},
+        xpu::{self, Xpu, XpuState, XpuStatus},
+    },
+};
+use yangtze_client::YangtzeClient;
+
+use crate::framework::Controller;
+
+/// Tracks the lifecycle of the Xpus reported by the agents: an Xpu without
+/// its Node, or stuck in `Provisioning`, is in `Error` until the agent
+/// provisions it again, see `XpuSpec::retries`.
+#[derive(Clone)]
+pub struct XpuController {
+    /// Seconds an Xpu may stay in `Provisioning`.
+    pub provision_timeout: u64,
+}
+
+impl XpuController {
+    /// The next status of an Xpu at `now`, none if it does not change.
+    fn next(&self, x: &Xpu, node_found: bool, now: u64) -> Option<XpuStatus> {
+        match &x.status {
+            None => Some(XpuStatus {
+                state: XpuState::Discovered,
+                firmware: String::new(),
+                representors: vec![],
+                reason: None,
+                last_transition: now,
+                rshim: None,
+                bfb: None,
+                resets: 0,
+                retries: 0,
+                console: vec![],
+            }),
+            // Left to the agent, which reports the next provisioning.
+            Some(status) if status.state == XpuState::Error => None,
+            Some(status) if !node_found => Some(XpuStatus {
+                state: XpuState::Error,
+                reason: Some(format!("Node <{}> not found", x.spec.node)),
+                last_transition: now,
+                ..status.clone()
+            }),
+            Some(status)
+                if status.state == XpuState::Provisioning
+                    && now.saturating_sub(status.last_transition) > self.provision_timeout =>
+            {
+                Some(XpuStatus {
+                    state: XpuState::Error,
+                    reason: Some(format!(
+                        "provisioning did not finish in {}s, increase spec.retries to retry",
+                        self.provision_timeout
+                    )),
+                    last_transition: now,
+                    ..status.clone()
+                })
+            }
+            Some(_) => None,
+        }
+    }
+}
+
+#[async_trait]
+impl Controller<Xpu> for XpuController {
+    async fn execute(&self, client: YangtzeClient, x: Xpu) -> Result<(), YangtzeError> {
+        let nodes = client
+            .clone()
+            .version(node::VERSION_KIND.version)
+            .kind(node::VERSION_KIND.kind)
+            .list::<Node>(NamespaceName {
+                namespace: Some(x.meta_data.namespace.clone()),
+                name: Some(x.spec.node.clone()),
+            })
+            .await?;
+
+        let Some(status) = self.next(&x, !nodes.is_empty(), now()) else {
+            return Ok(());
+        };
+
+        tracing::info!(
+            "Xpu <{}> is {}: {}",
+            x.meta_data,
+            status.state,
+            status.reason.clone().unwrap_or_default()
+        );
+
+        let mut x = x;
+        x.status = Some(status);
+        let _x = client.update::<Xpu>(x).await?;
+
+        Ok(())
+    }
+
+    fn get_version_kind(&self) -> VersionKind {
+        xpu::VERSION_KIND.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const CONTROLLER: XpuController = XpuController {
+        provision_timeout: 600,
+    };
+
+    fn xpu(state: Option<&str>, last_transition: u64) -> Xpu {
+        let status = state.map(|s| json!({"state": s, "last_transition": last_transition}));
+        serde_json::from_value(json!({
+            "meta_data": {"kind": "xpu", "namespace": "default", "name": "x1", "labels": [], "version": 0},
+            "spec": {"node": "n1"},
+            "status": status,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn discover_new_xpu() {
+        let status = CONTROLLER.next(&xpu(None, 0), true, 100).unwrap();
+        assert_eq!(status.state, XpuState::Discovered);
+        assert_eq!(status.last_transition, 100);
+
+        assert!(CONTROLLER
+            .next(&xpu(Some("discovered"), 0), true, 100)
+            .is_none());
+        assert!(CONTROLLER.next(&xpu(Some("ready"), 0), true, 100).is_none());
+    }
+
+    #[test]
+    fn fail_without_node() {
+        let status = CONTROLLER.next(&xpu(Some("ready"), 0), false, 100).unwrap();
+        assert_eq!(status.state, XpuState::Error);
+        assert_eq!(status.reason.as_deref(), Some("Node <n1> not found"));
+    }
+
+    #[test]
+    fn time_out_provisioning() {
+        let x = xpu(Some("provisioning"), 1000);
+        assert!(CONTROLLER.next(&x, true, 1600).is_none());
+
+        let status = CONTROLLER.next(&x, true, 1601).unwrap();
+        assert_eq!(status.state, XpuState::Error);
+        assert_eq!(status.last_transition, 1601);
+
+        // The agent moves it out of Error on the next provisioning.
+        assert!(CONTROLLER
+            .next(&xpu(Some("error"), 1601), true, 9999)
+            .is_none());
+        assert!(CONTROLLER
+            .next(&xpu(Some("error"), 1601), false, 9999)
+            .is_none());
+    }
+}
@@ -0,0 +1,32 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+/// The gNMI protos vendored from github.com/openconfig/gnmi v0.10.0, at their
+/// import paths.
+const PROTOS: &str = "proto";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // The protoc of the build host is not required.
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    let includes = [PROTOS.into(), protoc_bin_vendored::include_path()?];
+
+    tonic_build::configure().compile(
+        &[format!(
+            "{}/github.com/openconfig/gnmi/proto/gnmi/gnmi.proto",
+            PROTOS
+        )],
+        &includes,
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,25 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+//! The gNMI protocol of the switches managed over gNMI/OpenConfig: its
+//! messages, a client and a server routing to an implementation of `Gnmi`.
+
+pub mod client;
+pub mod path;
+pub mod proto;
+pub mod server;
+
+/// The extensions of gNMI, referred to by its messages.
+#[allow(clippy::doc_lazy_continuation)]
+pub mod gnmi_ext {
+    tonic::include_proto!("gnmi_ext");
+}
@@ -0,0 +1,100 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display, Formatter};
+
+use crate::proto::{Path, PathElem};
+
+impl Path {
+    /// Parses a path in the XPath-like syntax of gNMI, e.g.
+    /// `/interfaces/interface[name=Ethernet0]/state`; a `/` in the brackets
+    /// belongs to the key.
+    pub fn parse(s: &str) -> Result<Path, String> {
+        let mut elem = vec![];
+        let mut current = String::new();
+        let mut depth = 0;
+        for c in s.trim_start_matches('/').chars() {
+            match c {
+                '[' => depth += 1,
+                ']' if depth == 0 => return Err(format!("unbalanced <]> in <{}>", s)),
+                ']' => depth -= 1,
+                '/' if depth == 0 => {
+                    elem.push(parse_elem(&current, s)?);
+                    current.clear();
+                    continue;
+                }
+                _ => {}
+            }
+            current.push(c);
+        }
+        if depth != 0 {
+            return Err(format!("unbalanced <[> in <{}>", s));
+        }
+        if !current.is_empty() {
+            elem.push(parse_elem(&current, s)?);
+        }
+
+        Ok(Path {
+            elem,
+            ..Default::default()
+        })
+    }
+
+    /// Appends the elements of `other`, e.g. of an update to its prefix.
+    pub fn join(&self, other: &Path) -> Path {
+        let mut path = self.clone();
+        path.elem.extend(other.elem.iter().cloned());
+        path
+    }
+}
+
+fn parse_elem(s: &str, path: &str) -> Result<PathElem, String> {
+    let (name, keys) = match s.find('[') {
+        Some(i) => (&s[..i], &s[i..]),
+        None => (s, ""),
+    };
+    if name.is_empty() {
+        return Err(format!("empty element in <{}>", path));
+    }
+
+    let mut key = HashMap::new();
+    for k in keys.split_terminator(']') {
+        let (k, v) = k
+            .strip_prefix('[')
+            .and_then(|k| k.split_once('='))
+            .ok_or(format!("invalid key <{}> in <{}>", k, path))?;
+        key.insert(k.to_string(), v.to_string());
+    }
+
+    Ok(PathElem {
+        name: name.to_string(),
+        key,
+    })
+}
+
+impl Display for Path {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.elem.is_empty() {
+            return write!(f, "/");
+        }
+        for e in &self.elem {
+            write!(f, "/{}", e.name)?;
+            // Sorted, so that a path has a single form.
+            let keys: BTreeMap<_, _> = e.key.iter().collect();
+            for (k, v) in keys {
+                write!(f, "[{}={}]", k, v)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use yangtze_apis::v1::YangtzeError;
+use yangtze_gnmi::server::GnmiServer;
+use yangtze_gnmisim::target;
+
+#[derive(Parser)]
+#[command(name = "yangtze-gnmisim")]
+#[command(version = "0.1.0")]
+#[command(about = "A switch simulator serving gNMI with OpenConfig", long_about = None)]
+struct Cli {
+    #[arg(long, default_value = "127.0.0.1:9339")]
+    listen: SocketAddr,
+
+    /// A port reported DOWN, or a BGP neighbor reported IDLE
+    #[arg(long)]
+    down: Vec<String>,
+
+    /// A JSON file of the OpenConfig /lldp/interfaces reported by the switch
+    #[arg(long)]
+    lldp: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), YangtzeError> {
+    let subscriber = tracing_subscriber::FmtSubscriber::new();
+    // use that subscriber to process traces emitted after this point
+    tracing::subscriber::set_global_default(subscriber)
+        .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+
+    let cli = Cli::parse();
+
+    let mut target = target::Target::new(cli.down);
+    if let Some(path) = &cli.lldp {
+        let lldp = fs::read_to_string(path)
+            .map_err(|e| YangtzeError::GeneralError(format!("{}: {}", path.display(), e)))?;
+        target = target.lldp(serde_json::from_str(&lldp)?);
+    }
+    tracing::info!("Serve gNMI on <{}>.", cli.listen);
+    tonic::transport::Server::builder()
+        .add_service(GnmiServer::new(target))
+        .serve(cli.listen)
+        .await
+        .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+
+    Ok(())
+}
@@ -0,0 +1,473 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+use tokio::sync::{mpsc, watch};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+
+use yangtze_gnmi::{
+    proto::{
+        subscribe_request, subscribe_response, subscription_list, typed_value,
+        update_result::Operation, CapabilityRequest, CapabilityResponse, Encoding, GetRequest,
+        GetResponse, Notification, Path, SetRequest, SetResponse, SubscribeRequest,
+        SubscribeResponse, TypedValue, Update, UpdateResult,
+    },
+    server::Gnmi,
+};
+
+/// The OpenConfig subtrees the state is derived from.
+const INTERFACES: &str = "/interfaces";
+const BGP: &str =
+    "/network-instances/network-instance[name=default]/protocols/protocol[identifier=BGP][name=BGP]/bgp";
+
+/// The LLDP state of the ports, served as set by `lldp`.
+const LLDP: &str = "/lldp/interfaces";
+
+/// The version of gNMI of the vendored `gnmi.proto`.
+const GNMI_VERSION: &str = "0.10.0";
+
+/// The depth of the channel of the responses of a subscription.
+const RESPONSES: usize = 64;
+
+/// A fake switch storing the configuration set in a single tree, and
+/// deriving the operational state of its ports and BGP sessions from it.
+#[derive(Clone)]
+pub struct Target {
+    config: Arc<Mutex<Value>>,
+    /// The ports, and the BGP neighbors, reported down.
+    down: Arc<Vec<String>>,
+    /// Bumped on each Set, to notify the subscriptions.
+    changes: Arc<watch::Sender<u64>>,
+    /// The OpenConfig JSON of `/lldp/interfaces`, not found if null.
+    lldp: Arc<Value>,
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as i64)
+        .unwrap_or_default()
+}
+
+fn path(prefix: &Option<Path>, p: &Option<Path>) -> String {
+    let p = p.clone().unwrap_or_default();
+    match prefix {
+        Some(prefix) => prefix.join(&p).to_string(),
+        None => p.to_string(),
+    }
+}
+
+fn json(v: &Option<TypedValue>) -> Result<Value, String> {
+    match v.as_ref().and_then(|v| v.value.as_ref()) {
+        Some(typed_value::Value::JsonIetfVal(b)) | Some(typed_value::Value::JsonVal(b)) => {
+            serde_json::from_slice(b).map_err(|e| e.to_string())
+        }
+        Some(typed_value::Value::StringVal(s)) => Ok(Value::String(s.clone())),
+        Some(typed_value::Value::IntVal(i)) => Ok(Value::from(*i)),
+        Some(typed_value::Value::UintVal(u)) => Ok(Value::from(*u)),
+        Some(typed_value::Value::BoolVal(b)) => Ok(Value::Bool(*b)),
+        _ => Err("unsupported value".to_string()),
+    }
+}
+
+fn update(path: &str, value: &Value) -> Result<Update, String> {
+    Ok(Update {
+        path: Some(Path::parse(path)?),
+        val: Some(TypedValue {
+            value: Some(typed_value::Value::JsonIetfVal(
+                serde_json::to_vec(value).map_err(|e| e.to_string())?,
+            )),
+        }),
+        ..Default::default()
+    })
+}
+
+/// Whether a path matches a pattern of the same elements, whose keys may
+/// be `*`.
+fn matches(pattern: &Path, path: &Path) -> bool {
+    pattern.elem.len() == path.elem.len()
+        && pattern.elem.iter().zip(&path.elem).all(|(p, e)| {
+            p.name == e.name
+                && p.key
+                    .iter()
+                    .all(|(k, v)| v == "*" || e.key.get(k) == Some(v))
+        })
+}
+
+/// The name of the member of an object, whether qualified with its module
+/// or not.
+fn member(object: &serde_json::Map<String, Value>, name: &str) -> Option<String> {
+    object
+        .keys()
+        .find(|k| k.rsplit(':').next() == Some(name))
+        .cloned()
+}
+
+/// Whether a list entry has the keys of a path element.
+fn keyed(entry: &Value, key: &HashMap<String, String>) -> bool {
+    key.iter().all(|(k, v)| match entry.get(k) {
+        Some(Value::String(s)) => s == v,
+        Some(Value::Number(n)) => &n.to_string() == v,
+        _ => false,
+    })
+}
+
+/// The node at a path in the configuration.
+fn node<'a>(config: &'a Value, path: &Path) -> Option<&'a Value> {
+    let mut node = config;
+    for e in &path.elem {
+        node = &node[member(node.as_object()?, &e.name)?];
+        if !e.key.is_empty() {
+            node = node.as_array()?.iter().find(|n| keyed(n, &e.key))?;
+        }
+    }
+    Some(node)
+}
+
+/// The node at a path in the configuration, created with the containers
+/// and the list entries above it if missing.
+fn node_mut<'a>(config: &'a mut Value, path: &Path) -> &'a mut Value {
+    let mut node = config;
+    for e in &path.elem {
+        let object = match node {
+            Value::Object(o) => o,
+            other => {
+                *other = json!({});
+                other.as_object_mut().unwrap()
+            }
+        };
+        let name = member(object, &e.name).unwrap_or(e.name.clone());
+        let child = object.entry(name).or_insert(Value::Null);
+        if e.key.is_empty() {
+            node = child;
+            continue;
+        }
+        if !child.is_array() {
+            *child = json!([]);
+        }
+        let list = child.as_array_mut().unwrap();
+        let i = match list.iter().position(|n| keyed(n, &e.key)) {
+            Some(i) => i,
+            None => {
+                list.push(Value::Object(
+                    e.key.iter().map(|(k, v)| (k.clone(), json!(v))).collect(),
+                ));
+                list.len() - 1
+            }
+        };
+        node = &mut list[i];
+    }
+    node
+}
+
+/// Removes the node at a path from the configuration.
+fn remove(config: &mut Value, path: &Path) {
+    let Some((last, parent)) = path.elem.split_last() else {
+        *config = json!({});
+        return;
+    };
+    let parent = Path {
+        elem: parent.to_vec(),
+        ..Default::default()
+    };
+    if node(config, &parent).is_none() {
+        return;
+    }
+    let Value::Object(object) = node_mut(config, &parent) else {
+        return;
+    };
+    let Some(name) = member(object, &last.name) else {
+        return;
+    };
+    match object.get_mut(&name) {
+        Some(Value::Array(list)) if !last.key.is_empty() => list.retain(|n| !keyed(n, &last.key)),
+        _ => {
+            object.remove(&name);
+        }
+    }
+}
+
+/// Merges an update into the configuration, member by member.
+fn merge(node: &mut Value, value: Value) {
+    match (node, value) {
+        (Value::Object(node), Value::Object(value)) => {
+            for (k, v) in value {
+                let name = member(node, k.rsplit(':').next().unwrap_or(&k)).unwrap_or(k);
+                merge(node.entry(name).or_insert(Value::Null), v);
+            }
+        }
+        (node, value) => *node = value,
+    }
+}
+
+impl Target {
+    pub fn new(down: Vec<String>) -> Self {
+        Target {
+            config: Arc::new(Mutex::new(json!({}))),
+            down: Arc::new(down),
+            changes: Arc::new(watch::channel(0).0),
+            lldp: Arc::new(Value::Null),
+        }
+    }
+
+    /// Reports the LLDP neighbors of the ports in the OpenConfig JSON of
+    /// `/lldp/interfaces`.
+    pub fn lldp(mut self, lldp: Value) -> Self {
+        self.lldp = Arc::new(lldp);
+        self
+    }
+
+    fn status(&self, name: &str, up: bool, down: &str) -> String {
+        match up && !self.down.iter().any(|d| d == name) {
+            true => "UP".to_string(),
+            false => down.to_string(),
+        }
+    }
+
+    /// The leaves of the operational state, by path.
+    fn state(&self) -> BTreeMap<String, String> {
+        let config = self.config.lock().unwrap();
+        let mut state = BTreeMap::new();
+
+        let list = |p: String| {
+            Path::parse(&p)
+                .ok()
+                .and_then(|p| node(&config, &p)?.as_array().cloned())
+                .unwrap_or_default()
+        };
+
+        for i in list(format!("{}/interface", INTERFACES)) {
+            let Some(name) = i["name"].as_str() else {
+                continue;
+            };
+            let enabled = i["config"]["enabled"].as_bool().unwrap_or(true);
+            state.insert(
+                format!("/interfaces/interface[name={}]/state/oper-status", name),
+                self.status(name, enabled, "DOWN"),
+            );
+        }
+
+        let neighbors = list(format!("{}/neighbors/neighbor", BGP));
+        for n in neighbors {
+            let Some(address) = n["neighbor-address"].as_str() else {
+                continue;
+            };
+            let established = match self.status(address, true, "IDLE").as_str() {
+                "UP" => "ESTABLISHED".to_string(),
+                s => s.to_string(),
+            };
+            state.insert(
+                format!(
+                    "{}/neighbors/neighbor[neighbor-address={}]/state/session-state",
+                    BGP, address
+                ),
+                established,
+            );
+        }
+
+        state
+    }
+
+    /// The notification of the leaves of the state matching the patterns,
+    /// none if there is no such leaf.
+    fn notify(
+        &self,
+        patterns: &[Path],
+        state: &BTreeMap<String, String>,
+    ) -> Result<Option<SubscribeResponse>, String> {
+        let mut updates = vec![];
+        for (p, v) in state {
+            let path = Path::parse(p)?;
+            if patterns.iter().any(|pattern| matches(pattern, &path)) {
+                updates.push(update(p, &Value::String(v.clone()))?);
+            }
+        }
+        if updates.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(SubscribeResponse {
+            response: Some(subscribe_response::Response::Update(Notification {
+                timestamp: now(),
+                update: updates,
+                ..Default::default()
+            })),
+            ..Default::default()
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl Gnmi for Target {
+    async fn capabilities(
+        &self,
+        _request: Request<CapabilityRequest>,
+    ) -> Result<Response<CapabilityResponse>, Status> {
+        Ok(Response::new(CapabilityResponse {
+            supported_encodings: vec![Encoding::Json as i32, Encoding::JsonIetf as i32],
+            g_nmi_version: GNMI_VERSION.to_string(),
+            ..Default::default()
+        }))
+    }
+
+    async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetResponse>, Status> {
+        let request = request.into_inner();
+        let config = self.config.lock().unwrap();
+
+        let mut notification = vec![];
+        for p in &request.path {
+            let p = path(&request.prefix, &Some(p.clone()));
+            let value = match node(&config, &Path::parse(&p).map_err(Status::internal)?) {
+                Some(value) => value,
+                None if p == LLDP && !self.lldp.is_null() => &self.lldp,
+                None => return Err(Status::not_found(format!("{} not found", p))),
+            };
+            notification.push(Notification {
+                timestamp: now(),
+                update: vec![update(&p, value).map_err(Status::internal)?],
+                ..Default::default()
+            });
+        }
+
+        Ok(Response::new(GetResponse {
+            notification,
+            ..Default::default()
+        }))
+    }
+
+    async fn set(&self, request: Request<SetRequest>) -> Result<Response<SetResponse>, Status> {
+        let request = request.into_inner();
+
+        let mut response = vec![];
+        {
+            let mut config = self.config.lock().unwrap();
+            for p in &request.delete {
+                let p = path(&request.prefix, &Some(p.clone()));
+                remove(&mut config, &Path::parse(&p).map_err(Status::internal)?);
+                tracing::info!("Delete <{}>.", p);
+                response.push((p, Operation::Delete));
+            }
+            for (u, op) in request
+                .replace
+                .iter()
+                .map(|u| (u, Operation::Replace))
+                .chain(request.update.iter().map(|u| (u, Operation::Update)))
+            {
+                let p = path(&request.prefix, &u.path);
+                let value = json(&u.val).map_err(Status::invalid_argument)?;
+                let node = node_mut(&mut config, &Path::parse(&p).map_err(Status::internal)?);
+                match op {
+                    Operation::Update => merge(node, value),
+                    _ => *node = value,
+                }
+                tracing::info!("Set <{}>.", p);
+                response.push((p, op));
+            }
+        }
+        self.changes.send_modify(|v| *v += 1);
+
+        let response = response
+            .into_iter()
+            .map(|(p, op)| {
+                Ok(UpdateResult {
+                    path: Some(Path::parse(&p)?),
+                    op: op as i32,
+                    ..Default::default()
+                })
+            })
+            .collect::<Result<_, String>>()
+            .map_err(Status::internal)?;
+
+        Ok(Response::new(SetResponse {
+            prefix: request.prefix,
+            response,
+            timestamp: now(),
+            ..Default::default()
+        }))
+    }
+
+    type SubscribeStream = ReceiverStream<Result<SubscribeResponse, Status>>;
+
+    async fn subscribe(
+        &self,
+        request: Request<Streaming<SubscribeRequest>>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let mut requests = request.into_inner();
+        let Some(subscribe_request::Request::Subscribe(list)) =
+            requests.message().await?.and_then(|r| r.request)
+        else {
+            return Err(Status::invalid_argument("expect a subscription list"));
+        };
+        let mode = subscription_list::Mode::try_from(list.mode)
+            .map_err(|_| Status::invalid_argument("unknown mode"))?;
+        if mode == subscription_list::Mode::Poll {
+            return Err(Status::unimplemented("poll is not supported"));
+        }
+        let patterns: Vec<Path> = list
+            .subscription
+            .iter()
+            .map(|s| {
+                list.prefix
+                    .clone()
+                    .unwrap_or_default()
+                    .join(&s.path.clone().unwrap_or_default())
+            })
+            .collect();
+
+        let (tx, rx) = mpsc::channel(RESPONSES);
+        let target = self.clone();
+        tokio::spawn(async move {
+            let mut changes = target.changes.subscribe();
+            let mut sent = target.state();
+
+            let mut responses = vec![];
+            if !list.updates_only {
+                responses.extend(target.notify(&patterns, &sent).transpose());
+            }
+            responses.push(Ok(SubscribeResponse {
+                response: Some(subscribe_response::Response::SyncResponse(true)),
+                ..Default::default()
+            }));
+            for r in responses {
+                if tx.send(r.map_err(Status::internal)).await.is_err() {
+                    return;
+                }
+            }
+            if mode == subscription_list::Mode::Once {
+                return;
+            }
+
+            // Only the leaves changed are sent on change.
+            while changes.changed().await.is_ok() {
+                let state = target.state();
+                let changed: BTreeMap<String, String> = state
+                    .iter()
+                    .filter(|(p, v)| sent.get(*p) != Some(v))
+                    .map(|(p, v)| (p.clone(), v.clone()))
+                    .collect();
+                sent = state;
+                if let Some(r) = target.notify(&patterns, &changed).transpose() {
+                    if tx.send(r.map_err(Status::internal)).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
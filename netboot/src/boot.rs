@@ -0,0 +1,269 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::path::{Component, Path, PathBuf};
+
+use yangtze_apis::{
+    v1::{now, NamespaceName, YangtzeError},
+    v1alpha1::{
+        boot_profile::{self, BootProfile},
+        dhcp_lease::{self, DhcpLease},
+        node::{self, BootAttempt, Node},
+    },
+};
+use yangtze_client::YangtzeClient;
+
+/// The boot attempts kept in the status of a Node.
+const MAX_ATTEMPTS: usize = 20;
+
+/// Looks up the Nodes of the boot clients and records their boot attempts.
+#[derive(Clone)]
+pub struct Recorder {
+    pub client: YangtzeClient,
+    pub namespace: String,
+}
+
+impl Recorder {
+    fn nn(&self, name: Option<String>) -> NamespaceName {
+        NamespaceName {
+            namespace: Some(self.namespace.clone()),
+            name,
+        }
+    }
+
+    async fn nodes(&self) -> Result<Vec<Node>, YangtzeError> {
+        self.client
+            .clone()
+            .version(node::VERSION_KIND.version)
+            .kind(node::VERSION_KIND.kind)
+            .list(self.nn(None))
+            .await
+    }
+
+    /// The Node with a reservation or a NIC of `mac`.
+    pub async fn node_by_mac(&self, mac: &str) -> Result<Option<Node>, YangtzeError> {
+        let mac = mac.to_lowercase();
+        Ok(self.nodes().await?.into_iter().find(|n| {
+            n.spec
+                .reservations
+                .iter()
+                .any(|r| r.mac.to_lowercase() == mac)
+                || n.status
+                    .as_ref()
+                    .is_some_and(|s| s.nics.iter().any(|nic| nic.mac.to_lowercase() == mac))
+        }))
+    }
+
+    /// The Node with a reservation or a DHCP lease of `address`.
+    pub async fn node_by_address(&self, address: &str) -> Result<Option<Node>, YangtzeError> {
+        let nodes = self.nodes().await?;
+        if let Some(n) = nodes
+            .iter()
+            .position(|n| n.spec.reservations.iter().any(|r| r.address == address))
+        {
+            return Ok(nodes.into_iter().nth(n));
+        }
+
+        let leases: Vec<DhcpLease> = self
+            .client
+            .clone()
+            .version(dhcp_lease::VERSION_KIND.version)
+            .kind(dhcp_lease::VERSION_KIND.kind)
+            .list(self.nn(None))
+            .await?;
+        match leases.into_iter().find(|l| l.spec.address == address) {
+            Some(l) => self.node_by_mac(&l.spec.mac).await,
+            None => Ok(None),
+        }
+    }
+
+    pub async fn profile(&self, name: &str) -> Result<Option<BootProfile>, YangtzeError> {
+        Ok(self
+            .client
+            .clone()
+            .version(boot_profile::VERSION_KIND.version)
+            .kind(boot_profile::VERSION_KIND.kind)
+            .list(self.nn(Some(name.to_string())))
+            .await?
+            .pop())
+    }
+
+    /// Appends a boot attempt to the status of `node`.
+    pub async fn record(
+        &self,
+        node: Node,
+        stage: &str,
+        detail: &str,
+        address: &str,
+    ) -> Result<(), YangtzeError> {
+        tracing::info!(
+            "Node <{}> boot {}: {} from <{}>.",
+            node,
+            stage,
+            detail,
+            address
+        );
+
+        let client = self
+            .client
+            .clone()
+            .version(node::VERSION_KIND.version)
+            .kind(node::VERSION_KIND.kind);
+
+        let mut node = node;
+        if let Some(status) = node.status.as_mut() {
+            status.boots.push(BootAttempt {
+                time: now(),
+                stage: stage.to_string(),
+                detail: detail.to_string(),
+                address: address.to_string(),
+            });
+            let n = status.boots.len().saturating_sub(MAX_ATTEMPTS);
+            status.boots.drain(..n);
+        }
+        let _node = client.update(node).await?;
+
+        Ok(())
+    }
+}
+
+/// Renders the iPXE script of a Node; without a BootProfile, the Node boots
+/// from its local disk.
+pub fn script(node: &Node, mac: &str, profile: Option<&BootProfile>, base_url: &str) -> String {
+    let Some(profile) = profile else {
+        return format!(
+            "#!ipxe\necho No BootProfile for {}, boot from the local disk.\nexit\n",
+            node
+        );
+    };
+
+    let spec = &profile.spec;
+    let image = spec.image.clone().unwrap_or_default();
+    let cmdline = spec
+        .cmdline
+        .replace("{node}", &node.meta_data.name)
+        .replace("{mac}", mac)
+        .replace("{image}", &image);
+
+    let mut script = format!(
+        "#!ipxe\necho Booting {} with BootProfile {}.\nkernel {} {}\n",
+        node,
+        profile,
+        url(&spec.kernel, base_url),
+        cmdline
+    );
+    if let Some(initrd) = &spec.initrd {
+        script.push_str(&format!("initrd {}\n", url(initrd, base_url)));
+    }
+    script.push_str("boot\n");
+
+    script
+}
+
+/// The URL of a file served by this server, or the URL as is.
+fn url(path: &str, base_url: &str) -> String {
+    if path.contains("://") {
+        path.to_string()
+    } else {
+        format!("{}/files/{}", base_url, path.trim_start_matches('/'))
+    }
+}
+
+/// The path of a requested file under `root`, none if it would escape it.
+pub fn resolve(root: &Path, file: &str) -> Option<PathBuf> {
+    let mut path = root.to_path_buf();
+    for c in Path::new(file.trim_start_matches('/')).components() {
+        match c {
+            Component::Normal(c) => path.push(c),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const BASE_URL: &str = "http://10.0.0.2:8080";
+
+    fn node() -> Node {
+        serde_json::from_value(json!({
+            "meta_data": {"kind": "node", "namespace": "default", "name": "n1", "labels": [], "version": 0},
+            "spec": {"hostname": "n1", "serial": "s1", "boot_profile": "ubuntu"},
+        }))
+        .unwrap()
+    }
+
+    fn profile(initrd: Option<&str>) -> BootProfile {
+        serde_json::from_value(json!({
+            "meta_data": {"kind": "bootprofile", "namespace": "default", "name": "ubuntu", "labels": [], "version": 0},
+            "spec": {
+                "kernel": "ubuntu/vmlinuz",
+                "initrd": initrd,
+                "cmdline": "hostname={node} mac={mac} url={image}",
+                "image": "http://mirror/ubuntu.img",
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn script_of_profile() {
+        let script = script(
+            &node(),
+            "52:54:00:00:00:01",
+            Some(&profile(Some("https://mirror/initrd"))),
+            BASE_URL,
+        );
+        assert_eq!(
+            script,
+            "#!ipxe\n\
+             echo Booting n1 with BootProfile ubuntu.\n\
+             kernel http://10.0.0.2:8080/files/ubuntu/vmlinuz hostname=n1 mac=52:54:00:00:00:01 url=http://mirror/ubuntu.img\n\
+             initrd https://mirror/initrd\n\
+             boot\n"
+        );
+
+        let without = super::script(&node(), "52:54:00:00:00:01", Some(&profile(None)), BASE_URL);
+        assert!(!without.contains("initrd"));
+    }
+
+    #[test]
+    fn script_of_local_disk() {
+        let script = script(&node(), "52:54:00:00:00:01", None, BASE_URL);
+        assert!(script.starts_with("#!ipxe\n"));
+        assert!(script.contains("boot from the local disk"));
+        assert!(script.ends_with("exit\n"));
+    }
+
+    #[test]
+    fn resolve_under_root() {
+        let root = Path::new("/srv/netboot");
+        assert_eq!(
+            resolve(root, "ubuntu/vmlinuz"),
+            Some(PathBuf::from("/srv/netboot/ubuntu/vmlinuz"))
+        );
+        assert_eq!(
+            resolve(root, "/./ubuntu//initrd"),
+            Some(PathBuf::from("/srv/netboot/ubuntu/initrd"))
+        );
+        assert_eq!(resolve(root, "../etc/passwd"), None);
+        assert_eq!(resolve(root, "ubuntu/../../etc/passwd"), None);
+        assert_eq!(resolve(root, "ubuntu/.."), None);
+    }
+}
@@ -0,0 +1,795 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+
+use ipnet::Ipv4Net;
+
+use yangtze_apis::{
+    v1::{Metadata, YangtzeError},
+    v1alpha1::{
+        dhcp_lease::{self, DhcpLease, DhcpLeaseSpec, DhcpLeaseState, DhcpLeaseStatus},
+        network_interface::NetworkInterface,
+        node::Node,
+        subnet::{DhcpConfig, Subnet},
+    },
+};
+
+use super::packet::*;
+
+/// Seconds an offered address is kept for the client.
+const OFFER_TIMEOUT: u64 = 60;
+
+/// The BIOS architecture in the client system architecture option, RFC 4578.
+const ARCH_BIOS: u16 = 0;
+
+/// A Subnet with a DHCP range.
+#[derive(Clone, Debug)]
+pub struct Pool {
+    pub name: String,
+    pub net: Ipv4Net,
+    pub gateway: Option<Ipv4Addr>,
+    pub dns: Vec<Ipv4Addr>,
+    pub start: Ipv4Addr,
+    pub end: Ipv4Addr,
+    pub next_server: Option<Ipv4Addr>,
+    pub config: DhcpConfig,
+}
+
+impl TryFrom<&Subnet> for Pool {
+    type Error = YangtzeError;
+
+    fn try_from(s: &Subnet) -> Result<Self, Self::Error> {
+        let invalid = |e: String| YangtzeError::InvalidConfig(format!("Subnet <{}>: {}", s, e));
+        let addr = |a: &str| a.parse::<Ipv4Addr>().map_err(|e| invalid(e.to_string()));
+
+        let config = s
+            .spec
+            .dhcp
+            .clone()
+            .ok_or(invalid("no DHCP range".to_string()))?;
+        let net: Ipv4Net = s.spec.cidr.parse().map_err(|e| invalid(format!("{}", e)))?;
+        let (start, end) = (addr(&config.start)?, addr(&config.end)?);
+        if !net.contains(&start) || !net.contains(&end) || start > end {
+            return Err(invalid(format!(
+                "the range {}-{} is not in {}",
+                start, end, net
+            )));
+        }
+
+        Ok(Pool {
+            name: s.meta_data.name.clone(),
+            net,
+            gateway: s.spec.gateway.as_deref().map(addr).transpose()?,
+            dns: s
+                .spec
+                .dns
+                .iter()
+                .map(|a| addr(a))
+                .collect::<Result<_, _>>()?,
+            start,
+            end,
+            next_server: config.next_server.as_deref().map(addr).transpose()?,
+            config,
+        })
+    }
+}
+
+/// A fixed address of a Node or of a NetworkInterface.
+#[derive(Clone, Debug)]
+pub struct Reservation {
+    pub mac: String,
+    pub address: Ipv4Addr,
+    pub node: String,
+    pub hostname: String,
+}
+
+/// The reply to a client if any, and the lease to persist if it changed.
+#[derive(Debug)]
+pub struct Reply {
+    pub packet: Option<Packet>,
+    pub lease: Option<DhcpLease>,
+}
+
+/// Hands out the addresses of the Subnets; it only holds the state synced
+/// from the apiserver and the pending offers, so it is driven by calling
+/// `handle` with the decoded requests.
+pub struct Handler {
+    namespace: String,
+    /// The address of this server, i.e. the server identifier.
+    server: Ipv4Addr,
+    pools: Vec<Pool>,
+    reservations: Vec<Reservation>,
+    /// The leases by name.
+    leases: HashMap<String, DhcpLease>,
+    /// The offered addresses by lease name, with their expiry.
+    offers: HashMap<String, (Ipv4Addr, u64)>,
+}
+
+impl Handler {
+    pub fn new(namespace: &str, server: Ipv4Addr) -> Self {
+        Handler {
+            namespace: namespace.to_string(),
+            server,
+            pools: vec![],
+            reservations: vec![],
+            leases: HashMap::new(),
+            offers: HashMap::new(),
+        }
+    }
+
+    /// Replaces the Subnets, the reservations of the Nodes and of the
+    /// NetworkInterfaces, and the leases.
+    pub fn sync(
+        &mut self,
+        subnets: &[Subnet],
+        nodes: &[Node],
+        interfaces: &[NetworkInterface],
+        leases: Vec<DhcpLease>,
+    ) {
+        self.pools = subnets
+            .iter()
+            .filter(|s| s.spec.dhcp.is_some())
+            .filter_map(|s| match Pool::try_from(s) {
+                Ok(p) => Some(p),
+                Err(e) => {
+                    tracing::error!("{}", e);
+                    None
+                }
+            })
+            .collect();
+
+        self.reservations = nodes
+            .iter()
+            .flat_map(|n| {
+                n.spec.reservations.iter().filter_map(|r| {
+                    Some(Reservation {
+                        mac: r.mac.to_lowercase(),
+                        address: r.address.parse().ok()?,
+                        node: n.meta_data.name.clone(),
+                        hostname: match n.spec.hostname.as_str() {
+                            "" => n.meta_data.name.clone(),
+                            h => h.to_string(),
+                        },
+                    })
+                })
+            })
+            .collect();
+
+        // The addresses allocated to the interfaces by the controller.
+        self.reservations.extend(interfaces.iter().filter_map(|i| {
+            let status = i.status.as_ref()?;
+            Some(Reservation {
+                mac: status.mac.as_ref()?.to_lowercase(),
+                address: status.address.as_ref()?.parse().ok()?,
+                node: i.spec.node.clone(),
+                hostname: i.meta_data.name.clone(),
+            })
+        }));
+
+        // The leases handed out but not persisted yet are kept.
+        let mut unpersisted: HashMap<_, _> = self
+            .leases
+            .drain()
+            .filter(|(_, l)| l.meta_data.uuid.is_none())
+            .collect();
+        self.leases = leases
+            .into_iter()
+            .map(|l| {
+                unpersisted.remove(&l.meta_data.name);
+                (l.meta_data.name.clone(), l)
+            })
+            .collect();
+        self.leases.extend(unpersisted);
+    }
+
+    /// Takes the id and version of a lease persisted in the apiserver; the
+    /// lease itself was recorded when handed out, and may be newer.
+    pub fn persisted(&mut self, lease: DhcpLease) {
+        match self.leases.get_mut(&lease.meta_data.name) {
+            Some(l) => l.meta_data = lease.meta_data,
+            None => {
+                self.leases.insert(lease.meta_data.name.clone(), lease);
+            }
+        }
+    }
+
+    /// Takes the id and version of the lease persisted last, if any.
+    pub fn latest(&self, mut lease: DhcpLease) -> DhcpLease {
+        if let Some(l) = self.leases.get(&lease.meta_data.name) {
+            lease.meta_data = l.meta_data.clone();
+        }
+        lease
+    }
+
+    pub fn handle(&mut self, req: &Packet, now: u64) -> Option<Reply> {
+        if req.op != BOOTREQUEST {
+            return None;
+        }
+        let t = req.message_type()?;
+        let pool = self.pool(req)?.clone();
+        let mac = req.mac();
+
+        match t {
+            MessageType::Discover => {
+                let addr =
+                    self.allocate(&pool, &mac, req.address_option(OPT_REQUESTED_ADDRESS), now)?;
+                self.offers.insert(
+                    dhcp_lease::name(&pool.name, &mac),
+                    (addr, now + OFFER_TIMEOUT),
+                );
+
+                let mut reply = req.reply(MessageType::Offer);
+                reply.yiaddr = addr;
+                self.options(&pool, req, &mut reply, &mac, true);
+                Some(Reply {
+                    packet: Some(reply),
+                    lease: None,
+                })
+            }
+            MessageType::Request => {
+                let name = dhcp_lease::name(&pool.name, &mac);
+                // The client took the offer of another server.
+                if let Some(id) = req.address_option(OPT_SERVER_ID) {
+                    if id != self.server {
+                        self.offers.remove(&name);
+                        return None;
+                    }
+                }
+
+                let requested = req
+                    .address_option(OPT_REQUESTED_ADDRESS)
+                    .unwrap_or(req.ciaddr);
+                let addr = self.allocate(&pool, &mac, Some(requested), now);
+                if addr != Some(requested) {
+                    let mut reply = req.reply(MessageType::Nak);
+                    reply.set_option(OPT_SERVER_ID, self.server.octets().to_vec());
+                    reply.set_option(
+                        OPT_MESSAGE,
+                        format!("{} is not available", requested).into_bytes(),
+                    );
+                    return Some(Reply {
+                        packet: Some(reply),
+                        lease: None,
+                    });
+                }
+
+                self.offers.remove(&name);
+                let expires = now + pool.config.lease_time as u64;
+                let lease = self.lease(&pool, &mac, requested, DhcpLeaseState::Bound, expires);
+                // Recorded at once, so the address is not handed out again
+                // while the lease is persisted.
+                self.leases
+                    .insert(lease.meta_data.name.clone(), lease.clone());
+
+                let mut reply = req.reply(MessageType::Ack);
+                reply.yiaddr = requested;
+                reply.ciaddr = req.ciaddr;
+                self.options(&pool, req, &mut reply, &mac, true);
+                Some(Reply {
+                    packet: Some(reply),
+                    lease: Some(lease),
+                })
+            }
+            MessageType::Decline => {
+                let addr = req.address_option(OPT_REQUESTED_ADDRESS)?;
+                tracing::warn!("<{}> declined {} in Subnet <{}>.", mac, addr, pool.name);
+
+                let expires = now + pool.config.lease_time as u64;
+                let lease = self.lease(&pool, &mac, addr, DhcpLeaseState::Declined, expires);
+                self.offers.remove(&lease.meta_data.name);
+                self.leases
+                    .insert(lease.meta_data.name.clone(), lease.clone());
+
+                Some(Reply {
+                    packet: None,
+                    lease: Some(lease),
+                })
+            }
+            MessageType::Release => {
+                let name = dhcp_lease::name(&pool.name, &mac);
+                let lease = self.leases.get(&name)?;
+                let status = lease.status.as_ref()?;
+                if status.state != DhcpLeaseState::Bound {
+                    return None;
+                }
+                let addr = lease.spec.address.parse().ok()?;
+                let lease = self.lease(&pool, &mac, addr, DhcpLeaseState::Released, now);
+                self.leases
+                    .insert(lease.meta_data.name.clone(), lease.clone());
+
+                Some(Reply {
+                    packet: None,
+                    lease: Some(lease),
+                })
+            }
+            MessageType::Inform => {
+                let mut reply = req.reply(MessageType::Ack);
+                reply.ciaddr = req.ciaddr;
+                self.options(&pool, req, &mut reply, &mac, false);
+                Some(Reply {
+                    packet: Some(reply),
+                    lease: None,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// The pool of the client: the one of the relay agent, of its current
+    /// address when renewing, or of this server.
+    fn pool(&self, req: &Packet) -> Option<&Pool> {
+        let addr = [req.giaddr, req.ciaddr, self.server]
+            .into_iter()
+            .find(|a| !a.is_unspecified())?;
+
+        self.pools.iter().find(|p| p.net.contains(&addr))
+    }
+
+    /// The address of `mac` in `pool`: its reservation, its last lease, its
+    /// offer, the requested address, or the first free one of the range.
+    fn allocate(
+        &self,
+        pool: &Pool,
+        mac: &str,
+        requested: Option<Ipv4Addr>,
+        now: u64,
+    ) -> Option<Ipv4Addr> {
+        if let Some(r) = self
+            .reservations
+            .iter()
+            .find(|r| r.mac == mac && pool.net.contains(&r.address))
+        {
+            return Some(r.address);
+        }
+
+        let name = dhcp_lease::name(&pool.name, mac);
+        let last = self.leases.get(&name).and_then(|l| {
+            let state = &l.status.as_ref()?.state;
+            if *state == DhcpLeaseState::Declined {
+                return None;
+            }
+            l.spec.address.parse::<Ipv4Addr>().ok()
+        });
+        let offered = self
+            .offers
+            .get(&name)
+            .filter(|(_, expires)| *expires > now)
+            .map(|(a, _)| *a);
+        let in_range = |a: &Ipv4Addr| *a >= pool.start && *a <= pool.end;
+
+        [last, offered, requested.filter(in_range)]
+            .into_iter()
+            .flatten()
+            .find(|a| pool.net.contains(a) && self.free(pool, *a, mac, now))
+            .or_else(|| {
+                (u32::from(pool.start)..=u32::from(pool.end))
+                    .map(Ipv4Addr::from)
+                    .find(|a| self.free(pool, *a, mac, now))
+            })
+    }
+
+    /// Whether `addr` is neither reserved, leased nor offered to another MAC.
+    fn free(&self, pool: &Pool, addr: Ipv4Addr, mac: &str, now: u64) -> bool {
+        if Some(addr) == pool.gateway
+            || addr == self.server
+            || addr == pool.net.network()
+            || addr == pool.net.broadcast()
+        {
+            return false;
+        }
+
+        let reserved = self
+            .reservations
+            .iter()
+            .any(|r| r.address == addr && r.mac != mac);
+        let leased = self.leases.values().any(|l| {
+            l.spec.subnet == pool.name
+                && l.spec.mac != mac
+                && l.spec.address == addr.to_string()
+                && l.status
+                    .as_ref()
+                    .is_some_and(|s| s.state != DhcpLeaseState::Released && s.expires > now)
+        });
+        let offered = self.offers.iter().any(|(name, (a, expires))| {
+            *a == addr && *expires > now && *name != dhcp_lease::name(&pool.name, mac)
+        });
+
+        !reserved && !leased && !offered
+    }
+
+    fn options(&self, pool: &Pool, req: &Packet, reply: &mut Packet, mac: &str, lease: bool) {
+        reply.set_option(OPT_SERVER_ID, self.server.octets().to_vec());
+        if lease {
+            let t = pool.config.lease_time;
+            reply.set_option(OPT_LEASE_TIME, t.to_be_bytes().to_vec());
+            reply.set_option(OPT_RENEWAL_TIME, (t / 2).to_be_bytes().to_vec());
+            reply.set_option(OPT_REBINDING_TIME, (t / 8 * 7).to_be_bytes().to_vec());
+        }
+        reply.set_option(OPT_SUBNET_MASK, pool.net.netmask().octets().to_vec());
+        if let Some(gw) = pool.gateway {
+            reply.set_option(OPT_ROUTER, gw.octets().to_vec());
+        }
+        if !pool.dns.is_empty() {
+            reply.set_option(OPT_DNS, pool.dns.iter().flat_map(|a| a.octets()).collect());
+        }
+        if let Some(r) = self.reservations.iter().find(|r| r.mac == mac) {
+            reply.set_option(OPT_HOSTNAME, r.hostname.clone().into_bytes());
+        }
+
+        self.pxe(pool, req, reply);
+
+        if let Some(relay) = req.option(OPT_RELAY_AGENT) {
+            reply.set_option(OPT_RELAY_AGENT, relay.to_vec());
+        }
+    }
+
+    /// The boot options of PXE clients: the iPXE script for iPXE, which
+    /// sends the `iPXE` user class, or the bootloader of the architecture.
+    fn pxe(&self, pool: &Pool, req: &Packet, reply: &mut Packet) {
+        let pxe = req
+            .option(OPT_VENDOR_CLASS)
+            .is_some_and(|v| v.starts_with(b"PXEClient"));
+        let ipxe = req
+            .option(OPT_USER_CLASS)
+            .is_some_and(|v| user_class(v, b"iPXE"));
+        if !pxe && !ipxe {
+            return;
+        }
+
+        let arch = req
+            .option(OPT_CLIENT_ARCH)
+            .and_then(|v| v.get(..2))
+            .map(|v| u16::from_be_bytes([v[0], v[1]]))
+            .unwrap_or(ARCH_BIOS);
+
+        let config = &pool.config;
+        let file = match (ipxe, &config.ipxe_script) {
+            (true, Some(script)) => Some(script.clone()),
+            _ if arch == ARCH_BIOS => config.filename.clone(),
+            _ => config.efi_filename.clone().or(config.filename.clone()),
+        };
+        let Some(file) = file else {
+            return;
+        };
+
+        let next_server = pool.next_server.unwrap_or(self.server);
+        reply.siaddr = next_server;
+        reply.file = file.clone();
+        reply.set_option(OPT_TFTP_SERVER, next_server.to_string().into_bytes());
+        reply.set_option(OPT_BOOTFILE, file.into_bytes());
+        if pxe {
+            reply.set_option(OPT_VENDOR_CLASS, b"PXEClient".to_vec());
+        }
+    }
+
+    fn lease(
+        &self,
+        pool: &Pool,
+        mac: &str,
+        addr: Ipv4Addr,
+        state: DhcpLeaseState,
+        expires: u64,
+    ) -> DhcpLease {
+        let name = dhcp_lease::name(&pool.name, mac);
+        let reservation = self.reservations.iter().find(|r| r.mac == mac);
+
+        let meta_data = match self.leases.get(&name) {
+            Some(l) => l.meta_data.clone(),
+            None => Metadata {
+                uuid: None,
+                kind: dhcp_lease::VERSION_KIND.kind.to_string(),
+                namespace: self.namespace.clone(),
+                name,
+                labels: vec![],
+                version: 0,
+            },
+        };
+
+        DhcpLease {
+            meta_data,
+            spec: DhcpLeaseSpec {
+                subnet: pool.name.clone(),
+                mac: mac.to_string(),
+                address: addr.to_string(),
+                hostname: reservation.map(|r| r.hostname.clone()),
+                node: reservation.map(|r| r.node.clone()),
+            },
+            status: Some(DhcpLeaseStatus { state, expires }),
+        }
+    }
+}
+
+/// Whether the user class option, a list of length-prefixed classes
+/// (RFC 3004) or a bare string as sent by iPXE, holds `class`.
+fn user_class(v: &[u8], class: &[u8]) -> bool {
+    if v == class {
+        return true;
+    }
+
+    let mut i = 0;
+    while i < v.len() {
+        let len = v[i] as usize;
+        if v.get(i + 1..i + 1 + len) == Some(class) {
+            return true;
+        }
+        i += 1 + len;
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+    use yangtze_apis::v1alpha1::subnet::SubnetSpec;
+
+    use super::*;
+
+    const SERVER: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 2);
+    const NOW: u64 = 1_000_000;
+
+    fn handler() -> Handler {
+        let subnet = Subnet {
+            meta_data: Metadata {
+                uuid: None,
+                kind: "subnet".to_string(),
+                namespace: "default".to_string(),
+                name: "pxe".to_string(),
+                labels: vec![],
+                version: 0,
+            },
+            spec: SubnetSpec {
+                cidr: "10.0.0.0/24".to_string(),
+                gateway: Some("10.0.0.1".to_string()),
+                dns: vec!["10.0.0.53".to_string()],
+                dhcp: Some(DhcpConfig {
+                    start: "10.0.0.100".to_string(),
+                    end: "10.0.0.101".to_string(),
+                    lease_time: 3600,
+                    next_server: None,
+                    filename: Some("undionly.kpxe".to_string()),
+                    efi_filename: Some("ipxe.efi".to_string()),
+                    ipxe_script: None,
+                }),
+                vpc: None,
+                vlan: None,
+            },
+            status: None,
+        };
+
+        let mut handler = Handler::new("default", SERVER);
+        handler.sync(&[subnet], &[], &[], vec![]);
+        handler
+    }
+
+    /// A request of the client `52:54:00:00:00:<n>`, as received on the wire.
+    fn request(t: MessageType, n: u8, options: Vec<(u8, Vec<u8>)>) -> Packet {
+        let mut chaddr = [0; 16];
+        chaddr[..6].copy_from_slice(&[0x52, 0x54, 0, 0, 0, n]);
+        let mut req = Packet {
+            op: BOOTREQUEST,
+            htype: 1,
+            hlen: 6,
+            hops: 0,
+            xid: 0x1234_5678,
+            secs: 0,
+            flags: 0x8000,
+            ciaddr: Ipv4Addr::UNSPECIFIED,
+            yiaddr: Ipv4Addr::UNSPECIFIED,
+            siaddr: Ipv4Addr::UNSPECIFIED,
+            giaddr: Ipv4Addr::UNSPECIFIED,
+            chaddr,
+            sname: String::new(),
+            file: String::new(),
+            options: vec![(OPT_MESSAGE_TYPE, vec![t as u8])],
+        };
+        req.options.extend(options);
+
+        Packet::decode(&req.encode()).unwrap()
+    }
+
+    /// Handles a request, and decodes the reply as the client would.
+    fn handle(handler: &mut Handler, req: &Packet) -> (Option<Packet>, Option<DhcpLease>) {
+        let Some(reply) = handler.handle(req, NOW) else {
+            return (None, None);
+        };
+        let packet = reply.packet.map(|p| Packet::decode(&p.encode()).unwrap());
+        (packet, reply.lease)
+    }
+
+    fn address(a: Ipv4Addr) -> Vec<u8> {
+        a.octets().to_vec()
+    }
+
+    /// Persists a lease as the apiserver would.
+    fn persist(handler: &mut Handler, lease: DhcpLease) {
+        let mut lease = handler.latest(lease);
+        lease.meta_data.uuid.get_or_insert_with(Uuid::new_v4);
+        lease.meta_data.version += 1;
+        handler.persisted(lease);
+    }
+
+    #[test]
+    fn offer_to_discover() {
+        let mut handler = handler();
+        let discover = request(
+            MessageType::Discover,
+            1,
+            vec![(OPT_VENDOR_CLASS, b"PXEClient:Arch:00000".to_vec())],
+        );
+
+        let (offer, lease) = handle(&mut handler, &discover);
+        let offer = offer.unwrap();
+        assert!(lease.is_none());
+        assert_eq!(offer.op, BOOTREPLY);
+        assert_eq!(offer.xid, discover.xid);
+        assert_eq!(offer.message_type(), Some(MessageType::Offer));
+        assert_eq!(offer.yiaddr, Ipv4Addr::new(10, 0, 0, 100));
+        assert_eq!(offer.address_option(OPT_SERVER_ID), Some(SERVER));
+        assert_eq!(
+            offer.address_option(OPT_SUBNET_MASK),
+            Some(Ipv4Addr::new(255, 255, 255, 0))
+        );
+        assert_eq!(
+            offer.address_option(OPT_ROUTER),
+            Some(Ipv4Addr::new(10, 0, 0, 1))
+        );
+        assert_eq!(
+            offer.option(OPT_LEASE_TIME),
+            Some(&3600u32.to_be_bytes()[..])
+        );
+        assert_eq!(offer.file, "undionly.kpxe");
+        assert_eq!(offer.siaddr, SERVER);
+
+        // The offer is kept for the client, and not offered to another one.
+        let (again, _) = handle(&mut handler, &discover);
+        assert_eq!(again.unwrap().yiaddr, Ipv4Addr::new(10, 0, 0, 100));
+        let (other, _) = handle(&mut handler, &request(MessageType::Discover, 2, vec![]));
+        assert_eq!(other.unwrap().yiaddr, Ipv4Addr::new(10, 0, 0, 101));
+        // The range is exhausted.
+        let (none, _) = handle(&mut handler, &request(MessageType::Discover, 3, vec![]));
+        assert!(none.is_none());
+    }
+
+    #[test]
+    fn ack_to_request() {
+        let mut handler = handler();
+        let (offer, _) = handle(&mut handler, &request(MessageType::Discover, 1, vec![]));
+        let offered = offer.unwrap().yiaddr;
+
+        let req = request(
+            MessageType::Request,
+            1,
+            vec![
+                (OPT_REQUESTED_ADDRESS, address(offered)),
+                (OPT_SERVER_ID, address(SERVER)),
+            ],
+        );
+        let (ack, lease) = handle(&mut handler, &req);
+        let ack = ack.unwrap();
+        assert_eq!(ack.message_type(), Some(MessageType::Ack));
+        assert_eq!(ack.yiaddr, offered);
+
+        let lease = lease.unwrap();
+        assert_eq!(lease.meta_data.name, "pxe-525400000001");
+        assert_eq!(lease.spec.address, offered.to_string());
+        assert_eq!(lease.spec.mac, "52:54:00:00:00:01");
+        let status = lease.status.clone().unwrap();
+        assert_eq!(status.state, DhcpLeaseState::Bound);
+        assert_eq!(status.expires, NOW + 3600);
+
+        // A renewal updates the lease persisted.
+        persist(&mut handler, lease);
+        let (ack, lease) = handle(&mut handler, &req);
+        assert_eq!(ack.unwrap().message_type(), Some(MessageType::Ack));
+        assert!(lease.unwrap().meta_data.uuid.is_some());
+    }
+
+    #[test]
+    fn keep_ack_until_persisted() {
+        let mut handler = handler();
+        let req = request(
+            MessageType::Request,
+            1,
+            vec![(OPT_REQUESTED_ADDRESS, address(Ipv4Addr::new(10, 0, 0, 100)))],
+        );
+        let (ack, lease) = handle(&mut handler, &req);
+        assert_eq!(ack.unwrap().message_type(), Some(MessageType::Ack));
+        let lease = lease.unwrap();
+
+        // Another client, before the lease is persisted.
+        let discover = request(MessageType::Discover, 2, vec![]);
+        let (offer, _) = handle(&mut handler, &discover);
+        assert_eq!(offer.unwrap().yiaddr, Ipv4Addr::new(10, 0, 0, 101));
+        let (nak, _) = handle(
+            &mut handler,
+            &request(
+                MessageType::Request,
+                3,
+                vec![(OPT_REQUESTED_ADDRESS, address(Ipv4Addr::new(10, 0, 0, 100)))],
+            ),
+        );
+        assert_eq!(nak.unwrap().message_type(), Some(MessageType::Nak));
+
+        // Persisting an older lease only takes its id and version.
+        let mut older = handler.latest(lease.clone());
+        older.status.as_mut().unwrap().state = DhcpLeaseState::Released;
+        older.meta_data.uuid = Some(Uuid::new_v4());
+        older.meta_data.version = 1;
+        handler.persisted(older.clone());
+        let latest = handler.latest(lease);
+        assert_eq!(latest.meta_data.uuid, older.meta_data.uuid);
+        assert_eq!(latest.status.as_ref().unwrap().state, DhcpLeaseState::Bound);
+    }
+
+    #[test]
+    fn nak_to_unavailable_request() {
+        let mut handler = handler();
+        let req = request(
+            MessageType::Request,
+            1,
+            vec![(OPT_REQUESTED_ADDRESS, address(Ipv4Addr::new(10, 0, 0, 100)))],
+        );
+        let (_, lease) = handle(&mut handler, &req);
+        persist(&mut handler, lease.unwrap());
+
+        // Leased to another client.
+        let (nak, lease) = handle(
+            &mut handler,
+            &request(
+                MessageType::Request,
+                2,
+                vec![(OPT_REQUESTED_ADDRESS, address(Ipv4Addr::new(10, 0, 0, 100)))],
+            ),
+        );
+        let nak = nak.unwrap();
+        assert!(lease.is_none());
+        assert_eq!(nak.message_type(), Some(MessageType::Nak));
+        assert_eq!(nak.yiaddr, Ipv4Addr::UNSPECIFIED);
+        assert_eq!(nak.address_option(OPT_SERVER_ID), Some(SERVER));
+        assert_eq!(
+            nak.option(OPT_MESSAGE),
+            Some(&b"10.0.0.100 is not available"[..])
+        );
+
+        // Out of the Subnet, e.g. moved from another network.
+        let (nak, _) = handle(
+            &mut handler,
+            &request(
+                MessageType::Request,
+                2,
+                vec![(
+                    OPT_REQUESTED_ADDRESS,
+                    address(Ipv4Addr::new(192, 168, 0, 10)),
+                )],
+            ),
+        );
+        assert_eq!(nak.unwrap().message_type(), Some(MessageType::Nak));
+
+        // The client took the offer of another server.
+        let (none, _) = handle(
+            &mut handler,
+            &request(
+                MessageType::Request,
+                2,
+                vec![
+                    (OPT_REQUESTED_ADDRESS, address(Ipv4Addr::new(10, 0, 0, 101))),
+                    (OPT_SERVER_ID, address(Ipv4Addr::new(10, 0, 0, 3))),
+                ],
+            ),
+        );
+        assert!(none.is_none());
+    }
+}
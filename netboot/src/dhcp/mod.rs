@@ -0,0 +1,229 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+
+use yangtze_apis::{
+    v1::{now, NamespaceName, YangtzeError},
+    v1alpha1::{
+        dhcp_lease::{self, DhcpLease},
+        network_interface::{self, NetworkInterface},
+        node::{self, Node},
+        subnet::{self, Subnet},
+    },
+};
+use yangtze_client::YangtzeClient;
+
+mod handler;
+mod packet;
+
+use handler::Handler;
+use packet::{MessageType, Packet};
+
+const SERVER_PORT: u16 = 67;
+const CLIENT_PORT: u16 = 68;
+
+/// Serves DHCPv4 from the Subnets of a namespace, and persists the leases
+/// as DhcpLeases.
+pub struct Server {
+    pub client: YangtzeClient,
+    pub namespace: String,
+    pub listen: SocketAddr,
+    /// The interface to serve on, all of them by default.
+    pub interface: Option<String>,
+    /// The address of this server on the served network.
+    pub address: Ipv4Addr,
+    pub sync_interval: u64,
+}
+
+impl Server {
+    pub async fn run(self) -> Result<(), YangtzeError> {
+        let socket = self.bind()?;
+        tracing::info!("DHCP server is listening on <{}>.", self.listen);
+
+        let handler = Arc::new(Mutex::new(Handler::new(&self.namespace, self.address)));
+
+        // The leases are persisted one by one off the receive loop, so that a
+        // slow apiserver does not stall the replies to the clients.
+        let (leases, mut pending) = mpsc::unbounded_channel::<DhcpLease>();
+        {
+            let client = self
+                .client
+                .clone()
+                .version(dhcp_lease::VERSION_KIND.version)
+                .kind(dhcp_lease::VERSION_KIND.kind);
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                while let Some(lease) = pending.recv().await {
+                    // The lease may be built before its last version was persisted.
+                    let lease = handler.lock().unwrap().latest(lease);
+                    match persist(&client, lease).await {
+                        Ok(lease) => handler.lock().unwrap().persisted(lease),
+                        Err(e) => tracing::error!("Failed to persist the lease: {}", e),
+                    }
+                }
+            });
+        }
+
+        {
+            let (client, namespace, handler) =
+                (self.client.clone(), self.namespace.clone(), handler.clone());
+            let interval = self.sync_interval;
+            tokio::spawn(async move {
+                loop {
+                    if let Err(e) = sync(&client, &namespace, &handler).await {
+                        tracing::error!("Failed to sync the Subnets: {}", e);
+                    }
+                    tokio::time::sleep(Duration::from_secs(interval)).await;
+                }
+            });
+        }
+
+        let mut buf = [0; 1500];
+        loop {
+            let (n, from) = socket
+                .recv_from(&mut buf)
+                .await
+                .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+
+            let req = match Packet::decode(&buf[..n]) {
+                Ok(req) => req,
+                Err(e) => {
+                    tracing::debug!("Drop the message from <{}>: {}", from, e);
+                    continue;
+                }
+            };
+
+            let Some(reply) = handler.lock().unwrap().handle(&req, now()) else {
+                continue;
+            };
+
+            if let Some(packet) = reply.packet {
+                let to = destination(&req, &packet);
+                tracing::debug!(
+                    "Reply {:?} of {} to <{}>.",
+                    packet.message_type(),
+                    packet.yiaddr,
+                    to
+                );
+                if let Err(e) = socket.send_to(&packet.encode(), to).await {
+                    tracing::error!("Failed to reply to <{}>: {}", to, e);
+                }
+            }
+
+            if let Some(lease) = reply.lease {
+                tracing::info!(
+                    "Lease {} to <{}> in Subnet <{}> is {}.",
+                    lease.spec.address,
+                    lease.spec.mac,
+                    lease.spec.subnet,
+                    lease
+                        .status
+                        .as_ref()
+                        .map(|s| s.state.to_string())
+                        .unwrap_or_default()
+                );
+                if leases.send(lease).is_err() {
+                    tracing::error!("Failed to persist the lease: the persister is gone");
+                }
+            }
+        }
+    }
+
+    fn bind(&self) -> Result<UdpSocket, YangtzeError> {
+        let err = |e: std::io::Error| YangtzeError::GeneralError(e.to_string());
+
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)).map_err(err)?;
+        socket.set_reuse_address(true).map_err(err)?;
+        socket.set_broadcast(true).map_err(err)?;
+        if let Some(interface) = &self.interface {
+            socket
+                .bind_device(Some(interface.as_bytes()))
+                .map_err(err)?;
+        }
+        socket.set_nonblocking(true).map_err(err)?;
+        socket.bind(&self.listen.into()).map_err(err)?;
+
+        UdpSocket::from_std(socket.into()).map_err(err)
+    }
+}
+
+/// Where to send a reply, RFC 2131 4.1: to the relay agent, to the address
+/// of a configured client, or broadcast.
+fn destination(req: &Packet, reply: &Packet) -> SocketAddr {
+    let addr = if !req.giaddr.is_unspecified() {
+        return SocketAddr::V4(SocketAddrV4::new(req.giaddr, SERVER_PORT));
+    } else if reply.message_type() != Some(MessageType::Nak) && !req.ciaddr.is_unspecified() {
+        req.ciaddr
+    } else {
+        Ipv4Addr::BROADCAST
+    };
+
+    SocketAddr::V4(SocketAddrV4::new(addr, CLIENT_PORT))
+}
+
+async fn sync(
+    client: &YangtzeClient,
+    namespace: &str,
+    handler: &Mutex<Handler>,
+) -> Result<(), YangtzeError> {
+    let nn = NamespaceName {
+        namespace: Some(namespace.to_string()),
+        name: None,
+    };
+
+    let subnets: Vec<Subnet> = client
+        .clone()
+        .version(subnet::VERSION_KIND.version)
+        .kind(subnet::VERSION_KIND.kind)
+        .list(nn.clone())
+        .await?;
+    let nodes: Vec<Node> = client
+        .clone()
+        .version(node::VERSION_KIND.version)
+        .kind(node::VERSION_KIND.kind)
+        .list(nn.clone())
+        .await?;
+    let interfaces: Vec<NetworkInterface> = client
+        .clone()
+        .version(network_interface::VERSION_KIND.version)
+        .kind(network_interface::VERSION_KIND.kind)
+        .list(nn.clone())
+        .await?;
+    let leases: Vec<DhcpLease> = client
+        .clone()
+        .version(dhcp_lease::VERSION_KIND.version)
+        .kind(dhcp_lease::VERSION_KIND.kind)
+        .list(nn)
+        .await?;
+
+    handler
+        .lock()
+        .unwrap()
+        .sync(&subnets, &nodes, &interfaces, leases);
+
+    Ok(())
+}
+
+async fn persist(client: &YangtzeClient, lease: DhcpLease) -> Result<DhcpLease, YangtzeError> {
+    match lease.meta_data.uuid {
+        None => client.create(lease).await,
+        Some(_) => client.update(lease).await,
+    }
+}
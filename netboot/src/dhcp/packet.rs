@@ -0,0 +1,248 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::Ipv4Addr;
+
+use yangtze_apis::v1::YangtzeError;
+
+pub const BOOTREQUEST: u8 = 1;
+pub const BOOTREPLY: u8 = 2;
+
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+/// The fixed part of a message: op to file, and the magic cookie.
+const HEADER_LEN: usize = 240;
+
+pub const OPT_PAD: u8 = 0;
+pub const OPT_SUBNET_MASK: u8 = 1;
+pub const OPT_ROUTER: u8 = 3;
+pub const OPT_DNS: u8 = 6;
+pub const OPT_HOSTNAME: u8 = 12;
+pub const OPT_REQUESTED_ADDRESS: u8 = 50;
+pub const OPT_LEASE_TIME: u8 = 51;
+pub const OPT_MESSAGE_TYPE: u8 = 53;
+pub const OPT_SERVER_ID: u8 = 54;
+pub const OPT_MESSAGE: u8 = 56;
+pub const OPT_RENEWAL_TIME: u8 = 58;
+pub const OPT_REBINDING_TIME: u8 = 59;
+pub const OPT_VENDOR_CLASS: u8 = 60;
+pub const OPT_TFTP_SERVER: u8 = 66;
+pub const OPT_BOOTFILE: u8 = 67;
+pub const OPT_USER_CLASS: u8 = 77;
+pub const OPT_RELAY_AGENT: u8 = 82;
+pub const OPT_CLIENT_ARCH: u8 = 93;
+pub const OPT_END: u8 = 255;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageType {
+    Discover = 1,
+    Offer = 2,
+    Request = 3,
+    Decline = 4,
+    Ack = 5,
+    Nak = 6,
+    Release = 7,
+    Inform = 8,
+}
+
+impl TryFrom<u8> for MessageType {
+    type Error = YangtzeError;
+
+    fn try_from(t: u8) -> Result<Self, Self::Error> {
+        match t {
+            1 => Ok(MessageType::Discover),
+            2 => Ok(MessageType::Offer),
+            3 => Ok(MessageType::Request),
+            4 => Ok(MessageType::Decline),
+            5 => Ok(MessageType::Ack),
+            6 => Ok(MessageType::Nak),
+            7 => Ok(MessageType::Release),
+            8 => Ok(MessageType::Inform),
+            _ => Err(YangtzeError::InvalidConfig(format!(
+                "unknown DHCP message type {}",
+                t
+            ))),
+        }
+    }
+}
+
+/// A DHCPv4 message, RFC 2131.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Packet {
+    pub op: u8,
+    pub htype: u8,
+    pub hlen: u8,
+    pub hops: u8,
+    pub xid: u32,
+    pub secs: u16,
+    pub flags: u16,
+    pub ciaddr: Ipv4Addr,
+    pub yiaddr: Ipv4Addr,
+    pub siaddr: Ipv4Addr,
+    pub giaddr: Ipv4Addr,
+    pub chaddr: [u8; 16],
+    pub sname: String,
+    pub file: String,
+    /// The options in their order, without pad and end.
+    pub options: Vec<(u8, Vec<u8>)>,
+}
+
+impl Packet {
+    pub fn decode(buf: &[u8]) -> Result<Packet, YangtzeError> {
+        if buf.len() < HEADER_LEN || buf[236..240] != MAGIC_COOKIE {
+            return Err(YangtzeError::InvalidConfig(
+                "not a DHCP message".to_string(),
+            ));
+        }
+
+        let addr = |i: usize| Ipv4Addr::new(buf[i], buf[i + 1], buf[i + 2], buf[i + 3]);
+
+        let mut chaddr = [0; 16];
+        chaddr.copy_from_slice(&buf[28..44]);
+
+        let mut options = vec![];
+        let mut i = HEADER_LEN;
+        while i < buf.len() {
+            match buf[i] {
+                OPT_PAD => i += 1,
+                OPT_END => break,
+                code => {
+                    let len = *buf.get(i + 1).ok_or(truncated())? as usize;
+                    let value = buf.get(i + 2..i + 2 + len).ok_or(truncated())?;
+                    options.push((code, value.to_vec()));
+                    i += 2 + len;
+                }
+            }
+        }
+
+        Ok(Packet {
+            op: buf[0],
+            htype: buf[1],
+            hlen: buf[2],
+            hops: buf[3],
+            xid: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+            secs: u16::from_be_bytes([buf[8], buf[9]]),
+            flags: u16::from_be_bytes([buf[10], buf[11]]),
+            ciaddr: addr(12),
+            yiaddr: addr(16),
+            siaddr: addr(20),
+            giaddr: addr(24),
+            chaddr,
+            sname: cstr(&buf[44..108]),
+            file: cstr(&buf[108..236]),
+            options,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(HEADER_LEN + 64);
+        buf.extend([self.op, self.htype, self.hlen, self.hops]);
+        buf.extend(self.xid.to_be_bytes());
+        buf.extend(self.secs.to_be_bytes());
+        buf.extend(self.flags.to_be_bytes());
+        for addr in [self.ciaddr, self.yiaddr, self.siaddr, self.giaddr] {
+            buf.extend(addr.octets());
+        }
+        buf.extend(self.chaddr);
+        buf.extend(fixed(&self.sname, 64));
+        buf.extend(fixed(&self.file, 128));
+        buf.extend(MAGIC_COOKIE);
+
+        for (code, value) in &self.options {
+            // Longer values are split into several options, RFC 3396.
+            for chunk in value.chunks(255) {
+                buf.push(*code);
+                buf.push(chunk.len() as u8);
+                buf.extend(chunk);
+            }
+        }
+        buf.push(OPT_END);
+
+        // Some clients drop messages shorter than a BOOTP message.
+        if buf.len() < 300 {
+            buf.resize(300, OPT_PAD);
+        }
+
+        buf
+    }
+
+    pub fn option(&self, code: u8) -> Option<&[u8]> {
+        self.options
+            .iter()
+            .find(|(c, _)| *c == code)
+            .map(|(_, v)| v.as_slice())
+    }
+
+    pub fn set_option(&mut self, code: u8, value: Vec<u8>) {
+        self.options.retain(|(c, _)| *c != code);
+        self.options.push((code, value));
+    }
+
+    pub fn message_type(&self) -> Option<MessageType> {
+        let t = self.option(OPT_MESSAGE_TYPE)?.first()?;
+        MessageType::try_from(*t).ok()
+    }
+
+    pub fn address_option(&self, code: u8) -> Option<Ipv4Addr> {
+        let v = self.option(code)?;
+        let octets: [u8; 4] = v.try_into().ok()?;
+        Some(Ipv4Addr::from(octets))
+    }
+
+    /// The hardware address of the client, e.g. `52:54:00:12:34:56`.
+    pub fn mac(&self) -> String {
+        let len = (self.hlen as usize).min(16);
+        self.chaddr[..len]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    /// A reply to this request, with the fields the server has to copy.
+    pub fn reply(&self, t: MessageType) -> Packet {
+        Packet {
+            op: BOOTREPLY,
+            htype: self.htype,
+            hlen: self.hlen,
+            hops: 0,
+            xid: self.xid,
+            secs: 0,
+            flags: self.flags,
+            ciaddr: Ipv4Addr::UNSPECIFIED,
+            yiaddr: Ipv4Addr::UNSPECIFIED,
+            siaddr: Ipv4Addr::UNSPECIFIED,
+            giaddr: self.giaddr,
+            chaddr: self.chaddr,
+            sname: String::new(),
+            file: String::new(),
+            options: vec![(OPT_MESSAGE_TYPE, vec![t as u8])],
+        }
+    }
+}
+
+fn truncated() -> YangtzeError {
+    YangtzeError::InvalidConfig("truncated DHCP option".to_string())
+}
+
+fn cstr(buf: &[u8]) -> String {
+    let end = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..end]).to_string()
+}
+
+fn fixed(s: &str, len: usize) -> Vec<u8> {
+    let mut buf = s.as_bytes().to_vec();
+    buf.resize(len, 0);
+    // Keep the terminating NUL.
+    buf[len - 1] = 0;
+    buf
+}
@@ -0,0 +1,167 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use actix_web::{get, post, web, App, HttpRequest, HttpResponse, HttpServer, Responder};
+
+use yangtze_apis::v1::YangtzeError;
+
+use crate::boot::{self, Recorder};
+
+/// Serves the iPXE scripts of the Nodes and the boot files over HTTP:
+///
+/// * `GET /ipxe`: chainloads the script of the MAC iPXE booted from
+/// * `GET /ipxe/{mac}`: the script of the BootProfile of the Node of the MAC
+/// * `GET /files/{path}`: a file under the boot root
+/// * `POST /report/{mac}/{stage}`: records a boot stage, e.g. by the installer
+pub struct Server {
+    pub listen: SocketAddr,
+    pub root: PathBuf,
+    /// The URL the clients reach this server at.
+    pub base_url: String,
+    pub recorder: Recorder,
+}
+
+struct State {
+    root: PathBuf,
+    base_url: String,
+    recorder: Recorder,
+}
+
+impl Server {
+    pub async fn run(self) -> Result<(), YangtzeError> {
+        tracing::info!("HTTP boot server is listening on <{}>.", self.listen);
+
+        let state = web::Data::new(State {
+            root: self.root,
+            base_url: self.base_url,
+            recorder: self.recorder,
+        });
+
+        HttpServer::new(move || {
+            App::new()
+                .app_data(state.clone())
+                .service(chain)
+                .service(script)
+                .service(file)
+                .service(report)
+        })
+        .bind(self.listen)
+        .map_err(|e| YangtzeError::GeneralError(e.to_string()))?
+        .run()
+        .await
+        .map_err(|e| YangtzeError::GeneralError(e.to_string()))
+    }
+}
+
+#[get("/ipxe")]
+async fn chain(state: web::Data<State>) -> impl Responder {
+    ipxe(format!(
+        "#!ipxe\nchain {}/ipxe/${{net0/mac}}\n",
+        state.base_url
+    ))
+}
+
+#[get("/ipxe/{mac}")]
+async fn script(
+    mac: web::Path<String>,
+    req: HttpRequest,
+    state: web::Data<State>,
+) -> actix_web::Result<impl Responder> {
+    let recorder = &state.recorder;
+    let Some(node) = recorder.node_by_mac(&mac).await? else {
+        tracing::warn!("No Node of <{}> to boot.", mac);
+        return Ok(ipxe(format!(
+            "#!ipxe\necho No Node of {}, boot from the local disk.\nexit\n",
+            mac
+        )));
+    };
+
+    let profile = match &node.spec.boot_profile {
+        Some(name) => recorder.profile(name).await?,
+        None => None,
+    };
+    let script = boot::script(&node, &mac, profile.as_ref(), &state.base_url);
+
+    let detail = match &profile {
+        Some(p) => p.meta_data.name.clone(),
+        None => "local disk".to_string(),
+    };
+    let address = peer(&req);
+    if let Err(e) = recorder.record(node, "ipxe", &detail, &address).await {
+        tracing::error!("Failed to record the boot of <{}>: {}", address, e);
+    }
+
+    Ok(ipxe(script))
+}
+
+#[get("/files/{path:.*}")]
+async fn file(
+    path: web::Path<String>,
+    req: HttpRequest,
+    state: web::Data<State>,
+) -> actix_web::Result<impl Responder> {
+    let data = match boot::resolve(&state.root, &path) {
+        Some(p) => tokio::fs::read(p).await.ok(),
+        None => None,
+    };
+    let Some(data) = data else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let address = peer(&req);
+    match state.recorder.node_by_address(&address).await {
+        Ok(Some(node)) => {
+            if let Err(e) = state.recorder.record(node, "http", &path, &address).await {
+                tracing::error!("Failed to record the boot of <{}>: {}", address, e);
+            }
+        }
+        Ok(None) => tracing::debug!("No Node of <{}> fetching <{}>.", address, path),
+        Err(e) => tracing::error!("Failed to look up the Node of <{}>: {}", address, e),
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .body(data))
+}
+
+#[post("/report/{mac}/{stage}")]
+async fn report(
+    path: web::Path<(String, String)>,
+    req: HttpRequest,
+    state: web::Data<State>,
+) -> actix_web::Result<impl Responder> {
+    let (mac, stage) = path.into_inner();
+    let Some(node) = state.recorder.node_by_mac(&mac).await? else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let address = peer(&req);
+    if let Err(e) = state.recorder.record(node, &stage, "", &address).await {
+        tracing::error!("Failed to record the boot of <{}>: {}", address, e);
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+fn ipxe(body: String) -> HttpResponse {
+    HttpResponse::Ok().content_type("text/plain").body(body)
+}
+
+fn peer(req: &HttpRequest) -> String {
+    req.peer_addr()
+        .map(|a| a.ip().to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,120 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use yangtze_apis::v1::YangtzeError;
+use yangtze_client::{YangtzeClient, YangtzeConfig};
+
+mod boot;
+mod dhcp;
+mod http;
+mod tftp;
+
+#[derive(Parser)]
+#[command(name = "yangtze-netboot")]
+#[command(version = "0.1.0")]
+#[command(about = "Yangtze DHCP, TFTP and HTTP boot servers", long_about = None)]
+struct Cli {
+    #[arg(
+        long,
+        env = "YANGTZE_APISERVER",
+        default_value = "http://127.0.0.1:8080"
+    )]
+    apiserver: String,
+
+    #[arg(long, default_value = "default")]
+    namespace: String,
+
+    /// The address of this server on the provisioning network
+    #[arg(long)]
+    server_address: Ipv4Addr,
+
+    #[arg(long, default_value = "0.0.0.0:67")]
+    dhcp_listen: SocketAddr,
+
+    /// The interface to serve DHCP on, all of them by default
+    #[arg(long)]
+    dhcp_interface: Option<String>,
+
+    /// Seconds between two syncs of the Subnets, Nodes and leases
+    #[arg(long, default_value_t = 10)]
+    sync_interval: u64,
+
+    #[arg(long, default_value = "0.0.0.0:69")]
+    tftp_listen: SocketAddr,
+
+    #[arg(long, default_value = "0.0.0.0:8081")]
+    http_listen: SocketAddr,
+
+    /// The URL the clients reach the HTTP boot server at,
+    /// http://<server-address>:<http port> by default
+    #[arg(long)]
+    http_url: Option<String>,
+
+    /// The directory of the bootloaders and boot files
+    #[arg(long, default_value = "/var/lib/yangtze/boot")]
+    boot_root: PathBuf,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), YangtzeError> {
+    let subscriber = tracing_subscriber::FmtSubscriber::new();
+    // use that subscriber to process traces emitted after this point
+    tracing::subscriber::set_global_default(subscriber)
+        .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+
+    let cli = Cli::parse();
+
+    let client = YangtzeClient::new(&YangtzeConfig {
+        address: cli.apiserver.clone(),
+    })?;
+
+    let recorder = boot::Recorder {
+        client: client.clone(),
+        namespace: cli.namespace.clone(),
+    };
+
+    let dhcp = dhcp::Server {
+        client,
+        namespace: cli.namespace,
+        listen: cli.dhcp_listen,
+        interface: cli.dhcp_interface,
+        address: cli.server_address,
+        sync_interval: cli.sync_interval,
+    };
+
+    let tftp = tftp::Server {
+        listen: cli.tftp_listen,
+        root: cli.boot_root.clone(),
+        recorder: recorder.clone(),
+    };
+
+    let http = http::Server {
+        listen: cli.http_listen,
+        root: cli.boot_root,
+        base_url: cli.http_url.unwrap_or(format!(
+            "http://{}:{}",
+            cli.server_address,
+            cli.http_listen.port()
+        )),
+        recorder,
+    };
+
+    tokio::try_join!(dhcp.run(), tftp.run(), http.run())?;
+
+    Ok(())
+}
@@ -0,0 +1,239 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use tokio::net::UdpSocket;
+
+use yangtze_apis::v1::YangtzeError;
+
+use crate::boot::{self, Recorder};
+
+const OP_RRQ: u16 = 1;
+const OP_WRQ: u16 = 2;
+const OP_DATA: u16 = 3;
+const OP_ACK: u16 = 4;
+const OP_ERROR: u16 = 5;
+const OP_OACK: u16 = 6;
+
+const ERR_NOT_FOUND: u16 = 1;
+const ERR_ACCESS: u16 = 2;
+const ERR_ILLEGAL: u16 = 4;
+
+const DEFAULT_BLKSIZE: usize = 512;
+/// The largest block of RFC 2348.
+const MAX_BLKSIZE: usize = 65464;
+
+const TIMEOUT: Duration = Duration::from_secs(1);
+const RETRIES: usize = 5;
+
+/// A read-only TFTP server, RFC 1350, with the blksize and tsize options
+/// (RFC 2348, 2349) used by PXE ROMs.
+pub struct Server {
+    pub listen: SocketAddr,
+    pub root: PathBuf,
+    pub recorder: Recorder,
+}
+
+/// A read request.
+struct Request {
+    file: String,
+    blksize: Option<usize>,
+    tsize: bool,
+}
+
+impl Server {
+    pub async fn run(self) -> Result<(), YangtzeError> {
+        let socket = UdpSocket::bind(self.listen)
+            .await
+            .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+        tracing::info!("TFTP server is listening on <{}>.", self.listen);
+
+        let mut buf = [0; 1500];
+        loop {
+            let (n, from) = socket
+                .recv_from(&mut buf)
+                .await
+                .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+
+            let (root, recorder, listen) = (self.root.clone(), self.recorder.clone(), self.listen);
+            let packet = buf[..n].to_vec();
+            tokio::spawn(async move {
+                if let Err(e) = serve(listen, from, &packet, root, recorder).await {
+                    tracing::error!("Failed to serve <{}> over TFTP: {}", from, e);
+                }
+            });
+        }
+    }
+}
+
+/// Serves a request from a new socket, i.e. a new transfer id.
+async fn serve(
+    listen: SocketAddr,
+    client: SocketAddr,
+    packet: &[u8],
+    root: PathBuf,
+    recorder: Recorder,
+) -> Result<(), YangtzeError> {
+    let err = |e: std::io::Error| YangtzeError::GeneralError(e.to_string());
+
+    let socket = UdpSocket::bind(SocketAddr::new(listen.ip(), 0))
+        .await
+        .map_err(err)?;
+    socket.connect(client).await.map_err(err)?;
+
+    let req = match parse(packet) {
+        Ok(req) => req,
+        Err((code, msg)) => return send_error(&socket, code, &msg).await,
+    };
+
+    let data = match boot::resolve(&root, &req.file) {
+        Some(path) => tokio::fs::read(&path).await.ok(),
+        None => None,
+    };
+    let Some(data) = data else {
+        return send_error(&socket, ERR_NOT_FOUND, "file not found").await;
+    };
+
+    let address = client.ip().to_string();
+    match recorder.node_by_address(&address).await {
+        Ok(Some(node)) => {
+            if let Err(e) = recorder.record(node, "tftp", &req.file, &address).await {
+                tracing::error!("Failed to record the boot of <{}>: {}", address, e);
+            }
+        }
+        Ok(None) => tracing::debug!("No Node of <{}> fetching <{}>.", address, req.file),
+        Err(e) => tracing::error!("Failed to look up the Node of <{}>: {}", address, e),
+    }
+
+    let mut blksize = DEFAULT_BLKSIZE;
+    let mut options = vec![];
+    if let Some(size) = req.blksize {
+        blksize = size.clamp(8, MAX_BLKSIZE);
+        options.push(("blksize", blksize.to_string()));
+    }
+    if req.tsize {
+        options.push(("tsize", data.len().to_string()));
+    }
+    if !options.is_empty() {
+        let mut oack = OP_OACK.to_be_bytes().to_vec();
+        for (k, v) in options {
+            oack.extend(k.as_bytes());
+            oack.push(0);
+            oack.extend(v.as_bytes());
+            oack.push(0);
+        }
+        if !exchange(&socket, &oack, 0).await? {
+            return Ok(());
+        }
+    }
+
+    // The last block is shorter than blksize, empty if need be.
+    let blocks = data.len() / blksize + 1;
+    for (i, chunk) in
+        (0..blocks).map(|i| (i, &data[i * blksize..((i + 1) * blksize).min(data.len())]))
+    {
+        let block = (i + 1) as u16;
+        let mut packet = OP_DATA.to_be_bytes().to_vec();
+        packet.extend(block.to_be_bytes());
+        packet.extend(chunk);
+        if !exchange(&socket, &packet, block).await? {
+            return Ok(());
+        }
+    }
+
+    tracing::debug!("Sent <{}> to <{}>.", req.file, client);
+
+    Ok(())
+}
+
+/// Sends a packet until the client acknowledges `block`; false if the
+/// client gave up or never answered.
+async fn exchange(socket: &UdpSocket, packet: &[u8], block: u16) -> Result<bool, YangtzeError> {
+    let mut buf = [0; 1500];
+    for _ in 0..RETRIES {
+        socket
+            .send(packet)
+            .await
+            .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+
+        let deadline = tokio::time::Instant::now() + TIMEOUT;
+        while let Ok(n) = tokio::time::timeout_at(deadline, socket.recv(&mut buf)).await {
+            let n = n.map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+            if n < 4 {
+                continue;
+            }
+            match u16::from_be_bytes([buf[0], buf[1]]) {
+                OP_ACK if u16::from_be_bytes([buf[2], buf[3]]) == block => return Ok(true),
+                OP_ERROR => return Ok(false),
+                // A duplicated ACK of the previous block.
+                _ => {}
+            }
+        }
+    }
+
+    Ok(false)
+}
+
+async fn send_error(socket: &UdpSocket, code: u16, msg: &str) -> Result<(), YangtzeError> {
+    let mut packet = OP_ERROR.to_be_bytes().to_vec();
+    packet.extend(code.to_be_bytes());
+    packet.extend(msg.as_bytes());
+    packet.push(0);
+
+    socket
+        .send(&packet)
+        .await
+        .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+
+    Ok(())
+}
+
+fn parse(packet: &[u8]) -> Result<Request, (u16, String)> {
+    if packet.len() < 4 {
+        return Err((ERR_ILLEGAL, "short packet".to_string()));
+    }
+
+    match u16::from_be_bytes([packet[0], packet[1]]) {
+        OP_RRQ => {}
+        OP_WRQ => return Err((ERR_ACCESS, "read only".to_string())),
+        op => return Err((ERR_ILLEGAL, format!("unexpected opcode {}", op))),
+    }
+
+    let fields: Vec<String> = packet[2..]
+        .split(|b| *b == 0)
+        .map(|f| String::from_utf8_lossy(f).to_string())
+        .collect();
+    let file = fields.first().cloned().unwrap_or_default();
+    if file.is_empty() {
+        return Err((ERR_ILLEGAL, "no file name".to_string()));
+    }
+
+    let mut req = Request {
+        file,
+        blksize: None,
+        tsize: false,
+    };
+    // The file name and the mode, then option/value pairs.
+    for kv in fields.get(2..).unwrap_or_default().chunks(2) {
+        match (kv[0].to_lowercase().as_str(), kv.get(1)) {
+            ("blksize", Some(v)) => req.blksize = v.parse().ok(),
+            ("tsize", Some(_)) => req.tsize = true,
+            _ => {}
+        }
+    }
+
+    Ok(req)
+}
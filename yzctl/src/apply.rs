@@ -0,0 +1,201 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use yangtze_apis::v1::{Metadata, YangtzeError};
+use yangtze_client::YangtzeClient;
+
+use crate::helper;
+
+pub async fn run(client: YangtzeClient, filename: &str) -> Result<(), YangtzeError> {
+    let mut objs = vec![];
+    for (path, content) in read_manifests(filename)? {
+        objs.extend(parse_manifest(&path, &content)?);
+    }
+
+    for obj in objs {
+        apply(client.clone(), obj).await?;
+    }
+
+    Ok(())
+}
+
+async fn apply(client: YangtzeClient, mut obj: Value) -> Result<(), YangtzeError> {
+    let mut meta = Metadata::deserialize(&obj["meta_data"])?;
+    let vk = yangtze_apis::get_version_kind(&meta.kind.to_lowercase()).ok_or(
+        YangtzeError::InvalidConfig(format!("unknown kind <{}>", meta.kind)),
+    )?;
+    // The apiserver and the controllers look the objects up by the lowercase kind.
+    meta.kind = vk.kind.to_string();
+    obj["meta_data"]["kind"] = Value::from(vk.kind);
+    let client = client.version(vk.version).kind(vk.kind);
+
+    match helper::find(&client, &meta.namespace, &meta.name).await? {
+        None => {
+            client.create(obj).await?;
+            println!("{} created", meta);
+        }
+        Some(current) if contains(&current["spec"], &obj["spec"]) => {
+            println!("{} unchanged", meta);
+        }
+        Some(mut current) => {
+            current["spec"] = obj["spec"].clone();
+            client.update(current).await?;
+            println!("{} configured", meta);
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `current` holds all the fields set in `manifest`; the fields the
+/// manifest omits are filled with defaults by the apiserver.
+fn contains(current: &Value, manifest: &Value) -> bool {
+    match (current, manifest) {
+        (Value::Object(current), Value::Object(manifest)) => manifest
+            .iter()
+            .all(|(k, v)| contains(current.get(k).unwrap_or(&Value::Null), v)),
+        _ => current == manifest,
+    }
+}
+
+/// Reads the manifests from a file, a directory of files or stdin ("-").
+fn read_manifests(filename: &str) -> Result<Vec<(PathBuf, String)>, YangtzeError> {
+    if filename == "-" {
+        let mut content = String::new();
+        io::stdin()
+            .read_to_string(&mut content)
+            .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+        return Ok(vec![(PathBuf::from("-"), content)]);
+    }
+
+    let path = Path::new(filename);
+    let mut paths = vec![];
+    if path.is_dir() {
+        for entry in fs::read_dir(path).map_err(|e| YangtzeError::GeneralError(e.to_string()))? {
+            let p = entry
+                .map_err(|e| YangtzeError::GeneralError(e.to_string()))?
+                .path();
+            let ext = p.extension().and_then(|e| e.to_str());
+            if matches!(ext, Some("yaml") | Some("yml") | Some("json")) {
+                paths.push(p);
+            }
+        }
+        paths.sort();
+    } else {
+        paths.push(path.to_path_buf());
+    }
+
+    paths
+        .into_iter()
+        .map(|p| {
+            let content = fs::read_to_string(&p)
+                .map_err(|e| YangtzeError::GeneralError(format!("{}: {}", p.display(), e)))?;
+            Ok((p, content))
+        })
+        .collect()
+}
+
+/// Parses all documents of a manifest; JSON files may hold a stream of
+/// objects, YAML files may hold several documents separated by `---`.
+fn parse_manifest(path: &Path, content: &str) -> Result<Vec<Value>, YangtzeError> {
+    let mut docs = vec![];
+
+    if path.extension().and_then(|e| e.to_str()) == Some("json") {
+        for doc in serde_json::Deserializer::from_str(content).into_iter::<Value>() {
+            docs.push(doc?);
+        }
+    } else {
+        for doc in serde_yaml::Deserializer::from_str(content) {
+            let doc = Value::deserialize(doc)
+                .map_err(|e| YangtzeError::InvalidConfig(format!("{}: {}", path.display(), e)))?;
+            docs.push(doc);
+        }
+    }
+
+    let mut objs = vec![];
+    for doc in docs {
+        match doc {
+            Value::Null => {}
+            Value::Array(items) => objs.extend(items),
+            _ => objs.push(doc),
+        }
+    }
+
+    Ok(objs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_yaml_documents() {
+        let content = "
+meta_data: {kind: Fabric, name: f1}
+---
+---
+- meta_data: {kind: Node, name: n1}
+- meta_data: {kind: Node, name: n2}
+";
+        let objs = parse_manifest(Path::new("f.yaml"), content).unwrap();
+        let names: Vec<_> = objs.iter().map(|o| &o["meta_data"]["name"]).collect();
+        assert_eq!(names, vec!["f1", "n1", "n2"]);
+    }
+
+    #[test]
+    fn parse_json_stream() {
+        let content = r#"{"meta_data": {"name": "f1"}} [{"meta_data": {"name": "n1"}}]"#;
+        let objs = parse_manifest(Path::new("f.json"), content).unwrap();
+        let names: Vec<_> = objs.iter().map(|o| &o["meta_data"]["name"]).collect();
+        assert_eq!(names, vec!["f1", "n1"]);
+
+        assert!(parse_manifest(Path::new("f.json"), "{").is_err());
+        assert!(parse_manifest(Path::new("f.yaml"), "a: [").is_err());
+    }
+
+    #[test]
+    fn read_manifests_of_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        for f in ["b.yaml", "a.json", "c.yml", "README.md"] {
+            fs::write(dir.path().join(f), f).unwrap();
+        }
+
+        let files = read_manifests(dir.path().to_str().unwrap()).unwrap();
+        let names: Vec<_> = files.iter().map(|(_, content)| content.as_str()).collect();
+        assert_eq!(names, vec!["a.json", "b.yaml", "c.yml"]);
+
+        let file = dir.path().join("README.md");
+        let files = read_manifests(file.to_str().unwrap()).unwrap();
+        assert_eq!(files, vec![(file, "README.md".to_string())]);
+
+        assert!(read_manifests(dir.path().join("none.yaml").to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn compare_set_fields() {
+        let current = json!({"design": {"spines": 2}, "pools": {"asn": "65000-65100"}, "vni": 0});
+        assert!(contains(&current, &json!({"design": {"spines": 2}})));
+        assert!(contains(&current, &json!({})));
+        assert!(!contains(&current, &json!({"design": {"spines": 4}})));
+        assert!(!contains(&current, &json!({"mtu": 9000})));
+        assert!(!contains(&current, &json!({"vni": null, "x": 1})));
+    }
+}
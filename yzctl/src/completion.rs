@@ -0,0 +1,218 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeSet;
+use std::io;
+
+use clap::builder::PossibleValuesParser;
+use clap::CommandFactory;
+use clap_complete::Shell;
+use serde_json::Value;
+
+use yangtze_apis::v1::{NamespaceName, YangtzeError};
+use yangtze_client::YangtzeClient;
+
+use crate::helper;
+use crate::Cli;
+
+/// The options taking a value, which are skipped when looking for the
+/// positional arguments on the command line.
+const VALUE_OPTIONS: &str = "-n|--namespace|-o|--output|--timeout|--for";
+
+/// The namespace of the commands without `-n/--namespace`.
+const DEFAULT_NAMESPACE: &str = "default";
+
+/// Overrides the generated `_yzctl` to complete object names by
+/// `yzctl __complete`.
+const BASH_DYNAMIC: &str = r#"
+_yzctl_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}" args=() ns=default i
+    for (( i = 2; i < COMP_CWORD; i++ )); do
+        case "${COMP_WORDS[i]}" in
+            -n|--namespace) ns="${COMP_WORDS[i+1]}"; (( i++ )) ;;
+            VALUE_OPTIONS) (( i++ )) ;;
+            -*) ;;
+            *) args+=("${COMP_WORDS[i]}") ;;
+        esac
+    done
+    case "$prev" in
+        VALUE_OPTIONS) _yzctl "$@"; return ;;
+    esac
+    if [[ "$cur" != -* ]]; then
+        case "${COMP_WORDS[1]}" in
+            get|describe|delete|edit)
+                if [[ ${#args[@]} -eq 1 ]]; then
+                    COMPREPLY=( $(compgen -W "$(yzctl __complete names "${args[0]}" "$ns" 2>/dev/null)" -- "$cur") )
+                    return 0
+                fi
+                ;;
+            wait)
+                COMPREPLY=( $(compgen -W "$(yzctl __complete targets "$ns" 2>/dev/null)" -- "$cur") )
+                return 0
+                ;;
+        esac
+    fi
+    _yzctl "$@"
+}
+
+complete -F _yzctl_dynamic -o nosort -o bashdefault -o default yzctl
+"#;
+
+const ZSH_DYNAMIC: &str = r#"
+_yzctl_dynamic() {
+    local -a args
+    local ns=default i
+    for (( i = 3; i < CURRENT; i++ )); do
+        case "${words[i]}" in
+            -n|--namespace) ns="${words[i+1]}"; (( i++ )) ;;
+            VALUE_OPTIONS) (( i++ )) ;;
+            -*) ;;
+            *) args+=("${words[i]}") ;;
+        esac
+    done
+    case "${words[CURRENT-1]}" in
+        VALUE_OPTIONS) _yzctl "$@"; return ;;
+    esac
+    if [[ "${words[CURRENT]}" != -* ]]; then
+        case "${words[2]}" in
+            get|describe|delete|edit)
+                if (( ${#args} == 1 )); then
+                    compadd -- ${(f)"$(yzctl __complete names "${args[1]}" "$ns" 2>/dev/null)"}
+                    return
+                fi
+                ;;
+            wait)
+                compadd -- ${(f)"$(yzctl __complete targets "$ns" 2>/dev/null)"}
+                return
+                ;;
+        esac
+    fi
+    _yzctl "$@"
+}
+
+compdef _yzctl_dynamic yzctl
+"#;
+
+const FISH_DYNAMIC: &str = r#"
+function __yzctl_args
+    set -l tokens (commandline -opc)
+    set -e tokens[1..2]
+    set -l skip 0
+    for t in $tokens
+        if test $skip -eq 1
+            set skip 0
+            continue
+        end
+        switch $t
+            case VALUE_OPTIONS
+                set skip 1
+            case '-*'
+            case '*'
+                echo $t
+        end
+    end
+end
+
+function __yzctl_namespace
+    set -l tokens (commandline -opc)
+    set -l ns default
+    for i in (seq (count $tokens))
+        switch $tokens[$i]
+            case -n --namespace
+                if test $i -lt (count $tokens)
+                    set ns $tokens[(math $i + 1)]
+                end
+        end
+    end
+    echo $ns
+end
+
+complete -c yzctl -n "__fish_seen_subcommand_from get describe delete edit; and test (count (__yzctl_args)) -eq 1" -f -a "(yzctl __complete names (__yzctl_args)[1] (__yzctl_namespace) 2>/dev/null)"
+complete -c yzctl -n "__fish_seen_subcommand_from wait" -f -a "(yzctl __complete targets (__yzctl_namespace) 2>/dev/null)"
+"#;
+
+pub fn run(shell: Shell) -> Result<(), YangtzeError> {
+    clap_complete::generate(shell, &mut Cli::command(), "yzctl", &mut io::stdout());
+
+    let dynamic = match shell {
+        Shell::Bash => BASH_DYNAMIC.replace("VALUE_OPTIONS", VALUE_OPTIONS),
+        Shell::Zsh => ZSH_DYNAMIC.replace("VALUE_OPTIONS", VALUE_OPTIONS),
+        Shell::Fish => FISH_DYNAMIC.replace("VALUE_OPTIONS", &VALUE_OPTIONS.replace('|', " ")),
+        _ => String::new(),
+    };
+    print!("{}", dynamic);
+
+    Ok(())
+}
+
+/// The hidden `yzctl __complete` used by the completion scripts:
+///   * `names <kind> [namespace]`: the names of the objects of a kind;
+///   * `targets [namespace]`: all objects as `<kind>/<name>`, for `yzctl wait`.
+///
+/// The namespace is the one given by `-n/--namespace`, `default` if none.
+///
+/// Errors are swallowed, so an unreachable apiserver just yields nothing.
+pub async fn complete(client: YangtzeClient, args: &[String]) -> Result<(), YangtzeError> {
+    let (args, namespace) = match args {
+        [cmd, kind, namespace] if cmd == "names" => (&args[..2], namespace.as_str()),
+        [cmd, namespace] if cmd == "targets" => (&args[..1], namespace.as_str()),
+        _ => (args, DEFAULT_NAMESPACE),
+    };
+
+    let names = match args {
+        [cmd, kind] if cmd == "names" => names(client, kind, namespace).await.unwrap_or_default(),
+        [cmd] if cmd == "targets" => {
+            let mut targets = vec![];
+            for vk in yangtze_apis::KINDS {
+                for name in names(client.clone(), vk.kind, namespace)
+                    .await
+                    .unwrap_or_default()
+                {
+                    targets.push(format!("{}/{}", vk.kind, name));
+                }
+            }
+            targets
+        }
+        _ => vec![],
+    };
+
+    for n in names {
+        println!("{}", n);
+    }
+
+    Ok(())
+}
+
+async fn names(
+    client: YangtzeClient,
+    kind: &str,
+    namespace: &str,
+) -> Result<Vec<String>, YangtzeError> {
+    let client = helper::kind_client(client, kind)?;
+    let names: BTreeSet<_> = client
+        .list::<Value>(NamespaceName {
+            namespace: Some(namespace.to_string()),
+            name: None,
+        })
+        .await?
+        .iter()
+        .filter_map(|o| o["meta_data"]["name"].as_str().map(str::to_string))
+        .collect();
+
+    Ok(names.into_iter().collect())
+}
+
+/// Completes and validates the kind arguments.
+pub fn kinds() -> PossibleValuesParser {
+    PossibleValuesParser::new(yangtze_apis::KINDS.iter().map(|vk| vk.kind))
+}
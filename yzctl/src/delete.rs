@@ -0,0 +1,78 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use yangtze_apis::v1::{Metadata, YangtzeError};
+use yangtze_client::YangtzeClient;
+
+use crate::helper;
+
+pub async fn run(
+    client: YangtzeClient,
+    kind: &str,
+    namespace: &str,
+    name: &str,
+    yes: bool,
+    wait: Option<u64>,
+) -> Result<(), YangtzeError> {
+    let client = helper::kind_client(client, kind)?;
+    let obj = helper::get(&client, kind, namespace, name).await?;
+    let meta = Metadata::deserialize(&obj["meta_data"])?;
+
+    if !yes && !confirm(&format!("Delete {}?", meta))? {
+        println!("{} not deleted", meta);
+        return Ok(());
+    }
+
+    let id = meta.uuid.ok_or(YangtzeError::GeneralError(format!(
+        "the id of <{}> is none",
+        meta
+    )))?;
+    client.delete::<serde_json::Value>(id.to_string()).await?;
+
+    if let Some(timeout) = wait {
+        // Poll until the object is gone.
+        let deadline = Instant::now() + Duration::from_secs(timeout);
+        while helper::find(&client, namespace, name).await?.is_some() {
+            if Instant::now() > deadline {
+                return Err(YangtzeError::GeneralError(format!(
+                    "timed out waiting for {} to be deleted",
+                    meta
+                )));
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    println!("{} deleted", meta);
+
+    Ok(())
+}
+
+fn confirm(prompt: &str) -> Result<bool, YangtzeError> {
+    print!("{} [y/N] ", prompt);
+    io::stdout()
+        .flush()
+        .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
@@ -0,0 +1,100 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use yangtze_apis::v1::{Metadata, NamespaceName, YangtzeError};
+use yangtze_client::YangtzeClient;
+
+use crate::helper;
+
+pub async fn run(
+    client: YangtzeClient,
+    kind: &str,
+    namespace: &str,
+    name: &str,
+) -> Result<(), YangtzeError> {
+    let kind_client = helper::kind_client(client.clone(), kind)?;
+    let obj = helper::get(&kind_client, kind, namespace, name).await?;
+    let meta = Metadata::deserialize(&obj["meta_data"])?;
+
+    println!("{:<12}{}", "Name:", meta.name);
+    println!("{:<12}{}", "Namespace:", meta.namespace);
+    println!("{:<12}{}", "Kind:", meta.kind);
+    println!(
+        "{:<12}{}",
+        "UUID:",
+        meta.uuid.map(|u| u.to_string()).unwrap_or_default()
+    );
+    println!("{:<12}{}", "Version:", meta.version);
+    println!("{:<12}{}", "Labels:", meta.labels.join(","));
+    print_section("Spec:", &obj["spec"])?;
+    print_section("Status:", &obj["status"])?;
+
+    println!("Related:");
+    let related = related(client, &meta).await?;
+    if related.is_empty() {
+        println!("  <none>");
+    }
+    for r in related {
+        println!("  {}", r);
+    }
+
+    Ok(())
+}
+
+fn print_section(title: &str, v: &Value) -> Result<(), YangtzeError> {
+    println!("{}", title);
+    if v.is_null() {
+        println!("  <none>");
+        return Ok(());
+    }
+
+    for line in helper::to_yaml(v)?.lines() {
+        println!("  {}", line);
+    }
+
+    Ok(())
+}
+
+/// Objects of other kinds in the same namespace that refer to `meta` by a
+/// spec field named after its kind, e.g. `spec.fabric: <name>`.
+async fn related(client: YangtzeClient, meta: &Metadata) -> Result<Vec<Metadata>, YangtzeError> {
+    let mut res = vec![];
+
+    for vk in yangtze_apis::KINDS {
+        if vk.kind == meta.kind {
+            continue;
+        }
+
+        let nn = NamespaceName {
+            namespace: Some(meta.namespace.clone()),
+            name: None,
+        };
+        let objs = client
+            .clone()
+            .version(vk.version)
+            .kind(vk.kind)
+            .list::<Value>(nn)
+            .await?;
+
+        for o in objs {
+            if o["spec"][meta.kind.as_str()].as_str() == Some(meta.name.as_str()) {
+                res.push(Metadata::deserialize(&o["meta_data"])?);
+            }
+        }
+    }
+
+    Ok(res)
+}
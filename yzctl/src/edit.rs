@@ -0,0 +1,91 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::env;
+use std::fs;
+use std::process::{self, Command};
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use yangtze_apis::v1::{Metadata, YangtzeError};
+use yangtze_client::YangtzeClient;
+
+use crate::helper;
+
+pub async fn run(
+    client: YangtzeClient,
+    kind: &str,
+    namespace: &str,
+    name: &str,
+) -> Result<(), YangtzeError> {
+    let client = helper::kind_client(client, kind)?;
+    let obj = helper::get(&client, kind, namespace, name).await?;
+    let meta = Metadata::deserialize(&obj["meta_data"])?;
+
+    let path = env::temp_dir().join(format!("yzctl-edit-{}.yaml", process::id()));
+    fs::write(&path, helper::to_yaml(&obj)?)
+        .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+
+    let editor = env::var("EDITOR").unwrap_or("vi".to_string());
+    let status = Command::new(&editor)
+        .arg(&path)
+        .status()
+        .map_err(|e| YangtzeError::GeneralError(format!("failed to run {}: {}", editor, e)))?;
+    if !status.success() {
+        return Err(YangtzeError::GeneralError(format!(
+            "{} exited with {}",
+            editor, status
+        )));
+    }
+
+    let content =
+        fs::read_to_string(&path).map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+    let edited: Value = serde_yaml::from_str(&content)
+        .map_err(|e| YangtzeError::InvalidConfig(format!("{}: {}", path.display(), e)))?;
+
+    if edited == obj {
+        let _ = fs::remove_file(&path);
+        println!("Edit cancelled, no changes made.");
+        return Ok(());
+    }
+
+    let edited_meta = Metadata::deserialize(&edited["meta_data"])?;
+    if edited_meta.uuid != meta.uuid
+        || edited_meta.kind != meta.kind
+        || edited_meta.namespace != meta.namespace
+        || edited_meta.name != meta.name
+    {
+        return Err(YangtzeError::InvalidConfig(format!(
+            "the identity of {} can not be changed, your edit is kept at {}",
+            meta,
+            path.display()
+        )));
+    }
+
+    // The apiserver rejects the update if the object was changed after it
+    // was fetched, as the version in the edited copy is out of date.
+    if let Err(e) = client.update(edited).await {
+        return Err(YangtzeError::GeneralError(format!(
+            "failed to update {} ({}), it may have been modified concurrently; your edit is kept at {}",
+            meta,
+            e,
+            path.display()
+        )));
+    }
+
+    let _ = fs::remove_file(&path);
+    println!("{} edited", meta);
+
+    Ok(())
+}
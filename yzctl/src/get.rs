@@ -0,0 +1,41 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use yangtze_apis::v1::{NamespaceName, YangtzeError};
+use yangtze_client::YangtzeClient;
+
+use crate::helper;
+use crate::output::{self, Output};
+use crate::watch;
+
+pub async fn run(
+    client: YangtzeClient,
+    kind: &str,
+    namespace: &str,
+    name: &str,
+    output: &Output,
+    watch: bool,
+) -> Result<(), YangtzeError> {
+    let client = helper::kind_client(client, kind)?;
+    if watch {
+        let nn = NamespaceName {
+            namespace: Some(namespace.to_string()),
+            name: Some(name.to_string()),
+        };
+        return watch::run(client, kind, nn, output).await;
+    }
+
+    let obj = helper::get(&client, kind, namespace, name).await?;
+
+    output::print_one(&kind.to_lowercase(), obj, output)
+}
@@ -11,8 +11,63 @@
  * limitations under the License.
  */
 
-use yangtze_apis::v1::YangtzeError;
+use clap::CommandFactory;
+use serde_json::Value;
+
+use yangtze_apis::v1::{NamespaceName, YangtzeError};
+use yangtze_client::YangtzeClient;
+
+use crate::Cli;
 
 pub async fn run() -> Result<(), YangtzeError> {
-    todo!()
+    Cli::command()
+        .print_long_help()
+        .map_err(|e| YangtzeError::GeneralError(e.to_string()))?;
+
+    let kinds: Vec<_> = yangtze_apis::KINDS.iter().map(|vk| vk.kind).collect();
+    println!("\nAvailable kinds: {}", kinds.join(", "));
+
+    Ok(())
+}
+
+/// Returns a client bound to the version/kind of `kind`.
+pub fn kind_client(client: YangtzeClient, kind: &str) -> Result<YangtzeClient, YangtzeError> {
+    let vk = yangtze_apis::get_version_kind(&kind.to_lowercase()).ok_or(
+        YangtzeError::InvalidConfig(format!("unknown kind <{}>", kind)),
+    )?;
+
+    Ok(client.version(vk.version).kind(vk.kind))
+}
+
+/// Looks up an object by namespace/name, returning `None` if it does not exist.
+pub async fn find(
+    client: &YangtzeClient,
+    namespace: &str,
+    name: &str,
+) -> Result<Option<Value>, YangtzeError> {
+    let nn = NamespaceName {
+        namespace: Some(namespace.to_string()),
+        name: Some(name.to_string()),
+    };
+
+    Ok(client.list::<Value>(nn).await?.pop())
+}
+
+/// Same as `find`, but a missing object is an error.
+pub async fn get(
+    client: &YangtzeClient,
+    kind: &str,
+    namespace: &str,
+    name: &str,
+) -> Result<Value, YangtzeError> {
+    find(client, namespace, name)
+        .await?
+        .ok_or(YangtzeError::GeneralError(format!(
+            "{}/{}/{} not found",
+            kind, namespace, name
+        )))
+}
+
+pub fn to_yaml(v: &Value) -> Result<String, YangtzeError> {
+    serde_yaml::to_string(v).map_err(|e| YangtzeError::GeneralError(e.to_string()))
 }
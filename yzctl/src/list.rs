@@ -11,41 +11,27 @@
  * limitations under the License.
  */
 
-use yangtze_apis::{
-    v1::{YangtzeError, ALL},
-    v1alpha1::fabric::{Fabric, FabricState},
-};
+use serde_json::Value;
+
+use yangtze_apis::v1::{YangtzeError, ALL};
 use yangtze_client::YangtzeClient;
 
-pub async fn run(client: YangtzeClient, kind: &str) -> Result<(), YangtzeError> {
-    let vk = yangtze_apis::get_version_kind(&kind.to_lowercase())
-        .ok_or(YangtzeError::InvalidConfig("unknown kind".to_string()))?;
-    let client = client.version(vk.version).kind(vk.kind);
-    let fabric_list = client.list::<Fabric>(ALL).await?;
+use crate::helper;
+use crate::output::{self, Output};
+use crate::watch;
 
-    println!(
-        " {:<45}| {:<20}| {:<10}| {:<15}",
-        "UUID", "Namespace", "Name", "State"
-    );
-    for _ in 1..100 {
-        print!("-");
+pub async fn run(
+    client: YangtzeClient,
+    kind: &str,
+    output: &Output,
+    watch: bool,
+) -> Result<(), YangtzeError> {
+    let client = helper::kind_client(client, kind)?;
+    if watch {
+        return watch::run(client, kind, ALL, output).await;
     }
-    println!();
-
-    for f in fabric_list {
-        let state = match f.status {
-            Some(s) => s.state,
-            None => FabricState::Initializing,
-        };
 
-        println!(
-            " {:<45}| {:<20}| {:<10}| {:<15}",
-            f.meta_data.uuid.unwrap().to_string(),
-            f.meta_data.namespace,
-            f.meta_data.name,
-            state,
-        );
-    }
+    let objs = client.list::<Value>(ALL).await?;
 
-    Ok(())
+    output::print_list(&kind.to_lowercase(), objs, output)
 }
@@ -16,8 +16,17 @@ use clap::{Parser, Subcommand};
 use yangtze_apis::v1::YangtzeError;
 use yangtze_client::{YangtzeClient, YangtzeConfig};
 
+mod apply;
+mod completion;
+mod delete;
+mod describe;
+mod edit;
+mod get;
 mod helper;
 mod list;
+mod output;
+mod wait;
+mod watch;
 
 #[derive(Parser)]
 #[command(name = "yzctl")]
@@ -34,9 +43,87 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
+    /// Create or update objects from a file, a directory or stdin (-)
+    Apply {
+        #[arg(short, long)]
+        filename: String,
+    },
+    /// List all objects of a kind
     List {
+        #[arg(short, long, ignore_case = true, value_parser = completion::kinds())]
+        kind: String,
+        /// One of table, wide, json, yaml, name, jsonpath=..., custom-columns=NAME:PATH,...
+        #[arg(short, long, default_value = "table")]
+        output: output::Output,
+        /// Print the changes of the objects until interrupted
         #[arg(short, long)]
+        watch: bool,
+    },
+    /// Display an object
+    Get {
+        #[arg(ignore_case = true, value_parser = completion::kinds())]
+        kind: String,
+        name: String,
+        #[arg(short, long, default_value = "default")]
+        namespace: String,
+        /// One of table, wide, json, yaml, name, jsonpath=..., custom-columns=NAME:PATH,...
+        #[arg(short, long, default_value = "table")]
+        output: output::Output,
+        /// Print the changes of the objects until interrupted
+        #[arg(short, long)]
+        watch: bool,
+    },
+    /// Show the spec, status and related objects of an object; the API
+    /// server records no events
+    Describe {
+        #[arg(ignore_case = true, value_parser = completion::kinds())]
+        kind: String,
+        name: String,
+        #[arg(short, long, default_value = "default")]
+        namespace: String,
+    },
+    /// Delete an object
+    Delete {
+        #[arg(ignore_case = true, value_parser = completion::kinds())]
+        kind: String,
+        name: String,
+        #[arg(short, long, default_value = "default")]
+        namespace: String,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+        /// Wait until the object is gone
+        #[arg(long)]
+        wait: bool,
+        /// Seconds to wait for the deletion
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
+    },
+    /// Wait until a condition holds for the objects, e.g. --for=state=ready fabric/f1
+    Wait {
+        /// The objects as <kind>/<name>
+        #[arg(required = true)]
+        targets: Vec<String>,
+        #[arg(short, long, default_value = "default")]
+        namespace: String,
+        /// One of delete, state=<value> or <path>=<value>
+        #[arg(long = "for")]
+        condition: wait::Condition,
+        /// How long to wait, e.g. 30s or 5m
+        #[arg(long, default_value = "30s", value_parser = humantime::parse_duration)]
+        timeout: std::time::Duration,
+    },
+    /// Generate the completion script of a shell
+    Completion { shell: clap_complete::Shell },
+    #[command(name = "__complete", hide = true)]
+    Complete { args: Vec<String> },
+    /// Edit an object with $EDITOR
+    Edit {
+        #[arg(ignore_case = true, value_parser = completion::kinds())]
         kind: String,
+        name: String,
+        #[arg(short, long, default_value = "default")]
+        namespace: String,
     },
 }
 
@@ -53,7 +140,55 @@ async fn main() -> Result<(), YangtzeError> {
 
     let cli = Cli::parse();
     match &cli.command {
-        Some(Commands::List { kind }) => list::run(client, kind).await?,
+        Some(Commands::Apply { filename }) => apply::run(client, filename).await?,
+        Some(Commands::List {
+            kind,
+            output,
+            watch,
+        }) => list::run(client, kind, output, *watch).await?,
+        Some(Commands::Get {
+            kind,
+            name,
+            namespace,
+            output,
+            watch,
+        }) => get::run(client, kind, namespace, name, output, *watch).await?,
+        Some(Commands::Wait {
+            targets,
+            namespace,
+            condition,
+            timeout,
+        }) => wait::run(client, targets, namespace, condition, *timeout).await?,
+        Some(Commands::Describe {
+            kind,
+            name,
+            namespace,
+        }) => describe::run(client, kind, namespace, name).await?,
+        Some(Commands::Delete {
+            kind,
+            name,
+            namespace,
+            yes,
+            wait,
+            timeout,
+        }) => {
+            delete::run(
+                client,
+                kind,
+                namespace,
+                name,
+                *yes,
+                wait.then_some(*timeout),
+            )
+            .await?
+        }
+        Some(Commands::Completion { shell }) => completion::run(*shell)?,
+        Some(Commands::Complete { args }) => completion::complete(client, args).await?,
+        Some(Commands::Edit {
+            kind,
+            name,
+            namespace,
+        }) => edit::run(client, kind, namespace, name).await?,
         _ => helper::run().await?,
     };
 
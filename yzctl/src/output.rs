@@ -0,0 +1,331 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::str::FromStr;
+
+use serde_json::{json, Value};
+use serde_json_path::JsonPath;
+
+use yangtze_apis::v1::YangtzeError;
+
+use crate::helper;
+use crate::watch::Event;
+
+#[derive(Clone, Debug)]
+pub enum Output {
+    Table,
+    Wide,
+    Json,
+    Yaml,
+    Name,
+    JsonPath(String),
+    CustomColumns(Vec<(String, String)>),
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => return Ok(Output::Table),
+            "wide" => return Ok(Output::Wide),
+            "json" => return Ok(Output::Json),
+            "yaml" => return Ok(Output::Yaml),
+            "name" => return Ok(Output::Name),
+            _ => {}
+        }
+
+        if let Some(expr) = s.strip_prefix("jsonpath=") {
+            let expr = jsonpath(expr);
+            JsonPath::parse(&expr).map_err(|e| e.to_string())?;
+            return Ok(Output::JsonPath(expr));
+        }
+
+        if let Some(spec) = s.strip_prefix("custom-columns=") {
+            let mut columns = vec![];
+            for c in spec.split(',') {
+                let (name, path) = c
+                    .split_once(':')
+                    .ok_or(format!("invalid custom column <{}>, expect NAME:PATH", c))?;
+                columns.push((name.to_string(), path.trim_start_matches('.').to_string()));
+            }
+            return Ok(Output::CustomColumns(columns));
+        }
+
+        Err(format!(
+            "unknown output format <{}>, expect one of table, wide, json, yaml, name, jsonpath=..., custom-columns=...",
+            s
+        ))
+    }
+}
+
+/// Prints a list of objects of `kind`.
+pub fn print_list(kind: &str, objs: Vec<Value>, output: &Output) -> Result<(), YangtzeError> {
+    match output {
+        Output::Json | Output::Yaml | Output::JsonPath(_) => {
+            print_value(&Value::Array(objs), output)
+        }
+        _ => print_rows(kind, &objs, &[], output, &mut None),
+    }
+}
+
+/// Prints a single object of `kind`.
+pub fn print_one(kind: &str, obj: Value, output: &Output) -> Result<(), YangtzeError> {
+    match output {
+        Output::Json | Output::Yaml | Output::JsonPath(_) => print_value(&obj, output),
+        _ => print_rows(kind, &[obj], &[], output, &mut None),
+    }
+}
+
+/// Prints the objects of `kind` changed since the last call in watch mode,
+/// each with its event; the table header is only printed with the first
+/// changes, whose column widths are kept in `widths` so later rows line up
+/// with it.
+pub fn print_changes(
+    kind: &str,
+    changes: Vec<(Event, Value)>,
+    output: &Output,
+    widths: &mut Option<Vec<usize>>,
+) -> Result<(), YangtzeError> {
+    match output {
+        Output::Json | Output::Yaml => {
+            for (event, o) in changes {
+                if let Output::Yaml = output {
+                    println!("---");
+                }
+                print_value(&json!({"type": event.to_string(), "object": o}), output)?;
+            }
+            Ok(())
+        }
+        Output::JsonPath(expr) => {
+            for (event, o) in changes {
+                println!("{}\t{}", event, query(&o, expr)?);
+            }
+            Ok(())
+        }
+        _ => {
+            let (events, objs): (Vec<_>, Vec<_>) = changes.into_iter().unzip();
+            print_rows(kind, &objs, &events, output, widths)
+        }
+    }
+}
+
+fn print_value(v: &Value, output: &Output) -> Result<(), YangtzeError> {
+    match output {
+        Output::Json => println!("{}", serde_json::to_string_pretty(v)?),
+        Output::Yaml => print!("{}", helper::to_yaml(v)?),
+        Output::JsonPath(expr) => println!("{}", query(v, expr)?),
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Prints the objects as a table; the header is only printed, and the column
+/// widths only computed, if `widths` is none yet.
+fn print_rows(
+    kind: &str,
+    objs: &[Value],
+    events: &[Event],
+    output: &Output,
+    widths: &mut Option<Vec<usize>>,
+) -> Result<(), YangtzeError> {
+    if let Output::Name = output {
+        for (i, o) in objs.iter().enumerate() {
+            match events.get(i) {
+                Some(Event::Deleted) => {
+                    println!("{}/{} deleted", kind, cell(&o["meta_data"]["name"]))
+                }
+                _ => println!("{}/{}", kind, cell(&o["meta_data"]["name"])),
+            }
+        }
+        return Ok(());
+    }
+
+    let columns = columns(kind, output);
+    let mut rows = vec![columns.iter().map(|(name, _)| name.clone()).collect()];
+    for o in objs {
+        rows.push(
+            columns
+                .iter()
+                .map(|(_, path)| lookup(o, path).map(cell).unwrap_or("<none>".to_string()))
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    // Watch mode prints the event of each row first.
+    if !events.is_empty() {
+        rows[0].insert(0, "EVENT".to_string());
+        for (row, event) in rows[1..].iter_mut().zip(events) {
+            row.insert(0, event.to_string());
+        }
+    }
+
+    let header = widths.is_none();
+    let widths = widths.get_or_insert_with(|| {
+        let mut widths = vec![0; rows[0].len()];
+        for row in &rows {
+            for (i, c) in row.iter().enumerate() {
+                widths[i] = widths[i].max(c.len());
+            }
+        }
+        widths
+    });
+
+    for row in rows.into_iter().skip(if header { 0 } else { 1 }) {
+        let line: Vec<_> = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(c, w)| format!("{:<w$}", c, w = w))
+            .collect();
+        println!("{}", line.join("   ").trim_end());
+    }
+
+    Ok(())
+}
+
+/// The names and paths of the columns printed for `kind`.
+fn columns(kind: &str, output: &Output) -> Vec<(String, String)> {
+    match output {
+        Output::CustomColumns(columns) => columns.clone(),
+        _ => yangtze_apis::get_columns(kind)
+            .iter()
+            .filter(|c| !c.wide || matches!(output, Output::Wide))
+            .map(|c| (c.name.to_string(), c.path.to_string()))
+            .collect(),
+    }
+}
+
+/// Evaluates a JSONPath expression, joining the matched values by spaces.
+fn query(v: &Value, expr: &str) -> Result<String, YangtzeError> {
+    let path = JsonPath::parse(expr).map_err(|e| YangtzeError::InvalidConfig(e.to_string()))?;
+    let nodes: Vec<_> = path.query(v).all().into_iter().map(cell).collect();
+
+    Ok(nodes.join(" "))
+}
+
+/// Looks up a dotted path, e.g. `status.state` or `spec.ports.0.name`.
+pub fn lookup<'a>(v: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .filter(|p| !p.is_empty())
+        .try_fold(v, |v, p| match v {
+            Value::Array(items) => items.get(p.parse::<usize>().ok()?),
+            _ => v.get(p),
+        })
+        .filter(|v| !v.is_null())
+}
+
+pub fn cell(v: &Value) -> String {
+    match v {
+        Value::Null => "<none>".to_string(),
+        Value::String(s) => s.clone(),
+        _ => v.to_string(),
+    }
+}
+
+/// Accepts both the kubectl style `{.meta_data.name}` and `$.meta_data.name`.
+fn jsonpath(expr: &str) -> String {
+    let expr = expr
+        .strip_prefix('{')
+        .and_then(|e| e.strip_suffix('}'))
+        .unwrap_or(expr);
+
+    if expr.starts_with('$') {
+        expr.to_string()
+    } else {
+        format!("${}", expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(columns: Vec<(String, String)>) -> Vec<String> {
+        columns.into_iter().map(|(name, _)| name).collect()
+    }
+
+    #[test]
+    fn parse_output() {
+        assert!(matches!("table".parse(), Ok(Output::Table)));
+        assert!(matches!("wide".parse(), Ok(Output::Wide)));
+        assert!(matches!("yaml".parse(), Ok(Output::Yaml)));
+        assert!(matches!("name".parse(), Ok(Output::Name)));
+
+        let Ok(Output::JsonPath(expr)) = "jsonpath={.meta_data.name}".parse() else {
+            panic!("expect jsonpath");
+        };
+        assert_eq!(expr, "$.meta_data.name");
+        let Ok(Output::JsonPath(expr)) = "jsonpath=$.spec".parse() else {
+            panic!("expect jsonpath");
+        };
+        assert_eq!(expr, "$.spec");
+
+        let Ok(Output::CustomColumns(columns)) =
+            "custom-columns=NAME:.meta_data.name,STATE:status.state".parse()
+        else {
+            panic!("expect custom columns");
+        };
+        assert_eq!(
+            columns,
+            vec![
+                ("NAME".to_string(), "meta_data.name".to_string()),
+                ("STATE".to_string(), "status.state".to_string()),
+            ]
+        );
+
+        assert!("custom-columns=NAME".parse::<Output>().is_err());
+        assert!("jsonpath={.a[}".parse::<Output>().is_err());
+        assert!("xml".parse::<Output>().is_err());
+    }
+
+    #[test]
+    fn select_columns() {
+        let table = names(columns("fabric", &Output::Table));
+        let wide = names(columns("fabric", &Output::Wide));
+        assert!(table.contains(&"STATE".to_string()));
+        assert!(!table.contains(&"REASON".to_string()));
+        assert!(wide.contains(&"REASON".to_string()));
+
+        assert_eq!(
+            names(columns("unknown", &Output::Table)),
+            vec!["UUID", "NAMESPACE", "NAME"]
+        );
+
+        let custom = Output::CustomColumns(vec![("N".to_string(), "meta_data.name".to_string())]);
+        assert_eq!(names(columns("fabric", &custom)), vec!["N"]);
+    }
+
+    #[test]
+    fn lookup_paths() {
+        let v =
+            json!({"spec": {"ports": [{"name": "p0"}, {"name": "p1"}], "mtu": 9000, "bfb": null}});
+        assert_eq!(lookup(&v, "spec.ports.1.name"), Some(&json!("p1")));
+        assert_eq!(lookup(&v, "spec.mtu").map(cell), Some("9000".to_string()));
+        assert_eq!(lookup(&v, "spec.bfb"), None);
+        assert_eq!(lookup(&v, "spec.ports.2.name"), None);
+        assert_eq!(lookup(&v, "spec.ports.x"), None);
+    }
+
+    #[test]
+    fn query_jsonpath() {
+        let v = json!([
+            {"meta_data": {"name": "n1"}, "status": {"state": "Ready"}},
+            {"meta_data": {"name": "n2"}, "status": {"state": null}},
+        ]);
+        assert_eq!(query(&v, "$[*].meta_data.name").unwrap(), "n1 n2");
+        assert_eq!(query(&v, "$[*].status.state").unwrap(), "Ready <none>");
+        assert_eq!(query(&v, "$[5].meta_data.name").unwrap(), "");
+        assert!(query(&v, "meta_data").is_err());
+    }
+}
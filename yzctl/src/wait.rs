@@ -0,0 +1,161 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use yangtze_apis::v1::YangtzeError;
+use yangtze_client::YangtzeClient;
+
+use crate::helper;
+use crate::output;
+use crate::watch::INTERVAL;
+
+/// The condition of `yzctl wait --for`.
+#[derive(Clone, Debug)]
+pub enum Condition {
+    /// `delete`: the object is gone.
+    Delete,
+    /// `<path>=<value>`: the field at the dotted path has the value; `state`
+    /// is short for `status.state`.
+    Field(String, String),
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "delete" {
+            return Ok(Condition::Delete);
+        }
+
+        match s.split_once('=') {
+            Some(("state", value)) => Ok(Condition::Field(
+                "status.state".to_string(),
+                value.to_string(),
+            )),
+            Some((path, value)) if !path.is_empty() => Ok(Condition::Field(
+                path.trim_start_matches('.').to_string(),
+                value.to_string(),
+            )),
+            _ => Err(format!(
+                "invalid condition <{}>, expect delete, state=<value> or <path>=<value>",
+                s
+            )),
+        }
+    }
+}
+
+impl Condition {
+    fn is_met(&self, obj: Option<&Value>) -> bool {
+        match (self, obj) {
+            (Condition::Delete, obj) => obj.is_none(),
+            (Condition::Field(path, value), Some(obj)) => output::lookup(obj, path)
+                .map(|v| output::cell(v).eq_ignore_ascii_case(value))
+                .unwrap_or(false),
+            (Condition::Field(..), None) => false,
+        }
+    }
+}
+
+/// Blocks until the condition holds for all the `<kind>/<name>` targets.
+pub async fn run(
+    client: YangtzeClient,
+    targets: &[String],
+    namespace: &str,
+    condition: &Condition,
+    timeout: Duration,
+) -> Result<(), YangtzeError> {
+    let deadline = Instant::now() + timeout;
+
+    for target in targets {
+        let (kind, name) = target
+            .split_once('/')
+            .ok_or(YangtzeError::InvalidConfig(format!(
+                "invalid target <{}>, expect <kind>/<name>",
+                target
+            )))?;
+        let client = helper::kind_client(client.clone(), kind)?;
+
+        loop {
+            let obj = helper::find(&client, namespace, name).await?;
+            if condition.is_met(obj.as_ref()) {
+                println!("{}/{} condition met", kind.to_lowercase(), name);
+                break;
+            }
+
+            if Instant::now() > deadline {
+                return Err(YangtzeError::GeneralError(format!(
+                    "timed out waiting for the condition on {}/{}",
+                    kind.to_lowercase(),
+                    name
+                )));
+            }
+
+            tokio::time::sleep(INTERVAL).await;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_condition() {
+        assert!(matches!("delete".parse(), Ok(Condition::Delete)));
+
+        let Ok(Condition::Field(path, value)) = "state=Ready".parse() else {
+            panic!("expect a field condition");
+        };
+        assert_eq!((path.as_str(), value.as_str()), ("status.state", "Ready"));
+
+        let Ok(Condition::Field(path, value)) = ".spec.power=on".parse() else {
+            panic!("expect a field condition");
+        };
+        assert_eq!((path.as_str(), value.as_str()), ("spec.power", "on"));
+
+        let Ok(Condition::Field(path, value)) = "status.reason=".parse() else {
+            panic!("expect a field condition");
+        };
+        assert_eq!((path.as_str(), value.as_str()), ("status.reason", ""));
+
+        assert!("ready".parse::<Condition>().is_err());
+        assert!("=Ready".parse::<Condition>().is_err());
+    }
+
+    #[test]
+    fn meet_condition() {
+        let obj = json!({"status": {"state": "Ready", "ports": [{"up": true}]}});
+
+        assert!(Condition::Delete.is_met(None));
+        assert!(!Condition::Delete.is_met(Some(&obj)));
+
+        let ready: Condition = "state=ready".parse().unwrap();
+        assert!(ready.is_met(Some(&obj)));
+        assert!(!ready.is_met(None));
+        assert!("status.ports.0.up=true"
+            .parse::<Condition>()
+            .unwrap()
+            .is_met(Some(&obj)));
+        assert!(!"status.reason=x"
+            .parse::<Condition>()
+            .unwrap()
+            .is_met(Some(&obj)));
+    }
+}
@@ -0,0 +1,136 @@
+/*
+ * Copyright 2023 The xflops Authors.
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use serde_json::Value;
+
+use yangtze_apis::v1::{NamespaceName, YangtzeError};
+use yangtze_client::YangtzeClient;
+
+use crate::output::{self, Output};
+
+pub const INTERVAL: Duration = Duration::from_secs(2);
+
+/// How an object changed since the last poll.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    Added,
+    Modified,
+    Deleted,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Added => write!(f, "ADDED"),
+            Event::Modified => write!(f, "MODIFIED"),
+            Event::Deleted => write!(f, "DELETED"),
+        }
+    }
+}
+
+/// Polls the objects matching `nn` and prints the ones which were created,
+/// updated or deleted since the last poll; it runs until interrupted.
+pub async fn run(
+    client: YangtzeClient,
+    kind: &str,
+    nn: NamespaceName,
+    output: &Output,
+) -> Result<(), YangtzeError> {
+    let mut known: HashMap<String, Value> = HashMap::new();
+    // The widths of the table columns, fixed by the initial list.
+    let mut widths = None;
+
+    loop {
+        let objs = client.list::<Value>(nn.clone()).await?;
+        let changes = diff(&mut known, objs);
+
+        if !changes.is_empty() {
+            output::print_changes(&kind.to_lowercase(), changes, output, &mut widths)?;
+        }
+
+        tokio::time::sleep(INTERVAL).await;
+    }
+}
+
+/// Replaces the `known` objects by the listed ones, returning the changes;
+/// deleted objects come with their last known state.
+fn diff(known: &mut HashMap<String, Value>, objs: Vec<Value>) -> Vec<(Event, Value)> {
+    let mut current = HashMap::new();
+    let mut changes = vec![];
+    for o in objs {
+        let key = key(&o);
+        match known.get(&key) {
+            None => changes.push((Event::Added, o.clone())),
+            Some(k) if k != &o => changes.push((Event::Modified, o.clone())),
+            Some(_) => {}
+        }
+        current.insert(key, o);
+    }
+
+    for (key, o) in known.drain() {
+        if !current.contains_key(&key) {
+            changes.push((Event::Deleted, o));
+        }
+    }
+
+    *known = current;
+    changes
+}
+
+fn key(o: &Value) -> String {
+    let meta = &o["meta_data"];
+    match meta["uuid"].as_str() {
+        Some(uuid) => uuid.to_string(),
+        None => format!(
+            "{}/{}",
+            output::cell(&meta["namespace"]),
+            output::cell(&meta["name"])
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn obj(name: &str, state: &str) -> Value {
+        json!({"meta_data": {"namespace": "default", "name": name}, "status": {"state": state}})
+    }
+
+    #[test]
+    fn diff_polls() {
+        let mut known = HashMap::new();
+
+        let changes = diff(&mut known, vec![obj("f1", "Pending"), obj("f2", "Ready")]);
+        let mut events: Vec<_> = changes.iter().map(|(e, _)| *e).collect();
+        events.sort_by_key(|e| e.to_string());
+        assert_eq!(events, vec![Event::Added, Event::Added]);
+
+        assert!(diff(&mut known, vec![obj("f1", "Pending"), obj("f2", "Ready")]).is_empty());
+
+        let changes = diff(&mut known, vec![obj("f1", "Ready")]);
+        assert_eq!(
+            changes,
+            vec![
+                (Event::Modified, obj("f1", "Ready")),
+                (Event::Deleted, obj("f2", "Ready")),
+            ]
+        );
+        assert_eq!(known.len(), 1);
+    }
+}